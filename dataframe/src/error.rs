@@ -0,0 +1,87 @@
+use std::fmt;
+
+/// Errors raised while building, checking or executing a qir plan.
+#[derive(Debug)]
+pub enum Error {
+    /// a table referenced by a `Scan` is not registered in the execution context
+    TableNotFound(String),
+    /// a column referenced by an operator does not exist in its input
+    ColumnNotFound(String),
+    /// a value or vector does not have the expected data type
+    TypeMismatch { expected: String, found: String },
+    /// a value can not be converted to the data type
+    InvalidCast { value: String, data_type: String },
+    /// the result of a sink is referenced before its pipeline has been executed
+    ResultNotReady(String),
+    /// invalid qir text, `line` and `column` are 1-based and the message shows the offending source line
+    Parse { line: usize, column: usize, message: String },
+    /// a feature the engine does not support (yet)
+    Unsupported(String),
+    /// the static checks of a topology failed, see `qir::checker`
+    Check(Vec<CheckError>),
+    /// reading or writing a spill file failed
+    Io(std::io::Error),
+    /// reading a parquet file failed
+    Parquet(parquet::errors::ParquetError),
+    /// reading or writing an arrow ipc file, or converting an arrow array, failed
+    Arrow(arrow::error::ArrowError),
+}
+
+/// an error found by the checker, `path` names the operator as in the printed topology
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TableNotFound(name) => write!(f, "table not found: {name}"),
+            Error::ColumnNotFound(name) => write!(f, "column not found: {name}"),
+            Error::TypeMismatch { expected, found } => write!(f, "type mismatch: expected {expected}, found {found}"),
+            Error::InvalidCast { value, data_type } => write!(f, "can not cast {value} to {data_type}"),
+            Error::ResultNotReady(name) => write!(f, "result of {name} is not ready"),
+            Error::Parse { line, column, message } => write!(f, "parse error at {line}:{column}: {message}"),
+            Error::Unsupported(what) => write!(f, "unsupported: {what}"),
+            Error::Io(error) => write!(f, "io error: {error}"),
+            Error::Parquet(error) => write!(f, "parquet error: {error}"),
+            Error::Arrow(error) => write!(f, "arrow error: {error}"),
+            Error::Check(errors) => {
+                write!(f, "invalid topology:")?;
+                for error in errors {
+                    write!(f, "\n  {error}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(error: parquet::errors::ParquetError) -> Self {
+        Error::Parquet(error)
+    }
+}
+
+impl From<arrow::error::ArrowError> for Error {
+    fn from(error: arrow::error::ArrowError) -> Self {
+        Error::Arrow(error)
+    }
+}
@@ -0,0 +1,532 @@
+//! Aggregate functions of the group-by.
+//!
+//! A function keeps one state per group and updates the states with a whole vector at a time:
+//! row `i` of the input belongs to group `groups[i]`. Nulls are skipped by every function but
+//! `first` and `last`, a group without values aggregates to null (`count` to 0).
+
+use crate::error::{Error, Result};
+use crate::exec::group_by::GroupTable;
+use crate::qir::DataType;
+use crate::vector::{Bitmap, PrimaryType, PrimaryVector, StringVector, Value, Vector};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateKind {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+    CountDistinct,
+    First,
+    Last,
+}
+
+impl AggregateKind {
+    pub const ALL: [AggregateKind; 8] = [
+        AggregateKind::Count, AggregateKind::Sum, AggregateKind::Min, AggregateKind::Max,
+        AggregateKind::Avg, AggregateKind::CountDistinct, AggregateKind::First, AggregateKind::Last,
+    ];
+
+    /// the function name in the text format
+    pub fn name(self) -> &'static str {
+        match self {
+            AggregateKind::Count => "count",
+            AggregateKind::Sum => "sum",
+            AggregateKind::Min => "min",
+            AggregateKind::Max => "max",
+            AggregateKind::Avg => "avg",
+            AggregateKind::CountDistinct => "count_distinct",
+            AggregateKind::First => "first",
+            AggregateKind::Last => "last",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// the type of the result for an argument of type `input`
+    pub fn result_type(self, input: &DataType) -> Result<DataType> {
+        let unsupported = || Err(Error::Unsupported(format!("{}({input:?})", self.name())));
+        match self {
+            AggregateKind::Count | AggregateKind::CountDistinct => Ok(DataType::I64),
+            AggregateKind::Sum => match input {
+                t if t.is_signed_integer() => Ok(DataType::I64),
+                t if t.is_integer() => Ok(DataType::U64),
+                t if t.is_float() => Ok(DataType::F64),
+                DataType::Decimal => Ok(DataType::Decimal),
+                _ => unsupported(),
+            },
+            AggregateKind::Avg => match input {
+                DataType::Decimal => Ok(DataType::Decimal),
+                t if t.is_numeric() => Ok(DataType::F64),
+                _ => unsupported(),
+            },
+            AggregateKind::Min | AggregateKind::Max => match input {
+                t if t.is_numeric() || t.is_temporal() || matches!(t, DataType::Bool | DataType::String) => Ok(t.clone()),
+                _ => unsupported(),
+            },
+            AggregateKind::First | AggregateKind::Last => Ok(input.clone()),
+        }
+    }
+
+    /// a function without groups over an argument of type `input`
+    pub fn create(self, input: &DataType) -> Result<Box<dyn AggregateFunction>> {
+        let data_type = self.result_type(input)?;
+        macro_rules! numeric {
+            ($function:ident) => {
+                match input {
+                    DataType::I8 => Box::new($function::<i8>::new(data_type)),
+                    DataType::I16 => Box::new($function::<i16>::new(data_type)),
+                    DataType::I32 | DataType::Date => Box::new($function::<i32>::new(data_type)),
+                    DataType::I64 | DataType::DateTime => Box::new($function::<i64>::new(data_type)),
+                    DataType::U8 => Box::new($function::<u8>::new(data_type)),
+                    DataType::U16 => Box::new($function::<u16>::new(data_type)),
+                    DataType::U32 => Box::new($function::<u32>::new(data_type)),
+                    DataType::U64 => Box::new($function::<u64>::new(data_type)),
+                    DataType::F32 => Box::new($function::<f32>::new(data_type)),
+                    DataType::F64 => Box::new($function::<f64>::new(data_type)),
+                    DataType::Decimal => Box::new($function::<i128>::new(data_type)),
+                    _ => unreachable!("checked by result_type"),
+                }
+            };
+        }
+        let function: Box<dyn AggregateFunction> = match self {
+            AggregateKind::Count => Box::new(Count { counts: vec![] }),
+            AggregateKind::CountDistinct => Box::new(CountDistinct { pairs: GroupTable::new(&[DataType::U32, input.clone()]), counts: vec![] }),
+            AggregateKind::Sum => numeric!(Sum),
+            AggregateKind::Avg if *input == DataType::Decimal => Box::new(AvgDecimal { sums: vec![], counts: vec![] }),
+            AggregateKind::Avg => numeric!(Avg),
+            AggregateKind::Min | AggregateKind::Max => {
+                let max = self == AggregateKind::Max;
+                match input {
+                    DataType::String => Box::new(StringMinMax { values: vec![], max }),
+                    DataType::Bool => Box::new(MinMax::<bool>::new(data_type, max)),
+                    _ => {
+                        macro_rules! min_max {
+                            ($t:ty) => { Box::new(MinMax::<$t>::new(data_type, max)) };
+                        }
+                        match input {
+                            DataType::I8 => min_max!(i8),
+                            DataType::I16 => min_max!(i16),
+                            DataType::I32 | DataType::Date => min_max!(i32),
+                            DataType::I64 | DataType::DateTime => min_max!(i64),
+                            DataType::U8 => min_max!(u8),
+                            DataType::U16 => min_max!(u16),
+                            DataType::U32 => min_max!(u32),
+                            DataType::U64 => min_max!(u64),
+                            DataType::F32 => min_max!(f32),
+                            DataType::F64 => min_max!(f64),
+                            _ => min_max!(i128),
+                        }
+                    }
+                }
+            }
+            AggregateKind::First | AggregateKind::Last => {
+                Box::new(FirstLast { data_type, values: vec![], seen: vec![], last: self == AggregateKind::Last })
+            }
+        };
+        Ok(function)
+    }
+}
+
+/// the states of an aggregate function, one per group
+pub trait AggregateFunction {
+    /// make room for `groups` states, new states are empty
+    fn resize(&mut self, groups: usize);
+
+    /// add row `i` of `input` to the state of group `groups[i]`
+    fn update(&mut self, groups: &[u32], input: &Vector) -> Result<()>;
+
+    /// the result of every group, in group order
+    fn finish(self: Box<Self>) -> Result<Vector>;
+}
+
+fn mismatch<T: PrimaryType>(input: &Vector) -> Error {
+    Error::TypeMismatch { expected: format!("{:?}", T::DATA_TYPE), found: format!("{:?}", input.data_type()) }
+}
+
+/// call `f(group, value)` for every valid row of `input`
+#[inline]
+fn for_each_valid<T: PrimaryType>(groups: &[u32], input: &PrimaryVector<T>, mut f: impl FnMut(usize, T)) {
+    match input.validity() {
+        None => {
+            for (&group, &value) in groups.iter().zip(input.values()) {
+                f(group as usize, value);
+            }
+        }
+        Some(validity) => {
+            for (i, (&group, &value)) in groups.iter().zip(input.values()).enumerate() {
+                if validity.get(i) {
+                    f(group as usize, value);
+                }
+            }
+        }
+    }
+}
+
+/// wrap a vector of the physical type of `data_type`, e.g. an `i32` vector of a `Date`
+pub(crate) fn into_vector<T: PrimaryType>(data_type: &DataType, vector: PrimaryVector<T>) -> Vector {
+    match (data_type, T::into_vector(vector)) {
+        (DataType::Date, Vector::I32(v)) => Vector::Date(v),
+        (DataType::DateTime, Vector::I64(v)) => Vector::DateTime(v),
+        (_, vector) => vector,
+    }
+}
+
+struct Count {
+    counts: Vec<i64>,
+}
+
+impl AggregateFunction for Count {
+    fn resize(&mut self, groups: usize) {
+        self.counts.resize(groups, 0);
+    }
+
+    fn update(&mut self, groups: &[u32], input: &Vector) -> Result<()> {
+        match input.validity() {
+            None => groups.iter().for_each(|&g| self.counts[g as usize] += 1),
+            Some(validity) => {
+                for (i, &g) in groups.iter().enumerate() {
+                    self.counts[g as usize] += validity.get(i) as i64;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        Ok(Vector::I64(PrimaryVector::new(self.counts)))
+    }
+}
+
+/// counts the distinct (group, value) pairs with a group table of its own
+struct CountDistinct {
+    pairs: GroupTable,
+    counts: Vec<i64>,
+}
+
+impl AggregateFunction for CountDistinct {
+    fn resize(&mut self, groups: usize) {
+        self.counts.resize(groups, 0);
+    }
+
+    fn update(&mut self, groups: &[u32], input: &Vector) -> Result<()> {
+        let (groups, input) = match input.validity() {
+            None => (Vector::U32(PrimaryVector::new(groups.to_vec())), input.clone()),
+            Some(validity) => {
+                let rows: Vec<u32> = (0..input.len() as u32).filter(|&i| validity.get(i as usize)).collect();
+                (Vector::U32(PrimaryVector::new(rows.iter().map(|&i| groups[i as usize]).collect::<Vec<_>>())), input.take(&rows))
+            }
+        };
+        let known = self.pairs.len();
+        let pairs = self.pairs.find_or_insert(&[&groups, &input], input.len());
+        let Vector::U32(groups) = groups else { unreachable!() };
+        // a pair added by this update is counted once, at its first row
+        let mut counted = vec![false; self.pairs.len() - known];
+        for (&pair, &group) in pairs.iter().zip(groups.values()) {
+            let pair = pair as usize;
+            if pair >= known && !std::mem::replace(&mut counted[pair - known], true) {
+                self.counts[group as usize] += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        Ok(Vector::I64(PrimaryVector::new(self.counts)))
+    }
+}
+
+/// the accumulator of `sum` for a type: `i64` for signed integers, `u64` for unsigned ones,
+/// `f64` for floats and `i128` for decimals. integers wrap around on overflow.
+trait SumType: PrimaryType {
+    type Sum: PrimaryType;
+
+    fn add(sum: Self::Sum, value: Self) -> Self::Sum;
+
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_sum_type {
+    ($sum:ty, $add:expr; $($t:ty),*) => {
+        $(impl SumType for $t {
+            type Sum = $sum;
+
+            #[inline]
+            fn add(sum: $sum, value: Self) -> $sum {
+                $add(sum, value as $sum)
+            }
+
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        })*
+    };
+}
+
+impl_sum_type!(i64, i64::wrapping_add; i8, i16, i32, i64);
+impl_sum_type!(u64, u64::wrapping_add; u8, u16, u32, u64);
+impl_sum_type!(f64, std::ops::Add::add; f32, f64);
+impl_sum_type!(i128, i128::wrapping_add; i128);
+
+struct Sum<T: SumType> {
+    data_type: DataType,
+    sums: Vec<T::Sum>,
+    seen: Vec<bool>,
+}
+
+impl<T: SumType> Sum<T> {
+    fn new(data_type: DataType) -> Self {
+        Sum { data_type, sums: vec![], seen: vec![] }
+    }
+}
+
+impl<T: SumType> AggregateFunction for Sum<T> {
+    fn resize(&mut self, groups: usize) {
+        self.sums.resize(groups, T::Sum::default());
+        self.seen.resize(groups, false);
+    }
+
+    fn update(&mut self, groups: &[u32], input: &Vector) -> Result<()> {
+        let input = T::vector(input).ok_or_else(|| mismatch::<T>(input))?;
+        for_each_valid(groups, input, |g, value| {
+            self.sums[g] = T::add(self.sums[g], value);
+            self.seen[g] = true;
+        });
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        let validity: Bitmap = self.seen.into_iter().collect();
+        Ok(into_vector(&self.data_type, PrimaryVector::with_validity(self.sums, Some(validity))))
+    }
+}
+
+struct Avg<T: SumType> {
+    sums: Vec<f64>,
+    counts: Vec<u64>,
+    marker: std::marker::PhantomData<T>,
+}
+
+impl<T: SumType> Avg<T> {
+    fn new(_data_type: DataType) -> Self {
+        Avg { sums: vec![], counts: vec![], marker: std::marker::PhantomData }
+    }
+}
+
+impl<T: SumType> AggregateFunction for Avg<T> {
+    fn resize(&mut self, groups: usize) {
+        self.sums.resize(groups, 0.0);
+        self.counts.resize(groups, 0);
+    }
+
+    fn update(&mut self, groups: &[u32], input: &Vector) -> Result<()> {
+        let input = T::vector(input).ok_or_else(|| mismatch::<T>(input))?;
+        for_each_valid(groups, input, |g, value| {
+            self.sums[g] += value.to_f64();
+            self.counts[g] += 1;
+        });
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        let averages = self.sums.iter().zip(&self.counts).map(|(&sum, &count)| (count > 0).then(|| sum / count as f64));
+        Ok(Vector::F64(PrimaryVector::from_options(averages)))
+    }
+}
+
+/// `sum / count` rounded half away from zero, without overflowing for sums near the limits
+pub(crate) fn average_decimal(sum: i128, count: i128) -> i128 {
+    let (quotient, remainder) = (sum / count, sum % count);
+    if remainder.unsigned_abs() >= count.unsigned_abs() - remainder.unsigned_abs() {
+        quotient + sum.signum()
+    } else {
+        quotient
+    }
+}
+
+/// the average of decimals is a decimal, rounded half away from zero
+struct AvgDecimal {
+    sums: Vec<i128>,
+    counts: Vec<i128>,
+}
+
+impl AggregateFunction for AvgDecimal {
+    fn resize(&mut self, groups: usize) {
+        self.sums.resize(groups, 0);
+        self.counts.resize(groups, 0);
+    }
+
+    fn update(&mut self, groups: &[u32], input: &Vector) -> Result<()> {
+        let input = i128::vector(input).ok_or_else(|| mismatch::<i128>(input))?;
+        for_each_valid(groups, input, |g, value| {
+            self.sums[g] = self.sums[g].wrapping_add(value);
+            self.counts[g] += 1;
+        });
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        let averages = self.sums.iter().zip(&self.counts).map(|(&sum, &count)| {
+            (count > 0).then(|| average_decimal(sum, count))
+        });
+        Ok(Vector::Decimal(PrimaryVector::from_options(averages)))
+    }
+}
+
+struct MinMax<T: PrimaryType> {
+    data_type: DataType,
+    values: Vec<T>,
+    seen: Vec<bool>,
+    max: bool,
+}
+
+impl<T: PrimaryType> MinMax<T> {
+    fn new(data_type: DataType, max: bool) -> Self {
+        MinMax { data_type, values: vec![], seen: vec![], max }
+    }
+}
+
+impl<T: PrimaryType> AggregateFunction for MinMax<T> {
+    fn resize(&mut self, groups: usize) {
+        self.values.resize(groups, T::default());
+        self.seen.resize(groups, false);
+    }
+
+    fn update(&mut self, groups: &[u32], input: &Vector) -> Result<()> {
+        let input = T::vector(input).ok_or_else(|| mismatch::<T>(input))?;
+        let max = self.max;
+        for_each_valid(groups, input, |g, value| {
+            let current = self.values[g];
+            if !self.seen[g] || (max && value > current) || (!max && value < current) {
+                self.values[g] = value;
+                self.seen[g] = true;
+            }
+        });
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        let validity: Bitmap = self.seen.into_iter().collect();
+        Ok(into_vector(&self.data_type, PrimaryVector::with_validity(self.values, Some(validity))))
+    }
+}
+
+struct StringMinMax {
+    values: Vec<Option<String>>,
+    max: bool,
+}
+
+impl AggregateFunction for StringMinMax {
+    fn resize(&mut self, groups: usize) {
+        self.values.resize(groups, None);
+    }
+
+    fn update(&mut self, groups: &[u32], input: &Vector) -> Result<()> {
+        let Vector::String(input) = input else {
+            return Err(Error::TypeMismatch { expected: "String".to_string(), found: format!("{:?}", input.data_type()) });
+        };
+        for (i, &g) in groups.iter().enumerate() {
+            let Some(value) = input.get(i) else { continue };
+            let state = &mut self.values[g as usize];
+            let replace = match state {
+                None => true,
+                Some(current) => if self.max { value > current.as_str() } else { value < current.as_str() },
+            };
+            if replace {
+                *state = Some(value.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        Ok(Vector::String(StringVector::from_options(self.values.iter().map(|v| v.as_deref()))))
+    }
+}
+
+/// the value of the first or last row of each group, nulls included
+struct FirstLast {
+    data_type: DataType,
+    values: Vec<Value>,
+    seen: Vec<bool>,
+    last: bool,
+}
+
+impl AggregateFunction for FirstLast {
+    fn resize(&mut self, groups: usize) {
+        self.values.resize(groups, Value::Null);
+        self.seen.resize(groups, false);
+    }
+
+    fn update(&mut self, groups: &[u32], input: &Vector) -> Result<()> {
+        if self.last {
+            for (i, &g) in groups.iter().enumerate() {
+                self.values[g as usize] = input.get(i);
+            }
+        } else {
+            for (i, &g) in groups.iter().enumerate() {
+                if !std::mem::replace(&mut self.seen[g as usize], true) {
+                    self.values[g as usize] = input.get(i);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        Vector::from_values(&self.data_type, &self.values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aggregate(kind: AggregateKind, groups: &[u32], input: Vector) -> Vector {
+        let mut function = kind.create(&input.data_type()).unwrap();
+        let count = groups.iter().max().map(|&g| g as usize + 1).unwrap_or(0);
+        function.resize(count);
+        // two updates, states carry over
+        let half = groups.len() / 2;
+        function.update(&groups[..half], &input.slice(0, half)).unwrap();
+        function.update(&groups[half..], &input.slice(half, groups.len() - half)).unwrap();
+        function.finish().unwrap()
+    }
+
+    #[test]
+    fn test_numeric_aggregates() {
+        let groups = [0, 1, 0, 1, 2, 0];
+        let input = Vector::from(vec![Some(3i32), Some(5), None, Some(-2), None, Some(4)]);
+        assert_eq!(aggregate(AggregateKind::Count, &groups, input.clone()), Vector::from(vec![2i64, 2, 0]));
+        assert_eq!(aggregate(AggregateKind::Sum, &groups, input.clone()), Vector::from(vec![Some(7i64), Some(3), None]));
+        assert_eq!(aggregate(AggregateKind::Min, &groups, input.clone()), Vector::from(vec![Some(3i32), Some(-2), None]));
+        assert_eq!(aggregate(AggregateKind::Max, &groups, input.clone()), Vector::from(vec![Some(4i32), Some(5), None]));
+        assert_eq!(aggregate(AggregateKind::Avg, &groups, input), Vector::from(vec![Some(3.5f64), Some(1.5), None]));
+
+        let decimals = Vector::Decimal(PrimaryVector::new(vec![10000i128, 20000, 5]));
+        assert_eq!(aggregate(AggregateKind::Avg, &[0, 0, 1], decimals).get(0), Value::Decimal(15000));
+        assert_eq!(average_decimal(-5, 2), -3);
+        assert_eq!(average_decimal(i128::MAX, 2), i128::MAX / 2 + 1);
+        assert_eq!(average_decimal(i128::MIN + 1, 3), (i128::MIN + 1) / 3);
+    }
+
+    #[test]
+    fn test_distinct_first_last() {
+        let groups = [0, 0, 1, 0, 1, 0];
+        let input = Vector::from(vec![Some("a"), Some("b"), Some("a"), Some("a"), None, None]);
+        assert_eq!(aggregate(AggregateKind::CountDistinct, &groups, input.clone()), Vector::from(vec![2i64, 1]));
+        assert_eq!(aggregate(AggregateKind::Max, &groups, input.clone()), Vector::from(vec!["b", "a"]));
+        assert_eq!(aggregate(AggregateKind::First, &groups, input.clone()), Vector::from(vec!["a", "a"]));
+        assert_eq!(aggregate(AggregateKind::Last, &groups, input), Vector::from(vec![None, None::<&str>]));
+    }
+
+    #[test]
+    fn test_result_types() {
+        assert_eq!(AggregateKind::Sum.result_type(&DataType::U8).unwrap(), DataType::U64);
+        assert_eq!(AggregateKind::Min.result_type(&DataType::Date).unwrap(), DataType::Date);
+        assert!(AggregateKind::Sum.result_type(&DataType::String).is_err());
+    }
+}
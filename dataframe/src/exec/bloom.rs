@@ -0,0 +1,84 @@
+//! A blocked bloom filter of key hashes, the split block bloom filter of parquet.
+//!
+//! The filter is an array of 256-bit blocks of 8 words. A key sets one bit in every word of a
+//! single block: the high 32 bits of its hash choose the block, the low 32 bits multiplied by a
+//! salt per word choose the bits. A lookup reads one block and tests the 8 bits in one SIMD
+//! compare, with 16 bits per key less than 1% of the absent keys pass.
+
+use std::simd::cmp::SimdPartialEq;
+use std::simd::Simd;
+
+const WORDS: usize = 8;
+
+type Block = Simd<u32, WORDS>;
+
+/// the odd multipliers of the words of a block, those of parquet
+const SALT: Block = Simd::from_array([
+    0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d, 0x705495c7, 0x2df1424b, 0x9efc4947, 0x5c6bfb31,
+]);
+
+/// the bits of the filter per inserted key
+const BITS_PER_KEY: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    blocks: Vec<Block>,
+}
+
+/// the bit of every word set by a key with hash `hash`
+#[inline]
+fn mask(hash: u64) -> Block {
+    let bits = (Block::splat(hash as u32) * SALT) >> Block::splat(27);
+    Block::splat(1) << bits
+}
+
+impl BloomFilter {
+    /// a filter sized for `keys` keys
+    pub fn new(keys: usize) -> Self {
+        let blocks = (keys * BITS_PER_KEY).div_ceil(WORDS * 32).max(1);
+        BloomFilter { blocks: vec![Block::splat(0); blocks] }
+    }
+
+    #[inline]
+    fn block(&self, hash: u64) -> usize {
+        (((hash >> 32) * self.blocks.len() as u64) >> 32) as usize
+    }
+
+    pub fn insert(&mut self, hash: u64) {
+        let block = self.block(hash);
+        self.blocks[block] |= mask(hash);
+    }
+
+    /// false if no key with hash `hash` was inserted, true if one may have been
+    #[inline]
+    pub fn contains(&self, hash: u64) -> bool {
+        let mask = mask(hash);
+        (self.blocks[self.block(hash)] & mask).simd_eq(mask).all()
+    }
+
+    /// the memory size of the filter in bytes
+    pub fn size(&self) -> usize {
+        self.blocks.len() * WORDS * 4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::hash::hash_columns;
+    use crate::vector::Vector;
+
+    #[test]
+    fn test_bloom_filter() {
+        let keys = Vector::from((0..10_000i64).map(|i| i * 3).collect::<Vec<_>>());
+        let mut bloom = BloomFilter::new(keys.len());
+        assert_eq!(bloom.size(), 20_000);
+        let hashes = hash_columns(&[&keys], keys.len());
+        hashes.iter().for_each(|&hash| bloom.insert(hash));
+        assert!(hashes.iter().all(|&hash| bloom.contains(hash)));
+
+        let absent = Vector::from((0..10_000i64).map(|i| i * 3 + 1).collect::<Vec<_>>());
+        let passed = hash_columns(&[&absent], absent.len()).into_iter().filter(|&hash| bloom.contains(hash)).count();
+        assert!(passed < 100, "{passed} false positives");
+    }
+}
@@ -0,0 +1,215 @@
+//! Conversions between data types, used by `Cast` expressions and to fold literals.
+//!
+//! numeric conversions follow rust's `as` (integers wrap, floats saturate), decimals are rounded
+//! to DECIMAL_SCALE digits. a string that can not be parsed is an error for a single value and
+//! null inside a vector.
+
+use crate::error::{Error, Result};
+use crate::qir::DataType;
+use crate::vector::{PrimaryType, Value, Vector, VectorBuilder, DECIMAL_SCALE};
+
+const MICROS_PER_DAY: i64 = 86_400_000_000;
+
+/// conversion from and to the scaled `i128` of a decimal
+trait DecimalCast: PrimaryType {
+    fn to_decimal(self) -> i128;
+    fn from_decimal(value: i128) -> Self;
+}
+
+macro_rules! impl_decimal_cast {
+    (int: $($t:ty),*) => {$(
+        impl DecimalCast for $t {
+            fn to_decimal(self) -> i128 {
+                (self as i128).wrapping_mul(10i128.pow(DECIMAL_SCALE))
+            }
+            fn from_decimal(value: i128) -> Self {
+                (value / 10i128.pow(DECIMAL_SCALE)) as $t
+            }
+        }
+    )*};
+    (float: $($t:ty),*) => {$(
+        impl DecimalCast for $t {
+            fn to_decimal(self) -> i128 {
+                (self as f64 * 10f64.powi(DECIMAL_SCALE as i32)).round() as i128
+            }
+            fn from_decimal(value: i128) -> Self {
+                (value as f64 / 10f64.powi(DECIMAL_SCALE as i32)) as $t
+            }
+        }
+    )*};
+}
+
+impl_decimal_cast!(int: i8, i16, i32, i64, u8, u16, u32, u64);
+impl_decimal_cast!(float: f32, f64);
+
+/// convert a vector of a fixed width numeric type with `as`
+macro_rules! cast_numeric {
+    ($v:expr, $to:expr) => {
+        match $to {
+            DataType::I8 => Some(Vector::I8($v.map(|x| x as i8))),
+            DataType::I16 => Some(Vector::I16($v.map(|x| x as i16))),
+            DataType::I32 => Some(Vector::I32($v.map(|x| x as i32))),
+            DataType::I64 => Some(Vector::I64($v.map(|x| x as i64))),
+            DataType::U8 => Some(Vector::U8($v.map(|x| x as u8))),
+            DataType::U16 => Some(Vector::U16($v.map(|x| x as u16))),
+            DataType::U32 => Some(Vector::U32($v.map(|x| x as u32))),
+            DataType::U64 => Some(Vector::U64($v.map(|x| x as u64))),
+            DataType::F32 => Some(Vector::F32($v.map(|x| x as f32))),
+            DataType::F64 => Some(Vector::F64($v.map(|x| x as f64))),
+            DataType::Decimal => Some(Vector::Decimal($v.map(DecimalCast::to_decimal))),
+            _ => None,
+        }
+    };
+}
+
+/// whether values of `from` can be converted to `to`
+pub fn can_cast(from: &DataType, to: &DataType) -> bool {
+    let scalar = |t: &DataType| !matches!(t, DataType::List(_) | DataType::Struct(_) | DataType::Map(..));
+    from == to
+        || (from.is_numeric() || *from == DataType::Bool) && (to.is_numeric() || *to == DataType::Bool)
+        || from.is_temporal() && to.is_temporal()
+        || *from == DataType::String && scalar(to)
+        || *to == DataType::String && scalar(from)
+}
+
+fn invalid(value: &Value, to: &DataType) -> Error {
+    Error::InvalidCast { value: format!("{value:?}"), data_type: format!("{to:?}") }
+}
+
+/// convert a single value, `Null` stays `Null`
+pub fn cast_value(value: &Value, to: &DataType) -> Result<Value> {
+    if value.is_null() || value.data_type().as_ref() == Some(to) {
+        return Ok(value.clone());
+    }
+    match (value, to) {
+        (_, DataType::String) if can_cast(&value.data_type().unwrap_or(DataType::String), to) => Ok(Value::String(value.to_string())),
+        (Value::String(text), _) => parse(text.trim(), to).ok_or_else(|| invalid(value, to)),
+        (Value::Bool(b), _) if to.is_numeric() => cast_value(&Value::I8(*b as i8), to),
+        (_, DataType::Bool) if value.data_type().is_some_and(|t| t.is_numeric()) => {
+            let zero = cast_value(&Value::I8(0), &value.data_type().unwrap())?;
+            Ok(Value::Bool(*value != zero))
+        }
+        (Value::Date(days), DataType::DateTime) => Ok(Value::DateTime(*days as i64 * MICROS_PER_DAY)),
+        (Value::DateTime(micros), DataType::Date) => Ok(Value::Date(micros.div_euclid(MICROS_PER_DAY) as i32)),
+        _ => {
+            let from = value.data_type().ok_or_else(|| invalid(value, to))?;
+            if !(from.is_numeric() && to.is_numeric()) {
+                return Err(invalid(value, to));
+            }
+            let vector = Vector::from_values(&from, std::slice::from_ref(value))?;
+            Ok(cast_vector(&vector, to)?.get(0))
+        }
+    }
+}
+
+/// convert a single value like `cast_value`, but fail instead of wrapping or truncating a number
+/// that the integer type `to` can not hold, e.g. `300` to `U8` or `1.5` to `I32`
+pub fn cast_exact(value: &Value, to: &DataType) -> Result<Value> {
+    let converted = cast_value(value, to)?;
+    if let Some(from) = value.data_type().filter(|f| f.is_numeric() && to.is_integer())
+        && cast_value(&converted, &from)? != *value {
+        return Err(invalid(value, to));
+    }
+    Ok(converted)
+}
+
+fn parse(text: &str, to: &DataType) -> Option<Value> {
+    if to.is_integer() {
+        let value = match text.parse::<i64>() {
+            Ok(v) => Value::I64(v),
+            Err(_) => Value::U64(text.parse().ok()?),
+        };
+        return cast_value(&value, to).ok();
+    }
+    match to {
+        DataType::F32 | DataType::F64 => cast_value(&Value::F64(text.parse().ok()?), to).ok(),
+        DataType::Decimal => Value::parse_decimal(text),
+        DataType::Bool => match text {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        DataType::Date => Value::parse_date(text),
+        DataType::DateTime => Value::parse_datetime(text)
+            .or_else(|| cast_value(&Value::parse_date(text)?, to).ok()),
+        _ => None,
+    }
+}
+
+/// convert a vector, rows that can not be converted become null
+pub fn cast_vector(vector: &Vector, to: &DataType) -> Result<Vector> {
+    let from = vector.data_type();
+    if from == *to {
+        return Ok(vector.clone());
+    }
+    if !can_cast(&from, to) {
+        return Err(Error::TypeMismatch { expected: format!("{to:?}"), found: format!("cast from {from:?}") });
+    }
+    #[allow(clippy::unnecessary_cast)]
+    let fast = match vector {
+        Vector::I8(v) => cast_numeric!(v, to),
+        Vector::I16(v) => cast_numeric!(v, to),
+        Vector::I32(v) => cast_numeric!(v, to),
+        Vector::I64(v) => cast_numeric!(v, to),
+        Vector::U8(v) => cast_numeric!(v, to),
+        Vector::U16(v) => cast_numeric!(v, to),
+        Vector::U32(v) => cast_numeric!(v, to),
+        Vector::U64(v) => cast_numeric!(v, to),
+        Vector::F32(v) => cast_numeric!(v, to),
+        Vector::F64(v) => cast_numeric!(v, to),
+        Vector::Decimal(v) => {
+            macro_rules! from_decimal {
+                ($($variant:ident => $t:ty),*) => {
+                    match to {
+                        $(DataType::$variant => Some(Vector::$variant(v.map(<$t as DecimalCast>::from_decimal))),)*
+                        _ => None,
+                    }
+                };
+            }
+            from_decimal!(I8 => i8, I16 => i16, I32 => i32, I64 => i64, U8 => u8, U16 => u16, U32 => u32, U64 => u64,
+                F32 => f32, F64 => f64)
+        }
+        Vector::Date(v) if *to == DataType::DateTime => Some(Vector::DateTime(v.map(|d| d as i64 * MICROS_PER_DAY))),
+        Vector::DateTime(v) if *to == DataType::Date => Some(Vector::Date(v.map(|m| m.div_euclid(MICROS_PER_DAY) as i32))),
+        _ => None,
+    };
+    if let Some(vector) = fast {
+        return Ok(vector);
+    }
+
+    let mut builder = VectorBuilder::with_capacity(to, vector.len());
+    for i in 0..vector.len() {
+        builder.push(&cast_value(&vector.get(i), to).unwrap_or(Value::Null))?;
+    }
+    Ok(builder.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vector::PrimaryVector;
+    use super::*;
+
+    #[test]
+    fn test_cast_value() {
+        assert_eq!(cast_value(&Value::I32(300), &DataType::U8).unwrap(), Value::U8(44));
+        assert_eq!(cast_value(&Value::F64(1.25), &DataType::Decimal).unwrap(), Value::Decimal(12500));
+        assert_eq!(cast_value(&Value::Decimal(-12500), &DataType::I32).unwrap(), Value::I32(-1));
+        assert_eq!(cast_value(&"1990-01-01".into(), &DataType::Date).unwrap(), Value::Date(7305));
+        assert_eq!(cast_value(&Value::Date(1), &DataType::String).unwrap(), "1970-01-02".into());
+        assert_eq!(cast_value(&Value::I64(2), &DataType::Bool).unwrap(), Value::Bool(true));
+        assert!(cast_value(&"abc".into(), &DataType::I32).is_err());
+    }
+
+    #[test]
+    fn test_cast_vector() {
+        let strings = Vector::from(vec![Some("1"), Some("x"), None]);
+        let ints = cast_vector(&strings, &DataType::I64).unwrap();
+        assert_eq!(ints, Vector::from(vec![Some(1i64), None, None]));
+
+        let floats = cast_vector(&Vector::from(vec![Some(1i32), None]), &DataType::F64).unwrap();
+        assert_eq!(floats, Vector::from(vec![Some(1.0f64), None]));
+
+        let dates = Vector::DateTime(PrimaryVector::new(vec![-1, MICROS_PER_DAY]));
+        assert_eq!(cast_vector(&dates, &DataType::Date).unwrap(), Vector::Date(PrimaryVector::new(vec![-1, 1])));
+    }
+}
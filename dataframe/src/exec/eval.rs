@@ -0,0 +1,439 @@
+//! Vector-at-a-time evaluation of typed expressions.
+
+use regex::Regex;
+use crate::error::{Error, Result};
+use crate::exec::cast::cast_vector;
+use crate::qir::expr::{BinaryOp, Expr, ExprKind};
+use crate::qir::DataType;
+use crate::vector::{Bitmap, DataChunk, PrimaryType, PrimaryVector, SelectionVector, Value, Vector, VectorBuilder, DECIMAL_SCALE};
+
+/// the validity of a row-wise combination of two vectors
+fn and_validity(left: Option<&Bitmap>, right: Option<&Bitmap>) -> Option<Bitmap> {
+    match (left, right) {
+        (None, None) => None,
+        (Some(v), None) | (None, Some(v)) => Some(v.clone()),
+        (Some(l), Some(r)) => Some(l.and(r)),
+    }
+}
+
+fn compare_primary<T: PrimaryType>(op: BinaryOp, left: &PrimaryVector<T>, right: &PrimaryVector<T>) -> PrimaryVector<bool> {
+    macro_rules! compare {
+        ($f:expr) => {
+            left.values().iter().zip(right.values()).map(|(l, r)| $f(l, r)).collect::<Vec<bool>>()
+        };
+    }
+    let values = match op {
+        BinaryOp::Eq => compare!(|l, r| l == r),
+        BinaryOp::NotEq => compare!(|l, r| l != r),
+        BinaryOp::Lt => compare!(|l, r| l < r),
+        BinaryOp::LtEq => compare!(|l, r| l <= r),
+        BinaryOp::Gt => compare!(|l, r| l > r),
+        BinaryOp::GtEq => compare!(|l, r| l >= r),
+        _ => unreachable!("{op:?} is not a comparison"),
+    };
+    PrimaryVector::with_validity(values, and_validity(left.validity(), right.validity()))
+}
+
+fn compare_values<T: PartialOrd>(op: BinaryOp, left: Option<T>, right: Option<T>) -> Option<bool> {
+    let (l, r) = (left?, right?);
+    Some(match op {
+        BinaryOp::Eq => l == r,
+        BinaryOp::NotEq => l != r,
+        BinaryOp::Lt => l < r,
+        BinaryOp::LtEq => l <= r,
+        BinaryOp::Gt => l > r,
+        BinaryOp::GtEq => l >= r,
+        _ => unreachable!("{op:?} is not a comparison"),
+    })
+}
+
+/// compare two vectors of the same type
+fn compare(op: BinaryOp, left: &Vector, right: &Vector) -> Result<Vector> {
+    let result = match (left, right) {
+        (Vector::I8(l), Vector::I8(r)) => compare_primary(op, l, r),
+        (Vector::I16(l), Vector::I16(r)) => compare_primary(op, l, r),
+        (Vector::I32(l), Vector::I32(r)) | (Vector::Date(l), Vector::Date(r)) => compare_primary(op, l, r),
+        (Vector::I64(l), Vector::I64(r)) | (Vector::DateTime(l), Vector::DateTime(r)) => compare_primary(op, l, r),
+        (Vector::U8(l), Vector::U8(r)) => compare_primary(op, l, r),
+        (Vector::U16(l), Vector::U16(r)) => compare_primary(op, l, r),
+        (Vector::U32(l), Vector::U32(r)) => compare_primary(op, l, r),
+        (Vector::U64(l), Vector::U64(r)) => compare_primary(op, l, r),
+        (Vector::F32(l), Vector::F32(r)) => compare_primary(op, l, r),
+        (Vector::F64(l), Vector::F64(r)) => compare_primary(op, l, r),
+        (Vector::Decimal(l), Vector::Decimal(r)) => compare_primary(op, l, r),
+        (Vector::Bool(l), Vector::Bool(r)) => compare_primary(op, l, r),
+        (Vector::String(l), Vector::String(r)) => {
+            PrimaryVector::from_options(l.iter().zip(r.iter()).map(|(l, r)| compare_values(op, l, r)))
+        }
+        _ => {
+            if left.data_type() != right.data_type() {
+                return Err(Error::TypeMismatch { expected: format!("{:?}", left.data_type()), found: format!("{:?}", right.data_type()) });
+            }
+            let rows = (0..left.len()).map(|i| {
+                let (l, r) = (left.get(i), right.get(i));
+                compare_values(op, (!l.is_null()).then_some(l), (!r.is_null()).then_some(r))
+            });
+            PrimaryVector::from_options(rows)
+        }
+    };
+    Ok(Vector::Bool(result))
+}
+
+/// arithmetic on a primary type. division and remainder return `None` for a zero divisor.
+trait Arithmetic: PrimaryType {
+    fn add(self, other: Self) -> Self;
+    fn sub(self, other: Self) -> Self;
+    fn mul(self, other: Self) -> Self;
+    fn div(self, other: Self) -> Option<Self>;
+    fn rem(self, other: Self) -> Option<Self>;
+    fn neg(self) -> Self;
+}
+
+macro_rules! impl_integer_arithmetic {
+    ($($t:ty),*) => {$(
+        impl Arithmetic for $t {
+            fn add(self, other: Self) -> Self { self.wrapping_add(other) }
+            fn sub(self, other: Self) -> Self { self.wrapping_sub(other) }
+            fn mul(self, other: Self) -> Self { self.wrapping_mul(other) }
+            fn div(self, other: Self) -> Option<Self> { (other != 0).then(|| self.wrapping_div(other)) }
+            fn rem(self, other: Self) -> Option<Self> { (other != 0).then(|| self.wrapping_rem(other)) }
+            fn neg(self) -> Self { self.wrapping_neg() }
+        }
+    )*};
+}
+
+macro_rules! impl_float_arithmetic {
+    ($($t:ty),*) => {$(
+        impl Arithmetic for $t {
+            fn add(self, other: Self) -> Self { self + other }
+            fn sub(self, other: Self) -> Self { self - other }
+            fn mul(self, other: Self) -> Self { self * other }
+            fn div(self, other: Self) -> Option<Self> { Some(self / other) }
+            fn rem(self, other: Self) -> Option<Self> { Some(self % other) }
+            fn neg(self) -> Self { -self }
+        }
+    )*};
+}
+
+impl_integer_arithmetic!(i8, i16, i32, i64, u8, u16, u32, u64);
+impl_float_arithmetic!(f32, f64);
+
+/// `i128` is only used for decimals, so its multiplication and division keep the scale. a division
+/// or remainder that overflows is null, like one by zero
+impl Arithmetic for i128 {
+    fn add(self, other: Self) -> Self { self.wrapping_add(other) }
+    fn sub(self, other: Self) -> Self { self.wrapping_sub(other) }
+    fn mul(self, other: Self) -> Self { self.wrapping_mul(other) / 10i128.pow(DECIMAL_SCALE) }
+    fn div(self, other: Self) -> Option<Self> { self.checked_mul(10i128.pow(DECIMAL_SCALE))?.checked_div(other) }
+    fn rem(self, other: Self) -> Option<Self> { self.checked_rem(other) }
+    fn neg(self) -> Self { self.wrapping_neg() }
+}
+
+fn arithmetic_primary<T: Arithmetic>(op: BinaryOp, left: &PrimaryVector<T>, right: &PrimaryVector<T>) -> PrimaryVector<T> {
+    let validity = and_validity(left.validity(), right.validity());
+    let pairs = left.values().iter().zip(right.values());
+    match op {
+        BinaryOp::Add => PrimaryVector::with_validity(pairs.map(|(l, r)| l.add(*r)).collect::<Vec<T>>(), validity),
+        BinaryOp::Sub => PrimaryVector::with_validity(pairs.map(|(l, r)| l.sub(*r)).collect::<Vec<T>>(), validity),
+        BinaryOp::Mul => PrimaryVector::with_validity(pairs.map(|(l, r)| l.mul(*r)).collect::<Vec<T>>(), validity),
+        BinaryOp::Div | BinaryOp::Mod => {
+            let f = if op == BinaryOp::Div { T::div } else { T::rem };
+            let valid = |i: usize| validity.as_ref().is_none_or(|v| v.get(i));
+            PrimaryVector::from_options(pairs.enumerate().map(|(i, (l, r))| if valid(i) { f(*l, *r) } else { None }))
+        }
+        _ => unreachable!("{op:?} is not arithmetic"),
+    }
+}
+
+fn arithmetic(op: BinaryOp, left: &Vector, right: &Vector) -> Result<Vector> {
+    Ok(match (left, right) {
+        (Vector::I8(l), Vector::I8(r)) => Vector::I8(arithmetic_primary(op, l, r)),
+        (Vector::I16(l), Vector::I16(r)) => Vector::I16(arithmetic_primary(op, l, r)),
+        (Vector::I32(l), Vector::I32(r)) => Vector::I32(arithmetic_primary(op, l, r)),
+        (Vector::I64(l), Vector::I64(r)) => Vector::I64(arithmetic_primary(op, l, r)),
+        (Vector::U8(l), Vector::U8(r)) => Vector::U8(arithmetic_primary(op, l, r)),
+        (Vector::U16(l), Vector::U16(r)) => Vector::U16(arithmetic_primary(op, l, r)),
+        (Vector::U32(l), Vector::U32(r)) => Vector::U32(arithmetic_primary(op, l, r)),
+        (Vector::U64(l), Vector::U64(r)) => Vector::U64(arithmetic_primary(op, l, r)),
+        (Vector::F32(l), Vector::F32(r)) => Vector::F32(arithmetic_primary(op, l, r)),
+        (Vector::F64(l), Vector::F64(r)) => Vector::F64(arithmetic_primary(op, l, r)),
+        (Vector::Decimal(l), Vector::Decimal(r)) => Vector::Decimal(arithmetic_primary(op, l, r)),
+        // date +/- days, date - date
+        (Vector::Date(l), Vector::I32(r)) if matches!(op, BinaryOp::Add | BinaryOp::Sub) => Vector::Date(arithmetic_primary(op, l, r)),
+        (Vector::Date(l), Vector::Date(r)) if op == BinaryOp::Sub => Vector::I32(arithmetic_primary(op, l, r)),
+        _ => return Err(Error::Unsupported(format!("{:?} {} {:?}", left.data_type(), op.symbol(), right.data_type()))),
+    })
+}
+
+fn as_bool(vector: &Vector) -> Result<&PrimaryVector<bool>> {
+    match vector {
+        Vector::Bool(v) => Ok(v),
+        other => Err(Error::TypeMismatch { expected: "Bool".into(), found: format!("{:?}", other.data_type()) }),
+    }
+}
+
+/// three valued `AND`/`OR`: `false AND null` is false, `true OR null` is true
+fn logical(op: BinaryOp, left: &Vector, right: &Vector) -> Result<Vector> {
+    let (left, right) = (as_bool(left)?, as_bool(right)?);
+    if left.validity().is_none() && right.validity().is_none() {
+        let values: Vec<bool> = left.values().iter().zip(right.values())
+            .map(|(l, r)| if op == BinaryOp::And { *l && *r } else { *l || *r })
+            .collect();
+        return Ok(Vector::Bool(PrimaryVector::new(values)));
+    }
+    // the value that decides the result regardless of the other side
+    let dominant = op == BinaryOp::Or;
+    let rows = left.iter().zip(right.iter()).map(|(l, r)| match (l, r) {
+        (Some(l), _) if l == dominant => Some(dominant),
+        (_, Some(r)) if r == dominant => Some(dominant),
+        (Some(_), Some(_)) => Some(!dominant),
+        _ => None,
+    });
+    Ok(Vector::Bool(PrimaryVector::from_options(rows)))
+}
+
+fn negate(vector: &Vector) -> Result<Vector> {
+    Ok(match vector {
+        Vector::I8(v) => Vector::I8(v.map(Arithmetic::neg)),
+        Vector::I16(v) => Vector::I16(v.map(Arithmetic::neg)),
+        Vector::I32(v) => Vector::I32(v.map(Arithmetic::neg)),
+        Vector::I64(v) => Vector::I64(v.map(Arithmetic::neg)),
+        Vector::F32(v) => Vector::F32(v.map(Arithmetic::neg)),
+        Vector::F64(v) => Vector::F64(v.map(Arithmetic::neg)),
+        Vector::Decimal(v) => Vector::Decimal(v.map(Arithmetic::neg)),
+        other => return Err(Error::Unsupported(format!("-{:?}", other.data_type()))),
+    })
+}
+
+/// translate a sql `LIKE` pattern to an anchored regex
+fn like_regex(pattern: &str) -> Result<Regex> {
+    let mut regex = String::from("(?s)^");
+    for c in pattern.chars() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).map_err(|e| Error::Unsupported(format!("like pattern {pattern}: {e}")))
+}
+
+fn like(vector: &Vector, pattern: &str, negated: bool) -> Result<Vector> {
+    let strings = match vector {
+        Vector::String(v) => v,
+        other => return Err(Error::TypeMismatch { expected: "String".into(), found: format!("{:?}", other.data_type()) }),
+    };
+    let body = pattern.trim_end_matches('%');
+    let matches: Box<dyn Fn(&str) -> bool> = if !body.contains(['%', '_']) && body.len() + 1 == pattern.len() {
+        // `prefix%`
+        let prefix = body.to_string();
+        Box::new(move |s| s.starts_with(&prefix))
+    } else if !pattern.contains(['%', '_']) {
+        let pattern = pattern.to_string();
+        Box::new(move |s| s == pattern)
+    } else {
+        let regex = like_regex(pattern)?;
+        Box::new(move |s| regex.is_match(s))
+    };
+    let values: Vec<bool> = (0..strings.len()).map(|i| matches(strings.value(i)) != negated).collect();
+    Ok(Vector::Bool(PrimaryVector::with_validity(values, strings.validity().cloned())))
+}
+
+/// a scalar function, evaluated row by row
+fn call(name: &str, args: &[Value]) -> Value {
+    use chrono::Datelike;
+    let date = |value: &Value| match value {
+        Value::Date(days) => Some(chrono::NaiveDate::default() + chrono::Duration::days(*days as i64)),
+        Value::DateTime(micros) => chrono::DateTime::from_timestamp_micros(*micros).map(|d| d.date_naive()),
+        _ => None,
+    };
+    if name == "coalesce" {
+        return args.iter().find(|a| !a.is_null()).cloned().unwrap_or(Value::Null);
+    }
+    if args.iter().any(Value::is_null) {
+        return Value::Null;
+    }
+    let string = |i: usize| match &args[i] {
+        Value::String(s) => s.as_str(),
+        _ => "",
+    };
+    let integer = |i: usize| crate::exec::cast::cast_value(&args[i], &DataType::I64).ok().and_then(|v| i64::from_value(&v));
+    match name {
+        "abs" => match &args[0] {
+            Value::I8(v) => Value::I8(v.wrapping_abs()),
+            Value::I16(v) => Value::I16(v.wrapping_abs()),
+            Value::I32(v) => Value::I32(v.wrapping_abs()),
+            Value::I64(v) => Value::I64(v.wrapping_abs()),
+            Value::F32(v) => Value::F32(v.abs()),
+            Value::F64(v) => Value::F64(v.abs()),
+            Value::Decimal(v) => Value::Decimal(v.wrapping_abs()),
+            other => other.clone(),
+        },
+        "lower" => Value::String(string(0).to_lowercase()),
+        "upper" => Value::String(string(0).to_uppercase()),
+        "trim" => Value::String(string(0).trim().to_string()),
+        "length" => Value::I64(string(0).chars().count() as i64),
+        "substr" => {
+            // 1-based start, sql style
+            let (start, len) = (integer(1).unwrap_or(1).max(1) as usize - 1, integer(2).unwrap_or(0).max(0) as usize);
+            Value::String(string(0).chars().skip(start).take(len).collect())
+        }
+        "concat" => Value::String((0..args.len()).map(string).collect()),
+        "starts_with" => Value::Bool(string(0).starts_with(string(1))),
+        "ends_with" => Value::Bool(string(0).ends_with(string(1))),
+        "contains" => Value::Bool(string(0).contains(string(1))),
+        "year" => date(&args[0]).map(|d| Value::I32(d.year())).unwrap_or(Value::Null),
+        "month" => date(&args[0]).map(|d| Value::I32(d.month() as i32)).unwrap_or(Value::Null),
+        "day" => date(&args[0]).map(|d| Value::I32(d.day() as i32)).unwrap_or(Value::Null),
+        "to_date" => crate::exec::cast::cast_value(&args[0], &DataType::Date).unwrap_or(Value::Null),
+        "to_timestamp_seconds" => integer(0).map(|s| Value::DateTime(s.wrapping_mul(1_000_000))).unwrap_or(Value::Null),
+        _ => Value::Null,
+    }
+}
+
+impl Expr {
+    /// evaluate against a chunk whose columns are named `names`, producing a vector of `self.data_type`
+    pub fn evaluate(&self, chunk: &DataChunk, names: &[String]) -> Result<Vector> {
+        match &self.kind {
+            ExprKind::Column(name) => {
+                let index = names.iter().position(|n| n == name).ok_or_else(|| Error::ColumnNotFound(name.clone()))?;
+                Ok(chunk.column(index).clone())
+            }
+            ExprKind::Literal(value) => {
+                let mut builder = VectorBuilder::with_capacity(&self.data_type, chunk.len());
+                for _ in 0..chunk.len() {
+                    builder.push(value)?;
+                }
+                Ok(builder.finish())
+            }
+            ExprKind::Binary { op, left, right } => {
+                let (l, r) = (left.evaluate(chunk, names)?, right.evaluate(chunk, names)?);
+                if op.is_comparison() {
+                    compare(*op, &l, &r)
+                } else if op.is_logical() {
+                    logical(*op, &l, &r)
+                } else {
+                    arithmetic(*op, &l, &r)
+                }
+            }
+            ExprKind::Not(expr) => {
+                let value = expr.evaluate(chunk, names)?;
+                Ok(Vector::Bool(as_bool(&value)?.map(|b| !b)))
+            }
+            ExprKind::Negate(expr) => negate(&expr.evaluate(chunk, names)?),
+            ExprKind::IsNull { expr, negated } => {
+                let value = expr.evaluate(chunk, names)?;
+                let values: Vec<bool> = (0..value.len()).map(|i| value.is_valid(i) == *negated).collect();
+                Ok(Vector::Bool(PrimaryVector::new(values)))
+            }
+            ExprKind::Like { expr, pattern, negated } => like(&expr.evaluate(chunk, names)?, pattern, *negated),
+            ExprKind::InList { expr, list, negated } => {
+                let value = expr.evaluate(chunk, names)?;
+                let mut result = Vector::Bool(PrimaryVector::new(vec![false; chunk.len()]));
+                for item in list {
+                    let equal = compare(BinaryOp::Eq, &value, &item.evaluate(chunk, names)?)?;
+                    result = logical(BinaryOp::Or, &result, &equal)?;
+                }
+                if *negated {
+                    result = Vector::Bool(as_bool(&result)?.map(|b| !b));
+                }
+                Ok(result)
+            }
+            ExprKind::Cast(expr) => cast_vector(&expr.evaluate(chunk, names)?, &self.data_type),
+            ExprKind::Function { name, args } => {
+                let args = args.iter().map(|a| a.evaluate(chunk, names)).collect::<Result<Vec<_>>>()?;
+                let mut builder = VectorBuilder::with_capacity(&self.data_type, chunk.len());
+                for i in 0..chunk.len() {
+                    let row: Vec<Value> = args.iter().map(|a| a.get(i)).collect();
+                    builder.push(&call(name, &row))?;
+                }
+                Ok(builder.finish())
+            }
+        }
+    }
+
+    /// evaluate a predicate, setting the bits of the rows where it is true (not false or null)
+    pub fn mask(&self, chunk: &DataChunk, names: &[String]) -> Result<Bitmap> {
+        let result = self.evaluate(chunk, names)?;
+        let result = as_bool(&result)?;
+        let bits: Bitmap = result.values().iter().copied().collect();
+        Ok(match result.validity() {
+            Some(validity) => bits.and(validity),
+            None => bits,
+        })
+    }
+
+    /// evaluate a predicate, selecting the rows where it is true (not false or null)
+    pub fn select(&self, chunk: &DataChunk, names: &[String]) -> Result<SelectionVector> {
+        Ok(SelectionVector::from_bitmap(&self.mask(chunk, names)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::column;
+    use crate::qir::Column;
+    use super::*;
+
+    fn chunk() -> (Vec<Column>, Vec<String>, DataChunk) {
+        let columns = vec![
+            column! { name = "gender", data_type = String },
+            column! { name = "freight", data_type = F64 },
+            column! { name = "quantity", data_type = I32 },
+        ];
+        let names = columns.iter().map(|c| c.name.clone()).collect();
+        let chunk = DataChunk::new(vec![
+            Vector::from(vec![Some("M"), Some("F"), Some("M"), None]),
+            Vector::from(vec![Some(12.5), Some(20.0), None, Some(30.0)]),
+            Vector::from(vec![Some(3), Some(0), Some(4), None]),
+        ]);
+        (columns, names, chunk)
+    }
+
+    #[test]
+    fn test_readme_predicate() {
+        let (columns, names, chunk) = chunk();
+        let predicate = Expr::binary(BinaryOp::Eq, Expr::column(&columns, "gender").unwrap(), Expr::literal("M")).unwrap()
+            .and(Expr::binary(BinaryOp::Gt, Expr::column(&columns, "freight").unwrap(), Expr::literal(10)).unwrap())
+            .unwrap();
+        assert_eq!(predicate.evaluate(&chunk, &names).unwrap(), Vector::from(vec![Some(true), Some(false), None, None]));
+        assert_eq!(predicate.select(&chunk, &names).unwrap().indices(), &[0]);
+    }
+
+    #[test]
+    fn test_arithmetic_and_nulls() {
+        let (columns, names, chunk) = chunk();
+        let freight = Expr::column(&columns, "freight").unwrap();
+        let quantity = Expr::column(&columns, "quantity").unwrap();
+
+        let ratio = Expr::binary(BinaryOp::Div, Expr::literal(12), quantity.clone()).unwrap();
+        assert_eq!(ratio.evaluate(&chunk, &names).unwrap(), Vector::from(vec![Some(4), None, Some(3), None]));
+
+        let total = Expr::binary(BinaryOp::Mul, freight, quantity.clone()).unwrap();
+        assert_eq!(total.evaluate(&chunk, &names).unwrap().get(0), Value::F64(37.5));
+
+        let is_null = quantity.clone().is_null(false).or(Expr::binary(BinaryOp::Gt, quantity, Expr::literal(3)).unwrap()).unwrap();
+        assert_eq!(is_null.evaluate(&chunk, &names).unwrap(), Vector::from(vec![false, false, true, true]));
+
+        // decimal division keeps the scale, and is null where it would overflow
+        assert_eq!(Arithmetic::div(15_0000i128, 2_0000), Some(7_5000));
+        assert_eq!(Arithmetic::div(i128::MIN, -1), None);
+        assert_eq!(Arithmetic::div(i128::MAX / 100, 1_0000), None);
+        assert_eq!(Arithmetic::rem(i128::MIN, -1), None);
+    }
+
+    #[test]
+    fn test_like_in_and_functions() {
+        let (columns, names, chunk) = chunk();
+        let gender = Expr::column(&columns, "gender").unwrap();
+        assert_eq!(gender.clone().like("M%", false).unwrap().select(&chunk, &names).unwrap().indices(), &[0, 2]);
+        assert_eq!(gender.clone().like("_", true).unwrap().select(&chunk, &names).unwrap().indices(), &[] as &[u32]);
+
+        let in_list = gender.clone().in_list(vec![Expr::literal("F"), Expr::literal("X")], false).unwrap();
+        assert_eq!(in_list.select(&chunk, &names).unwrap().indices(), &[1]);
+
+        let lower = Expr::function("coalesce", vec![Expr::function("lower", vec![gender]).unwrap(), Expr::literal("?")]).unwrap();
+        assert_eq!(lower.evaluate(&chunk, &names).unwrap(), Vector::from(vec!["m", "f", "m", "?"]));
+    }
+}
@@ -0,0 +1,306 @@
+//! The external sort of the `order_by` sink, for inputs larger than the memory budget.
+//!
+//! Chunks are buffered until their size exceeds the budget of the execution context, then the
+//! buffered rows are sorted by their normalized keys and spilled to a temp file as a sorted run
+//! (see `exec::spill`). The runs are merged while the result is scanned: a heap holds the key of
+//! the next row of every run, equal keys go to the earlier run so the sort stays stable. Only the
+//! current block of every run is in memory. When there are more than `MAX_FAN_IN` runs, groups of
+//! consecutive runs are first merged into longer runs, so a merge opens a bounded number of files.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use crate::error::Result;
+use crate::exec::order_by::{SortKeys, SortOrder};
+use crate::exec::sort::gather_chunk;
+use crate::exec::spill::{memory_size, read_chunk, write_chunk, SpillFile};
+use crate::exec::SourceReader;
+use crate::qir::join::concat_or_empty;
+use crate::qir::DataType;
+use crate::vector::{DataChunk, Vector, VECTOR_SIZE};
+
+/// the runs read at once by a merge
+const MAX_FAN_IN: usize = 64;
+
+/// the key columns of the sorted chunks and their orders
+#[derive(Debug, Clone)]
+struct Keys {
+    columns: Vec<usize>,
+    orders: Vec<SortOrder>,
+}
+
+impl Keys {
+    fn encode(&self, chunk: &DataChunk) -> Result<SortKeys> {
+        let columns: Vec<&Vector> = self.columns.iter().map(|&i| chunk.column(i)).collect();
+        SortKeys::encode(&columns, &self.orders)
+    }
+}
+
+pub struct ExternalSort {
+    keys: Keys,
+    /// the data types of the columns, to read the spilled blocks
+    types: Vec<DataType>,
+    budget: Option<usize>,
+    spill_dir: PathBuf,
+    chunks: Vec<DataChunk>,
+    /// the memory size of `chunks`
+    buffered: usize,
+    runs: Vec<SpillFile>,
+    max_fan_in: usize,
+}
+
+/// the sorted rows, in memory if they never exceeded the budget
+pub enum Sorted {
+    Memory(DataChunk),
+    Runs(SortedRuns),
+}
+
+impl ExternalSort {
+    pub fn new(keys: Vec<usize>, orders: Vec<SortOrder>, types: Vec<DataType>, budget: Option<usize>, spill_dir: PathBuf) -> Self {
+        ExternalSort { keys: Keys { columns: keys, orders }, types, budget, spill_dir, chunks: vec![], buffered: 0, runs: vec![], max_fan_in: MAX_FAN_IN }
+    }
+
+    pub fn push(&mut self, chunk: DataChunk) -> Result<()> {
+        self.buffered += chunk.columns.iter().map(memory_size).sum::<usize>();
+        self.chunks.push(chunk);
+        if self.budget.is_some_and(|budget| self.buffered > budget) {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// sort the buffered rows, emptying the buffer
+    fn sort_buffered(&mut self) -> Result<DataChunk> {
+        let rows = concat_or_empty(&std::mem::take(&mut self.chunks), &self.types)?;
+        self.buffered = 0;
+        let permutation = self.keys.encode(&rows)?.argsort();
+        Ok(gather_chunk(&rows, &permutation))
+    }
+
+    /// write the buffered rows as a sorted run
+    fn spill(&mut self) -> Result<()> {
+        let rows = self.sort_buffered()?;
+        let (file, mut writer) = SpillFile::create(&self.spill_dir)?;
+        for offset in (0..rows.len()).step_by(VECTOR_SIZE) {
+            write_chunk(&mut writer, &rows.slice(offset, VECTOR_SIZE.min(rows.len() - offset)))?;
+        }
+        writer.flush()?;
+        self.runs.push(file);
+        Ok(())
+    }
+
+    /// merge `runs` into a single run, whose files are removed once it is written
+    fn merge_runs(&self, runs: Vec<SpillFile>) -> Result<SpillFile> {
+        let runs = Rc::new(SortedRuns { keys: self.keys.clone(), types: self.types.clone(), runs });
+        let mut merge = SortedRuns::merge(&runs)?;
+        let (file, mut writer) = SpillFile::create(&self.spill_dir)?;
+        while let Some(chunk) = merge.next_chunk()? {
+            write_chunk(&mut writer, &chunk)?;
+        }
+        writer.flush()?;
+        Ok(file)
+    }
+
+    pub fn finish(mut self) -> Result<Sorted> {
+        if self.runs.is_empty() {
+            return Ok(Sorted::Memory(self.sort_buffered()?));
+        }
+        if !self.chunks.is_empty() {
+            self.spill()?;
+        }
+        // merging consecutive runs keeps equal keys in the order of the input
+        while self.runs.len() > self.max_fan_in {
+            let mut runs = std::mem::take(&mut self.runs).into_iter().peekable();
+            let mut merged = vec![];
+            while runs.peek().is_some() {
+                let group: Vec<SpillFile> = runs.by_ref().take(self.max_fan_in).collect();
+                merged.push(match group.len() {
+                    1 => group.into_iter().next().expect("a run"),
+                    _ => self.merge_runs(group)?,
+                });
+            }
+            self.runs = merged;
+        }
+        Ok(Sorted::Runs(SortedRuns { keys: self.keys, types: self.types, runs: self.runs }))
+    }
+}
+
+/// the sorted runs of a sort that exceeded its budget, removed when dropped
+pub struct SortedRuns {
+    keys: Keys,
+    types: Vec<DataType>,
+    runs: Vec<SpillFile>,
+}
+
+impl SortedRuns {
+    pub fn len(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// a reader of the merged rows, in chunks of `VECTOR_SIZE` rows
+    pub fn merge(runs: &Rc<SortedRuns>) -> Result<MergeReader> {
+        let mut merge = MergeReader { sorted: runs.clone(), runs: vec![], heap: BinaryHeap::new() };
+        for (index, file) in runs.runs.iter().enumerate() {
+            let mut run = Run { reader: file.open()?, block: DataChunk::empty(0), keys: SortKeys::default(), row: 0 };
+            if run.load(runs)? {
+                merge.heap.push(Reverse((run.keys.key(0).into(), index)));
+            }
+            merge.runs.push(run);
+        }
+        Ok(merge)
+    }
+}
+
+/// the current block of a run and its keys
+struct Run {
+    reader: BufReader<File>,
+    block: DataChunk,
+    keys: SortKeys,
+    row: usize,
+}
+
+impl Run {
+    /// read the next block, false at the end of the run
+    fn load(&mut self, sorted: &SortedRuns) -> Result<bool> {
+        while let Some(block) = read_chunk(&mut self.reader, &sorted.types)? {
+            if !block.is_empty() {
+                self.keys = sorted.keys.encode(&block)?;
+                (self.block, self.row) = (block, 0);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+pub struct MergeReader {
+    sorted: Rc<SortedRuns>,
+    runs: Vec<Run>,
+    /// the key of the next row of every run that is not exhausted, with the index of the run
+    heap: BinaryHeap<Reverse<(Box<[u8]>, usize)>>,
+}
+
+impl SourceReader for MergeReader {
+    fn next_chunk(&mut self) -> Result<Option<DataChunk>> {
+        // the rows are gathered from the current blocks of the runs, `blocks[current[run]]`
+        let mut blocks: Vec<DataChunk> = vec![];
+        let mut offsets = vec![];
+        let mut gathered = 0;
+        let mut current = vec![None; self.runs.len()];
+        let mut positions = Vec::with_capacity(VECTOR_SIZE);
+        while positions.len() < VECTOR_SIZE {
+            let Some(Reverse((_, index))) = self.heap.pop() else { break };
+            let run = &mut self.runs[index];
+            let block = match current[index] {
+                Some(block) => block,
+                None => {
+                    offsets.push(gathered);
+                    gathered += run.block.len() as u32;
+                    blocks.push(run.block.clone());
+                    current[index] = Some(blocks.len() - 1);
+                    blocks.len() - 1
+                }
+            };
+            positions.push(offsets[block] + run.row as u32);
+            run.row += 1;
+            if run.row == run.block.len() {
+                current[index] = None;
+                if !run.load(&self.sorted)? {
+                    continue;
+                }
+            }
+            self.heap.push(Reverse((run.keys.key(run.row).into(), index)));
+        }
+        if positions.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(gather_chunk(&DataChunk::concat(&blocks)?, &positions)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vector::Value;
+    use super::*;
+
+    fn chunk(rows: std::ops::Range<i64>) -> DataChunk {
+        DataChunk::new(vec![
+            Vector::from(rows.clone().map(|i| (i % 13 != 0).then_some((i * 7919) % 1009)).collect::<Vec<_>>()),
+            Vector::from(rows.clone().map(|i| format!("row {i}")).collect::<Vec<_>>().iter().map(|s| s.as_str()).collect::<Vec<_>>()),
+        ])
+    }
+
+    fn sort(budget: Option<usize>, rows: i64) -> Sorted {
+        let types = vec![DataType::I64, DataType::String];
+        let mut sort = ExternalSort::new(vec![0], vec![SortOrder::DESC], types, budget, std::env::temp_dir());
+        for start in (0..rows).step_by(1000) {
+            sort.push(chunk(start..rows.min(start + 1000))).unwrap();
+        }
+        sort.finish().unwrap()
+    }
+
+    #[test]
+    fn test_external_sort() {
+        let rows = 3 * VECTOR_SIZE as i64 + 100;
+        let Sorted::Memory(expected) = sort(None, rows) else { panic!("spilled without a budget") };
+        let Sorted::Runs(runs) = sort(Some(32 * 1024), rows) else { panic!("did not spill") };
+        assert!(runs.len() > 2);
+
+        let runs = Rc::new(runs);
+        let mut merge = SortedRuns::merge(&runs).unwrap();
+        let mut chunks = vec![];
+        while let Some(chunk) = merge.next_chunk().unwrap() {
+            chunks.push(chunk);
+        }
+        assert!(chunks[..chunks.len() - 1].iter().all(|c| c.len() == VECTOR_SIZE));
+        // the merge is stable: equal keys keep the order of the input
+        let merged = DataChunk::concat(&chunks).unwrap();
+        assert_eq!(merged, expected);
+        assert_eq!(merged.column(1).get(0), Value::String("row 0".into()));
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let Sorted::Memory(rows) = sort(None, 0) else { panic!("spilled without a budget") };
+        assert_eq!((rows.len(), rows.columns.len()), (0, 2));
+    }
+
+    #[test]
+    fn test_merge_passes() {
+        let rows = 3 * VECTOR_SIZE as i64 + 100;
+        let Sorted::Memory(expected) = sort(None, rows) else { panic!("spilled without a budget") };
+        let types = vec![DataType::I64, DataType::String];
+        let mut sort = ExternalSort::new(vec![0], vec![SortOrder::DESC], types, Some(1), std::env::temp_dir());
+        sort.max_fan_in = 2;
+        for start in (0..rows).step_by(500) {
+            sort.push(chunk(start..rows.min(start + 500))).unwrap();
+        }
+        // 13 runs are merged in passes of at most 2 runs
+        let Sorted::Runs(runs) = sort.finish().unwrap() else { panic!("did not spill") };
+        assert_eq!(runs.len(), 2);
+        let mut merge = SortedRuns::merge(&Rc::new(runs)).unwrap();
+        let mut chunks = vec![];
+        while let Some(chunk) = merge.next_chunk().unwrap() {
+            chunks.push(chunk);
+        }
+        assert_eq!(DataChunk::concat(&chunks).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_spilled_files() {
+        let Sorted::Runs(runs) = sort(Some(1), 2500) else { panic!("did not spill") };
+        // every pushed chunk exceeds the budget and is spilled as a run
+        assert_eq!(runs.len(), 3);
+        let paths: Vec<PathBuf> = runs.runs.iter().map(|f| f.path().to_path_buf()).collect();
+        assert!(paths.iter().all(|p| p.exists()));
+        drop(runs);
+        assert!(paths.iter().all(|p| !p.exists()));
+    }
+}
@@ -0,0 +1,404 @@
+//! SIMD kernels of the filter: a predicate is compiled once into comparisons of a column with a
+//! literal, of two columns or with a range, combined with `&&` and `||`.
+//!
+//! Each kernel compares `LANES` rows at a time with `std::simd` and writes the result as a
+//! bitmap, a set bit for every row where the comparison is true. Null rows are cleared with the
+//! validity of the columns, so the bitmaps of `&&` and `||` combine word by word and give the
+//! rows where the predicate is true, as `Expr::select` does. Predicates without a kernel, e.g.
+//! on strings or with arithmetic, are evaluated by `Expr::select`.
+
+use std::simd::cmp::{SimdPartialEq, SimdPartialOrd};
+use std::simd::{Mask, Simd, SimdElement};
+use crate::error::Result;
+use crate::qir::expr::{BinaryOp, Expr, ExprKind};
+use crate::qir::DataType;
+use crate::vector::{Bitmap, DataChunk, PrimaryType, SelectionVector, Value, Vector};
+
+/// the rows compared at a time, 16 as in the POC
+pub const LANES: usize = 16;
+
+/// run `$kernel!(simd_method, operator)` for the comparison `$op`
+macro_rules! comparison {
+    ($op:expr, $kernel:ident) => {
+        match $op {
+            BinaryOp::Eq => $kernel!(simd_eq, ==),
+            BinaryOp::NotEq => $kernel!(simd_ne, !=),
+            BinaryOp::Lt => $kernel!(simd_lt, <),
+            BinaryOp::LtEq => $kernel!(simd_le, <=),
+            BinaryOp::Gt => $kernel!(simd_gt, >),
+            BinaryOp::GtEq => $kernel!(simd_ge, >=),
+            op => unreachable!("{op:?} is not a comparison"),
+        }
+    };
+}
+
+/// or the bits of `lanes(i)`, the bitmask of rows `i..i + LANES`, into `out` and those of `row`
+/// for the rows after the last full lanes
+#[inline(always)]
+fn fill(len: usize, out: &mut [u64], lanes: impl Fn(usize) -> u64, row: impl Fn(usize) -> bool) {
+    let simd_len = len - len % LANES;
+    for i in (0..simd_len).step_by(LANES) {
+        out[i / 64] |= lanes(i) << (i % 64);
+    }
+    for i in simd_len..len {
+        out[i / 64] |= (row(i) as u64) << (i % 64);
+    }
+}
+
+/// the bitmask of `LANES` rows computed one row at a time, for types without SIMD lanes
+#[inline(always)]
+fn rows_mask(i: usize, row: impl Fn(usize) -> bool) -> u64 {
+    (0..LANES).fold(0, |mask, lane| mask | (row(i + lane) as u64) << lane)
+}
+
+fn simd_compare_scalar<T>(op: BinaryOp, values: &[T], scalar: T, out: &mut [u64])
+where T: SimdElement + PartialOrd, Simd<T, LANES>: SimdPartialOrd<Mask = Mask<T::Mask, LANES>> {
+    let splat = Simd::<T, LANES>::splat(scalar);
+    macro_rules! kernel {
+        ($simd:ident, $op:tt) => {
+            fill(values.len(), out, |i| Simd::from_slice(&values[i..]).$simd(splat).to_bitmask(), |i| values[i] $op scalar)
+        };
+    }
+    comparison!(op, kernel)
+}
+
+fn simd_compare<T>(op: BinaryOp, left: &[T], right: &[T], out: &mut [u64])
+where T: SimdElement + PartialOrd, Simd<T, LANES>: SimdPartialOrd<Mask = Mask<T::Mask, LANES>> {
+    macro_rules! kernel {
+        ($simd:ident, $op:tt) => {
+            fill(left.len(), out, |i| Simd::<T, LANES>::from_slice(&left[i..]).$simd(Simd::from_slice(&right[i..])).to_bitmask(),
+                |i| left[i] $op right[i])
+        };
+    }
+    comparison!(op, kernel)
+}
+
+fn simd_range<T>(values: &[T], low: T, low_inclusive: bool, high: T, high_inclusive: bool, out: &mut [u64])
+where T: SimdElement + PartialOrd, Simd<T, LANES>: SimdPartialOrd<Mask = Mask<T::Mask, LANES>> {
+    let (low_lanes, high_lanes) = (Simd::<T, LANES>::splat(low), Simd::<T, LANES>::splat(high));
+    macro_rules! kernel {
+        ($low:ident $low_op:tt, $high:ident $high_op:tt) => {
+            fill(values.len(), out, |i| {
+                let lanes = Simd::from_slice(&values[i..]);
+                (lanes.$low(low_lanes) & lanes.$high(high_lanes)).to_bitmask()
+            }, |i| values[i] $low_op low && values[i] $high_op high)
+        };
+    }
+    match (low_inclusive, high_inclusive) {
+        (true, true) => kernel!(simd_ge >=, simd_le <=),
+        (true, false) => kernel!(simd_ge >=, simd_lt <),
+        (false, true) => kernel!(simd_gt >, simd_le <=),
+        (false, false) => kernel!(simd_gt >, simd_lt <),
+    }
+}
+
+/// the primary types with comparison kernels: SIMD lanes for integers and floats, a loop over
+/// the rows for `Decimal`. every kernel ors its result into the bitmap words `out`.
+pub trait Lanes: PrimaryType {
+    /// `values[i] op scalar`
+    fn compare_scalar(op: BinaryOp, values: &[Self], scalar: Self, out: &mut [u64]);
+
+    /// `left[i] op right[i]`
+    fn compare(op: BinaryOp, left: &[Self], right: &[Self], out: &mut [u64]);
+
+    /// `low <= values[i] <= high`, or `<` for the bounds that are not inclusive
+    fn range(values: &[Self], low: Self, low_inclusive: bool, high: Self, high_inclusive: bool, out: &mut [u64]);
+}
+
+macro_rules! impl_simd_lanes {
+    ($($t:ty),*) => {$(
+        impl Lanes for $t {
+            fn compare_scalar(op: BinaryOp, values: &[Self], scalar: Self, out: &mut [u64]) {
+                simd_compare_scalar(op, values, scalar, out)
+            }
+
+            fn compare(op: BinaryOp, left: &[Self], right: &[Self], out: &mut [u64]) {
+                simd_compare(op, left, right, out)
+            }
+
+            fn range(values: &[Self], low: Self, low_inclusive: bool, high: Self, high_inclusive: bool, out: &mut [u64]) {
+                simd_range(values, low, low_inclusive, high, high_inclusive, out)
+            }
+        }
+    )*};
+}
+
+impl_simd_lanes!(i8, i16, i32, i64, u8, u16, u32, u64, f32, f64);
+
+impl Lanes for i128 {
+    fn compare_scalar(op: BinaryOp, values: &[Self], scalar: Self, out: &mut [u64]) {
+        macro_rules! kernel {
+            ($simd:ident, $op:tt) => {{
+                let row = |i: usize| values[i] $op scalar;
+                fill(values.len(), out, |i| rows_mask(i, row), row)
+            }};
+        }
+        comparison!(op, kernel)
+    }
+
+    fn compare(op: BinaryOp, left: &[Self], right: &[Self], out: &mut [u64]) {
+        macro_rules! kernel {
+            ($simd:ident, $op:tt) => {{
+                let row = |i: usize| left[i] $op right[i];
+                fill(left.len(), out, |i| rows_mask(i, row), row)
+            }};
+        }
+        comparison!(op, kernel)
+    }
+
+    fn range(values: &[Self], low: Self, low_inclusive: bool, high: Self, high_inclusive: bool, out: &mut [u64]) {
+        let row = |i: usize| {
+            let value = values[i];
+            (if low_inclusive { value >= low } else { value > low }) && (if high_inclusive { value <= high } else { value < high })
+        };
+        fill(values.len(), out, |i| rows_mask(i, row), row)
+    }
+}
+
+/// whether a column of `data_type` can be compared by the kernels
+fn has_lanes(data_type: &DataType) -> bool {
+    data_type.is_numeric() || data_type.is_temporal()
+}
+
+/// call `$f::<T>($args)` with the `Lanes` type of `$vector`
+macro_rules! with_lanes {
+    ($vector:expr, $f:ident($($arg:expr),*)) => {
+        match $vector {
+            Vector::I8(_) => $f::<i8>($($arg),*),
+            Vector::I16(_) => $f::<i16>($($arg),*),
+            Vector::I32(_) | Vector::Date(_) => $f::<i32>($($arg),*),
+            Vector::I64(_) | Vector::DateTime(_) => $f::<i64>($($arg),*),
+            Vector::U8(_) => $f::<u8>($($arg),*),
+            Vector::U16(_) => $f::<u16>($($arg),*),
+            Vector::U32(_) => $f::<u32>($($arg),*),
+            Vector::U64(_) => $f::<u64>($($arg),*),
+            Vector::F32(_) => $f::<f32>($($arg),*),
+            Vector::F64(_) => $f::<f64>($($arg),*),
+            Vector::Decimal(_) => $f::<i128>($($arg),*),
+            other => panic!("no kernels for {:?}", other.data_type()),
+        }
+    };
+}
+
+/// clear the bits of the null rows of `vector`
+fn and_validity(vector: &Vector, out: &mut [u64]) {
+    if let Some(validity) = vector.validity() {
+        out.iter_mut().zip(validity.words()).for_each(|(word, valid)| *word &= valid);
+    }
+}
+
+fn compare_scalar<T: Lanes>(column: &Vector, op: BinaryOp, value: &Value, out: &mut [u64]) {
+    let values = T::vector(column).expect("column of the kernel type").values();
+    T::compare_scalar(op, values, T::from_value(value).expect("literal of the column type"), out);
+}
+
+fn compare<T: Lanes>(left: &Vector, op: BinaryOp, right: &Vector, out: &mut [u64]) {
+    let (left, right) = (T::vector(left).expect("column of the kernel type"), T::vector(right).expect("column of the kernel type"));
+    T::compare(op, left.values(), right.values(), out);
+}
+
+fn range<T: Lanes>(column: &Vector, low: &(Value, bool), high: &(Value, bool), out: &mut [u64]) {
+    let values = T::vector(column).expect("column of the kernel type").values();
+    let bound = |value: &Value| T::from_value(value).expect("bound of the column type");
+    T::range(values, bound(&low.0), low.1, bound(&high.0), high.1, out);
+}
+
+/// a filter predicate compiled to kernels, for the chunks of the columns it was compiled with
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    /// `column op value`
+    Compare { column: usize, op: BinaryOp, value: Value },
+    /// `left op right`, two columns of the same type
+    Columns { left: usize, op: BinaryOp, right: usize },
+    /// `column > low && column < high`, with whether each bound is inclusive
+    Range { column: usize, low: (Value, bool), high: (Value, bool) },
+    IsNull { column: usize, negated: bool },
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+    /// any other predicate, evaluated by `Expr::select`
+    Expr(Expr),
+}
+
+/// the operands of a chain of `op`, `a && b && c` gives `[a, b, c]`
+fn flatten<'a>(expr: &'a Expr, op: BinaryOp, operands: &mut Vec<&'a Expr>) {
+    match &expr.kind {
+        ExprKind::Binary { op: o, left, right } if *o == op => {
+            flatten(left, op, operands);
+            flatten(right, op, operands);
+        }
+        _ => operands.push(expr),
+    }
+}
+
+fn is_lower_bound(op: BinaryOp) -> bool {
+    matches!(op, BinaryOp::Gt | BinaryOp::GtEq)
+}
+
+fn is_upper_bound(op: BinaryOp) -> bool {
+    matches!(op, BinaryOp::Lt | BinaryOp::LtEq)
+}
+
+/// replace a lower and an upper bound of the same column by a range
+fn merge_ranges(predicates: Vec<Predicate>) -> Vec<Predicate> {
+    let mut merged: Vec<Predicate> = vec![];
+    for predicate in predicates {
+        let Predicate::Compare { column, op, value } = &predicate else {
+            merged.push(predicate);
+            continue;
+        };
+        let other = merged.iter().position(|m| matches!(m, Predicate::Compare { column: c, op: o, .. }
+            if c == column && (is_lower_bound(*op) && is_upper_bound(*o) || is_upper_bound(*op) && is_lower_bound(*o))));
+        let Some(other) = other else {
+            merged.push(predicate);
+            continue;
+        };
+        let Predicate::Compare { op: other_op, value: other_value, .. } = &merged[other] else { unreachable!() };
+        let (this, other_bound) = ((value.clone(), matches!(op, BinaryOp::GtEq | BinaryOp::LtEq)),
+            (other_value.clone(), matches!(other_op, BinaryOp::GtEq | BinaryOp::LtEq)));
+        let (low, high) = if is_lower_bound(*op) { (this, other_bound) } else { (other_bound, this) };
+        merged[other] = Predicate::Range { column: *column, low, high };
+    }
+    merged
+}
+
+impl Predicate {
+    /// compile a Bool expression over columns `names`, parts without kernels stay expressions
+    pub fn compile(expr: &Expr, names: &[String]) -> Predicate {
+        let position = |e: &Expr| match &e.kind {
+            ExprKind::Column(name) => names.iter().position(|n| n == name),
+            _ => None,
+        };
+        let lanes = |e: &Expr| position(e).filter(|_| has_lanes(&e.data_type));
+        let literal = |e: &Expr, column: &Expr| match &e.kind {
+            ExprKind::Literal(value) if !value.is_null() && e.data_type == column.data_type => Some(value.clone()),
+            _ => None,
+        };
+        match &expr.kind {
+            ExprKind::Binary { op: op @ (BinaryOp::And | BinaryOp::Or), .. } => {
+                let mut operands = vec![];
+                flatten(expr, *op, &mut operands);
+                let predicates = operands.into_iter().map(|e| Predicate::compile(e, names)).collect();
+                match op {
+                    BinaryOp::And => Predicate::And(merge_ranges(predicates)),
+                    _ => Predicate::Or(predicates),
+                }
+            }
+            ExprKind::Binary { op, left, right } if op.is_comparison() => {
+                match (lanes(left), lanes(right)) {
+                    (Some(l), Some(r)) if left.data_type == right.data_type => Predicate::Columns { left: l, op: *op, right: r },
+                    (Some(column), None) if let Some(value) = literal(right, left) => Predicate::Compare { column, op: *op, value },
+                    (None, Some(column)) if let Some(value) = literal(left, right) => Predicate::Compare { column, op: op.flip(), value },
+                    _ => Predicate::Expr(expr.clone()),
+                }
+            }
+            ExprKind::IsNull { expr: inner, negated } if let Some(column) = position(inner) => Predicate::IsNull { column, negated: *negated },
+            _ => Predicate::Expr(expr.clone()),
+        }
+    }
+
+    /// the rows of `chunk` where the predicate is true, `names` are the columns of the chunk
+    pub fn mask(&self, chunk: &DataChunk, names: &[String]) -> Result<Bitmap> {
+        let len = chunk.len();
+        let mut words = vec![0u64; len.div_ceil(64)];
+        match self {
+            Predicate::Compare { column, op, value } => {
+                let column = chunk.column(*column);
+                with_lanes!(column, compare_scalar(column, *op, value, &mut words));
+                and_validity(column, &mut words);
+            }
+            Predicate::Columns { left, op, right } => {
+                let (left, right) = (chunk.column(*left), chunk.column(*right));
+                with_lanes!(left, compare(left, *op, right, &mut words));
+                and_validity(left, &mut words);
+                and_validity(right, &mut words);
+            }
+            Predicate::Range { column, low, high } => {
+                let column = chunk.column(*column);
+                with_lanes!(column, range(column, low, high, &mut words));
+                and_validity(column, &mut words);
+            }
+            Predicate::IsNull { column, negated } => {
+                let column = chunk.column(*column);
+                return Ok((0..len).map(|i| column.is_valid(i) == *negated).collect());
+            }
+            Predicate::And(predicates) => {
+                let mut mask = Bitmap::new(len, true);
+                for predicate in predicates {
+                    if mask.count_ones() == 0 {
+                        break;
+                    }
+                    mask = mask.and(&predicate.mask(chunk, names)?);
+                }
+                return Ok(mask);
+            }
+            Predicate::Or(predicates) => {
+                let mut mask = Bitmap::new(len, false);
+                for predicate in predicates {
+                    mask = mask.or(&predicate.mask(chunk, names)?);
+                }
+                return Ok(mask);
+            }
+            Predicate::Expr(expr) => return expr.mask(chunk, names),
+        }
+        Ok(Bitmap::from_words(words, len))
+    }
+
+    pub fn select(&self, chunk: &DataChunk, names: &[String]) -> Result<SelectionVector> {
+        Ok(SelectionVector::from_bitmap(&self.mask(chunk, names)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qir::Column;
+
+    fn selected(mask: &[u64], len: usize) -> Vec<u32> {
+        SelectionVector::from_bitmap(&Bitmap::from_words(mask.to_vec(), len)).into_indices()
+    }
+
+    #[test]
+    fn test_kernels() {
+        let values: Vec<i32> = (0..100).collect();
+        let mut out = vec![0u64; 2];
+        i32::compare_scalar(BinaryOp::GtEq, &values, 90, &mut out);
+        assert_eq!(selected(&out, 100), (90..100).collect::<Vec<_>>());
+
+        let floats: Vec<f64> = (0..40).map(|i| i as f64 / 2.0).collect();
+        let mut out = vec![0u64; 1];
+        f64::range(&floats, 3.0, false, 5.0, true, &mut out);
+        assert_eq!(selected(&out, 40), vec![7, 8, 9, 10]);
+
+        let decimals: Vec<i128> = (0..20).collect();
+        let reversed: Vec<i128> = (0..20).rev().collect();
+        let mut out = vec![0u64; 1];
+        i128::compare(BinaryOp::Lt, &decimals, &reversed, &mut out);
+        assert_eq!(selected(&out, 20), (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_compile_and_select() {
+        let columns = vec![
+            Column { name: "sku_id".to_string(), data_type: DataType::U32, nullable: false },
+            Column { name: "amount".to_string(), data_type: DataType::F64, nullable: true },
+            Column { name: "name".to_string(), data_type: DataType::String, nullable: true },
+        ];
+        let names: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+        let predicate = Expr::parse("$in.amount > 20 && 100 >= $in.sku_id && $in.amount <= 50 || $in.name like \"a%\"", &columns).unwrap();
+        let compiled = Predicate::compile(&predicate, &names);
+        let Predicate::Or(operands) = &compiled else { panic!("{compiled:?}") };
+        assert_eq!(operands[0], Predicate::And(vec![
+            Predicate::Range { column: 1, low: (Value::F64(20.0), false), high: (Value::F64(50.0), true) },
+            Predicate::Compare { column: 0, op: BinaryOp::LtEq, value: Value::U32(100) },
+        ]));
+        assert!(matches!(operands[1], Predicate::Expr(_)));
+
+        let len = 1000;
+        let chunk = DataChunk::new(vec![
+            Vector::from((0..len as u32).collect::<Vec<_>>()),
+            Vector::from((0..len).map(|i| (i % 7 != 0).then_some(i as f64 % 60.0)).collect::<Vec<_>>()),
+            Vector::from((0..len).map(|i| if i % 300 == 0 { "abc" } else { "xyz" }).collect::<Vec<_>>()),
+        ]);
+        assert_eq!(compiled.select(&chunk, &names).unwrap(), predicate.select(&chunk, &names).unwrap());
+    }
+}
@@ -0,0 +1,173 @@
+//! The partitions of a grace hash join, for build sides larger than the memory budget.
+//!
+//! The rows of both sides are split into `FANOUT` partitions by bits of the hash of their keys
+//! and spilled to disk (see `exec::spill`), so that the rows with equal keys are in partitions
+//! of the same number. Each pair of partitions is then joined in memory. A build partition that
+//! is still too large is split again by the next bits of the hash, up to `MAX_LEVEL`.
+//!
+//! ```text
+//! level 0: bits 32..36 of the hash, level 1: bits 36..40, ...
+//! ```
+//!
+//! The swiss table of the join uses the low bits and the top 15 bits of the hash, the rows of a
+//! partition still spread over all its groups.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+use crate::error::Result;
+use crate::exec::hash::hash_columns;
+use crate::exec::runtime_filter::RuntimeFilter;
+use crate::exec::spill::{memory_size, read_chunk, write_chunk, SpillFile};
+use crate::qir::DataType;
+use crate::vector::{DataChunk, Vector};
+
+/// the number of partitions of a split
+pub const FANOUT: usize = 16;
+const BITS: u32 = FANOUT.trailing_zeros();
+
+/// the deepest level of partitions, they are not split again
+pub const MAX_LEVEL: u32 = 3;
+
+/// the partition of a row with key hash `hash` at `level`
+#[inline]
+pub fn partition(hash: u64, level: u32) -> usize {
+    (hash >> (32 + BITS * level)) as usize & (FANOUT - 1)
+}
+
+/// the spilled rows of a partition
+#[derive(Debug, Clone)]
+pub struct Partition {
+    file: Rc<SpillFile>,
+    pub level: u32,
+    pub rows: usize,
+    /// the memory size of the rows
+    pub bytes: usize,
+}
+
+impl Partition {
+    pub fn reader(&self) -> Result<PartitionReader> {
+        Ok(PartitionReader { reader: self.file.open()? })
+    }
+
+    /// all rows of the partition, in chunks
+    pub fn read_all(&self, types: &[DataType]) -> Result<Vec<DataChunk>> {
+        let mut reader = self.reader()?;
+        let mut chunks = vec![];
+        while let Some(chunk) = reader.next_chunk(types)? {
+            chunks.push(chunk);
+        }
+        Ok(chunks)
+    }
+
+    /// split the rows again by the hash bits of the next level
+    pub fn split(&self, types: &[DataType], keys: &[usize], spill_dir: &Path) -> Result<Vec<Partition>> {
+        let mut partitions = Partitioner::new(spill_dir, keys.to_vec(), self.level + 1)?;
+        let mut reader = self.reader()?;
+        while let Some(chunk) = reader.next_chunk(types)? {
+            partitions.push(&chunk)?;
+        }
+        partitions.finish()
+    }
+}
+
+pub struct PartitionReader {
+    reader: BufReader<File>,
+}
+
+impl PartitionReader {
+    pub fn next_chunk(&mut self, types: &[DataType]) -> Result<Option<DataChunk>> {
+        read_chunk(&mut self.reader, types)
+    }
+}
+
+/// splits chunks into the `FANOUT` partitions of a level by the hash of their `keys` columns
+pub struct Partitioner {
+    keys: Vec<usize>,
+    level: u32,
+    files: Vec<SpillFile>,
+    writers: Vec<BufWriter<File>>,
+    rows: Vec<usize>,
+    bytes: Vec<usize>,
+}
+
+impl Partitioner {
+    pub fn new(spill_dir: &Path, keys: Vec<usize>, level: u32) -> Result<Self> {
+        let (mut files, mut writers) = (vec![], vec![]);
+        for _ in 0..FANOUT {
+            let (file, writer) = SpillFile::create(spill_dir)?;
+            files.push(file);
+            writers.push(writer);
+        }
+        Ok(Partitioner { keys, level, files, writers, rows: vec![0; FANOUT], bytes: vec![0; FANOUT] })
+    }
+
+    pub fn push(&mut self, chunk: &DataChunk) -> Result<()> {
+        let keys: Vec<&Vector> = self.keys.iter().map(|&i| chunk.column(i)).collect();
+        let mut rows: Vec<Vec<u32>> = vec![vec![]; FANOUT];
+        for (row, hash) in hash_columns(&keys, chunk.len()).into_iter().enumerate() {
+            rows[partition(hash, self.level)].push(row as u32);
+        }
+        for (p, rows) in rows.iter().enumerate().filter(|(_, rows)| !rows.is_empty()) {
+            let part = chunk.take(rows);
+            self.rows[p] += part.len();
+            self.bytes[p] += part.columns.iter().map(memory_size).sum::<usize>();
+            write_chunk(&mut self.writers[p], &part)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<Vec<Partition>> {
+        let mut partitions = Vec::with_capacity(FANOUT);
+        for (((file, mut writer), rows), bytes) in self.files.into_iter().zip(self.writers).zip(self.rows).zip(self.bytes) {
+            writer.flush()?;
+            partitions.push(Partition { file: Rc::new(file), level: self.level, rows, bytes });
+        }
+        Ok(partitions)
+    }
+}
+
+/// the build side of a hash join that exceeded the memory budget, the result of a `build_hash`
+/// sink in place of a `JoinHashTable`
+#[derive(Debug)]
+pub struct GraceTable {
+    pub partitions: Vec<Partition>,
+    pub filter: Rc<RuntimeFilter>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::vector::Value;
+    use super::*;
+
+    #[test]
+    fn test_partitions() {
+        let types = [DataType::I64, DataType::String];
+        let chunk = DataChunk::new(vec![
+            Vector::from((0..5000i64).map(|i| i % 700).collect::<Vec<_>>()),
+            Vector::from((0..5000).map(|i| format!("v{i}")).collect::<Vec<_>>()),
+        ]);
+        let mut partitioner = Partitioner::new(&std::env::temp_dir(), vec![0], 0).unwrap();
+        partitioner.push(&chunk).unwrap();
+        partitioner.push(&chunk.slice(0, 100)).unwrap();
+        let partitions = partitioner.finish().unwrap();
+        assert_eq!(partitions.iter().map(|p| p.rows).sum::<usize>(), 5100);
+        assert!(partitions.iter().all(|p| p.rows > 0));
+
+        // a key is in one partition, also after a split
+        let mut seen = HashSet::new();
+        for part in partitions.iter().take(2) {
+            let split = part.split(&types, &[0], &std::env::temp_dir()).unwrap();
+            assert_eq!(split.iter().map(|p| p.rows).sum::<usize>(), part.rows);
+            for sub in split {
+                assert_eq!(sub.level, 1);
+                let keys: HashSet<i64> = sub.read_all(&types).unwrap().iter()
+                    .flat_map(|c| (0..c.len()).map(|i| match c.column(0).get(i) { Value::I64(v) => v, _ => unreachable!() }).collect::<Vec<_>>())
+                    .collect();
+                assert!(keys.into_iter().all(|k| seen.insert(k)), "a key in two partitions");
+            }
+        }
+    }
+}
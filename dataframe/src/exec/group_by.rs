@@ -0,0 +1,453 @@
+//! The hash table of the hash group-by: assigns a group id to every row of a chunk, adding a
+//! group for every new key.
+//!
+//! The groups are the entries of a `SwissTable`. Their keys are stored column by column and
+//! compared a batch at a time: each round, every pending row is compared with its next candidate
+//! group, and the rows whose key is not in the table become new groups. Nulls are keys like any
+//! other value.
+//!
+//! When the ranges of integer keys are known, e.g. from the minmaxes of a scan, a `DirectTable`
+//! uses the key values themselves as the slot and skips hashing and comparing.
+
+use crate::exec::aggregate::into_vector;
+use crate::exec::cast::cast_vector;
+use crate::exec::hash::{hash_columns, HashKey};
+use crate::exec::swiss::{EntryKeys, SwissTable};
+use crate::qir::DataType;
+use crate::vector::{Bitmap, PrimaryType, PrimaryVector, StringVector, Value, Vector};
+
+/// a slot of a `DirectTable` without a group
+const EMPTY: u32 = u32::MAX;
+
+/// the most slots of a `DirectTable`, 4 MiB of group ids
+pub const DIRECT_SLOTS: u64 = 1 << 20;
+
+/// the keys of every group for one key column
+trait KeyStore {
+    /// add row `row` of `vector` as the key of a new group
+    fn push(&mut self, vector: &Vector, row: usize);
+
+    /// and `equal[i]` with whether row `rows[i]` of `vector` equals the key of group `groups[i]`
+    fn equal(&self, vector: &Vector, rows: &[u32], groups: &[u32], equal: &mut [bool]);
+
+    fn finish(self: Box<Self>) -> Vector;
+}
+
+struct PrimaryKeys<T: HashKey> {
+    data_type: DataType,
+    values: Vec<T>,
+    validity: Bitmap,
+}
+
+impl<T: HashKey> KeyStore for PrimaryKeys<T> {
+    fn push(&mut self, vector: &Vector, row: usize) {
+        let vector = T::vector(vector).expect("key of the group table type");
+        self.values.push(vector.values()[row]);
+        self.validity.push(vector.is_valid(row));
+    }
+
+    fn equal(&self, vector: &Vector, rows: &[u32], groups: &[u32], equal: &mut [bool]) {
+        let vector = T::vector(vector).expect("key of the group table type");
+        let values = vector.values();
+        for ((eq, &row), &group) in equal.iter_mut().zip(rows).zip(groups) {
+            if *eq {
+                let (row, group) = (row as usize, group as usize);
+                *eq = match (vector.is_valid(row), self.validity.get(group)) {
+                    (true, true) => values[row].key_eq(self.values[group]),
+                    (valid, valid_group) => valid == valid_group,
+                };
+            }
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Vector {
+        into_vector(&self.data_type, PrimaryVector::with_validity(self.values, Some(self.validity)))
+    }
+}
+
+#[derive(Default)]
+struct StringKeys {
+    offsets: Vec<usize>,
+    data: Vec<u8>,
+    validity: Bitmap,
+}
+
+impl StringKeys {
+    fn get(&self, group: usize) -> Option<&[u8]> {
+        self.validity.get(group).then(|| &self.data[self.offsets[group]..self.offsets[group + 1]])
+    }
+}
+
+impl KeyStore for StringKeys {
+    fn push(&mut self, vector: &Vector, row: usize) {
+        let Vector::String(vector) = vector else { panic!("key of the group table type") };
+        if self.offsets.is_empty() {
+            self.offsets.push(0);
+        }
+        let value = vector.get(row);
+        self.data.extend_from_slice(value.unwrap_or("").as_bytes());
+        self.offsets.push(self.data.len());
+        self.validity.push(value.is_some());
+    }
+
+    fn equal(&self, vector: &Vector, rows: &[u32], groups: &[u32], equal: &mut [bool]) {
+        let Vector::String(vector) = vector else { panic!("key of the group table type") };
+        for ((eq, &row), &group) in equal.iter_mut().zip(rows).zip(groups) {
+            if *eq {
+                *eq = vector.get(row as usize).map(str::as_bytes) == self.get(group as usize);
+            }
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Vector {
+        let values = (0..self.validity.len()).map(|g| self.get(g).map(|v| std::str::from_utf8(v).expect("utf-8 keys")));
+        Vector::String(StringVector::from_options(values))
+    }
+}
+
+/// keys of nested types, compared as values
+struct ValueKeys {
+    data_type: DataType,
+    values: Vec<Value>,
+}
+
+impl KeyStore for ValueKeys {
+    fn push(&mut self, vector: &Vector, row: usize) {
+        self.values.push(vector.get(row));
+    }
+
+    fn equal(&self, vector: &Vector, rows: &[u32], groups: &[u32], equal: &mut [bool]) {
+        for ((eq, &row), &group) in equal.iter_mut().zip(rows).zip(groups) {
+            if *eq {
+                *eq = vector.get(row as usize) == self.values[group as usize];
+            }
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Vector {
+        Vector::from_values(&self.data_type, &self.values).expect("keys of the group table type")
+    }
+}
+
+fn key_store(data_type: &DataType) -> Box<dyn KeyStore> {
+    macro_rules! primary {
+        ($t:ty) => {
+            Box::new(PrimaryKeys::<$t> { data_type: data_type.clone(), values: vec![], validity: Bitmap::default() })
+        };
+    }
+    match data_type {
+        DataType::I8 => primary!(i8),
+        DataType::I16 => primary!(i16),
+        DataType::I32 | DataType::Date => primary!(i32),
+        DataType::I64 | DataType::DateTime => primary!(i64),
+        DataType::U8 => primary!(u8),
+        DataType::U16 => primary!(u16),
+        DataType::U32 => primary!(u32),
+        DataType::U64 => primary!(u64),
+        DataType::F32 => primary!(f32),
+        DataType::F64 => primary!(f64),
+        DataType::Decimal => primary!(i128),
+        DataType::Bool => primary!(bool),
+        DataType::String => Box::new(StringKeys::default()),
+        DataType::List(_) | DataType::Struct(_) | DataType::Map(_, _) => Box::new(ValueKeys { data_type: data_type.clone(), values: vec![] }),
+    }
+}
+
+/// maps the keys of the rows to group ids `0, 1, ...`
+pub struct GroupTable {
+    keys: Vec<Box<dyn KeyStore>>,
+    table: SwissTable,
+}
+
+/// the rows of a chunk looked up in the stored keys of the groups
+struct RowKeys<'a> {
+    stores: &'a mut [Box<dyn KeyStore>],
+    keys: &'a [&'a Vector],
+}
+
+impl EntryKeys for RowKeys<'_> {
+    fn equal(&self, rows: &[u32], groups: &[u32], equal: &mut [bool]) {
+        for (store, key) in self.stores.iter().zip(self.keys) {
+            store.equal(key, rows, groups, equal);
+        }
+    }
+
+    fn insert(&mut self, row: u32) {
+        for (store, key) in self.stores.iter_mut().zip(self.keys) {
+            store.push(key, row as usize);
+        }
+    }
+}
+
+impl GroupTable {
+    /// a table for keys of `types`. without key columns, every row is in the single group 0.
+    pub fn new(types: &[DataType]) -> Self {
+        GroupTable { keys: types.iter().map(key_store).collect(), table: SwissTable::new() }
+    }
+
+    /// the number of groups
+    pub fn len(&self) -> usize {
+        if self.keys.is_empty() { 1 } else { self.table.len() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the group of every row of the key columns, adding groups for new keys
+    pub fn find_or_insert(&mut self, keys: &[&Vector], len: usize) -> Vec<u32> {
+        if self.keys.is_empty() {
+            return vec![0; len];
+        }
+        let hashes = hash_columns(keys, len);
+        self.table.find_or_insert(&hashes, &mut RowKeys { stores: &mut self.keys, keys })
+    }
+
+    /// the key columns of all groups, in group order
+    pub fn finish(self) -> Vec<Vector> {
+        self.keys.into_iter().map(|store| store.finish()).collect()
+    }
+}
+
+/// add `(value - min) * stride` to the slot of each row, nulls take the last of the `size`
+/// values. rows outside the range are marked in `outside`.
+fn add_offsets<T: PrimaryType + Into<i128>>(vector: &Vector, min: i64, size: u64, stride: u64, slots: &mut [u64], outside: &mut [bool]) {
+    let vector = T::vector(vector).expect("key of the direct table type");
+    for (row, (slot, outside)) in slots.iter_mut().zip(outside).enumerate() {
+        let offset = match vector.is_valid(row) {
+            true => vector.values()[row].into() - min as i128,
+            false => size as i128 - 1,
+        };
+        if (0..size as i128).contains(&offset) {
+            *slot += offset as u64 * stride;
+        } else {
+            *outside = true;
+        }
+    }
+}
+
+/// a group table for integer keys with known ranges, the slot of a row is computed from the key
+/// values. rows outside the ranges, e.g. of stale statistics, are grouped by a `GroupTable`.
+pub struct DirectTable {
+    types: Vec<DataType>,
+    /// the min of each key and its number of values, the last one stands for null
+    dims: Vec<(i64, u64)>,
+    /// the group of each slot, or `EMPTY`
+    slots: Vec<u32>,
+    /// the slot of each group of the slots, in the order they were added
+    indices: Vec<u64>,
+    overflow: GroupTable,
+    /// the group of each group of `overflow`
+    overflow_groups: Vec<u32>,
+    /// for each group, whether its key is in `overflow` and its position there or in `indices`
+    positions: Vec<(bool, u32)>,
+}
+
+impl DirectTable {
+    /// a table for keys of `types` within the inclusive ranges `bounds`. `None` unless the keys
+    /// are integers and fit in `DIRECT_SLOTS` slots.
+    pub fn new(types: &[DataType], bounds: &[(i64, i64)]) -> Option<Self> {
+        if types.is_empty() || types.len() != bounds.len() || !types.iter().all(DataType::is_integer) {
+            return None;
+        }
+        let (mut slots, mut dims) = (1u64, vec![]);
+        for &(min, max) in bounds {
+            let size = u64::try_from(max as i128 - min as i128 + 2).ok().filter(|&size| size >= 2)?;
+            slots = slots.checked_mul(size).filter(|&slots| slots <= DIRECT_SLOTS)?;
+            dims.push((min, size));
+        }
+        Some(DirectTable {
+            types: types.to_vec(),
+            dims,
+            slots: vec![EMPTY; slots as usize],
+            indices: vec![],
+            overflow: GroupTable::new(types),
+            overflow_groups: vec![],
+            positions: vec![],
+        })
+    }
+
+    /// the number of groups
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// the group of every row of the key columns, adding groups for new keys
+    pub fn find_or_insert(&mut self, keys: &[&Vector], len: usize) -> Vec<u32> {
+        let (mut slots, mut outside) = (vec![0u64; len], vec![false; len]);
+        let mut stride = 1;
+        for (key, &(min, size)) in keys.iter().zip(&self.dims) {
+            let add = match key {
+                Vector::I8(_) => add_offsets::<i8>,
+                Vector::I16(_) => add_offsets::<i16>,
+                Vector::I32(_) => add_offsets::<i32>,
+                Vector::I64(_) => add_offsets::<i64>,
+                Vector::U8(_) => add_offsets::<u8>,
+                Vector::U16(_) => add_offsets::<u16>,
+                Vector::U32(_) => add_offsets::<u32>,
+                Vector::U64(_) => add_offsets::<u64>,
+                _ => panic!("key of the direct table type"),
+            };
+            add(key, min, size, stride, &mut slots, &mut outside);
+            stride *= size;
+        }
+
+        let mut groups = vec![0u32; len];
+        let mut rows = vec![];
+        for (row, (&slot, &outside)) in slots.iter().zip(&outside).enumerate() {
+            if outside {
+                rows.push(row as u32);
+                continue;
+            }
+            let group = &mut self.slots[slot as usize];
+            if *group == EMPTY {
+                *group = self.positions.len() as u32;
+                self.positions.push((false, self.indices.len() as u32));
+                self.indices.push(slot);
+            }
+            groups[row] = *group;
+        }
+
+        if !rows.is_empty() {
+            let keys: Vec<Vector> = keys.iter().map(|key| key.take(&rows)).collect();
+            let overflow = self.overflow.find_or_insert(&keys.iter().collect::<Vec<_>>(), rows.len());
+            for group in self.overflow_groups.len()..self.overflow.len() {
+                self.overflow_groups.push(self.positions.len() as u32);
+                self.positions.push((true, group as u32));
+            }
+            for (&row, &group) in rows.iter().zip(&overflow) {
+                groups[row as usize] = self.overflow_groups[group as usize];
+            }
+        }
+        groups
+    }
+
+    /// the key columns of all groups, in group order
+    pub fn finish(self) -> Vec<Vector> {
+        let direct = self.indices.len() as u32;
+        let positions: Vec<u32> = self.positions.iter().map(|&(overflow, p)| if overflow { direct + p } else { p }).collect();
+        let has_overflow = !self.overflow.is_empty();
+        let overflow = self.overflow.finish();
+        let mut stride = 1;
+        let mut columns = vec![];
+        for ((data_type, &(min, size)), overflow) in self.types.iter().zip(&self.dims).zip(overflow) {
+            let values = self.indices.iter().map(|&slot| {
+                let offset = slot / stride % size;
+                (offset + 1 < size).then(|| min + offset as i64)
+            });
+            let keys = Vector::I64(PrimaryVector::from_options(values));
+            let keys = cast_vector(&keys, data_type).expect("keys within their type");
+            columns.push(match has_overflow {
+                true => Vector::concat(data_type, &[keys, overflow]).expect("keys of one type").take(&positions),
+                false => keys,
+            });
+            stride *= size;
+        }
+        columns
+    }
+}
+
+/// the group table of a group-by, a `DirectTable` when the key ranges allow it
+pub enum Groups {
+    Hash(GroupTable),
+    Direct(DirectTable),
+}
+
+impl Groups {
+    /// a direct table when `bounds` are known and suitable, otherwise a hash table
+    pub fn new(types: &[DataType], bounds: Option<&[(i64, i64)]>) -> Self {
+        match bounds.and_then(|bounds| DirectTable::new(types, bounds)) {
+            Some(table) => Groups::Direct(table),
+            None => Groups::Hash(GroupTable::new(types)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Groups::Hash(table) => table.len(),
+            Groups::Direct(table) => table.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn find_or_insert(&mut self, keys: &[&Vector], len: usize) -> Vec<u32> {
+        match self {
+            Groups::Hash(table) => table.find_or_insert(keys, len),
+            Groups::Direct(table) => table.find_or_insert(keys, len),
+        }
+    }
+
+    pub fn finish(self) -> Vec<Vector> {
+        match self {
+            Groups::Hash(table) => table.finish(),
+            Groups::Direct(table) => table.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_or_insert() {
+        let mut table = GroupTable::new(&[DataType::String, DataType::I32]);
+        let names = Vector::from(vec![Some("a"), Some("b"), Some("a"), None, None]);
+        let ids = Vector::from(vec![Some(1i32), Some(1), Some(1), None, None]);
+        let groups = table.find_or_insert(&[&names, &ids], 5);
+        assert_eq!(table.len(), 3);
+        assert_eq!((groups[0], groups[3]), (groups[2], groups[4]));
+        assert_ne!(groups[0], groups[1]);
+
+        // the groups survive growing the slots
+        let many = Vector::from((0..5000).map(|i| format!("k{}", i % 1000)).collect::<Vec<_>>());
+        let ids = Vector::from(vec![1i32; 5000]);
+        let groups = table.find_or_insert(&[&many, &ids], 5000);
+        assert_eq!(table.len(), 1003);
+        assert!((0..5000).all(|i| groups[i] == groups[i % 1000]));
+
+        let keys = table.finish();
+        assert_eq!(keys[0].len(), 1003);
+        assert_eq!(keys[1].null_count(), 1);
+    }
+
+    #[test]
+    fn test_direct_table() {
+        let mut table = DirectTable::new(&[DataType::I32, DataType::U8], &[(-5, 5), (0, 3)]).unwrap();
+        let ids = Vector::from(vec![Some(-5i32), Some(5), None, Some(-5), Some(100)]);
+        let kinds = Vector::from(vec![Some(0u8), Some(3), Some(1), Some(0), Some(1)]);
+        assert_eq!(table.find_or_insert(&[&ids, &kinds], 5), vec![0, 1, 2, 0, 3]);
+        // 100 is outside the range and went to the overflow table
+        let ids = Vector::from(vec![Some(100i32), None]);
+        let kinds = Vector::from(vec![Some(1u8), None]);
+        assert_eq!(table.find_or_insert(&[&ids, &kinds], 2), vec![3, 4]);
+
+        let keys = table.finish();
+        assert_eq!(keys[0], Vector::from(vec![Some(-5i32), Some(5), None, Some(100), None]));
+        assert_eq!(keys[1], Vector::from(vec![Some(0u8), Some(3), Some(1), Some(1), None]));
+    }
+
+    #[test]
+    fn test_choose_table() {
+        let bounds = [(1, 1000)];
+        assert!(matches!(Groups::new(&[DataType::I64], Some(&bounds)), Groups::Direct(_)));
+        assert!(matches!(Groups::new(&[DataType::I64], None), Groups::Hash(_)));
+        assert!(matches!(Groups::new(&[DataType::String], Some(&bounds)), Groups::Hash(_)));
+        assert!(matches!(Groups::new(&[DataType::I64], Some(&[(0, DIRECT_SLOTS as i64)])), Groups::Hash(_)));
+        assert!(matches!(Groups::new(&[DataType::I64, DataType::I64], Some(&[(0, 2000), (0, 2000)])), Groups::Hash(_)));
+    }
+
+    #[test]
+    fn test_without_keys() {
+        let mut table = GroupTable::new(&[]);
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.find_or_insert(&[], 3), vec![0, 0, 0]);
+    }
+}
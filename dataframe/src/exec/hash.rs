@@ -0,0 +1,251 @@
+//! Hashing and equality of key columns, shared by the hash join and the hash group-by.
+//!
+//! Both work a column at a time: `hash_columns` folds the hash of every key column into one
+//! hash per row, `equal_rows` compares a batch of (left row, right row) pairs column by column.
+//! Floats are compared by their bits after folding `-0.0` into `0.0` and every NaN into one, so
+//! equal keys always have equal hashes.
+
+use crate::vector::{PrimaryType, PrimaryVector, Vector};
+
+/// the hash of a null value, whatever its type
+const NULL_HASH: u64 = 0x2f69_3b2e_8c51_d0a7;
+
+/// the finalizer of murmur3, spreads every input bit over the whole hash
+#[inline]
+fn mix(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// a fixed width type that can be a key
+pub trait HashKey: PrimaryType {
+    fn hash_key(self) -> u64;
+
+    fn key_eq(self, other: Self) -> bool {
+        self == other
+    }
+}
+
+macro_rules! impl_integer_key {
+    ($($t:ty),*) => {
+        $(impl HashKey for $t {
+            #[inline]
+            fn hash_key(self) -> u64 {
+                mix(self as u64)
+            }
+        })*
+    };
+}
+
+impl_integer_key!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+impl HashKey for bool {
+    #[inline]
+    fn hash_key(self) -> u64 {
+        mix(self as u64)
+    }
+}
+
+impl HashKey for i128 {
+    #[inline]
+    fn hash_key(self) -> u64 {
+        mix(self as u64 ^ mix((self >> 64) as u64))
+    }
+}
+
+macro_rules! impl_float_key {
+    ($($t:ty),*) => {
+        $(impl HashKey for $t {
+            #[inline]
+            fn hash_key(self) -> u64 {
+                mix(float_bits(self as f64))
+            }
+
+            #[inline]
+            fn key_eq(self, other: Self) -> bool {
+                float_bits(self as f64) == float_bits(other as f64)
+            }
+        })*
+    };
+}
+
+impl_float_key!(f32, f64);
+
+#[inline]
+fn float_bits(v: f64) -> u64 {
+    if v == 0.0 {
+        0
+    } else if v.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        v.to_bits()
+    }
+}
+
+/// hash a byte string 8 bytes at a time
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut h = bytes.len() as u64;
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        h = mix(h ^ u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        let mut last = [0u8; 8];
+        last[..rest.len()].copy_from_slice(rest);
+        h = mix(h ^ u64::from_le_bytes(last) ^ 0xff);
+    }
+    h
+}
+
+fn hash_primary<T: HashKey>(vector: &PrimaryVector<T>, hashes: &mut [u64], first: bool) {
+    let combine = |h: &mut u64, v: u64| *h = if first { v } else { mix(h.rotate_left(32) ^ v) };
+    match vector.validity() {
+        None => {
+            for (h, &v) in hashes.iter_mut().zip(vector.values()) {
+                combine(h, v.hash_key());
+            }
+        }
+        Some(validity) => {
+            for (i, (h, &v)) in hashes.iter_mut().zip(vector.values()).enumerate() {
+                combine(h, if validity.get(i) { v.hash_key() } else { NULL_HASH });
+            }
+        }
+    }
+}
+
+/// fold the hashes of `vector` into `hashes`, which are overwritten for the first key column
+fn hash_vector(vector: &Vector, hashes: &mut [u64], first: bool) {
+    macro_rules! primary {
+        ($v:expr) => { hash_primary($v, hashes, first) };
+    }
+    match vector {
+        Vector::I8(v) => primary!(v),
+        Vector::I16(v) => primary!(v),
+        Vector::I32(v) | Vector::Date(v) => primary!(v),
+        Vector::I64(v) | Vector::DateTime(v) => primary!(v),
+        Vector::U8(v) => primary!(v),
+        Vector::U16(v) => primary!(v),
+        Vector::U32(v) => primary!(v),
+        Vector::U64(v) => primary!(v),
+        Vector::F32(v) => primary!(v),
+        Vector::F64(v) => primary!(v),
+        Vector::Decimal(v) => primary!(v),
+        Vector::Bool(v) => primary!(v),
+        Vector::String(v) => {
+            for (i, h) in hashes.iter_mut().enumerate() {
+                let value = v.get(i).map(|s| hash_bytes(s.as_bytes())).unwrap_or(NULL_HASH);
+                *h = if first { value } else { mix(h.rotate_left(32) ^ value) };
+            }
+        }
+        // nested keys are rare, hash their printed value
+        Vector::List(_) | Vector::Struct(_) | Vector::Map(_) => {
+            for (i, h) in hashes.iter_mut().enumerate() {
+                let value = vector.get(i);
+                let value = if value.is_null() { NULL_HASH } else { hash_bytes(format!("{value:?}").as_bytes()) };
+                *h = if first { value } else { mix(h.rotate_left(32) ^ value) };
+            }
+        }
+    }
+}
+
+/// the hash of every row of the key columns, all of length `len`. a null hashes to the same
+/// value in every column.
+pub fn hash_columns(columns: &[&Vector], len: usize) -> Vec<u64> {
+    let mut hashes = vec![NULL_HASH; len];
+    for (i, column) in columns.iter().enumerate() {
+        hash_vector(column, &mut hashes, i == 0);
+    }
+    hashes
+}
+
+fn equal_primary<T: HashKey>(left: &PrimaryVector<T>, left_rows: &[u32], right: &PrimaryVector<T>, right_rows: &[u32], equal: &mut [bool]) {
+    let (lv, rv) = (left.values(), right.values());
+    let nullable = left.validity().is_some() || right.validity().is_some();
+    for ((eq, &l), &r) in equal.iter_mut().zip(left_rows).zip(right_rows) {
+        if *eq {
+            let (l, r) = (l as usize, r as usize);
+            *eq = if nullable {
+                match (left.is_valid(l), right.is_valid(r)) {
+                    (true, true) => lv[l].key_eq(rv[r]),
+                    (valid_l, valid_r) => valid_l == valid_r,
+                }
+            } else {
+                lv[l].key_eq(rv[r])
+            };
+        }
+    }
+}
+
+/// and `equal[i]` with whether row `left_rows[i]` of `left` equals row `right_rows[i]` of
+/// `right`. a null equals a null here, the hash join skips null keys before. pairs that are
+/// already unequal are not compared again.
+pub fn equal_rows(left: &Vector, left_rows: &[u32], right: &Vector, right_rows: &[u32], equal: &mut [bool]) {
+    macro_rules! primary {
+        ($l:expr, $r:expr) => { equal_primary($l, left_rows, $r, right_rows, equal) };
+    }
+    match (left, right) {
+        (Vector::I8(l), Vector::I8(r)) => primary!(l, r),
+        (Vector::I16(l), Vector::I16(r)) => primary!(l, r),
+        (Vector::I32(l), Vector::I32(r)) | (Vector::Date(l), Vector::Date(r)) => primary!(l, r),
+        (Vector::I64(l), Vector::I64(r)) | (Vector::DateTime(l), Vector::DateTime(r)) => primary!(l, r),
+        (Vector::U8(l), Vector::U8(r)) => primary!(l, r),
+        (Vector::U16(l), Vector::U16(r)) => primary!(l, r),
+        (Vector::U32(l), Vector::U32(r)) => primary!(l, r),
+        (Vector::U64(l), Vector::U64(r)) => primary!(l, r),
+        (Vector::F32(l), Vector::F32(r)) => primary!(l, r),
+        (Vector::F64(l), Vector::F64(r)) => primary!(l, r),
+        (Vector::Decimal(l), Vector::Decimal(r)) => primary!(l, r),
+        (Vector::Bool(l), Vector::Bool(r)) => primary!(l, r),
+        (Vector::String(l), Vector::String(r)) => {
+            for ((eq, &i), &j) in equal.iter_mut().zip(left_rows).zip(right_rows) {
+                if *eq {
+                    *eq = l.get(i as usize) == r.get(j as usize);
+                }
+            }
+        }
+        _ if left.data_type() == right.data_type() => {
+            for ((eq, &i), &j) in equal.iter_mut().zip(left_rows).zip(right_rows) {
+                if *eq {
+                    *eq = left.get(i as usize) == right.get(j as usize);
+                }
+            }
+        }
+        _ => equal.fill(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_columns() {
+        let ids = Vector::from(vec![Some(1i32), Some(2), None, Some(1)]);
+        let names = Vector::from(vec![Some("a"), Some("a"), None, Some("a")]);
+        let hashes = hash_columns(&[&ids, &names], 4);
+        assert_eq!(hashes[0], hashes[3]);
+        assert_ne!(hashes[0], hashes[1]);
+        assert_eq!(hash_columns(&[&names], 4)[2], hash_columns(&[&ids], 4)[2]);
+
+        let floats = Vector::from(vec![0.0f64, -0.0, f64::NAN, -f64::NAN]);
+        let hashes = hash_columns(&[&floats], 4);
+        assert_eq!((hashes[0], hashes[2]), (hashes[1], hashes[3]));
+    }
+
+    #[test]
+    fn test_equal_rows() {
+        let left = Vector::from(vec![Some(1i64), None, Some(3)]);
+        let right = Vector::from(vec![Some(3i64), Some(1), None]);
+        let mut equal = vec![true; 4];
+        equal_rows(&left, &[0, 1, 2, 0], &right, &[1, 2, 0, 0], &mut equal);
+        assert_eq!(equal, vec![true, true, true, false]);
+
+        let mut equal = vec![true, false];
+        equal_rows(&Vector::from(vec!["x", "y"]), &[0, 1], &Vector::from(vec!["x", "x"]), &[0, 0], &mut equal);
+        assert_eq!(equal, vec![true, false]);
+    }
+}
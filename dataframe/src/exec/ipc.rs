@@ -0,0 +1,276 @@
+//! Reading and writing arrow ipc files for an `arrow_scan` and a `write_arrow`, see `qir::ipc`.
+//! A file is memory mapped and its record batches are decoded in place, so the vectors of a scan
+//! view the pages of the file (see `vector::arrow`) and only the touched pages are read.
+//!
+//! ```text
+//! users.arrow: magic | schema | batch 0 | batch 1 | footer: schema, dictionaries, batch blocks | len | magic
+//! batch 1 block -> RecordBatch "id": Int32, "name": Utf8 -> chunks of VECTOR_SIZE rows
+//! ```
+//!
+//! Dictionaries are read up front. A written file holds a record batch per chunk of the pipeline.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use arrow::array::{RecordBatch, RecordBatchOptions};
+use arrow::buffer::Buffer as ArrowBuffer;
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use arrow::error::ArrowError;
+use arrow::ipc::convert::fb_to_schema;
+use arrow::ipc::reader::{read_footer_length, FileDecoder};
+use arrow::ipc::writer::FileWriter;
+use arrow::ipc::{root_as_footer, Block};
+use memmap2::Mmap;
+use crate::error::{Error, Result};
+use crate::exec::SourceReader;
+use crate::qir::Column;
+use crate::vector::{from_arrow_type, to_arrow_type, DataChunk, Vector, VECTOR_SIZE};
+
+/// the trailer of a file: the length of the footer and the magic `ARROW1`
+const TRAILER_LEN: usize = 10;
+
+fn invalid(message: &str) -> Error {
+    Error::Arrow(ArrowError::IpcError(message.to_string()))
+}
+
+/// an arrow ipc file in memory
+pub struct ArrowFile {
+    buffer: ArrowBuffer,
+    schema: SchemaRef,
+    decoder: FileDecoder,
+    /// the locations of the record batches in the buffer
+    batches: Vec<Block>,
+}
+
+impl ArrowFile {
+    /// map the file at `path` into memory
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the file is not changed while it is mapped, ipc files are written once
+        let mmap = unsafe { Mmap::map(&file)? };
+        let (ptr, len) = (NonNull::from(&mmap[..]).cast(), mmap.len());
+        // SAFETY: the buffer owns the mapping, which stays valid and unchanged while a vector views it
+        Self::new(unsafe { ArrowBuffer::from_custom_allocation(ptr, len, Arc::new(mmap)) })
+    }
+
+    /// read the footer and the dictionaries of a file in `buffer`
+    pub fn new(buffer: ArrowBuffer) -> Result<Self> {
+        if buffer.len() < TRAILER_LEN {
+            return Err(invalid("an arrow ipc file too short for its footer"));
+        }
+        let trailer_start = buffer.len() - TRAILER_LEN;
+        let footer_len = read_footer_length(buffer[trailer_start..].try_into().expect("a trailer"))?;
+        let footer_start = trailer_start.checked_sub(footer_len).ok_or_else(|| invalid("an arrow ipc footer out of the file"))?;
+        let footer = root_as_footer(&buffer[footer_start..trailer_start]).map_err(|e| invalid(&format!("an invalid footer: {e}")))?;
+        let schema = Arc::new(fb_to_schema(footer.schema().ok_or_else(|| invalid("an arrow ipc file without a schema"))?));
+        let mut decoder = FileDecoder::new(schema.clone(), footer.version());
+        for block in footer.dictionaries().iter().flatten() {
+            decoder.read_dictionary(block, &Self::block(&buffer, block)?)?;
+        }
+        let batches = footer.recordBatches().map(|b| b.iter().copied().collect()).unwrap_or_default();
+        Ok(ArrowFile { buffer, schema, decoder, batches })
+    }
+
+    /// the bytes of `block`, an error if they are not all in the buffer
+    fn block(buffer: &ArrowBuffer, block: &Block) -> Result<ArrowBuffer> {
+        let offset = usize::try_from(block.offset()).ok();
+        let len = usize::try_from(block.metaDataLength()).ok()
+            .zip(usize::try_from(block.bodyLength()).ok())
+            .and_then(|(metadata, body)| metadata.checked_add(body));
+        match offset.zip(len) {
+            Some((offset, len)) if offset.checked_add(len).is_some_and(|end| end <= buffer.len()) => Ok(buffer.slice_with_length(offset, len)),
+            _ => Err(invalid("an arrow ipc block out of the file")),
+        }
+    }
+
+    /// the columns of the file, in file order, e.g. for the `:columns` of a scan
+    pub fn schema(&self) -> Result<Vec<Column>> {
+        self.schema.fields().iter()
+            .map(|f| Ok(Column { name: f.name().clone(), data_type: from_arrow_type(f.data_type())?, nullable: f.is_nullable() }))
+            .collect()
+    }
+
+    pub fn num_batches(&self) -> usize {
+        self.batches.len()
+    }
+
+    /// decode record batch `index`, its arrays view the buffer of the file
+    pub fn batch(&self, index: usize) -> Result<RecordBatch> {
+        let block = &self.batches[index];
+        self.decoder.read_record_batch(block, &Self::block(&self.buffer, block)?)?
+            .ok_or_else(|| invalid("a record batch block without a record batch"))
+    }
+
+    /// a reader of `columns`, which must have the same type in the file
+    pub fn read(mut self, columns: &[Column]) -> Result<ArrowReader> {
+        let schema = self.schema()?;
+        let mut indices = vec![];
+        for column in columns {
+            let index = schema.iter().position(|c| c.name == column.name).ok_or_else(|| Error::ColumnNotFound(column.name.clone()))?;
+            if schema[index].data_type != column.data_type {
+                return Err(Error::TypeMismatch { expected: format!("{:?}", column.data_type), found: format!("{:?} in column `{}`", schema[index].data_type, column.name) });
+            }
+            indices.push(index);
+        }
+        // the decoder returns the projected columns in file order
+        let mut projection = indices.clone();
+        projection.sort_unstable();
+        projection.dedup();
+        let columns = indices.iter().map(|i| projection.binary_search(i).expect("a projected column")).collect();
+        self.decoder = self.decoder.with_projection(projection);
+        Ok(ArrowReader { file: self, columns, next_batch: 0, current: vec![], rows: 0, offset: 0 })
+    }
+}
+
+/// reads the chunks of the columns of an arrow ipc file, one record batch after the other
+pub struct ArrowReader {
+    file: ArrowFile,
+    /// the column of the projected batches of each column of the reader
+    columns: Vec<usize>,
+    next_batch: usize,
+    /// the vectors of the current batch, its rows and the first row not returned yet
+    current: Vec<Vector>,
+    rows: usize,
+    offset: usize,
+}
+
+impl SourceReader for ArrowReader {
+    fn next_chunk(&mut self) -> Result<Option<DataChunk>> {
+        while self.offset == self.rows {
+            if self.next_batch == self.file.num_batches() {
+                return Ok(None);
+            }
+            let batch = self.file.batch(self.next_batch)?;
+            self.next_batch += 1;
+            self.current = self.columns.iter().map(|&i| Vector::from_arrow(batch.column(i))).collect::<Result<_>>()?;
+            (self.rows, self.offset) = (batch.num_rows(), 0);
+        }
+        let len = VECTOR_SIZE.min(self.rows - self.offset);
+        let chunk = match self.current.is_empty() {
+            true => DataChunk::empty(len),
+            false => DataChunk::new(self.current.iter().map(|v| v.slice(self.offset, len)).collect()),
+        };
+        self.offset += len;
+        Ok(Some(chunk))
+    }
+}
+
+/// writes the chunks of a pipeline as the record batches of an arrow ipc file
+pub struct ArrowWriter {
+    writer: FileWriter<BufWriter<File>>,
+    schema: SchemaRef,
+    rows: usize,
+}
+
+impl ArrowWriter {
+    /// create the file at `path` for chunks of `columns`
+    pub fn create(path: &Path, columns: &[Column]) -> Result<Self> {
+        let fields = columns.iter()
+            .map(|c| Ok(Field::new(c.name.clone(), to_arrow_type(&c.data_type)?, c.nullable)))
+            .collect::<Result<Vec<_>>>()?;
+        let schema = Arc::new(Schema::new(fields));
+        let writer = FileWriter::try_new_buffered(File::create(path)?, &schema)?;
+        Ok(ArrowWriter { writer, schema, rows: 0 })
+    }
+
+    pub fn write(&mut self, chunk: &DataChunk) -> Result<()> {
+        let columns = chunk.columns.iter().map(|c| c.to_arrow()).collect::<Result<Vec<_>>>()?;
+        let options = RecordBatchOptions::new().with_row_count(Some(chunk.len()));
+        self.writer.write(&RecordBatch::try_new_with_options(self.schema.clone(), columns, &options)?)?;
+        self.rows += chunk.len();
+        Ok(())
+    }
+
+    /// write the footer, returning the rows written
+    pub fn finish(mut self) -> Result<usize> {
+        self.writer.finish()?;
+        Ok(self.rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::qir::DataType;
+    use crate::vector::{PrimaryVector, Value};
+    use super::*;
+
+    fn users() -> (Vec<Column>, DataChunk) {
+        let columns = vec![
+            Column { name: "id".into(), data_type: DataType::I32, nullable: false },
+            Column { name: "name".into(), data_type: DataType::String, nullable: false },
+            Column { name: "email".into(), data_type: DataType::String, nullable: true },
+        ];
+        let emails = (0..5000).map(|i| (i % 3 != 0).then(|| format!("user{i}@example.com"))).collect::<Vec<_>>();
+        let names = (0..5000).map(|i| format!("user {i}")).collect::<Vec<_>>();
+        let chunk = DataChunk::new(vec![
+            Vector::I32(PrimaryVector::new((0..5000).collect::<Vec<_>>())),
+            Vector::from(names.iter().map(|n| n.as_str()).collect::<Vec<_>>()),
+            Vector::from(emails.iter().map(|e| e.as_deref()).collect::<Vec<_>>()),
+        ]);
+        (columns, chunk)
+    }
+
+    #[test]
+    fn test_write_read_arrow() {
+        let path = std::env::temp_dir().join(format!("dataframe-{}-users.arrow", std::process::id()));
+        let (columns, chunk) = users();
+        let mut writer = ArrowWriter::create(&path, &columns).unwrap();
+        writer.write(&chunk.slice(0, 3000)).unwrap();
+        writer.write(&chunk.slice(3000, 2000)).unwrap();
+        assert_eq!(writer.finish().unwrap(), 5000);
+
+        let file = ArrowFile::open(&path).unwrap();
+        assert_eq!(file.schema().unwrap(), columns);
+        assert_eq!(file.num_batches(), 2);
+        // the columns are read in the order asked, in chunks of at most VECTOR_SIZE rows
+        let mut reader = file.read(&[columns[2].clone(), columns[0].clone()]).unwrap();
+        let mut lens = vec![];
+        let mut rows = vec![];
+        while let Some(chunk) = reader.next_chunk().unwrap() {
+            lens.push(chunk.len());
+            rows.extend((0..chunk.len()).map(|i| chunk.row(i)));
+        }
+        assert_eq!(lens, vec![2048, 952, 2000]);
+        assert_eq!(rows[3], vec![Value::Null, Value::I32(3)]);
+        assert_eq!(rows[4001], vec![Value::String("user4001@example.com".into()), Value::I32(4001)]);
+
+        let mut wrong = columns[0].clone();
+        wrong.data_type = DataType::I64;
+        let err = ArrowFile::open(&path).unwrap().read(&[wrong]).err().unwrap().to_string();
+        assert_eq!(err, "type mismatch: expected I64, found I32 in column `id`");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_arrow_in_place() {
+        let path = std::env::temp_dir().join(format!("dataframe-{}-in-place.arrow", std::process::id()));
+        let (columns, chunk) = users();
+        let mut writer = ArrowWriter::create(&path, &columns).unwrap();
+        writer.write(&chunk).unwrap();
+        writer.finish().unwrap();
+
+        // the values of a batch point into the mapped file
+        let file = ArrowFile::open(&path).unwrap();
+        let range = file.buffer.as_ptr_range();
+        let mut reader = file.read(&columns[..2]).unwrap();
+        let chunk = reader.next_chunk().unwrap().unwrap();
+        let (Vector::I32(ids), Vector::String(names)) = (chunk.column(0), chunk.column(1)) else { unreachable!() };
+        assert!(range.contains(&(ids.values().as_ptr() as *const u8)));
+        assert!(range.contains(&names.data().as_ptr()));
+        assert_eq!(names.value(7), "user 7");
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(ArrowFile::new(ArrowBuffer::from(b"ARROW1".as_slice())), Err(Error::Arrow(_))));
+    }
+
+    #[test]
+    fn test_block_out_of_file() {
+        let buffer = ArrowBuffer::from(vec![0u8; 64]);
+        assert_eq!(ArrowFile::block(&buffer, &Block::new(8, 16, 40)).unwrap().len(), 56);
+        for block in [Block::new(8, 16, 41), Block::new(-8, 16, 8), Block::new(i64::MAX, 16, 8), Block::new(0, 8, i64::MAX)] {
+            assert!(matches!(ArrowFile::block(&buffer, &block), Err(Error::Arrow(_))));
+        }
+    }
+}
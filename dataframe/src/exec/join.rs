@@ -0,0 +1,180 @@
+//! The hash table of a hash join, the bucket chained `BuildTable` of
+//! `poc/vector_example1/src/bin/test_hash_join2.rs` made complete.
+//!
+//! The distinct keys of the build side are the entries of a `SwissTable`, and `first[entry]` is
+//! the first build row of a key and `next[row]` the next row with the same key. A probe looks up
+//! the keys of a whole chunk at once, comparing each probe row with the first row of its
+//! candidate keys column by column, then walks the rows of the key it found without comparing
+//! again.
+
+use crate::error::{Error, Result};
+use std::rc::Rc;
+use crate::exec::hash::{equal_rows, hash_columns};
+use crate::exec::runtime_filter::RuntimeFilter;
+use crate::exec::swiss::{EntryKeys, SwissTable, NONE};
+use crate::vector::{DataChunk, Vector};
+
+/// the build side of a hash join
+#[derive(Debug)]
+pub struct JoinHashTable {
+    /// the key columns followed by the value columns. the last row is all nulls, it is the
+    /// build row of the unmatched probe rows of a left join.
+    data: DataChunk,
+    keys: usize,
+    table: SwissTable,
+    /// the first build row of each key, or `NONE` for a key with a null
+    first: Vec<u32>,
+    next: Vec<u32>,
+    /// the filter of the probe rows pushed down to the scans, see `exec::runtime_filter`
+    filter: Option<Rc<RuntimeFilter>>,
+}
+
+/// the build rows looked up in the keys of the table while it is built
+struct BuildKeys<'a> {
+    columns: &'a [&'a Vector],
+    /// a build row of each key
+    rows: Vec<u32>,
+}
+
+impl EntryKeys for BuildKeys<'_> {
+    fn equal(&self, rows: &[u32], entries: &[u32], equal: &mut [bool]) {
+        let keys: Vec<u32> = entries.iter().map(|&e| self.rows[e as usize]).collect();
+        for column in self.columns {
+            equal_rows(column, rows, column, &keys, equal);
+        }
+    }
+
+    fn insert(&mut self, row: u32) {
+        self.rows.push(row);
+    }
+}
+
+impl JoinHashTable {
+    /// build the table from all rows of the build side, the first `keys` columns are the keys.
+    /// rows with a null key never match and are not chained.
+    pub fn build(data: DataChunk, keys: usize) -> Result<Self> {
+        let rows = data.len();
+        if rows >= NONE as usize {
+            return Err(Error::Unsupported(format!("a hash join build side of {rows} rows")));
+        }
+        let key_columns: Vec<&Vector> = data.columns[..keys].iter().collect();
+        let hashes = hash_columns(&key_columns, rows);
+        let mut table = SwissTable::with_capacity(rows);
+        let entries = table.find_or_insert(&hashes, &mut BuildKeys { columns: &key_columns, rows: vec![] });
+
+        let mut first = vec![NONE; table.len()];
+        let mut next = vec![NONE; rows];
+        // chain backwards, so that a key lists its rows in build order
+        for row in (0..rows).rev() {
+            if key_columns.iter().all(|c| c.is_valid(row)) {
+                let entry = entries[row] as usize;
+                next[row] = first[entry];
+                first[entry] = row as u32;
+            }
+        }
+
+        let nulls: Vec<Vector> = data.columns.iter().map(|c| Vector::new_null(&c.data_type(), 1)).collect();
+        let data = DataChunk::concat(&[data, DataChunk::new(nulls)])?;
+        Ok(JoinHashTable { data, keys, table, first, next, filter: None })
+    }
+
+    pub fn with_filter(self, filter: RuntimeFilter) -> Self {
+        JoinHashTable { filter: Some(Rc::new(filter)), ..self }
+    }
+
+    pub fn filter(&self) -> Option<Rc<RuntimeFilter>> {
+        self.filter.clone()
+    }
+
+    /// the number of build rows
+    pub fn len(&self) -> usize {
+        self.next.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.next.is_empty()
+    }
+
+    /// the key and value columns, with the extra null row
+    pub fn data(&self) -> &DataChunk {
+        &self.data
+    }
+
+    /// the (probe row, build row) pairs of the probe rows whose `keys` equal the keys of a
+    /// build row, in probe row order. with `outer`, a probe row without a match is paired
+    /// with the null row.
+    pub fn matches(&self, keys: &[&Vector], len: usize, outer: bool) -> (Vec<u32>, Vec<u32>) {
+        let (mut probe_rows, mut build_rows) = (Vec::with_capacity(len), Vec::with_capacity(len));
+        let null_row = self.len() as u32;
+        for (row, first) in self.probe(keys, len).into_iter().enumerate() {
+            let mut build = first;
+            while build != NONE {
+                probe_rows.push(row as u32);
+                build_rows.push(build);
+                build = self.next[build as usize];
+            }
+            if outer && first == NONE {
+                probe_rows.push(row as u32);
+                build_rows.push(null_row);
+            }
+        }
+        (probe_rows, build_rows)
+    }
+
+    /// whether the keys of each probe row equal the keys of some build row
+    pub fn contains(&self, keys: &[&Vector], len: usize) -> Vec<bool> {
+        self.probe(keys, len).into_iter().map(|first| first != NONE).collect()
+    }
+
+    /// the first build row with the keys of every probe row, or `NONE`
+    fn probe(&self, keys: &[&Vector], len: usize) -> Vec<u32> {
+        debug_assert_eq!(keys.len(), self.keys);
+        let hashes = hash_columns(keys, len);
+        let entries = self.table.find(&hashes, |rows, entries, equal| {
+            let firsts: Vec<u32> = entries.iter().map(|&e| self.first[e as usize]).collect();
+            // a key with a null has no rows and never matches
+            equal.iter_mut().zip(&firsts).for_each(|(eq, &first)| *eq &= first != NONE);
+            for (k, key) in keys.iter().enumerate() {
+                equal_rows(key, rows, &self.data.columns[k], &firsts, equal);
+            }
+        });
+        entries.into_iter().map(|entry| if entry == NONE { NONE } else { self.first[entry as usize] }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> JoinHashTable {
+        // sku 1 twice, a null key that never matches
+        let data = DataChunk::new(vec![
+            Vector::from(vec![Some(1u32), Some(2), Some(1), None]),
+            Vector::from(vec!["a", "b", "c", "d"]),
+        ]);
+        JoinHashTable::build(data, 1).unwrap()
+    }
+
+    #[test]
+    fn test_matches() {
+        let table = table();
+        let probe = Vector::from(vec![Some(2u32), Some(3), Some(1), None]);
+        let (probe_rows, build_rows) = table.matches(&[&probe], 4, false);
+        assert_eq!((probe_rows, build_rows), (vec![0, 2, 2], vec![1, 0, 2]));
+
+        let (probe_rows, build_rows) = table.matches(&[&probe], 4, true);
+        assert_eq!(probe_rows, vec![0, 1, 2, 2, 3]);
+        assert_eq!(build_rows, vec![1, 4, 0, 2, 4]);
+        assert!(table.data().column(1).take(&build_rows).get(1).is_null());
+    }
+
+    #[test]
+    fn test_contains_with_long_chains() {
+        // buckets shared by several keys, probes walk their chains
+        let keys: Vec<i64> = (0..10_000).map(|i| i * 7).collect();
+        let table = JoinHashTable::build(DataChunk::new(vec![Vector::from(keys)]), 1).unwrap();
+        let probe = Vector::from((0..3000i64).collect::<Vec<_>>());
+        let found = table.contains(&[&probe], 3000);
+        assert!(found.iter().enumerate().all(|(i, &f)| f == (i % 7 == 0)));
+    }
+}
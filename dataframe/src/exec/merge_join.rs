@@ -0,0 +1,139 @@
+//! The right side of a merge join: the rows of a parent pipeline sorted by their keys, merged
+//! with the chunks of an input sorted by the same keys.
+//!
+//! Both sides are compared by their normalized keys in ascending order with nulls last, the
+//! order of `order_by` (see `exec::order_by`). A cursor points at the first right row whose key
+//! is not less than the key of the last input row, and only moves forward: the input rows with
+//! equal keys pair with the same run of right rows, the duplicates of both sides give all their
+//! pairs. A row with a null key never matches.
+
+use std::cmp::Ordering;
+use crate::error::{Error, Result};
+use crate::exec::order_by::{SortKeys, SortOrder};
+use crate::vector::{DataChunk, Vector};
+
+/// the sorted rows of the right side and the position of the merge
+pub struct SortedSide {
+    /// the rows followed by a null row, the right row of the unmatched input rows of a left join
+    data: DataChunk,
+    keys: SortKeys,
+    /// the right rows with the key of the last input row are `cursor..end`
+    cursor: usize,
+    end: usize,
+    last: Option<Vec<u8>>,
+}
+
+/// the normalized keys of `columns`, ascending with nulls last
+fn encode(columns: &[&Vector]) -> Result<SortKeys> {
+    SortKeys::encode(columns, &vec![SortOrder::ASC; columns.len()])
+}
+
+fn not_ordered(side: &str) -> Error {
+    Error::Unsupported(format!("a merge join of a {side} side that is not ordered by its keys"))
+}
+
+impl SortedSide {
+    /// the right side from its rows, sorted by the columns `keys`
+    pub fn new(data: DataChunk, keys: &[usize]) -> Result<Self> {
+        let columns: Vec<&Vector> = keys.iter().map(|&k| data.column(k)).collect();
+        let encoded = encode(&columns)?;
+        if (1..encoded.len()).any(|row| encoded.key(row - 1) > encoded.key(row)) {
+            return Err(not_ordered("right"));
+        }
+        let nulls: Vec<Vector> = data.columns.iter().map(|c| Vector::new_null(&c.data_type(), 1)).collect();
+        let data = DataChunk::concat(&[data, DataChunk::new(nulls)])?;
+        Ok(SortedSide { data, keys: encoded, cursor: 0, end: 0, last: None })
+    }
+
+    /// the number of right rows
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// the right rows with the extra null row
+    pub fn data(&self) -> &DataChunk {
+        &self.data
+    }
+
+    /// the right rows `start..end` with the key of every row of the next input chunk, empty for
+    /// a null key
+    fn ranges(&mut self, keys: &[&Vector], len: usize) -> Result<Vec<(u32, u32)>> {
+        let encoded = encode(keys)?;
+        let mut ranges = Vec::with_capacity(len);
+        for row in 0..len {
+            let key = encoded.key(row);
+            match self.last.as_deref().map(|last| key.cmp(last)) {
+                Some(Ordering::Less) => return Err(not_ordered("left")),
+                Some(Ordering::Equal) => {}
+                _ => {
+                    self.cursor = self.end.max(self.cursor);
+                    while self.cursor < self.len() && self.keys.key(self.cursor) < key {
+                        self.cursor += 1;
+                    }
+                    self.end = self.cursor;
+                    while self.end < self.len() && self.keys.key(self.end) == key {
+                        self.end += 1;
+                    }
+                    self.last = Some(key.to_vec());
+                }
+            }
+            let valid = keys.iter().all(|k| k.is_valid(row));
+            let (start, end) = if valid { (self.cursor, self.end) } else { (self.cursor, self.cursor) };
+            ranges.push((start as u32, end as u32));
+        }
+        Ok(ranges)
+    }
+
+    /// the (input row, right row) pairs of equal keys, in input row order. with `outer`, an input
+    /// row without a match is paired with the null row.
+    pub fn matches(&mut self, keys: &[&Vector], len: usize, outer: bool) -> Result<(Vec<u32>, Vec<u32>)> {
+        let (mut left_rows, mut right_rows) = (Vec::with_capacity(len), Vec::with_capacity(len));
+        let null_row = self.len() as u32;
+        for (row, (start, end)) in self.ranges(keys, len)?.into_iter().enumerate() {
+            left_rows.extend(std::iter::repeat_n(row as u32, (end - start) as usize));
+            right_rows.extend(start..end);
+            if outer && start == end {
+                left_rows.push(row as u32);
+                right_rows.push(null_row);
+            }
+        }
+        Ok((left_rows, right_rows))
+    }
+
+    /// whether the keys of each input row equal the keys of some right row
+    pub fn contains(&mut self, keys: &[&Vector], len: usize) -> Result<Vec<bool>> {
+        Ok(self.ranges(keys, len)?.into_iter().map(|(start, end)| start < end).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        // duplicates on both sides, a null key on both sides
+        let right = DataChunk::new(vec![
+            Vector::from(vec![Some(1i64), Some(3), Some(3), Some(5), None]),
+            Vector::from(vec!["a", "b", "c", "d", "e"]),
+        ]);
+        let mut side = SortedSide::new(right, &[0]).unwrap();
+        let chunks = [vec![Some(0i64), Some(3), Some(3)], vec![Some(3), Some(4), Some(5), None]];
+        let mut pairs = vec![];
+        for chunk in &chunks {
+            let keys = Vector::from(chunk.clone());
+            pairs.push(side.matches(&[&keys], chunk.len(), true).unwrap());
+        }
+        assert_eq!(pairs[0], (vec![0, 1, 1, 2, 2], vec![5, 1, 2, 1, 2]));
+        assert_eq!(pairs[1], (vec![0, 0, 1, 2, 3], vec![1, 2, 5, 3, 5]));
+
+        let keys = Vector::from(vec![Some(2i64)]);
+        assert!(side.contains(&[&keys], 1).is_err(), "the input went back");
+        let unsorted = DataChunk::new(vec![Vector::from(vec![2i64, 1])]);
+        assert!(SortedSide::new(unsorted, &[0]).is_err());
+    }
+}
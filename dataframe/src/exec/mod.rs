@@ -0,0 +1,297 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::rc::Rc;
+use crate::error::{Error, Result};
+use crate::exec::runtime_filter::ScanFilter;
+use crate::qir::checker::Schemas;
+use crate::qir::{Column, Operator, Pipeline, Sink, Topology};
+use crate::vector::DataChunk;
+
+pub mod aggregate;
+pub mod bloom;
+pub mod cast;
+pub mod eval;
+pub mod external_sort;
+pub mod filter;
+pub mod grace;
+pub mod group_by;
+pub mod hash;
+pub mod ipc;
+pub mod join;
+pub mod merge_join;
+pub mod order_by;
+pub mod parquet;
+pub mod reduce;
+pub mod runtime_filter;
+pub mod sort;
+pub mod spill;
+pub mod swiss;
+pub mod top_n;
+pub mod window;
+
+/// the output of a sink, e.g. `Vec<DataChunk>` for an `IdentitySink`. downcast to the sink's result type.
+pub type SinkResult = Rc<dyn Any>;
+
+/// produces the chunks of a pipeline, created by `Source::open`
+pub trait SourceReader {
+    /// the next chunk, or `None` when the source is exhausted
+    fn next_chunk(&mut self) -> Result<Option<DataChunk>>;
+}
+
+/// consumes the chunks of a pipeline, created by `Sink::open`
+pub trait SinkState {
+    fn consume(&mut self, ctx: &ExecutionContext, chunk: DataChunk) -> Result<()>;
+
+    /// called after the source is exhausted
+    fn finish(self: Box<Self>, ctx: &ExecutionContext) -> Result<SinkResult>;
+}
+
+/// runtime state shared by all pipelines of a topology: the registered tables, the checked
+/// schemas of the operators and the results of the sinks that have finished.
+#[derive(Default)]
+pub struct ExecutionContext {
+    tables: HashMap<String, Rc<DataChunk>>,
+    schemas: RefCell<Schemas>,
+    results: RefCell<HashMap<usize, SinkResult>>,
+    /// the bytes a sort or the build side of a hash join may buffer before it spills to disk, as
+    /// sorted runs or grace partitions, unlimited when `None`
+    memory_budget: Option<usize>,
+    spill_dir: Option<PathBuf>,
+    /// the threads decoding the row groups of a parquet scan, the thread of the pipeline when `None`
+    scan_threads: Option<usize>,
+    /// the state of operators that keep rows between chunks, e.g. the partitions of a grace hash join
+    states: RefCell<HashMap<usize, Box<dyn Any>>>,
+}
+
+/// identify a sink by the address of its allocation, so `Rc<dyn Sink>` and `Rc<ConcreteSink>` agree
+fn sink_key<T: ?Sized>(sink: &Rc<T>) -> usize {
+    Rc::as_ptr(sink) as *const () as usize
+}
+
+impl ExecutionContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// register the data of a table, columns in the order of its `Table` definition
+    pub fn register_table(&mut self, name: &str, data: DataChunk) {
+        self.tables.insert(name.to_string(), Rc::new(data));
+    }
+
+    pub fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = Some(bytes);
+    }
+
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.memory_budget
+    }
+
+    /// the directory of the spill files, the system temp directory by default
+    pub fn set_spill_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.spill_dir = Some(dir.into());
+    }
+
+    pub fn spill_dir(&self) -> PathBuf {
+        self.spill_dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+
+    pub fn set_scan_threads(&mut self, threads: usize) {
+        self.scan_threads = Some(threads);
+    }
+
+    pub fn scan_threads(&self) -> usize {
+        self.scan_threads.unwrap_or(1)
+    }
+
+    pub fn table(&self, name: &str) -> Result<Rc<DataChunk>> {
+        self.tables.get(name).cloned().ok_or_else(|| Error::TableNotFound(name.to_string()))
+    }
+
+    /// the output columns of an operator of the running topology
+    pub fn schema<T: ?Sized>(&self, operator: &Rc<T>) -> Result<Vec<Column>> {
+        self.schemas.borrow().get(operator).map(|s| s.to_vec())
+            .ok_or_else(|| Error::ResultNotReady(format!("schema of operator@{:x}", sink_key(operator))))
+    }
+
+    /// the result of a sink of a pipeline that has already been executed
+    pub fn sink_result<T: ?Sized>(&self, sink: &Rc<T>) -> Result<SinkResult> {
+        self.results.borrow().get(&sink_key(sink)).cloned()
+            .ok_or_else(|| Error::ResultNotReady(format!("sink@{:x}", sink_key(sink))))
+    }
+
+    fn set_sink_result(&self, sink: &Rc<dyn Sink>, result: SinkResult) {
+        self.results.borrow_mut().insert(sink_key(sink), result);
+    }
+
+    /// remove the state of `operator`, which it keeps between its chunks
+    pub fn take_state<T: ?Sized>(&self, operator: &T) -> Option<Box<dyn Any>> {
+        self.states.borrow_mut().remove(&(operator as *const T as *const () as usize))
+    }
+
+    pub fn set_state<T: ?Sized>(&self, operator: &T, state: Box<dyn Any>) {
+        self.states.borrow_mut().insert(operator as *const T as *const () as usize, state);
+    }
+}
+
+/// pass a chunk through `operators` into the sink
+fn push(ctx: &ExecutionContext, operators: &[Rc<dyn Operator>], sink: &mut dyn SinkState, mut chunk: DataChunk) -> Result<()> {
+    for operator in operators {
+        chunk = operator.execute(ctx, chunk)?;
+        if chunk.is_empty() {
+            return Ok(());
+        }
+    }
+    sink.consume(ctx, chunk)
+}
+
+/// the runtime filters of the operators of `pipeline` whose columns come from its source
+fn runtime_filters(ctx: &ExecutionContext, pipeline: &Pipeline) -> Result<Vec<ScanFilter>> {
+    let mut filters = vec![];
+    for (i, operator) in pipeline.operators.iter().enumerate() {
+        let Some((filter, keys)) = operator.runtime_filter(ctx)? else { continue };
+        // follow each key through the operators before it
+        let source_column = |key: &String| pipeline.operators[..i].iter().rev()
+            .try_fold(key.as_str(), |column, operator| operator.input_column(column))
+            .and_then(|column| pipeline.source.output().iter().position(|c| c == column));
+        if let Some(columns) = keys.iter().map(source_column).collect::<Option<Vec<_>>>() {
+            filters.push(ScanFilter { filter, columns });
+        }
+    }
+    Ok(filters)
+}
+
+/// a vectorized interpreter: runs the pipelines of a topology one chunk at a time
+pub struct Interpreter {
+    pub ctx: ExecutionContext,
+}
+
+impl Interpreter {
+    pub fn new(ctx: ExecutionContext) -> Self {
+        Interpreter { ctx }
+    }
+
+    /// check the topology, run the parent pipelines (each once) and then the main pipeline,
+    /// returning the main sink's result
+    pub fn run(&self, topology: &Topology) -> Result<SinkResult> {
+        self.ctx.schemas.replace(topology.check()?);
+        let mut done = HashSet::new();
+        self.run_parents(&topology.main, &mut done)?;
+        self.run_pipeline(&topology.main)
+    }
+
+    fn run_parents(&self, pipeline: &Rc<Pipeline>, done: &mut HashSet<usize>) -> Result<()> {
+        for parent in &pipeline.parents {
+            if done.insert(Rc::as_ptr(parent) as usize) {
+                self.run_parents(parent, done)?;
+                self.run_pipeline(parent)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn run_pipeline(&self, pipeline: &Pipeline) -> Result<SinkResult> {
+        let ctx = &self.ctx;
+        let predicate = pipeline.operators.first().and_then(|operator| operator.predicate());
+        let mut reader = pipeline.source.open_pushdown(ctx, runtime_filters(ctx, pipeline)?, predicate)?;
+        let mut sink = pipeline.sink.open(ctx)?;
+
+        while let Some(chunk) = reader.next_chunk()? {
+            push(ctx, &pipeline.operators, sink.as_mut(), chunk)?;
+        }
+        // the rows kept back by an operator pass through the operators after it
+        for (i, operator) in pipeline.operators.iter().enumerate() {
+            if let Some(mut rest) = operator.finish(ctx)? {
+                while let Some(chunk) = rest.next_chunk()? {
+                    push(ctx, &pipeline.operators[i + 1..], sink.as_mut(), chunk)?;
+                }
+            }
+        }
+
+        let result = sink.finish(ctx)?;
+        ctx.set_sink_result(&pipeline.sink, result.clone());
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::qir::expr::{BinaryOp, Expr};
+    use crate::qir::{Operator, Scan, Sink, Table, Topology};
+    use crate::vector::{DataChunk, Value, Vector, VECTOR_SIZE};
+    use crate::{column, filter, identity, pipeline, scan, table};
+    use super::*;
+
+    fn customers() -> (Rc<Table>, DataChunk) {
+        let table = Rc::new(table! {
+            name: "customers",
+            columns: [
+                column! { name = "customer_id", data_type = I32 },
+                column! { name = "name", data_type = String },
+            ],
+        });
+        let rows = VECTOR_SIZE * 2 + 10;
+        let data = DataChunk::new(vec![
+            Vector::from((0..rows as i32).collect::<Vec<_>>()),
+            Vector::from((0..rows).map(|i| format!("c{i}")).collect::<Vec<_>>()),
+        ]);
+        (table, data)
+    }
+
+    #[test]
+    fn test_run_pipeline_in_chunks() {
+        let (table, data) = customers();
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("customers", data);
+
+        let scan: Rc<Scan> = Rc::new(scan! { name: "v1", table: table.clone(), output: ["customer_id", "name"] });
+        let predicate = Expr::binary(BinaryOp::GtEq, Expr::column(&table.columns, "customer_id").unwrap(), Expr::literal(10)).unwrap();
+        let filter: Rc<dyn Operator> = Rc::new(filter! { input: scan.clone(), predicate: predicate, output: ["name"] });
+        let sink: Rc<dyn Sink> = identity! { input: filter.clone() };
+        let main = Rc::new(pipeline! { source: scan, operators: [filter], sink: sink });
+
+        let interpreter = Interpreter::new(ctx);
+        let result = interpreter.run(&Topology { main }).unwrap();
+        let chunks = result.downcast_ref::<Vec<DataChunk>>().unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks.iter().map(|c| c.len()).collect::<Vec<_>>(), vec![VECTOR_SIZE - 10, VECTOR_SIZE, 10]);
+        assert_eq!(chunks[2].columns.len(), 1);
+        assert_eq!(chunks[2].column(0).get(0), Value::String(format!("c{}", VECTOR_SIZE * 2)));
+    }
+
+    #[test]
+    fn test_parents_run_before_main() {
+        let (table, data) = customers();
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("customers", data);
+
+        let scan1: Rc<Scan> = Rc::new(scan! { name: "v1", table: table.clone(), output: ["customer_id"] });
+        let sink1: Rc<dyn Sink> = identity! { input: scan1.clone() };
+        let pipeline1 = Rc::new(pipeline! { source: scan1, operators: [], sink: sink1.clone() });
+
+        let scan2: Rc<Scan> = Rc::new(scan! { name: "v2", table: table, output: ["name"] });
+        let sink2: Rc<dyn Sink> = identity! { input: scan2.clone() };
+        let main = Rc::new(pipeline! { source: scan2, operators: [], sink: sink2, parent: pipeline1 });
+
+        let interpreter = Interpreter::new(ctx);
+        interpreter.run(&Topology { main }).unwrap();
+
+        let parent = interpreter.ctx.sink_result(&sink1).unwrap();
+        let chunks = parent.downcast_ref::<Vec<DataChunk>>().unwrap();
+        assert_eq!(chunks.iter().map(|c| c.len()).sum::<usize>(), VECTOR_SIZE * 2 + 10);
+    }
+
+    #[test]
+    fn test_missing_table() {
+        let (table, _) = customers();
+        let scan: Rc<Scan> = Rc::new(scan! { name: "v1", table: table, output: ["customer_id"] });
+        let sink: Rc<dyn Sink> = identity! { input: scan.clone() };
+        let main = Rc::new(pipeline! { source: scan, operators: [], sink: sink });
+
+        let interpreter = Interpreter::new(ExecutionContext::new());
+        assert!(matches!(interpreter.run(&Topology { main }), Err(Error::TableNotFound(_))));
+    }
+}
@@ -0,0 +1,292 @@
+//! Normalized sort keys of the `order_by` sink.
+//!
+//! The key columns of a row are encoded into one byte string that compares, byte by byte, in
+//! the order of the row. Every column writes a null marker, ordering nulls first or last, then
+//! the value for valid rows: the big-endian bytes of the sort key of numbers and temporal
+//! types (see `exec::sort`), and strings with their `0` bytes escaped as `0 0xff` and ended by
+//! `0 0`, so that a string sorts before the strings it is a prefix of. The value bytes of a
+//! descending column are inverted.
+//!
+//! Rows are sorted with the SIMD `argsort` by the first 8 bytes of their keys, rows with the same
+//! prefix by the whole key.
+
+use crate::error::{Error, Result};
+use crate::exec::sort::{argsort_keys, SortKey};
+use crate::qir::DataType;
+use crate::vector::{PrimaryType, PrimaryVector, StringVector, Vector};
+
+/// the null marker of a row sorted before the valid rows, of a valid row and of a row sorted after
+const NULL_FIRST: u8 = 0;
+const VALID: u8 = 1;
+const NULL_LAST: u8 = 2;
+
+/// the order of a key column: `asc`, `desc`, `asc_nulls_first`, ... nulls are larger than any
+/// value by default, last in ascending order and first in descending order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortOrder {
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+impl SortOrder {
+    pub const ASC: SortOrder = SortOrder { descending: false, nulls_first: false };
+    pub const DESC: SortOrder = SortOrder { descending: true, nulls_first: true };
+
+    pub fn name(&self) -> &'static str {
+        match (self.descending, self.nulls_first) {
+            (false, false) => "asc",
+            (false, true) => "asc_nulls_first",
+            (true, true) => "desc",
+            (true, false) => "desc_nulls_last",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SortOrder> {
+        match name {
+            "asc" | "asc_nulls_last" => Some(SortOrder::ASC),
+            "asc_nulls_first" => Some(SortOrder { descending: false, nulls_first: true }),
+            "desc" | "desc_nulls_first" => Some(SortOrder::DESC),
+            "desc_nulls_last" => Some(SortOrder { descending: true, nulls_first: false }),
+            _ => None,
+        }
+    }
+
+    fn null_marker(&self) -> u8 {
+        if self.nulls_first { NULL_FIRST } else { NULL_LAST }
+    }
+}
+
+/// the primary types written into a key as `WIDTH` bytes in the order of their values
+trait Normalize: PrimaryType {
+    const WIDTH: usize;
+
+    fn write(self, out: &mut [u8]);
+}
+
+macro_rules! impl_normalize {
+    ($($t:ty),*) => {$(
+        impl Normalize for $t {
+            const WIDTH: usize = size_of::<<$t as SortKey>::Key>();
+
+            fn write(self, out: &mut [u8]) {
+                out.copy_from_slice(&self.to_key().to_be_bytes());
+            }
+        }
+    )*};
+}
+
+impl_normalize!(i8, i16, i32, i64, u8, u16, u32, u64, f32, f64);
+
+impl Normalize for i128 {
+    const WIDTH: usize = 16;
+
+    fn write(self, out: &mut [u8]) {
+        out.copy_from_slice(&((self as u128) ^ (1 << 127)).to_be_bytes());
+    }
+}
+
+impl Normalize for bool {
+    const WIDTH: usize = 1;
+
+    fn write(self, out: &mut [u8]) {
+        out[0] = self as u8;
+    }
+}
+
+/// call `$primary` with `$v` bound to a `PrimaryVector` of a `Normalize` type, or `$string` with
+/// `$s` bound to a `StringVector`. other types can not be sort keys.
+macro_rules! with_normalize {
+    ($vector:expr, $v:ident => $primary:expr, $s:ident => $string:expr) => {
+        match $vector {
+            Vector::I8($v) => $primary,
+            Vector::I16($v) => $primary,
+            Vector::I32($v) | Vector::Date($v) => $primary,
+            Vector::I64($v) | Vector::DateTime($v) => $primary,
+            Vector::U8($v) => $primary,
+            Vector::U16($v) => $primary,
+            Vector::U32($v) => $primary,
+            Vector::U64($v) => $primary,
+            Vector::F32($v) => $primary,
+            Vector::F64($v) => $primary,
+            Vector::Decimal($v) => $primary,
+            Vector::Bool($v) => $primary,
+            Vector::String($s) => $string,
+            other => return Err(Error::Unsupported(format!("order by a {:?} column", other.data_type()))),
+        }
+    };
+}
+
+/// whether columns of `data_type` can be sort keys
+pub fn is_sortable(data_type: &DataType) -> bool {
+    !matches!(data_type, DataType::List(_) | DataType::Struct(_) | DataType::Map(..))
+}
+
+/// the normalized keys of the rows of a chunk
+#[derive(Debug, Default)]
+pub struct SortKeys {
+    data: Vec<u8>,
+    /// the key of row `i` is `data[offsets[i]..offsets[i + 1]]`
+    offsets: Vec<usize>,
+}
+
+impl SortKeys {
+    /// encode the rows of the key `columns`, sorted in `orders`
+    pub fn encode(columns: &[&Vector], orders: &[SortOrder]) -> Result<SortKeys> {
+        let rows = columns.first().map_or(0, |c| c.len());
+        let mut widths = vec![0; rows];
+        for column in columns {
+            with_normalize!(column, v => primary_widths(v, &mut widths), s => string_widths(s, &mut widths));
+        }
+        let mut offsets = Vec::with_capacity(rows + 1);
+        offsets.push(0);
+        for width in widths {
+            offsets.push(offsets[offsets.len() - 1] + width);
+        }
+
+        let mut data = vec![0; offsets[rows]];
+        let mut cursors = offsets[..rows].to_vec();
+        for (column, order) in columns.iter().zip(orders) {
+            with_normalize!(column, v => write_primary(v, *order, &mut data, &mut cursors),
+                s => write_string(s, *order, &mut data, &mut cursors));
+        }
+        Ok(SortKeys { data, offsets })
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn key(&self, row: usize) -> &[u8] {
+        &self.data[self.offsets[row]..self.offsets[row + 1]]
+    }
+
+    /// the first 8 bytes of the key of `row`, padded with zeros
+    fn prefix(&self, row: usize) -> u64 {
+        let key = self.key(row);
+        let mut bytes = [0; 8];
+        let len = key.len().min(8);
+        bytes[..len].copy_from_slice(&key[..len]);
+        u64::from_be_bytes(bytes)
+    }
+
+    /// the permutation of the rows that sorts the keys, rows with equal keys in their order
+    pub fn argsort(&self) -> Vec<u32> {
+        let prefixes: Vec<u64> = (0..self.len()).map(|row| self.prefix(row)).collect();
+        let mut permutation = argsort_keys(&prefixes);
+        let mut start = 0;
+        while start < permutation.len() {
+            let prefix = prefixes[permutation[start] as usize];
+            let end = permutation[start..].iter().position(|&row| prefixes[row as usize] != prefix)
+                .map_or(permutation.len(), |len| start + len);
+            if end - start > 1 {
+                permutation[start..end].sort_by(|&a, &b| self.key(a as usize).cmp(self.key(b as usize)));
+            }
+            start = end;
+        }
+        permutation
+    }
+}
+
+fn primary_widths<T: Normalize>(vector: &PrimaryVector<T>, widths: &mut [usize]) {
+    for (row, width) in widths.iter_mut().enumerate() {
+        *width += 1 + if vector.is_valid(row) { T::WIDTH } else { 0 };
+    }
+}
+
+fn string_widths(vector: &StringVector, widths: &mut [usize]) {
+    for (row, width) in widths.iter_mut().enumerate() {
+        *width += 1 + match vector.get(row) {
+            Some(s) => s.len() + s.bytes().filter(|&b| b == 0).count() + 2,
+            None => 0,
+        };
+    }
+}
+
+/// write the null marker of `row` at its cursor, returning the start of its value bytes for a
+/// valid row
+#[inline(always)]
+fn marker(valid: bool, order: SortOrder, data: &mut [u8], cursor: &mut usize) -> Option<usize> {
+    data[*cursor] = if valid { VALID } else { order.null_marker() };
+    *cursor += 1;
+    valid.then_some(*cursor)
+}
+
+fn invert(descending: bool, bytes: &mut [u8]) {
+    if descending {
+        bytes.iter_mut().for_each(|b| *b = !*b);
+    }
+}
+
+fn write_primary<T: Normalize>(vector: &PrimaryVector<T>, order: SortOrder, data: &mut [u8], cursors: &mut [usize]) {
+    for (row, cursor) in cursors.iter_mut().enumerate() {
+        if let Some(start) = marker(vector.is_valid(row), order, data, cursor) {
+            *cursor += T::WIDTH;
+            vector.values()[row].write(&mut data[start..*cursor]);
+            invert(order.descending, &mut data[start..*cursor]);
+        }
+    }
+}
+
+fn write_string(vector: &StringVector, order: SortOrder, data: &mut [u8], cursors: &mut [usize]) {
+    for (row, cursor) in cursors.iter_mut().enumerate() {
+        if let Some(start) = marker(vector.is_valid(row), order, data, cursor) {
+            for &byte in vector.value(row).as_bytes() {
+                data[*cursor] = byte;
+                *cursor += 1;
+                if byte == 0 {
+                    data[*cursor] = 0xff;
+                    *cursor += 1;
+                }
+            }
+            // the terminator `0 0` is already zero
+            *cursor += 2;
+            invert(order.descending, &mut data[start..*cursor]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vector::{Value, Vector};
+    use super::*;
+
+    fn sorted(columns: &[Vector], orders: &[SortOrder]) -> Vec<u32> {
+        SortKeys::encode(&columns.iter().collect::<Vec<_>>(), orders).unwrap().argsort()
+    }
+
+    #[test]
+    fn test_single_keys() {
+        let ints = Vector::from(vec![Some(3i32), None, Some(-7), Some(3), Some(0)]);
+        assert_eq!(sorted(std::slice::from_ref(&ints), &[SortOrder::ASC]), vec![2, 4, 0, 3, 1]);
+        assert_eq!(sorted(std::slice::from_ref(&ints), &[SortOrder::DESC]), vec![1, 0, 3, 4, 2]);
+        let nulls_first = SortOrder::from_name("asc_nulls_first").unwrap();
+        assert_eq!(sorted(&[ints], &[nulls_first]), vec![1, 2, 4, 0, 3]);
+
+        let strings = Vector::from(vec![Some("b"), Some("ab"), Some(""), None, Some("a\0"), Some("a"), Some("abc")]);
+        assert_eq!(sorted(std::slice::from_ref(&strings), &[SortOrder::ASC]), vec![2, 5, 4, 1, 6, 0, 3]);
+        assert_eq!(sorted(&[strings], &[SortOrder::DESC]), vec![3, 0, 6, 1, 4, 5, 2]);
+
+        let decimals = Vector::from_values(&DataType::Decimal, &[Value::Decimal(5), Value::Decimal(-5), Value::Decimal(0)]).unwrap();
+        assert_eq!(sorted(&[decimals], &[SortOrder::ASC]), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_multiple_keys() {
+        // long strings share their prefix, the rows are ordered by the rest of the key
+        let names = Vector::from(vec!["customer_b", "customer_a", "customer_b", "customer_a", "customer_b"]);
+        let freight = Vector::from(vec![Some(1.5f64), Some(2.0), None, Some(-1.0), Some(1.5)]);
+        let orders = [SortOrder::ASC, SortOrder::DESC];
+        assert_eq!(sorted(&[names.clone(), freight.clone()], &orders), vec![1, 3, 2, 0, 4]);
+        let orders = [SortOrder::DESC, SortOrder { descending: false, nulls_first: false }];
+        assert_eq!(sorted(&[names, freight], &orders), vec![0, 4, 2, 3, 1]);
+
+        for order in [SortOrder::ASC, SortOrder::DESC, SortOrder { descending: true, nulls_first: false }] {
+            assert_eq!(SortOrder::from_name(order.name()), Some(order));
+        }
+        assert!(SortKeys::encode(&[&Vector::new_null(&DataType::List(Box::new(DataType::I32)), 2)], &[SortOrder::ASC]).is_err());
+    }
+}
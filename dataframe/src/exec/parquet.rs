@@ -0,0 +1,896 @@
+//! Reading the columns of parquet files for a `parquet_scan`, see `qir::parquet`. The column
+//! readers of the `parquet` crate decode the pages of a column into a typed buffer of its non-null
+//! values and its definition levels, which spread the values into the rows of a vector.
+//!
+//! ```text
+//! row group 0: "id" pages -> [1, 3] + levels [1, 0, 1] -> I64 [1, null, 3]
+//! row group 1: statistics min 700, max 900 outside of "id": [1, 500], not read
+//! ```
+//!
+//! The row groups whose statistics show no value in the range of a column are skipped without
+//! reading their pages. With the predicate of the `filter` after the scan, the columns of the
+//! predicate are read first, and the other columns only for the rows passing it (late
+//! materialization). Only the top-level columns of a file can be read.
+//!
+//! A scan of a directory or a pattern of file names reads its files as one table of the common
+//! type of each column (see `ParquetFiles::schema`). Its row groups are the morsels of the scan:
+//! with `ExecutionContext::set_scan_threads`, worker threads decode them ahead of the pipeline,
+//! each into a channel of its own, and the reader takes the channels in order, so the rows keep
+//! the order of the files.
+
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use parquet::basic::{ConvertedType, LogicalType, TimeUnit, Type as PhysicalType};
+use parquet::column::reader::{ColumnReader, ColumnReaderImpl};
+use parquet::data_type::{ByteArray, DataType as ParquetType};
+use parquet::file::metadata::RowGroupMetaData;
+use parquet::file::properties::ReaderProperties;
+use parquet::file::reader::{FileReader, RowGroupReader, SerializedFileReader};
+use parquet::file::serialized_reader::SerializedRowGroupReader;
+use parquet::file::statistics::Statistics;
+use parquet::schema::types::ColumnDescriptor;
+use crate::error::{Error, Result};
+use crate::exec::cast::{cast_value, cast_vector};
+use crate::exec::filter::Predicate;
+use crate::exec::runtime_filter::ScanFilter;
+use crate::exec::SourceReader;
+use crate::qir::expr::{common_type, Expr};
+use crate::qir::{Column, DataType};
+use crate::vector::{Bitmap, DataChunk, PrimaryType, PrimaryVector, SelectionVector, StringVector, Value, Vector, DECIMAL_SCALE, VECTOR_SIZE};
+
+/// a top-level column of a parquet file and how its physical values convert to the engine type
+#[derive(Debug, Clone)]
+struct Leaf {
+    /// the index of the column chunk in a row group
+    index: usize,
+    column: Column,
+    /// the power of ten the physical values are multiplied by, e.g. 3 for timestamps in milliseconds
+    scale: i32,
+}
+
+/// the logical type of a column, from the converted type of the files written before logical types
+fn logical_type(descr: &ColumnDescriptor) -> Option<LogicalType> {
+    let integer = |bit_width, is_signed| Some(LogicalType::Integer { bit_width, is_signed });
+    descr.logical_type().or_else(|| match descr.converted_type() {
+        ConvertedType::UTF8 => Some(LogicalType::String),
+        ConvertedType::INT_8 => integer(8, true),
+        ConvertedType::INT_16 => integer(16, true),
+        ConvertedType::INT_32 => integer(32, true),
+        ConvertedType::INT_64 => integer(64, true),
+        ConvertedType::UINT_8 => integer(8, false),
+        ConvertedType::UINT_16 => integer(16, false),
+        ConvertedType::UINT_32 => integer(32, false),
+        ConvertedType::UINT_64 => integer(64, false),
+        ConvertedType::DATE => Some(LogicalType::Date),
+        ConvertedType::DECIMAL => Some(LogicalType::Decimal { scale: descr.type_scale(), precision: descr.type_precision() }),
+        ConvertedType::TIMESTAMP_MILLIS => Some(LogicalType::Timestamp { is_adjusted_to_u_t_c: true, unit: TimeUnit::MILLIS(Default::default()) }),
+        ConvertedType::TIMESTAMP_MICROS => Some(LogicalType::Timestamp { is_adjusted_to_u_t_c: true, unit: TimeUnit::MICROS(Default::default()) }),
+        _ => None,
+    })
+}
+
+/// `value * 10^scale`
+fn rescale(value: i128, scale: i32) -> i128 {
+    match scale {
+        0.. => value * 10i128.pow(scale as u32),
+        _ => value / 10i128.pow(scale.unsigned_abs()),
+    }
+}
+
+/// the error of a column chunk with fewer rows than its row group
+fn short_column(rows: usize, len: usize) -> Error {
+    Error::Unsupported(format!("a parquet column chunk ended after {rows} of {len} rows"))
+}
+
+/// append the values of the next `len` rows of a column to `values`, the non-null values are
+/// spread into the rows by the definition levels of a nullable column
+fn read_values<T: ParquetType>(reader: &mut ColumnReaderImpl<T>, len: usize, nullable: bool, values: &mut Vec<T::T>, validity: &mut Bitmap) -> Result<()> {
+    let start = values.len();
+    let mut levels = Vec::with_capacity(if nullable { len } else { 0 });
+    let (rows, count, _) = reader.read_records(len, nullable.then_some(&mut levels), None, values)?;
+    if rows != len {
+        return Err(short_column(rows, len));
+    }
+    if !nullable {
+        return Ok(());
+    }
+    // from the last row back, the non-null value `next` moves to its row
+    values.resize(start + len, T::T::default());
+    let mut next = start + count;
+    for row in (start..start + len).rev() {
+        if levels[row - start] > 0 {
+            next -= 1;
+            values.swap(row, next);
+        } else {
+            values[row] = T::T::default();
+        }
+    }
+    levels.iter().for_each(|&level| validity.push(level > 0));
+    Ok(())
+}
+
+/// the values of the next `len` rows of a column, or of the `selected` ones of them. the other
+/// rows are skipped, which does not copy their values and skips whole pages without a selected row.
+fn read_rows<T: ParquetType>(reader: &mut ColumnReaderImpl<T>, len: usize, selected: Option<&Bitmap>, nullable: bool) -> Result<(Vec<T::T>, Option<Bitmap>)> {
+    let rows = selected.map_or(len, |s| s.count_ones());
+    let mut values = Vec::with_capacity(rows);
+    let mut validity = Bitmap::with_capacity(if nullable { rows } else { 0 });
+    let is_selected = |row: usize| selected.is_none_or(|s| s.get(row));
+    let mut row = 0;
+    while row < len {
+        let take = is_selected(row);
+        let run = (row..len).take_while(|&r| is_selected(r) == take).count();
+        if take {
+            read_values(reader, run, nullable, &mut values, &mut validity)?;
+        } else {
+            let skipped = reader.skip_records(run)?;
+            if skipped != run {
+                return Err(short_column(row + skipped, len));
+            }
+        }
+        row += run;
+    }
+    Ok((values, Some(validity).filter(|v| !v.all_set())))
+}
+
+fn convert<T: Copy, U: PrimaryType>(values: Vec<T>, validity: Option<Bitmap>, f: impl Fn(T) -> U) -> PrimaryVector<U> {
+    PrimaryVector::with_validity(values.into_iter().map(f).collect::<Vec<_>>(), validity)
+}
+
+/// the strings of byte arrays, replacing invalid utf-8. the byte arrays of null rows have no data.
+fn strings(values: &[ByteArray], validity: Option<Bitmap>) -> Vector {
+    let valid = |row: usize| validity.as_ref().is_none_or(|v| v.get(row));
+    let bytes = |row: usize| if valid(row) { values[row].data() } else { &[] };
+    let mut offsets = Vec::with_capacity(values.len() + 1);
+    let mut data = Vec::with_capacity((0..values.len()).map(|row| bytes(row).len()).sum());
+    offsets.push(0);
+    for row in 0..values.len() {
+        data.extend_from_slice(bytes(row));
+        offsets.push(data.len() as i32);
+    }
+    match StringVector::try_new(offsets.into(), data.into(), validity.clone()) {
+        Some(vector) => Vector::String(vector),
+        None => {
+            let strings: Vec<_> = (0..values.len()).map(|row| String::from_utf8_lossy(bytes(row))).collect();
+            Vector::String(StringVector::from_options(strings.iter().enumerate().map(|(row, s)| valid(row).then_some(s.as_ref()))))
+        }
+    }
+}
+
+impl Leaf {
+    /// the leaf of column `index`, `None` for nested columns and the types the engine does not read
+    fn new(index: usize, descr: &ColumnDescriptor) -> Option<Self> {
+        if descr.path().parts().len() != 1 || descr.max_rep_level() > 0 {
+            return None;
+        }
+        let (data_type, scale) = match (descr.physical_type(), logical_type(descr)) {
+            (PhysicalType::BOOLEAN, _) => (DataType::Bool, 0),
+            (PhysicalType::INT32 | PhysicalType::INT64, Some(LogicalType::Decimal { scale, .. })) => (DataType::Decimal, DECIMAL_SCALE as i32 - scale),
+            (PhysicalType::INT32, Some(LogicalType::Date)) => (DataType::Date, 0),
+            (PhysicalType::INT32, Some(LogicalType::Integer { bit_width, is_signed })) => match (bit_width, is_signed) {
+                (8, true) => (DataType::I8, 0),
+                (16, true) => (DataType::I16, 0),
+                (8, false) => (DataType::U8, 0),
+                (16, false) => (DataType::U16, 0),
+                (32, false) => (DataType::U32, 0),
+                _ => (DataType::I32, 0),
+            },
+            (PhysicalType::INT32, None) => (DataType::I32, 0),
+            (PhysicalType::INT64, Some(LogicalType::Integer { is_signed: false, .. })) => (DataType::U64, 0),
+            (PhysicalType::INT64, Some(LogicalType::Timestamp { unit, .. })) => match unit {
+                TimeUnit::MILLIS(_) => (DataType::DateTime, 3),
+                TimeUnit::MICROS(_) => (DataType::DateTime, 0),
+                TimeUnit::NANOS(_) => (DataType::DateTime, -3),
+            },
+            (PhysicalType::INT64, None | Some(LogicalType::Integer { .. })) => (DataType::I64, 0),
+            (PhysicalType::FLOAT, None) => (DataType::F32, 0),
+            (PhysicalType::DOUBLE, None) => (DataType::F64, 0),
+            // some writers store strings as byte arrays without a logical type
+            (PhysicalType::BYTE_ARRAY, None | Some(LogicalType::String | LogicalType::Enum | LogicalType::Json)) => (DataType::String, 0),
+            _ => return None,
+        };
+        let column = Column { name: descr.name().to_string(), data_type, nullable: descr.max_def_level() > 0 };
+        Some(Leaf { index, column, scale })
+    }
+
+    /// the engine value of a physical INT32 value
+    fn int32(&self, value: i32) -> Value {
+        match self.column.data_type {
+            DataType::I8 => Value::I8(value as i8),
+            DataType::I16 => Value::I16(value as i16),
+            DataType::U8 => Value::U8(value as u8),
+            DataType::U16 => Value::U16(value as u16),
+            DataType::U32 => Value::U32(value as u32),
+            DataType::Date => Value::Date(value),
+            DataType::Decimal => Value::Decimal(rescale(value as i128, self.scale)),
+            _ => Value::I32(value),
+        }
+    }
+
+    /// the engine value of a physical INT64 value
+    fn int64(&self, value: i64) -> Value {
+        match self.column.data_type {
+            DataType::U64 => Value::U64(value as u64),
+            DataType::DateTime => Value::DateTime(rescale(value as i128, self.scale) as i64),
+            DataType::Decimal => Value::Decimal(rescale(value as i128, self.scale)),
+            _ => Value::I64(value),
+        }
+    }
+
+    /// the min and max of the values of a row group by its statistics
+    fn statistics(&self, statistics: &Statistics) -> Option<(Value, Value)> {
+        // the min and max of old files may be compared as signed values
+        if statistics.is_min_max_deprecated() {
+            return None;
+        }
+        let string = |value: &ByteArray| std::str::from_utf8(value.data()).ok().map(|s| Value::String(s.to_string()));
+        match statistics {
+            Statistics::Boolean(s) => Some((Value::Bool(*s.min_opt()?), Value::Bool(*s.max_opt()?))),
+            Statistics::Int32(s) => Some((self.int32(*s.min_opt()?), self.int32(*s.max_opt()?))),
+            Statistics::Int64(s) => Some((self.int64(*s.min_opt()?), self.int64(*s.max_opt()?))),
+            Statistics::Float(s) => Some((Value::F32(*s.min_opt()?), Value::F32(*s.max_opt()?))),
+            Statistics::Double(s) => Some((Value::F64(*s.min_opt()?), Value::F64(*s.max_opt()?))),
+            Statistics::ByteArray(s) => Some((string(s.min_opt()?)?, string(s.max_opt()?)?)),
+            _ => None,
+        }
+    }
+
+    /// the next `len` rows of the column, or the `selected` ones of them
+    fn read(&self, reader: &mut ColumnReader, len: usize, selected: Option<&Bitmap>) -> Result<Vector> {
+        let nullable = self.column.nullable;
+        let scale = self.scale;
+        Ok(match reader {
+            ColumnReader::BoolColumnReader(reader) => {
+                let (values, validity) = read_rows(reader, len, selected, nullable)?;
+                Vector::Bool(PrimaryVector::with_validity(values, validity))
+            }
+            ColumnReader::Int32ColumnReader(reader) => {
+                let (values, validity) = read_rows(reader, len, selected, nullable)?;
+                match self.column.data_type {
+                    DataType::I8 => Vector::I8(convert(values, validity, |v| v as i8)),
+                    DataType::I16 => Vector::I16(convert(values, validity, |v| v as i16)),
+                    DataType::U8 => Vector::U8(convert(values, validity, |v| v as u8)),
+                    DataType::U16 => Vector::U16(convert(values, validity, |v| v as u16)),
+                    DataType::U32 => Vector::U32(convert(values, validity, |v| v as u32)),
+                    DataType::Date => Vector::Date(PrimaryVector::with_validity(values, validity)),
+                    DataType::Decimal => Vector::Decimal(convert(values, validity, |v| rescale(v as i128, scale))),
+                    _ => Vector::I32(PrimaryVector::with_validity(values, validity)),
+                }
+            }
+            ColumnReader::Int64ColumnReader(reader) => {
+                let (values, validity) = read_rows(reader, len, selected, nullable)?;
+                match self.column.data_type {
+                    DataType::U64 => Vector::U64(convert(values, validity, |v| v as u64)),
+                    DataType::DateTime if scale == 0 => Vector::DateTime(PrimaryVector::with_validity(values, validity)),
+                    DataType::DateTime => Vector::DateTime(convert(values, validity, |v| rescale(v as i128, scale) as i64)),
+                    DataType::Decimal => Vector::Decimal(convert(values, validity, |v| rescale(v as i128, scale))),
+                    _ => Vector::I64(PrimaryVector::with_validity(values, validity)),
+                }
+            }
+            ColumnReader::FloatColumnReader(reader) => {
+                let (values, validity) = read_rows(reader, len, selected, nullable)?;
+                Vector::F32(PrimaryVector::with_validity(values, validity))
+            }
+            ColumnReader::DoubleColumnReader(reader) => {
+                let (values, validity) = read_rows(reader, len, selected, nullable)?;
+                Vector::F64(PrimaryVector::with_validity(values, validity))
+            }
+            ColumnReader::ByteArrayColumnReader(reader) => {
+                let (values, validity) = read_rows(reader, len, selected, nullable)?;
+                strings(&values, validity)
+            }
+            _ => return Err(Error::Unsupported(format!("reading the parquet column `{}`", self.column.name))),
+        })
+    }
+}
+
+/// the parquet files of `path`: a file, the `.parquet` files of a directory, or the files matching
+/// a pattern of file names with `*` and `?` such as `hits_multi/hits_*.parquet`, in name order
+pub fn files(path: &Path) -> Result<Vec<PathBuf>> {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let (dir, pattern) = match name.contains(['*', '?']) {
+        true => (path.parent().unwrap_or(Path::new("")), name.as_ref()),
+        false if path.is_dir() => (path, "*.parquet"),
+        false => return Ok(vec![path.to_path_buf()]),
+    };
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.file_name().is_some_and(|n| glob_match(pattern.as_bytes(), n.as_encoded_bytes())) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    if files.is_empty() {
+        return Err(Error::TableNotFound(path.display().to_string()));
+    }
+    Ok(files)
+}
+
+/// whether `name` matches `pattern`, where `*` matches any bytes and `?` any one byte
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, _) => name.is_empty(),
+        (Some((b'*', rest)), _) => (0..=name.len()).any(|skip| glob_match(rest, &name[skip..])),
+        (Some((b'?', rest)), Some((_, name))) => glob_match(rest, name),
+        (Some((p, rest)), Some((n, name))) => p == n && glob_match(rest, name),
+        (Some(_), None) => false,
+    }
+}
+
+/// an open parquet file
+pub struct ParquetFile {
+    path: PathBuf,
+    reader: SerializedFileReader<File>,
+}
+
+impl ParquetFile {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(ParquetFile { path: path.to_path_buf(), reader: SerializedFileReader::new(File::open(path)?)? })
+    }
+
+    /// the columns of the file the engine can read, in file order, e.g. for the `:columns` of a scan
+    pub fn schema(&self) -> Vec<Column> {
+        let schema = self.reader.metadata().file_metadata().schema_descr();
+        (0..schema.num_columns()).filter_map(|i| Leaf::new(i, &schema.column(i))).map(|leaf| leaf.column).collect()
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.reader.metadata().file_metadata().num_rows() as usize
+    }
+
+    /// how the file provides `column` of a scan: a column of its type or of a type widened to it,
+    /// or nulls when a file lacks a nullable column
+    fn column(&self, column: &Column) -> Result<FileColumn> {
+        let schema = self.reader.metadata().file_metadata().schema_descr();
+        let Some(index) = (0..schema.num_columns()).find(|&i| schema.column(i).path().parts() == [column.name.as_str()]) else {
+            return match column.nullable {
+                true => Ok(FileColumn::Missing(column.data_type.clone())),
+                false => Err(Error::ColumnNotFound(format!("{} in {}", column.name, self.path.display()))),
+            };
+        };
+        let descr = schema.column(index);
+        let leaf = Leaf::new(index, &descr)
+            .ok_or_else(|| Error::Unsupported(format!("reading the parquet column `{}` of type {}", column.name, descr.physical_type())))?;
+        if common_type(&leaf.column.data_type, &column.data_type).as_ref() != Some(&column.data_type) {
+            return Err(Error::TypeMismatch { expected: format!("{:?}", column.data_type), found: format!("{:?} in column `{}`", leaf.column.data_type, column.name) });
+        }
+        Ok(FileColumn::Leaf(leaf, column.data_type.clone()))
+    }
+}
+
+/// how a file provides a column of a scan
+enum FileColumn {
+    /// a column of the file, cast to the type of the scan when that is wider
+    Leaf(Leaf, DataType),
+    /// a nullable column the file lacks, all its rows are null
+    Missing(DataType),
+}
+
+impl FileColumn {
+    /// whether row group `group` may have a value in `min..=max`, a `Null` bound is open
+    fn may_contain(&self, group: &RowGroupMetaData, min: &Value, max: &Value) -> bool {
+        let FileColumn::Leaf(leaf, data_type) = self else {
+            return false;
+        };
+        let Some((low, high)) = group.column(leaf.index).statistics().and_then(|s| leaf.statistics(s)) else {
+            return true;
+        };
+        let (Ok(low), Ok(high)) = (cast_value(&low, data_type), cast_value(&high, data_type)) else {
+            return true;
+        };
+        (max.is_null() || low.partial_cmp(max) != Some(Ordering::Greater))
+            && (min.is_null() || high.partial_cmp(min) != Some(Ordering::Less))
+    }
+
+    /// the next `len` rows of the column, or the `selected` ones of them
+    fn read(&self, reader: Option<&mut ColumnReader>, len: usize, selected: Option<&Bitmap>) -> Result<Vector> {
+        match (self, reader) {
+            (FileColumn::Leaf(leaf, data_type), Some(reader)) => {
+                let vector = leaf.read(reader, len, selected)?;
+                match leaf.column.data_type == *data_type {
+                    true => Ok(vector),
+                    false => cast_vector(&vector, data_type),
+                }
+            }
+            (FileColumn::Missing(data_type), _) => Ok(Vector::new_null(data_type, selected.map_or(len, |s| s.count_ones()))),
+            (FileColumn::Leaf(leaf, _), None) => Err(Error::Unsupported(format!("reading the parquet column `{}`", leaf.column.name))),
+        }
+    }
+}
+
+/// the files of a scan, read as one table
+pub struct ParquetFiles {
+    files: Vec<ParquetFile>,
+}
+
+impl ParquetFiles {
+    /// open the files of `path`, see `files`
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(ParquetFiles { files: files(path)?.iter().map(|f| ParquetFile::open(f)).collect::<Result<_>>()? })
+    }
+
+    /// the columns of all the files, in the order they first appear, of the common type of their
+    /// types in the files and nullable when a file lacks them. strings and binary columns are
+    /// both read as strings.
+    pub fn schema(&self) -> Result<Vec<Column>> {
+        let mut columns: Vec<Column> = vec![];
+        for (i, file) in self.files.iter().enumerate() {
+            let schema = file.schema();
+            for column in &mut columns {
+                column.nullable |= !schema.iter().any(|c| c.name == column.name);
+            }
+            for column in schema {
+                let Some(unified) = columns.iter_mut().find(|c| c.name == column.name) else {
+                    columns.push(Column { nullable: column.nullable || i > 0, ..column });
+                    continue;
+                };
+                unified.data_type = common_type(&unified.data_type, &column.data_type).ok_or_else(|| Error::TypeMismatch {
+                    expected: format!("{:?}", unified.data_type),
+                    found: format!("{:?} in column `{}` of {}", column.data_type, column.name, file.path.display()),
+                })?;
+                unified.nullable |= column.nullable;
+            }
+        }
+        Ok(columns)
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.files.iter().map(|f| f.num_rows()).sum()
+    }
+
+    /// a reader of `columns` in the row groups which may have a value in every range
+    /// `(column, min, max)`. the rows failing `filters` or `predicate` may be left out, like by
+    /// `Source::open_pushdown`. with more than one of `threads`, worker threads decode the row
+    /// groups and the reader returns their chunks in order.
+    pub fn read(self, columns: &[Column], ranges: &[(Column, Value, Value)], filters: Vec<ScanFilter>, predicate: Option<&Expr>,
+        threads: usize) -> Result<ParquetReader> {
+        let late = predicate.and_then(|predicate| LatePredicate::new(predicate, columns));
+        let mut files = vec![];
+        let mut morsels = vec![];
+        for file in self.files {
+            let ranges = ranges.iter()
+                .map(|(column, min, max)| Ok((file.column(column)?, min, max)))
+                .collect::<Result<Vec<_>>>()?;
+            let metadata = file.reader.metadata();
+            morsels.extend((0..metadata.num_row_groups())
+                .filter(|&i| ranges.iter().all(|(column, min, max)| column.may_contain(metadata.row_group(i), min, max)))
+                .map(|i| (files.len(), i)));
+            let columns = columns.iter().map(|c| file.column(c)).collect::<Result<Vec<_>>>()?;
+            files.push(FilePlan { path: file.path, reader: file.reader, columns });
+        }
+        let scan = Arc::new(ScanPlan { files, late });
+        let chunks = match threads.min(morsels.len()) {
+            0 | 1 => Chunks::Serial { scan, morsels: morsels.into_iter(), current: None },
+            threads => {
+                let mut receivers = VecDeque::new();
+                let mut queue = vec![];
+                for (file, row_group) in morsels {
+                    let (sender, receiver) = mpsc::sync_channel(MORSEL_CHUNKS);
+                    receivers.push_back(receiver);
+                    queue.push((file, row_group, sender));
+                }
+                let queue = Arc::new(Mutex::new(queue.into_iter()));
+                let workers = (0..threads)
+                    .map(|_| {
+                        let (scan, queue) = (scan.clone(), queue.clone());
+                        thread::spawn(move || work(&scan, &queue))
+                    })
+                    .collect();
+                Chunks::Parallel { receivers, workers }
+            }
+        };
+        Ok(ParquetReader { chunks, filters })
+    }
+}
+
+/// the chunks of a row group a worker decodes ahead of the pipeline
+const MORSEL_CHUNKS: usize = 2;
+
+/// a file of a scan and how it provides the columns of the scan
+struct FilePlan {
+    path: PathBuf,
+    reader: SerializedFileReader<File>,
+    columns: Vec<FileColumn>,
+}
+
+/// the files of a scan, shared by its worker threads
+struct ScanPlan {
+    files: Vec<FilePlan>,
+    late: Option<LatePredicate>,
+}
+
+/// the predicate of the `filter` after a scan, tested on the columns it uses before the other
+/// columns are read, which are then only decoded for the rows passing it
+struct LatePredicate {
+    predicate: Predicate,
+    /// the columns of the reader the predicate uses, and their names
+    columns: Vec<usize>,
+    names: Vec<String>,
+}
+
+impl LatePredicate {
+    /// `None` when the predicate uses all `columns`, or a column the scan does not read
+    fn new(predicate: &Expr, columns: &[Column]) -> Option<Self> {
+        let used = predicate.columns();
+        let tested: Vec<usize> = (0..columns.len()).filter(|&i| used.contains(&columns[i].name.as_str())).collect();
+        if tested.len() != used.len() || tested.len() == columns.len() {
+            return None;
+        }
+        let names: Vec<String> = tested.iter().map(|&i| columns[i].name.clone()).collect();
+        Some(LatePredicate { predicate: Predicate::compile(predicate, &names), columns: tested, names })
+    }
+}
+
+/// the unit of work of a scan: reads the chunks of a row group of a file
+struct Morsel {
+    file: usize,
+    /// the column readers of the row group, `None` for the columns the file lacks
+    readers: Vec<Option<ColumnReader>>,
+    /// the rows not read yet
+    remaining: usize,
+}
+
+impl Morsel {
+    fn open(scan: &ScanPlan, file: usize, row_group: usize) -> Result<Self> {
+        let plan = &scan.files[file];
+        // a handle of its own: the handles cloned from the reader of the file share its offset,
+        // which the row groups decoded by the other workers seek
+        let handle = Arc::new(File::open(&plan.path)?);
+        let metadata = plan.reader.metadata().row_group(row_group);
+        let group = SerializedRowGroupReader::new(handle, metadata, None, Arc::new(ReaderProperties::builder().build()))?;
+        let readers = plan.columns.iter()
+            .map(|column| match column {
+                FileColumn::Leaf(leaf, _) => group.get_column_reader(leaf.index).map(Some),
+                FileColumn::Missing(_) => Ok(None),
+            })
+            .collect::<parquet::errors::Result<_>>()?;
+        Ok(Morsel { file, readers, remaining: group.metadata().num_rows() as usize })
+    }
+
+    /// the next chunk of at most `VECTOR_SIZE` rows passing the late predicate, `None` at the end
+    fn next_chunk(&mut self, scan: &ScanPlan) -> Result<Option<DataChunk>> {
+        while self.remaining > 0 {
+            let len = VECTOR_SIZE.min(self.remaining);
+            self.remaining -= len;
+            let chunk = match &scan.late {
+                Some(late) => self.read_late(scan, late, len)?,
+                None => Some(self.read(scan, len)?),
+            };
+            if chunk.is_some() {
+                return Ok(chunk);
+            }
+        }
+        Ok(None)
+    }
+
+    /// the next `len` rows of the columns
+    fn read(&mut self, scan: &ScanPlan, len: usize) -> Result<DataChunk> {
+        let columns = scan.files[self.file].columns.iter().zip(&mut self.readers)
+            .map(|(column, reader)| column.read(reader.as_mut(), len, None))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(DataChunk::new(columns))
+    }
+
+    /// the rows of the next `len` rows passing the late predicate, `None` when none passes
+    fn read_late(&mut self, scan: &ScanPlan, late: &LatePredicate, len: usize) -> Result<Option<DataChunk>> {
+        let columns = &scan.files[self.file].columns;
+        let tested = late.columns.iter()
+            .map(|&i| columns[i].read(self.readers[i].as_mut(), len, None))
+            .collect::<Result<Vec<_>>>()?;
+        let mut tested = DataChunk::new(tested);
+        let mask = late.predicate.mask(&tested, &late.names)?;
+        let rows = mask.count_ones();
+        let selected = (rows < len).then_some(&mask);
+        let mut read = Vec::with_capacity(columns.len());
+        for (i, (column, reader)) in columns.iter().zip(&mut self.readers).enumerate() {
+            // the columns of the predicate are filled in below, the rows of the others are skipped when none passes
+            read.push(match late.columns.contains(&i) {
+                true => None,
+                false => Some(column.read(reader.as_mut(), len, selected)?),
+            });
+        }
+        if rows == 0 {
+            return Ok(None);
+        }
+        if let Some(mask) = selected {
+            tested = tested.select(&SelectionVector::from_bitmap(mask));
+        }
+        for (j, &i) in late.columns.iter().enumerate() {
+            read[i] = Some(tested.column(j).clone());
+        }
+        Ok(Some(DataChunk::new(read.into_iter().map(|c| c.expect("a read column")).collect())))
+    }
+}
+
+/// a file, a row group of it, and the channel of its chunks
+type MorselTask = (usize, usize, SyncSender<Result<DataChunk>>);
+
+/// a worker thread of a scan: decodes the next row group of `queue` into its channel, until the
+/// queue is empty or the reader is dropped
+fn work(scan: &ScanPlan, queue: &Mutex<std::vec::IntoIter<MorselTask>>) {
+    loop {
+        let Some((file, row_group, sender)) = queue.lock().expect("a scan queue").next() else {
+            return;
+        };
+        let mut morsel = match Morsel::open(scan, file, row_group) {
+            Ok(morsel) => morsel,
+            Err(error) => {
+                let _ = sender.send(Err(error));
+                return;
+            }
+        };
+        loop {
+            let chunk = match morsel.next_chunk(scan) {
+                Ok(Some(chunk)) => Ok(chunk),
+                Ok(None) => break,
+                Err(error) => Err(error),
+            };
+            let failed = chunk.is_err();
+            if sender.send(chunk).is_err() || failed {
+                return;
+            }
+        }
+    }
+}
+
+enum Chunks {
+    /// the row groups are read by the thread of the pipeline
+    Serial { scan: Arc<ScanPlan>, morsels: std::vec::IntoIter<(usize, usize)>, current: Option<Morsel> },
+    /// worker threads decode the row groups, each into the channel of the row group
+    Parallel { receivers: VecDeque<Receiver<Result<DataChunk>>>, workers: Vec<JoinHandle<()>> },
+}
+
+/// reads the chunks of the columns of parquet files, one row group after the other
+pub struct ParquetReader {
+    chunks: Chunks,
+    filters: Vec<ScanFilter>,
+}
+
+impl ParquetReader {
+    fn next_morsel_chunk(&mut self) -> Result<Option<DataChunk>> {
+        match &mut self.chunks {
+            Chunks::Serial { scan, morsels, current } => loop {
+                if let Some(morsel) = current && let Some(chunk) = morsel.next_chunk(scan)? {
+                    return Ok(Some(chunk));
+                }
+                let Some((file, row_group)) = morsels.next() else {
+                    return Ok(None);
+                };
+                *current = Some(Morsel::open(scan, file, row_group)?);
+            },
+            Chunks::Parallel { receivers, .. } => {
+                while let Some(receiver) = receivers.front() {
+                    match receiver.recv() {
+                        Ok(chunk) => return chunk.map(Some),
+                        // the worker of the row group is done
+                        Err(_) => receivers.pop_front(),
+                    };
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl SourceReader for ParquetReader {
+    fn next_chunk(&mut self) -> Result<Option<DataChunk>> {
+        while let Some(chunk) = self.next_morsel_chunk()? {
+            if self.filters.is_empty() {
+                return Ok(Some(chunk));
+            }
+            let mask = ScanFilter::mask_all(&self.filters, &chunk)?;
+            match mask.count_ones() {
+                0 => continue,
+                rows if rows == chunk.len() => return Ok(Some(chunk)),
+                _ => return Ok(Some(chunk.select(&SelectionVector::from_bitmap(&mask)))),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for ParquetReader {
+    fn drop(&mut self) {
+        if let Chunks::Parallel { receivers, workers } = &mut self.chunks {
+            // the workers stop at their next chunk, which they can not send
+            receivers.clear();
+            workers.drain(..).for_each(|worker| worker.join().expect("a scan worker"));
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::ops::Range;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use parquet::column::writer::ColumnWriter;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use super::*;
+
+    /// a file of `rows` orders in row groups of 1000 rows: `order_id` 0.., `customer_id` `i % 100`
+    /// with every 7th null, `ordered_at` in milliseconds and `note` with every 3rd null
+    pub(crate) fn write_orders(name: &str, rows: usize) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("dataframe-{}-{name}.parquet", std::process::id()));
+        write_orders_to(&path, 0..rows as i64, false);
+        path
+    }
+
+    /// the orders `ids` like `write_orders`, or when `wide` with an int64 `customer_id`, a binary
+    /// `note` without a string annotation and no `ordered_at`
+    fn write_orders_to(path: &Path, ids: Range<i64>, wide: bool) {
+        let (names, schema) = match wide {
+            false => (["order_id", "customer_id", "ordered_at", "note"].as_slice(), "
+                message orders {
+                    required int64 order_id;
+                    optional int32 customer_id (INTEGER(16, true));
+                    required int64 ordered_at (TIMESTAMP(MILLIS, true));
+                    optional binary note (UTF8);
+                }"),
+            true => (["order_id", "note", "customer_id"].as_slice(), "
+                message orders {
+                    required int64 order_id;
+                    optional binary note;
+                    optional int64 customer_id;
+                }"),
+        };
+        let schema = parse_message_type(schema).unwrap();
+        let properties = WriterProperties::builder().set_max_row_group_size(1000).build();
+        let mut writer = SerializedFileWriter::new(File::create(path).unwrap(), Arc::new(schema), Arc::new(properties)).unwrap();
+        for start in ids.clone().step_by(1000) {
+            let ids: Vec<i64> = (start..ids.end.min(start + 1000)).collect();
+            let levels = |n: i64| ids.iter().map(|i| (i % n != 0) as i16).collect::<Vec<_>>();
+            let mut group = writer.next_row_group().unwrap();
+            for name in names {
+                let mut column = group.next_column().unwrap().unwrap();
+                match (*name, column.untyped()) {
+                    ("order_id", ColumnWriter::Int64ColumnWriter(writer)) => writer.write_batch(&ids, None, None),
+                    ("customer_id", ColumnWriter::Int32ColumnWriter(writer)) => {
+                        let customers: Vec<i32> = ids.iter().filter(|i| *i % 7 != 0).map(|i| (i % 100) as i32).collect();
+                        writer.write_batch(&customers, Some(&levels(7)), None)
+                    }
+                    ("customer_id", ColumnWriter::Int64ColumnWriter(writer)) => {
+                        let customers: Vec<i64> = ids.iter().filter(|i| *i % 7 != 0).map(|i| i % 100).collect();
+                        writer.write_batch(&customers, Some(&levels(7)), None)
+                    }
+                    ("ordered_at", ColumnWriter::Int64ColumnWriter(writer)) => {
+                        let times: Vec<i64> = ids.iter().map(|i| 1_700_000_000_000 + i * 1000).collect();
+                        writer.write_batch(&times, None, None)
+                    }
+                    ("note", ColumnWriter::ByteArrayColumnWriter(writer)) => {
+                        let notes: Vec<ByteArray> = ids.iter().filter(|i| *i % 3 != 0).map(|i| ByteArray::from(format!("note {i}").as_str())).collect();
+                        writer.write_batch(&notes, Some(&levels(3)), None)
+                    }
+                    _ => unreachable!(),
+                }.unwrap();
+                column.close().unwrap();
+            }
+            group.close().unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    fn read(path: &Path, columns: &[Column], ranges: &[(Column, Value, Value)]) -> Vec<Vec<Value>> {
+        read_threads(path, columns, ranges, 1)
+    }
+
+    fn read_threads(path: &Path, columns: &[Column], ranges: &[(Column, Value, Value)], threads: usize) -> Vec<Vec<Value>> {
+        let mut reader = ParquetFiles::open(path).unwrap().read(columns, ranges, vec![], None, threads).unwrap();
+        let mut rows = vec![];
+        while let Some(chunk) = reader.next_chunk().unwrap() {
+            assert!(chunk.len() <= VECTOR_SIZE);
+            rows.extend((0..chunk.len()).map(|i| chunk.row(i)));
+        }
+        rows
+    }
+
+    #[test]
+    fn test_read_parquet() {
+        let path = write_orders("read", 2500);
+        let file = ParquetFile::open(&path).unwrap();
+        assert_eq!(file.num_rows(), 2500);
+        let schema = file.schema();
+        let types: Vec<_> = schema.iter().map(|c| (c.name.as_str(), c.data_type.clone(), c.nullable)).collect();
+        assert_eq!(types, vec![
+            ("order_id", DataType::I64, false),
+            ("customer_id", DataType::I16, true),
+            ("ordered_at", DataType::DateTime, false),
+            ("note", DataType::String, true),
+        ]);
+
+        // only the projected columns are read, in the order of the projection
+        let rows = read(&path, &[schema[3].clone(), schema[1].clone(), schema[2].clone()], &[]);
+        assert_eq!(rows.len(), 2500);
+        for (i, row) in rows.iter().enumerate() {
+            let note = if i % 3 == 0 { Value::Null } else { Value::String(format!("note {i}")) };
+            let customer = if i % 7 == 0 { Value::Null } else { Value::I16((i % 100) as i16) };
+            assert_eq!(row, &vec![note, customer, Value::DateTime(1_700_000_000_000_000 + i as i64 * 1_000_000)]);
+        }
+
+        let mut wrong = schema[1].clone();
+        wrong.data_type = DataType::I8;
+        let err = ParquetFiles::open(&path).unwrap().read(&[wrong], &[], vec![], None, 1).err().unwrap().to_string();
+        assert_eq!(err, "type mismatch: expected I8, found I16 in column `customer_id`");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_prune_row_groups() {
+        let path = write_orders("prune", 3500);
+        let schema = ParquetFile::open(&path).unwrap().schema();
+        let ids = |rows: Vec<Vec<Value>>| -> Vec<i64> { rows.iter().map(|r| match r[0] { Value::I64(id) => id, _ => unreachable!() }).collect() };
+
+        // the row groups overlapping the range are read whole
+        let rows = read(&path, &schema[..1], &[(schema[0].clone(), Value::I64(1500), Value::I64(2100))]);
+        assert_eq!(ids(rows), (1000..3000).collect::<Vec<_>>());
+        let rows = read(&path, &schema[..1], &[(schema[0].clone(), Value::Null, Value::I64(999))]);
+        assert_eq!(ids(rows), (0..1000).collect::<Vec<_>>());
+        let start = Value::DateTime(1_700_000_000_000_000 + 3200 * 1_000_000);
+        let rows = read(&path, &schema[..1], &[(schema[2].clone(), start, Value::Null)]);
+        assert_eq!(ids(rows), (3000..3500).collect::<Vec<_>>());
+        // the customers of every row group are 0 to 99
+        assert!(read(&path, &schema[..1], &[(schema[1].clone(), Value::I16(100), Value::I16(200))]).is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_parquet_files() {
+        let dir = std::env::temp_dir().join(format!("dataframe-{}-files", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_orders_to(&dir.join("orders_0.parquet"), 0..2500, false);
+        write_orders_to(&dir.join("orders_1.parquet"), 2500..4000, true);
+        std::fs::write(dir.join("readme.txt"), "not a parquet file").unwrap();
+        let files = ParquetFiles::open(&dir.join("orders_?.parquet")).unwrap();
+        assert_eq!(files.num_rows(), 4000);
+        assert!(matches!(ParquetFiles::open(&dir.join("order_*")), Err(Error::TableNotFound(_))));
+
+        // the wider type of the two files, nullable when a file lacks the column
+        let schema = files.schema().unwrap();
+        let types: Vec<_> = schema.iter().map(|c| (c.name.as_str(), c.data_type.clone(), c.nullable)).collect();
+        assert_eq!(types, vec![
+            ("order_id", DataType::I64, false),
+            ("customer_id", DataType::I64, true),
+            ("ordered_at", DataType::DateTime, true),
+            ("note", DataType::String, true),
+        ]);
+        let rows = read(&dir, &[schema[0].clone(), schema[1].clone(), schema[2].clone(), schema[3].clone()], &[]);
+        assert_eq!(rows.len(), 4000);
+        for (i, row) in rows.iter().enumerate() {
+            let customer = if i % 7 == 0 { Value::Null } else { Value::I64(i as i64 % 100) };
+            let time = if i < 2500 { Value::DateTime(1_700_000_000_000_000 + i as i64 * 1_000_000) } else { Value::Null };
+            let note = if i % 3 == 0 { Value::Null } else { Value::String(format!("note {i}")) };
+            assert_eq!(row, &vec![Value::I64(i as i64), customer, time, note]);
+        }
+        // the row groups of the second file have no `ordered_at`
+        let start = Value::DateTime(1_700_000_000_000_000 + 1200 * 1_000_000);
+        assert_eq!(read(&dir, &schema[..1], &[(schema[2].clone(), start, Value::Null)]).len(), 1500);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_parquet_threads() {
+        let path = write_orders("threads", 9500);
+        let schema = ParquetFile::open(&path).unwrap().schema();
+        let columns = [schema[3].clone(), schema[0].clone()];
+        let ranges = [(schema[0].clone(), Value::I64(1500), Value::Null)];
+        // the chunks of the row groups decoded by the workers are returned in the order of the file
+        let rows = read_threads(&path, &columns, &ranges, 3);
+        assert_eq!(rows.len(), 8500);
+        assert_eq!(rows, read(&path, &columns, &ranges));
+
+        // a reader dropped before its end stops its workers
+        let mut reader = ParquetFiles::open(&path).unwrap().read(&columns, &[], vec![], None, 4).unwrap();
+        assert_eq!(reader.next_chunk().unwrap().unwrap().row(0), vec![Value::Null, Value::I64(0)]);
+        drop(reader);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
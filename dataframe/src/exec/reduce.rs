@@ -0,0 +1,416 @@
+//! Ungrouped aggregates of the `aggregate` sink, reducing a whole column to one value.
+//!
+//! The numeric reductions keep `LANES` accumulators in a `Simd` register and add every chunk
+//! lane by lane, they are reduced to one value only when the sink finishes. Null rows are
+//! replaced by the identity of the reduction with the validity bits of their lanes. `Decimal`
+//! has no lanes and uses the group-by functions with a single group.
+
+use std::ops::{AddAssign, Mul};
+use std::simd::cmp::SimdPartialOrd;
+use std::simd::num::{SimdFloat, SimdInt, SimdUint};
+use std::simd::{Mask, Select, Simd, SimdElement};
+use crate::error::{Error, Result};
+use crate::exec::aggregate::{into_vector, AggregateFunction, AggregateKind};
+use crate::exec::cast::cast_vector;
+use crate::exec::filter::LANES;
+use crate::qir::DataType;
+use crate::vector::{Bitmap, PrimaryType, PrimaryVector, Vector};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reduction {
+    /// the number of rows, nulls included
+    Count,
+    CountNonNull,
+    Sum,
+    SumSquares,
+    Min,
+    Max,
+    Avg,
+}
+
+impl Reduction {
+    pub const ALL: [Reduction; 7] = [
+        Reduction::Count, Reduction::CountNonNull, Reduction::Sum, Reduction::SumSquares,
+        Reduction::Min, Reduction::Max, Reduction::Avg,
+    ];
+
+    /// the function name in the text format
+    pub fn name(self) -> &'static str {
+        match self {
+            Reduction::Count => "count",
+            Reduction::CountNonNull => "count_non_null",
+            Reduction::Sum => "sum",
+            Reduction::SumSquares => "sum_squares",
+            Reduction::Min => "min",
+            Reduction::Max => "max",
+            Reduction::Avg => "avg",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.name() == name)
+    }
+
+    /// the type of the result for a column of type `input`, the types of the group-by
+    /// functions of the same name
+    pub fn result_type(self, input: &DataType) -> Result<DataType> {
+        let unsupported = || Err(Error::Unsupported(format!("{}({input:?})", self.name())));
+        match self {
+            Reduction::Count | Reduction::CountNonNull => Ok(DataType::I64),
+            Reduction::SumSquares if input.is_numeric() => Ok(DataType::F64),
+            Reduction::Sum => AggregateKind::Sum.result_type(input),
+            Reduction::Avg => AggregateKind::Avg.result_type(input),
+            Reduction::Min | Reduction::Max if input.is_numeric() || input.is_temporal() => Ok(input.clone()),
+            _ => unsupported(),
+        }
+    }
+
+    /// the accumulator of this reduction over a column of type `input`
+    pub fn create(self, input: &DataType) -> Result<Box<dyn Reducer>> {
+        let output = self.result_type(input)?;
+        let reducer: Box<dyn Reducer> = match (self, input) {
+            (Reduction::Count, _) => Box::new(Count { rows: 0, non_null: false }),
+            (Reduction::CountNonNull, _) => Box::new(Count { rows: 0, non_null: true }),
+            (Reduction::SumSquares, DataType::Decimal) => {
+                Box::new(CastTo { data_type: DataType::F64, reducer: Reduction::SumSquares.create(&DataType::F64)? })
+            }
+            (Reduction::Sum | Reduction::Avg | Reduction::Min | Reduction::Max, DataType::Decimal) => {
+                let kind = AggregateKind::from_name(self.name()).expect("a group-by function of the same name");
+                let mut function = kind.create(input)?;
+                function.resize(1);
+                Box::new(Grouped { function })
+            }
+            (Reduction::Sum, _) => sum_lanes(input, output, Sum::Values),
+            (Reduction::SumSquares, _) => sum_lanes(input, output, Sum::Squares),
+            (Reduction::Avg, _) => sum_lanes(input, output, Sum::Avg),
+            (Reduction::Min | Reduction::Max, _) => {
+                let max = self == Reduction::Max;
+                match input {
+                    DataType::I8 => Box::new(MinMaxLanes::<i8>::new(output, max)),
+                    DataType::I16 => Box::new(MinMaxLanes::<i16>::new(output, max)),
+                    DataType::I32 | DataType::Date => Box::new(MinMaxLanes::<i32>::new(output, max)),
+                    DataType::I64 | DataType::DateTime => Box::new(MinMaxLanes::<i64>::new(output, max)),
+                    DataType::U8 => Box::new(MinMaxLanes::<u8>::new(output, max)),
+                    DataType::U16 => Box::new(MinMaxLanes::<u16>::new(output, max)),
+                    DataType::U32 => Box::new(MinMaxLanes::<u32>::new(output, max)),
+                    DataType::U64 => Box::new(MinMaxLanes::<u64>::new(output, max)),
+                    DataType::F32 => Box::new(MinMaxLanes::<f32>::new(output, max)),
+                    _ => Box::new(MinMaxLanes::<f64>::new(output, max)),
+                }
+            }
+        };
+        Ok(reducer)
+    }
+}
+
+/// the accumulator of a reduction
+pub trait Reducer {
+    fn update(&mut self, input: &Vector) -> Result<()>;
+
+    /// the result, a vector of one row
+    fn finish(self: Box<Self>) -> Result<Vector>;
+}
+
+fn mismatch<T: PrimaryType>(input: &Vector) -> Error {
+    Error::TypeMismatch { expected: format!("{:?}", T::DATA_TYPE), found: format!("{:?}", input.data_type()) }
+}
+
+/// the bits of the valid rows among `i..i + LANES`, `i` is a multiple of `LANES`
+#[inline(always)]
+fn lane_bits(validity: &Bitmap, i: usize) -> u64 {
+    (validity.words()[i / 64] >> (i % 64)) & ((1 << LANES) - 1)
+}
+
+struct Count {
+    rows: i64,
+    non_null: bool,
+}
+
+impl Reducer for Count {
+    fn update(&mut self, input: &Vector) -> Result<()> {
+        let nulls = if self.non_null { input.null_count() } else { 0 };
+        self.rows += (input.len() - nulls) as i64;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        Ok(Vector::from(vec![self.rows]))
+    }
+}
+
+/// an accumulator type of the sums, adding like the group-by does
+pub trait SumType: SimdElement + PrimaryType {
+    fn add(self, other: Self) -> Self;
+
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_integer_sum {
+    ($($t:ty),*) => {$(
+        impl SumType for $t {
+            fn add(self, other: Self) -> Self {
+                self.wrapping_add(other)
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        }
+    )*};
+}
+
+impl_integer_sum!(i64, u64);
+
+impl SumType for f64 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// convert lanes of an input type to the lanes of the accumulator type `A`
+pub trait Widen<A: SimdElement>: SimdElement {
+    fn widen(lanes: Simd<Self, LANES>) -> Simd<A, LANES>;
+}
+
+macro_rules! impl_widen {
+    ($($t:ty => $a:ty),*) => {$(
+        impl Widen<$a> for $t {
+            fn widen(lanes: Simd<Self, LANES>) -> Simd<$a, LANES> {
+                lanes.cast()
+            }
+        }
+    )*};
+}
+
+impl_widen!(i8 => i64, i16 => i64, i32 => i64, i64 => i64, u8 => u64, u16 => u64, u32 => u64, u64 => u64);
+impl_widen!(i8 => f64, i16 => f64, i32 => f64, i64 => f64, u8 => f64, u16 => f64, u32 => f64, u64 => f64, f32 => f64, f64 => f64);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Sum {
+    Values,
+    Squares,
+    /// the sum in f64 divided by the count
+    Avg,
+}
+
+/// sum the values of `T` in lanes of `A`
+struct SumLanes<T, A: SimdElement> {
+    output: DataType,
+    sum: Sum,
+    lanes: Simd<A, LANES>,
+    count: i64,
+    input: std::marker::PhantomData<T>,
+}
+
+fn sum_lanes(input: &DataType, output: DataType, sum: Sum) -> Box<dyn Reducer> {
+    macro_rules! lanes {
+        ($t:ty, $a:ty) => {
+            match sum {
+                Sum::Values => Box::new(SumLanes::<$t, $a>::new(output, sum)),
+                _ => Box::new(SumLanes::<$t, f64>::new(output, sum)),
+            }
+        };
+    }
+    match input {
+        DataType::I8 => lanes!(i8, i64),
+        DataType::I16 => lanes!(i16, i64),
+        DataType::I32 => lanes!(i32, i64),
+        DataType::I64 => lanes!(i64, i64),
+        DataType::U8 => lanes!(u8, u64),
+        DataType::U16 => lanes!(u16, u64),
+        DataType::U32 => lanes!(u32, u64),
+        DataType::U64 => lanes!(u64, u64),
+        DataType::F32 => lanes!(f32, f64),
+        _ => lanes!(f64, f64),
+    }
+}
+
+impl<T, A: SumType> SumLanes<T, A> {
+    fn new(output: DataType, sum: Sum) -> Self {
+        SumLanes { output, sum, lanes: Simd::splat(A::default()), count: 0, input: std::marker::PhantomData }
+    }
+}
+
+impl<T, A> Reducer for SumLanes<T, A>
+where
+    T: PrimaryType + Widen<A>,
+    A: SumType,
+    Simd<A, LANES>: AddAssign + Mul<Output = Simd<A, LANES>>,
+{
+    fn update(&mut self, input: &Vector) -> Result<()> {
+        let vector = T::vector(input).ok_or_else(|| mismatch::<T>(input))?;
+        let values = vector.values();
+        let zero = Simd::splat(A::default());
+        for i in (0..values.len()).step_by(LANES) {
+            // the missing lanes of the last rows are loaded as 0
+            let mut lanes = T::widen(Simd::load_or_default(&values[i..]));
+            if let Some(validity) = vector.validity() {
+                lanes = Mask::<A::Mask, LANES>::from_bitmask(lane_bits(validity, i)).select(lanes, zero);
+            }
+            self.lanes += if self.sum == Sum::Squares { lanes * lanes } else { lanes };
+        }
+        self.count += (values.len() - input.null_count()) as i64;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        let sum = self.lanes.to_array().into_iter().fold(A::default(), A::add);
+        let count = self.count;
+        Ok(match self.sum {
+            Sum::Avg => Vector::F64(PrimaryVector::from_options([(count > 0).then(|| sum.to_f64() / count as f64)])),
+            _ => into_vector(&self.output, PrimaryVector::from_options([(count > 0).then_some(sum)])),
+        })
+    }
+}
+
+/// the value no other value is smaller (`max` false) or larger than
+pub trait Bounded: PrimaryType + SimdElement {
+    const MIN: Self;
+    const MAX: Self;
+}
+
+macro_rules! impl_bounded {
+    ($($t:ty),*) => {$(
+        impl Bounded for $t {
+            const MIN: Self = <$t>::MIN;
+            const MAX: Self = <$t>::MAX;
+        }
+    )*};
+}
+
+impl_bounded!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+impl Bounded for f32 {
+    const MIN: Self = f32::NEG_INFINITY;
+    const MAX: Self = f32::INFINITY;
+}
+
+impl Bounded for f64 {
+    const MIN: Self = f64::NEG_INFINITY;
+    const MAX: Self = f64::INFINITY;
+}
+
+/// the min or max of `T` in lanes, null rows take the identity
+struct MinMaxLanes<T: SimdElement> {
+    output: DataType,
+    max: bool,
+    lanes: Simd<T, LANES>,
+    count: i64,
+}
+
+impl<T: Bounded> MinMaxLanes<T> {
+    fn new(output: DataType, max: bool) -> Self {
+        let identity = if max { T::MIN } else { T::MAX };
+        MinMaxLanes { output, max, lanes: Simd::splat(identity), count: 0 }
+    }
+}
+
+impl<T: Bounded> Reducer for MinMaxLanes<T>
+where Simd<T, LANES>: SimdPartialOrd<Mask = Mask<T::Mask, LANES>> {
+    fn update(&mut self, input: &Vector) -> Result<()> {
+        let vector = T::vector(input).ok_or_else(|| mismatch::<T>(input))?;
+        let values = vector.values();
+        let identity = Simd::splat(if self.max { T::MIN } else { T::MAX });
+        for i in (0..values.len()).step_by(LANES) {
+            let mut lanes = Simd::<T, LANES>::load_or(&values[i..], identity);
+            if let Some(validity) = vector.validity() {
+                lanes = Mask::<T::Mask, LANES>::from_bitmask(lane_bits(validity, i)).select(lanes, identity);
+            }
+            let better = if self.max { lanes.simd_gt(self.lanes) } else { lanes.simd_lt(self.lanes) };
+            self.lanes = better.select(lanes, self.lanes);
+        }
+        self.count += (values.len() - input.null_count()) as i64;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        let max = self.max;
+        let value = self.lanes.to_array().into_iter()
+            .reduce(|a, b| if (max && b > a) || (!max && b < a) { b } else { a })
+            .filter(|_| self.count > 0);
+        Ok(into_vector(&self.output, PrimaryVector::from_options([value])))
+    }
+}
+
+/// a group-by function with every row in group 0
+struct Grouped {
+    function: Box<dyn AggregateFunction>,
+}
+
+impl Reducer for Grouped {
+    fn update(&mut self, input: &Vector) -> Result<()> {
+        self.function.update(&vec![0; input.len()], input)
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        self.function.finish()
+    }
+}
+
+/// convert the input before reducing it
+struct CastTo {
+    data_type: DataType,
+    reducer: Box<dyn Reducer>,
+}
+
+impl Reducer for CastTo {
+    fn update(&mut self, input: &Vector) -> Result<()> {
+        self.reducer.update(&cast_vector(input, &self.data_type)?)
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vector> {
+        self.reducer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Value;
+
+    fn reduce(reduction: Reduction, input: &Vector) -> Value {
+        let mut reducer = reduction.create(&input.data_type()).unwrap();
+        // in two chunks, the first not a multiple of the lanes
+        let split = input.len() / 3;
+        reducer.update(&input.slice(0, split)).unwrap();
+        reducer.update(&input.slice(split, input.len() - split)).unwrap();
+        reducer.finish().unwrap().get(0)
+    }
+
+    #[test]
+    fn test_reductions() {
+        let ints = Vector::from((0..1000i32).map(|i| (i % 10 != 0).then_some(i - 500)).collect::<Vec<_>>());
+        let valid = (0..1000).filter(|i| i % 10 != 0).map(|i| i as i64 - 500);
+        assert_eq!(reduce(Reduction::Count, &ints), Value::I64(1000));
+        assert_eq!(reduce(Reduction::CountNonNull, &ints), Value::I64(900));
+        assert_eq!(reduce(Reduction::Sum, &ints), Value::I64(valid.clone().sum()));
+        assert_eq!(reduce(Reduction::SumSquares, &ints), Value::F64(valid.clone().map(|v| (v * v) as f64).sum()));
+        assert_eq!(reduce(Reduction::Min, &ints), Value::I32(-499));
+        assert_eq!(reduce(Reduction::Max, &ints), Value::I32(499));
+        assert_eq!(reduce(Reduction::Avg, &ints), Value::F64(valid.sum::<i64>() as f64 / 900.0));
+
+        let bytes = Vector::from((0..100).map(|i| i as u8).collect::<Vec<_>>());
+        assert_eq!(reduce(Reduction::Sum, &bytes), Value::U64(4950));
+        let floats = Vector::from(vec![Some(1.5f32), None, Some(-2.5)]);
+        assert_eq!(reduce(Reduction::Max, &floats), Value::F32(1.5));
+        assert_eq!(reduce(Reduction::Sum, &floats), Value::F64(-1.0));
+    }
+
+    #[test]
+    fn test_empty_and_decimal() {
+        let nulls = Vector::from(vec![None::<i64>; 20]);
+        assert_eq!(reduce(Reduction::Min, &nulls), Value::Null);
+        assert_eq!(reduce(Reduction::Sum, &nulls), Value::Null);
+        assert_eq!(reduce(Reduction::CountNonNull, &nulls), Value::I64(0));
+
+        let decimals = Vector::from_values(&DataType::Decimal, &[Value::Decimal(15_000), Value::Decimal(25_000)]).unwrap();
+        assert_eq!(reduce(Reduction::Sum, &decimals), Value::Decimal(40_000));
+        assert_eq!(reduce(Reduction::Max, &decimals), Value::Decimal(25_000));
+        assert_eq!(reduce(Reduction::SumSquares, &decimals), Value::F64(1.5 * 1.5 + 2.5 * 2.5));
+        assert!(Reduction::Sum.create(&DataType::String).is_err());
+    }
+}
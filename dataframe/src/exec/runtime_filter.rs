@@ -0,0 +1,186 @@
+//! Runtime filters: the `build_hash` sink of a join collects the range of every key column and a
+//! bloom filter of the key hashes of its rows (see `exec::bloom`), and the scan of a later
+//! pipeline drops the rows that can not match before they reach the join.
+//!
+//! ```text
+//! pipeline1: ... ht1 = build_hash :input = v2 :key = "customer_id"
+//! pipeline2: v1 = table_scan :table = "sale_orders" ...
+//!            v2 = hash_join :input = v1 :ht = pipeline1.ht1 :key = $in.customer_id ...
+//! ```
+//!
+//! The interpreter follows the keys of the inner and semi joins of a pipeline back to the columns
+//! of its source through the operators before the join, here `v1.customer_id`. The scan skips
+//! the chunks without a key in the range, then keeps the rows whose key passes the bloom filter.
+//! The key hashes count against the memory budget: a build side whose hashes exceed it keeps only
+//! the ranges.
+
+use std::rc::Rc;
+use crate::error::Result;
+use crate::exec::bloom::BloomFilter;
+use crate::exec::filter::Predicate;
+use crate::exec::hash::hash_columns;
+use crate::exec::reduce::{Reducer, Reduction};
+use crate::qir::DataType;
+use crate::vector::{Bitmap, DataChunk, Value, Vector};
+
+/// the keys of the build side of a join, a probe row with other keys has no match
+#[derive(Debug)]
+pub struct RuntimeFilter {
+    /// the min and max of every key column, for the integer and temporal keys
+    ranges: Vec<Option<(Value, Value)>>,
+    /// `None` when the key hashes exceeded the memory budget
+    bloom: Option<BloomFilter>,
+    /// the number of rows without a null key
+    rows: usize,
+}
+
+/// whether the range of keys of `data_type` is collected
+fn has_range(data_type: &DataType) -> bool {
+    !data_type.is_float() && Reduction::Min.result_type(data_type).is_ok()
+}
+
+impl RuntimeFilter {
+    /// the min and max of every key column, `None` for the keys without a collected range
+    pub fn ranges(&self) -> &[Option<(Value, Value)>] {
+        &self.ranges
+    }
+
+    /// the rows of `chunk` whose key columns `keys` may equal the keys of a build row
+    pub fn mask(&self, chunk: &DataChunk, keys: &[usize]) -> Result<Bitmap> {
+        let len = chunk.len();
+        let mut mask = Bitmap::new(len, self.rows > 0);
+        for (&key, range) in keys.iter().zip(&self.ranges) {
+            if mask.count_ones() == 0 {
+                return Ok(mask);
+            }
+            mask = match range {
+                Some((min, max)) => {
+                    let range = Predicate::Range { column: key, low: (min.clone(), true), high: (max.clone(), true) };
+                    mask.and(&range.mask(chunk, &[])?)
+                }
+                None => match chunk.column(key).validity() {
+                    Some(validity) => mask.and(validity),
+                    None => mask,
+                },
+            };
+        }
+        let Some(bloom) = self.bloom.as_ref().filter(|_| mask.count_ones() > 0) else { return Ok(mask) };
+        let columns: Vec<&Vector> = keys.iter().map(|&k| chunk.column(k)).collect();
+        for (row, hash) in hash_columns(&columns, len).into_iter().enumerate() {
+            if mask.get(row) && !bloom.contains(hash) {
+                mask.set(row, false);
+            }
+        }
+        Ok(mask)
+    }
+}
+
+/// collects the runtime filter of the chunks of a build side
+pub struct RuntimeFilterBuilder {
+    min: Vec<Option<Box<dyn Reducer>>>,
+    max: Vec<Option<Box<dyn Reducer>>>,
+    /// the key hash of every row without a null key, `None` once they exceed `budget` bytes
+    hashes: Option<Vec<u64>>,
+    budget: Option<usize>,
+    rows: usize,
+}
+
+impl RuntimeFilterBuilder {
+    /// a builder for keys of types `types`, whose key hashes may take `budget` bytes
+    pub fn new(types: &[DataType], budget: Option<usize>) -> Result<Self> {
+        let reducers = |reduction: Reduction| types.iter()
+            .map(|t| has_range(t).then(|| reduction.create(t)).transpose())
+            .collect::<Result<Vec<_>>>();
+        Ok(RuntimeFilterBuilder { min: reducers(Reduction::Min)?, max: reducers(Reduction::Max)?, hashes: Some(vec![]), budget, rows: 0 })
+    }
+
+    pub fn push(&mut self, keys: &[&Vector], len: usize) -> Result<()> {
+        for (key, reducer) in keys.iter().zip(&mut self.min).chain(keys.iter().zip(&mut self.max)) {
+            if let Some(reducer) = reducer {
+                reducer.update(key)?;
+            }
+        }
+        let valid = (0..len).filter(|&row| keys.iter().all(|k| k.is_valid(row)));
+        let Some(hashes) = &mut self.hashes else {
+            self.rows += valid.count();
+            return Ok(());
+        };
+        let row_hashes = hash_columns(keys, len);
+        hashes.extend(valid.map(|row| row_hashes[row]));
+        self.rows = hashes.len();
+        if self.budget.is_some_and(|budget| size_of_val(hashes.as_slice()) > budget) {
+            self.hashes = None;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<RuntimeFilter> {
+        let bloom = self.hashes.map(|hashes| {
+            let mut bloom = BloomFilter::new(hashes.len());
+            hashes.iter().for_each(|&hash| bloom.insert(hash));
+            bloom
+        });
+        let ranges = self.min.into_iter().zip(self.max)
+            .map(|(min, max)| match (min, max) {
+                (Some(min), Some(max)) => Ok(Some((min.finish()?.get(0), max.finish()?.get(0)))),
+                _ => Ok(None),
+            })
+            .collect::<Result<_>>()?;
+        Ok(RuntimeFilter { ranges, bloom, rows: self.rows })
+    }
+}
+
+/// a runtime filter pushed down to the source of a pipeline, on the source columns `columns`
+#[derive(Debug, Clone)]
+pub struct ScanFilter {
+    pub filter: Rc<RuntimeFilter>,
+    pub columns: Vec<usize>,
+}
+
+impl ScanFilter {
+    /// the rows of a chunk of the source that may pass all `filters`
+    pub fn mask_all(filters: &[ScanFilter], chunk: &DataChunk) -> Result<Bitmap> {
+        let mut mask = Bitmap::new(chunk.len(), true);
+        for scan in filters {
+            mask = mask.and(&scan.filter.mask(chunk, &scan.columns)?);
+            if mask.count_ones() == 0 {
+                break;
+            }
+        }
+        Ok(mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_filter() {
+        let mut builder = RuntimeFilterBuilder::new(&[DataType::I32, DataType::String], None).unwrap();
+        let ids = Vector::from(vec![Some(100i32), Some(150), None, Some(120)]);
+        let regions = Vector::from(vec!["north", "south", "north", "east"]);
+        builder.push(&[&ids, &regions], 4).unwrap();
+        let filter = builder.finish().unwrap();
+        assert_eq!(filter.rows, 3);
+        assert_eq!(filter.ranges, vec![Some((Value::I32(100), Value::I32(150))), None]);
+
+        let chunk = DataChunk::new(vec![
+            Vector::from(vec![Some(100i32), Some(150), Some(99), None, Some(120), Some(100)]),
+            Vector::from(vec!["north", "south", "north", "north", "east", "west"]),
+        ]);
+        let mask = filter.mask(&chunk, &[0, 1]).unwrap();
+        // 99 is out of the range, the null key never matches, (100, "west") fails the bloom filter
+        // with a false positive rate small enough for the test
+        assert_eq!(mask.iter().collect::<Vec<_>>(), vec![true, true, false, false, true, false]);
+
+        // past the budget only the ranges are kept
+        let mut builder = RuntimeFilterBuilder::new(&[DataType::I32, DataType::String], Some(16)).unwrap();
+        builder.push(&[&ids, &regions], 4).unwrap();
+        let filter = builder.finish().unwrap();
+        assert!(filter.bloom.is_none());
+        assert_eq!(filter.rows, 3);
+        let mask = filter.mask(&chunk, &[0, 1]).unwrap();
+        assert_eq!(mask.iter().collect::<Vec<_>>(), vec![true, true, false, false, true, true]);
+    }
+}
@@ -0,0 +1,535 @@
+//! SIMD sort of the values of a column, generalizing the bitonic networks of the POC `sort2` to
+//! any length, signed integers, floats and descending order.
+//!
+//! Every value is mapped to an unsigned key, `u32` or `u64`, whose unsigned order is the order of
+//! the values: the sign bit of signed integers is flipped, floats use the bits of their total
+//! order, and the keys of a descending sort are inverted. The keys are sorted `LANES` at a time
+//! by a bitonic network, the sorted runs are merged two vectors at a time by the bitonic merge
+//! network, and the values are mapped back from the sorted keys. The last run is padded with the
+//! maximum key, which sorts after every key and is dropped afterwards.
+//!
+//! `argsort` sorts the pairs of a key and its row with the same networks, the rows following
+//! the compare-exchanges of their keys, and returns the permutation of the rows. `gather` then
+//! reorders every column of a chunk by the permutation.
+
+use std::ops::Not;
+use std::simd::cmp::{SimdOrd, SimdPartialEq, SimdPartialOrd};
+use std::simd::num::SimdUint;
+use std::simd::{simd_swizzle, Mask, Select, Simd, SimdElement};
+use crate::exec::filter::LANES;
+use crate::vector::{DataChunk, PrimaryVector, Vector};
+
+/// the partner of every lane in a compare-exchange with the lane `j` apart
+const fn partners(j: usize) -> [usize; LANES] {
+    let mut partners = [0; LANES];
+    let mut i = 0;
+    while i < LANES {
+        partners[i] = i ^ j;
+        i += 1;
+    }
+    partners
+}
+
+/// the lanes that keep the minimum of their pair in the step `j` of the bitonic merge of
+/// sequences of `k` lanes, the others keep the maximum. merges of `LANES` lanes are ascending.
+const fn keeps_min(k: usize, j: usize) -> [bool; LANES] {
+    let mut keeps = [false; LANES];
+    let mut i = 0;
+    while i < LANES {
+        keeps[i] = (i & j == 0) == (i & k == 0);
+        i += 1;
+    }
+    keeps
+}
+
+/// the unsigned keys sorted by the networks
+pub trait Lane: SimdElement + Ord + Not<Output = Self> {
+    /// the padding of the last run
+    const MAX: Self;
+
+    /// sort the lanes of `v` ascending
+    fn sort_lanes(v: Simd<Self, LANES>) -> Simd<Self, LANES>;
+
+    /// merge the sorted lanes of `a` and `b` into the lower and the upper half
+    fn merge_lanes(a: Simd<Self, LANES>, b: Simd<Self, LANES>) -> (Simd<Self, LANES>, Simd<Self, LANES>);
+
+    /// the row `i` as a lane next to the keys
+    fn from_row(i: usize) -> Self;
+
+    fn to_row(self) -> u32;
+
+    /// sort the pairs of keys and rows ascending by key, then by row
+    fn sort_pairs(pairs: Pairs<Self>) -> Pairs<Self>;
+
+    /// merge the sorted pairs `a` and `b` into the lower and the upper half
+    fn merge_pairs(a: Pairs<Self>, b: Pairs<Self>) -> (Pairs<Self>, Pairs<Self>);
+}
+
+/// the keys and the rows of `LANES` pairs
+pub type Pairs<K> = (Simd<K, LANES>, Simd<K, LANES>);
+
+/// the compare-exchange of every lane of `v`, a `Simd` of `$t`, with the lane `j` apart in the
+/// step `j` of the bitonic merge of sequences of `k` lanes
+macro_rules! exchange {
+    ($t:ty, $v:expr, $k:expr, $j:expr) => {{
+        let v = $v;
+        let partner = simd_swizzle!(v, partners($j));
+        Mask::<<$t as SimdElement>::Mask, LANES>::from_array(keeps_min($k, $j)).select(v.simd_min(partner), v.simd_max(partner))
+    }};
+}
+
+/// `exchange!` of the pairs `$v` of keys and rows, comparing the rows of equal keys. the rows are
+/// distinct, so every pair is either less or greater than its partner.
+macro_rules! exchange_pairs {
+    ($t:ty, $v:expr, $k:expr, $j:expr) => {{
+        let (keys, rows) = $v;
+        let (partner_keys, partner_rows) = (simd_swizzle!(keys, partners($j)), simd_swizzle!(rows, partners($j)));
+        let less = partner_keys.simd_lt(keys) | (partner_keys.simd_eq(keys) & partner_rows.simd_lt(rows));
+        let take = !(less ^ Mask::<<$t as SimdElement>::Mask, LANES>::from_array(keeps_min($k, $j)));
+        (take.select(partner_keys, keys), take.select(partner_rows, rows))
+    }};
+}
+
+/// the bitonic sort of `$v` by `$exchange`, up to the ascending merge of all lanes
+macro_rules! sort {
+    ($exchange:ident, $t:ty, $v:expr) => {{
+        let v = $exchange!($t, $v, 2, 1);
+        let v = $exchange!($t, $exchange!($t, v, 4, 2), 4, 1);
+        let v = $exchange!($t, $exchange!($t, $exchange!($t, v, 8, 4), 8, 2), 8, 1);
+        merge!($exchange, $t, v)
+    }};
+}
+
+/// the ascending bitonic merge of the bitonic lanes of `$v` by `$exchange`
+macro_rules! merge {
+    ($exchange:ident, $t:ty, $v:expr) => {
+        $exchange!($t, $exchange!($t, $exchange!($t, $exchange!($t, $v, LANES, 8), LANES, 4), LANES, 2), LANES, 1)
+    };
+}
+
+macro_rules! impl_lane {
+    ($($t:ty),*) => {$(
+        impl Lane for $t {
+            const MAX: Self = <$t>::MAX;
+
+            #[inline(always)]
+            fn sort_lanes(v: Simd<Self, LANES>) -> Simd<Self, LANES> {
+                sort!(exchange, $t, v)
+            }
+
+            #[inline(always)]
+            fn merge_lanes(a: Simd<Self, LANES>, b: Simd<Self, LANES>) -> (Simd<Self, LANES>, Simd<Self, LANES>) {
+                // `a` followed by the reversed `b` is bitonic, its halves split at the median
+                let b = b.reverse();
+                (merge!(exchange, $t, a.simd_min(b)), merge!(exchange, $t, a.simd_max(b)))
+            }
+
+            #[inline(always)]
+            fn from_row(i: usize) -> Self {
+                i as $t
+            }
+
+            #[inline(always)]
+            fn to_row(self) -> u32 {
+                self as u32
+            }
+
+            #[inline(always)]
+            fn sort_pairs(pairs: Pairs<Self>) -> Pairs<Self> {
+                sort!(exchange_pairs, $t, pairs)
+            }
+
+            #[inline(always)]
+            fn merge_pairs(a: Pairs<Self>, b: Pairs<Self>) -> (Pairs<Self>, Pairs<Self>) {
+                let (b_keys, b_rows) = (b.0.reverse(), b.1.reverse());
+                let less = b_keys.simd_lt(a.0) | (b_keys.simd_eq(a.0) & b_rows.simd_lt(a.1));
+                let low = (less.select(b_keys, a.0), less.select(b_rows, a.1));
+                let high = (less.select(a.0, b_keys), less.select(a.1, b_rows));
+                (merge!(exchange_pairs, $t, low), merge!(exchange_pairs, $t, high))
+            }
+        }
+    )*};
+}
+
+impl_lane!(u32, u64);
+
+/// merge the sorted runs `a` and `b`, both a multiple of `LANES` long, into `out`
+fn merge_runs<K: Lane>(a: &[K], b: &[K], out: &mut [K]) {
+    if a[a.len() - 1] <= b[0] {
+        out[..a.len()].copy_from_slice(a);
+        out[a.len()..].copy_from_slice(b);
+        return;
+    }
+    let (mut i, mut j, mut o) = (LANES, LANES, 0);
+    let (mut low, mut high) = (Simd::from_slice(a), Simd::from_slice(b));
+    loop {
+        (low, high) = K::merge_lanes(low, high);
+        low.copy_to_slice(&mut out[o..]);
+        o += LANES;
+        // the next vector is the one with the smaller head, all of `high` is merged after it
+        let next = if i < a.len() && (j == b.len() || a[i] <= b[j]) {
+            i += LANES;
+            &a[i - LANES..i]
+        } else if j < b.len() {
+            j += LANES;
+            &b[j - LANES..j]
+        } else {
+            break;
+        };
+        low = Simd::from_slice(next);
+    }
+    high.copy_to_slice(&mut out[o..]);
+}
+
+/// sort `keys` ascending
+pub fn sort_keys<K: Lane>(keys: &mut [K]) {
+    let len = keys.len();
+    if len < 2 {
+        return;
+    }
+    let padded = len.div_ceil(LANES) * LANES;
+    let mut current = Vec::with_capacity(padded);
+    current.extend_from_slice(keys);
+    current.resize(padded, K::MAX);
+    for run in current.chunks_exact_mut(LANES) {
+        K::sort_lanes(Simd::from_slice(run)).copy_to_slice(run);
+    }
+
+    let mut next = vec![K::MAX; padded];
+    let mut width = LANES;
+    while width < padded {
+        for (from, to) in current.chunks(width * 2).zip(next.chunks_mut(width * 2)) {
+            if from.len() > width {
+                merge_runs(&from[..width], &from[width..], to);
+            } else {
+                to.copy_from_slice(from);
+            }
+        }
+        std::mem::swap(&mut current, &mut next);
+        width *= 2;
+    }
+    keys.copy_from_slice(&current[..len]);
+}
+
+/// merge the sorted runs of pairs `a` and `b`, both a multiple of `LANES` long, into `out`
+fn merge_pair_runs<K: Lane>(a: (&[K], &[K]), b: (&[K], &[K]), out: (&mut [K], &mut [K])) {
+    let (len_a, len_b) = (a.0.len(), b.0.len());
+    let head = |run: (&[K], &[K]), i: usize| (run.0[i], run.1[i]);
+    if head(a, len_a - 1) < head(b, 0) {
+        out.0[..len_a].copy_from_slice(a.0);
+        out.0[len_a..].copy_from_slice(b.0);
+        out.1[..len_a].copy_from_slice(a.1);
+        out.1[len_a..].copy_from_slice(b.1);
+        return;
+    }
+    let load = |run: (&[K], &[K]), i: usize| (Simd::from_slice(&run.0[i..]), Simd::from_slice(&run.1[i..]));
+    let (mut i, mut j, mut o) = (LANES, LANES, 0);
+    let (mut low, mut high) = (load(a, 0), load(b, 0));
+    loop {
+        (low, high) = K::merge_pairs(low, high);
+        low.0.copy_to_slice(&mut out.0[o..]);
+        low.1.copy_to_slice(&mut out.1[o..]);
+        o += LANES;
+        low = if i < len_a && (j == len_b || head(a, i) < head(b, j)) {
+            i += LANES;
+            load(a, i - LANES)
+        } else if j < len_b {
+            j += LANES;
+            load(b, j - LANES)
+        } else {
+            break;
+        };
+    }
+    high.0.copy_to_slice(&mut out.0[o..]);
+    high.1.copy_to_slice(&mut out.1[o..]);
+}
+
+/// the permutation of the rows that sorts `keys` ascending, rows with equal keys in their order
+pub fn argsort_keys<K: Lane>(keys: &[K]) -> Vec<u32> {
+    let len = keys.len();
+    debug_assert!(len <= u32::MAX as usize);
+    let padded = len.div_ceil(LANES) * LANES;
+    let mut current = (Vec::with_capacity(padded), (0..padded).map(K::from_row).collect::<Vec<_>>());
+    current.0.extend_from_slice(keys);
+    current.0.resize(padded, K::MAX);
+    for (run_keys, run_rows) in current.0.chunks_exact_mut(LANES).zip(current.1.chunks_exact_mut(LANES)) {
+        let (sorted_keys, sorted_rows) = K::sort_pairs((Simd::from_slice(run_keys), Simd::from_slice(run_rows)));
+        sorted_keys.copy_to_slice(run_keys);
+        sorted_rows.copy_to_slice(run_rows);
+    }
+
+    let mut next = (vec![K::MAX; padded], vec![K::MAX; padded]);
+    let mut width = LANES;
+    while width < padded {
+        let runs = current.0.chunks(width * 2).zip(current.1.chunks(width * 2));
+        for ((from_keys, from_rows), (to_keys, to_rows)) in runs.zip(next.0.chunks_mut(width * 2).zip(next.1.chunks_mut(width * 2))) {
+            if from_keys.len() > width {
+                let (a, b) = ((&from_keys[..width], &from_rows[..width]), (&from_keys[width..], &from_rows[width..]));
+                merge_pair_runs(a, b, (to_keys, to_rows));
+            } else {
+                to_keys.copy_from_slice(from_keys);
+                to_rows.copy_from_slice(from_rows);
+            }
+        }
+        std::mem::swap(&mut current, &mut next);
+        width *= 2;
+    }
+    // the padding sorts after every row, its rows are the largest
+    current.1[..len].iter().map(|r| r.to_row()).collect()
+}
+
+/// the values sorted by their keys: every value maps to a key with the same order and back
+pub trait SortKey: Copy {
+    type Key: Lane;
+
+    fn to_key(self) -> Self::Key;
+
+    fn from_key(key: Self::Key) -> Self;
+}
+
+macro_rules! impl_unsigned_key {
+    ($($t:ty => $k:ty),*) => {$(
+        impl SortKey for $t {
+            type Key = $k;
+
+            #[inline(always)]
+            fn to_key(self) -> $k {
+                self as $k
+            }
+
+            #[inline(always)]
+            fn from_key(key: $k) -> Self {
+                key as $t
+            }
+        }
+    )*};
+}
+
+impl_unsigned_key!(u8 => u32, u16 => u32, u32 => u32, u64 => u64);
+
+macro_rules! impl_signed_key {
+    ($($t:ty => $s:ty, $k:ty),*) => {$(
+        impl SortKey for $t {
+            type Key = $k;
+
+            #[inline(always)]
+            fn to_key(self) -> $k {
+                (self as $s as $k) ^ (1 << (<$k>::BITS - 1))
+            }
+
+            #[inline(always)]
+            fn from_key(key: $k) -> Self {
+                (key ^ (1 << (<$k>::BITS - 1))) as $s as $t
+            }
+        }
+    )*};
+}
+
+impl_signed_key!(i8 => i32, u32, i16 => i32, u32, i32 => i32, u32, i64 => i64, u64);
+
+/// floats sort by their total order, `-0.0` before `0.0`, with every NaN after infinity as in
+/// SQL. the payload of a NaN is not kept.
+macro_rules! impl_float_key {
+    ($($t:ty => $k:ty),*) => {$(
+        impl SortKey for $t {
+            type Key = $k;
+
+            #[inline(always)]
+            fn to_key(self) -> $k {
+                let bits = if self.is_nan() { <$t>::NAN.to_bits() } else { self.to_bits() };
+                if bits >> (<$k>::BITS - 1) == 1 { !bits } else { bits | 1 << (<$k>::BITS - 1) }
+            }
+
+            #[inline(always)]
+            fn from_key(key: $k) -> Self {
+                let bits = if key >> (<$k>::BITS - 1) == 1 { key & !(1 << (<$k>::BITS - 1)) } else { !key };
+                <$t>::from_bits(bits)
+            }
+        }
+    )*};
+}
+
+impl_float_key!(f32 => u32, f64 => u64);
+
+/// the key of `value`, inverted for a descending sort
+#[inline(always)]
+pub fn key<T: SortKey>(value: T, descending: bool) -> T::Key {
+    if descending { !value.to_key() } else { value.to_key() }
+}
+
+/// sort `values` ascending, or descending
+pub fn sort<T: SortKey>(values: &mut [T], descending: bool) {
+    let mut keys: Vec<T::Key> = values.iter().map(|v| key(*v, descending)).collect();
+    sort_keys(&mut keys);
+    for (value, key) in values.iter_mut().zip(keys) {
+        *value = T::from_key(if descending { !key } else { key });
+    }
+}
+
+/// the permutation of the rows that sorts `values`, ascending or descending, rows with equal
+/// values in their order
+pub fn argsort<T: SortKey>(values: &[T], descending: bool) -> Vec<u32> {
+    let keys: Vec<T::Key> = values.iter().map(|v| key(*v, descending)).collect();
+    argsort_keys(&keys)
+}
+
+/// `values[permutation[i]]`, `LANES` rows at a time
+fn gather_values<T: SimdElement + Default>(values: &[T], permutation: &[u32]) -> Vec<T> {
+    let mut out = Vec::with_capacity(permutation.len());
+    let mut rows = permutation.chunks_exact(LANES);
+    for lanes in &mut rows {
+        let indices = Simd::<u32, LANES>::from_slice(lanes).cast::<usize>();
+        out.extend_from_slice(Simd::gather_or_default(values, indices).as_array());
+    }
+    out.extend(rows.remainder().iter().map(|&i| values[i as usize]));
+    out
+}
+
+/// the rows of `vector` reordered by `permutation`, gathered with SIMD for the numeric and
+/// temporal types
+pub fn gather(vector: &Vector, permutation: &[u32]) -> Vector {
+    macro_rules! gather_primary {
+        ($($variant:ident),*) => {
+            match vector {
+                $(Vector::$variant(v) => Vector::$variant(PrimaryVector::with_validity(
+                    gather_values(v.values(), permutation), v.validity().map(|b| b.take(permutation)))),)*
+                other => other.take(permutation),
+            }
+        };
+    }
+    gather_primary!(I8, I16, I32, I64, U8, U16, U32, U64, F32, F64, Date, DateTime)
+}
+
+/// the rows of every column of `chunk` reordered by `permutation`
+pub fn gather_chunk(chunk: &DataChunk, permutation: &[u32]) -> DataChunk {
+    if chunk.columns.is_empty() {
+        return DataChunk::empty(permutation.len());
+    }
+    DataChunk::new(chunk.columns.iter().map(|c| gather(c, permutation)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::simd::Simd;
+    use crate::vector::{DataChunk, Vector};
+    use super::*;
+
+    /// deterministic pseudo random numbers, a xorshift
+    fn random(n: usize, mut seed: u64) -> Vec<u64> {
+        (0..n).map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        }).collect()
+    }
+
+    #[test]
+    fn test_networks() {
+        for seed in 1..200 {
+            let values: Vec<u32> = random(32, seed).into_iter().map(|v| (v % 50) as u32).collect();
+            let mut expected = values[..16].to_vec();
+            expected.sort_unstable();
+            assert_eq!(u32::sort_lanes(Simd::from_slice(&values)).to_array().to_vec(), expected);
+
+            let (mut a, mut b) = (values[..16].to_vec(), values[16..].to_vec());
+            a.sort_unstable();
+            b.sort_unstable();
+            let (low, high) = u64::merge_lanes(Simd::from_slice(&a.iter().map(|&v| v as u64).collect::<Vec<_>>()),
+                Simd::from_slice(&b.iter().map(|&v| v as u64).collect::<Vec<_>>()));
+            let mut expected: Vec<u64> = values.iter().map(|&v| v as u64).collect();
+            expected.sort_unstable();
+            assert_eq!([low.to_array(), high.to_array()].concat(), expected);
+        }
+    }
+
+    #[test]
+    fn test_sort() {
+        for len in [0, 1, 2, 15, 16, 17, 33, 100, 257, 1000, 4099] {
+            let numbers = random(len, len as u64 + 7);
+
+            let mut ints: Vec<i32> = numbers.iter().map(|&v| v as i32 % 1000).collect();
+            let mut expected = ints.clone();
+            expected.sort_unstable();
+            sort(&mut ints, false);
+            assert_eq!(ints, expected, "i32 of {len}");
+
+            let mut longs: Vec<i64> = numbers.iter().map(|&v| v as i64).collect();
+            let mut expected = longs.clone();
+            expected.sort_unstable_by(|a, b| b.cmp(a));
+            sort(&mut longs, true);
+            assert_eq!(longs, expected, "i64 desc of {len}");
+
+            let mut unsigned = numbers.clone();
+            let mut expected = unsigned.clone();
+            expected.sort_unstable();
+            sort(&mut unsigned, false);
+            assert_eq!(unsigned, expected, "u64 of {len}");
+
+            let mut bytes: Vec<i8> = numbers.iter().map(|&v| v as i8).collect();
+            let mut expected = bytes.clone();
+            expected.sort_unstable();
+            sort(&mut bytes, false);
+            assert_eq!(bytes, expected, "i8 of {len}");
+        }
+    }
+
+    #[test]
+    fn test_float_order() {
+        let mut values = vec![1.5f64, f64::NAN, -0.0, f64::NEG_INFINITY, 0.0, -f64::NAN, f64::INFINITY, -2.5, f64::MIN_POSITIVE];
+        sort(&mut values, false);
+        let expected = [f64::NEG_INFINITY, -2.5, -0.0, 0.0, f64::MIN_POSITIVE, 1.5, f64::INFINITY];
+        assert_eq!(values[..7].iter().map(|v| v.to_bits()).collect::<Vec<_>>(), expected.map(f64::to_bits));
+        assert!(values[7..].iter().all(|v| v.is_nan()));
+
+        let mut values: Vec<f32> = random(100, 3).into_iter().map(|v| (v % 2000) as f32 / 8.0 - 125.0).collect();
+        values.push(f32::NAN);
+        let mut expected = values.clone();
+        expected.sort_unstable_by(|a, b| b.total_cmp(a));
+        sort(&mut values, true);
+        assert!(values[0].is_nan());
+        assert_eq!(values[1..], expected[1..]);
+    }
+
+    #[test]
+    fn test_argsort() {
+        for len in [0, 1, 5, 16, 31, 64, 300, 2050] {
+            let numbers = random(len, len as u64 + 11);
+
+            // few distinct keys, the permutation keeps the order of equal keys
+            let ints: Vec<i32> = numbers.iter().map(|&v| (v % 7) as i32 - 3).collect();
+            let mut expected: Vec<u32> = (0..len as u32).collect();
+            expected.sort_by_key(|&i| ints[i as usize]);
+            assert_eq!(argsort(&ints, false), expected, "i32 of {len}");
+            expected.sort_by_key(|&i| std::cmp::Reverse(ints[i as usize]));
+            assert_eq!(argsort(&ints, true), expected, "i32 desc of {len}");
+
+            let floats: Vec<f64> = numbers.iter().map(|&v| (v % 100) as f64 / 4.0 - 10.0).collect();
+            let mut expected: Vec<u32> = (0..len as u32).collect();
+            expected.sort_by(|&a, &b| floats[a as usize].total_cmp(&floats[b as usize]));
+            assert_eq!(argsort(&floats, false), expected, "f64 of {len}");
+
+            // keys equal to the padding
+            let unsigned: Vec<u64> = numbers.iter().map(|&v| if v % 3 == 0 { u64::MAX } else { v }).collect();
+            let mut expected: Vec<u32> = (0..len as u32).collect();
+            expected.sort_by_key(|&i| unsigned[i as usize]);
+            assert_eq!(argsort(&unsigned, false), expected, "u64 of {len}");
+        }
+    }
+
+    #[test]
+    fn test_gather() {
+        let len: usize = 37;
+        let chunk = DataChunk::new(vec![
+            Vector::from((0..len).map(|i| (i % 4 != 0).then_some(i as i32)).collect::<Vec<_>>()),
+            Vector::from((0..len).map(|i| i as f64 / 2.0).collect::<Vec<_>>()),
+            Vector::from((0..len).map(|i| if i % 2 == 0 { "even" } else { "odd" }).collect::<Vec<_>>()),
+        ]);
+        let keys: Vec<f64> = (0..len).map(|i| ((i * 7) % len) as f64).collect();
+        let permutation = argsort(&keys, true);
+        let sorted = gather_chunk(&chunk, &permutation);
+        assert_eq!(sorted, chunk.take(&permutation));
+        assert_eq!(sorted.row(0), chunk.row(permutation[0] as usize));
+        assert_eq!(gather_chunk(&DataChunk::empty(3), &[2, 1]).len(), 2);
+    }
+}
@@ -0,0 +1,359 @@
+//! The spill files of the external sort: chunks written to local temp files as blocks, column by
+//! column.
+//!
+//! ```text
+//! block    = size: u64, rows: u64, column*
+//! column   = validity, values
+//! validity = 0 | 1 word: u64 * ceil(rows / 64)
+//! ```
+//!
+//! Numbers are little-endian. Primary vectors write their values (bools one byte each), strings
+//! and lists their offsets rebased to zero and the bytes or child vector they cover, structs
+//! their children and maps their offsets, keys and values. Blocks carry no types, they are read
+//! back with the data types of the columns.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::error::Result;
+use crate::qir::DataType;
+use crate::vector::{Bitmap, DataChunk, ListVector, MapVector, PrimaryType, PrimaryVector, StringVector, StructVector, Vector};
+
+/// a temp file in the spill directory, removed when dropped
+#[derive(Debug)]
+pub struct SpillFile {
+    path: PathBuf,
+}
+
+/// numbers the spill files of this process
+static FILES: AtomicUsize = AtomicUsize::new(0);
+
+impl SpillFile {
+    /// create a new empty file in `dir`, returning the writer of its blocks
+    pub fn create(dir: &Path) -> Result<(SpillFile, BufWriter<File>)> {
+        let name = format!("dataframe-{}-{}.spill", std::process::id(), FILES.fetch_add(1, Ordering::Relaxed));
+        let path = dir.join(name);
+        let file = File::options().write(true).create_new(true).open(&path)?;
+        Ok((SpillFile { path }, BufWriter::new(file)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn open(&self) -> Result<BufReader<File>> {
+        Ok(BufReader::new(File::open(&self.path)?))
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// the primary types written as `WIDTH` little-endian bytes
+trait Fixed: PrimaryType {
+    const WIDTH: usize;
+
+    fn write(self, out: &mut Vec<u8>);
+
+    fn read(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_fixed {
+    ($($t:ty),*) => {$(
+        impl Fixed for $t {
+            const WIDTH: usize = size_of::<$t>();
+
+            fn write(self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes.try_into().unwrap())
+            }
+        }
+    )*};
+}
+
+impl_fixed!(i8, i16, i32, i64, i128, u8, u16, u32, u64, f32, f64);
+
+impl Fixed for bool {
+    const WIDTH: usize = 1;
+
+    fn write(self, out: &mut Vec<u8>) {
+        out.push(self as u8);
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        bytes[0] != 0
+    }
+}
+
+/// append `chunk` to a spill file as one block
+pub fn write_chunk(out: &mut impl Write, chunk: &DataChunk) -> Result<()> {
+    let mut block = vec![0; 16];
+    for column in &chunk.columns {
+        write_vector(&mut block, column);
+    }
+    let size = (block.len() - 16) as u64;
+    block[..8].copy_from_slice(&size.to_le_bytes());
+    block[8..16].copy_from_slice(&(chunk.len() as u64).to_le_bytes());
+    out.write_all(&block)?;
+    Ok(())
+}
+
+/// read the next block of a spill file, `None` at the end of the file
+pub fn read_chunk(input: &mut impl Read, types: &[DataType]) -> Result<Option<DataChunk>> {
+    let mut header = [0; 16];
+    match input.read_exact(&mut header) {
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let size = u64::from_le_bytes(header[..8].try_into().unwrap()) as usize;
+    let rows = u64::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+    let mut block = vec![0; size];
+    input.read_exact(&mut block)?;
+
+    let mut cursor = Cursor { bytes: &block, position: 0 };
+    let columns = types.iter().map(|t| cursor.vector(t, rows)).collect::<Result<Vec<_>>>()?;
+    Ok(Some(if columns.is_empty() { DataChunk::empty(rows) } else { DataChunk::new(columns) }))
+}
+
+/// the bytes `vector` takes in memory, about the size of its block in a spill file
+pub fn memory_size(vector: &Vector) -> usize {
+    let validity = vector.validity().map_or(0, |v| v.len().div_ceil(8));
+    validity + match vector {
+        Vector::String(v) => 4 * (v.len() + 1) + offset_range(v.offsets()).1,
+        Vector::List(v) => {
+            let (first, len) = offset_range(v.offsets());
+            4 * (v.len() + 1) + memory_size(&v.values().slice(first, len))
+        }
+        Vector::Struct(v) => v.children().iter().map(memory_size).sum(),
+        Vector::Map(v) => {
+            let (first, len) = offset_range(v.offsets());
+            4 * (v.len() + 1) + memory_size(&v.keys().slice(first, len)) + memory_size(&v.values().slice(first, len))
+        }
+        Vector::I8(_) | Vector::U8(_) | Vector::Bool(_) => vector.len(),
+        Vector::I16(_) | Vector::U16(_) => 2 * vector.len(),
+        Vector::I32(_) | Vector::U32(_) | Vector::F32(_) | Vector::Date(_) => 4 * vector.len(),
+        Vector::I64(_) | Vector::U64(_) | Vector::F64(_) | Vector::DateTime(_) => 8 * vector.len(),
+        Vector::Decimal(_) => 16 * vector.len(),
+    }
+}
+
+/// the first offset and the number of child rows covered by `offsets`
+fn offset_range(offsets: &[i32]) -> (usize, usize) {
+    (offsets[0] as usize, (offsets[offsets.len() - 1] - offsets[0]) as usize)
+}
+
+fn write_vector(out: &mut Vec<u8>, vector: &Vector) {
+    match vector.validity() {
+        Some(validity) => {
+            out.push(1);
+            validity.words().iter().for_each(|word| word.write(out));
+        }
+        None => out.push(0),
+    }
+    match vector {
+        Vector::I8(v) => write_values(out, v),
+        Vector::I16(v) => write_values(out, v),
+        Vector::I32(v) | Vector::Date(v) => write_values(out, v),
+        Vector::I64(v) | Vector::DateTime(v) => write_values(out, v),
+        Vector::U8(v) => write_values(out, v),
+        Vector::U16(v) => write_values(out, v),
+        Vector::U32(v) => write_values(out, v),
+        Vector::U64(v) => write_values(out, v),
+        Vector::F32(v) => write_values(out, v),
+        Vector::F64(v) => write_values(out, v),
+        Vector::Decimal(v) => write_values(out, v),
+        Vector::Bool(v) => write_values(out, v),
+        Vector::String(v) => {
+            let (first, len) = write_offsets(out, v.offsets());
+            out.extend_from_slice(&v.data()[first..first + len]);
+        }
+        Vector::List(v) => {
+            let (first, len) = write_offsets(out, v.offsets());
+            write_vector(out, &v.values().slice(first, len));
+        }
+        Vector::Struct(v) => v.children().iter().for_each(|child| write_vector(out, child)),
+        Vector::Map(v) => {
+            let (first, len) = write_offsets(out, v.offsets());
+            write_vector(out, &v.keys().slice(first, len));
+            write_vector(out, &v.values().slice(first, len));
+        }
+    }
+}
+
+fn write_values<T: Fixed>(out: &mut Vec<u8>, vector: &PrimaryVector<T>) {
+    out.reserve(vector.len() * T::WIDTH);
+    vector.values().iter().for_each(|value| value.write(out));
+}
+
+/// write `offsets` rebased to zero, returning the range of child rows they cover
+fn write_offsets(out: &mut Vec<u8>, offsets: &[i32]) -> (usize, usize) {
+    offsets.iter().for_each(|offset| (offset - offsets[0]).write(out));
+    offset_range(offsets)
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, "corrupt spill block")
+}
+
+/// reads the columns of a block
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self.bytes.get(self.position..self.position + len).ok_or_else(corrupt)?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn values<T: Fixed>(&mut self, len: usize) -> Result<Vec<T>> {
+        Ok(self.take(len * T::WIDTH)?.chunks_exact(T::WIDTH).map(T::read).collect())
+    }
+
+    fn primary<T: Fixed>(&mut self, len: usize, validity: Option<Bitmap>) -> Result<PrimaryVector<T>> {
+        Ok(PrimaryVector::with_validity(self.values::<T>(len)?, validity))
+    }
+
+    /// offsets written by `write_offsets`, which start at zero and increase
+    fn offsets(&mut self, len: usize) -> Result<Vec<i32>> {
+        let offsets = self.values::<i32>(len + 1)?;
+        if offsets[0] != 0 || offsets.windows(2).any(|w| w[0] > w[1]) {
+            return Err(corrupt().into());
+        }
+        Ok(offsets)
+    }
+
+    fn vector(&mut self, data_type: &DataType, len: usize) -> Result<Vector> {
+        let validity = match self.take(1)?[0] {
+            0 => None,
+            _ => Some(Bitmap::from_words(self.values(len.div_ceil(64))?, len)),
+        };
+        Ok(match data_type {
+            DataType::I8 => Vector::I8(self.primary(len, validity)?),
+            DataType::I16 => Vector::I16(self.primary(len, validity)?),
+            DataType::I32 => Vector::I32(self.primary(len, validity)?),
+            DataType::I64 => Vector::I64(self.primary(len, validity)?),
+            DataType::U8 => Vector::U8(self.primary(len, validity)?),
+            DataType::U16 => Vector::U16(self.primary(len, validity)?),
+            DataType::U32 => Vector::U32(self.primary(len, validity)?),
+            DataType::U64 => Vector::U64(self.primary(len, validity)?),
+            DataType::F32 => Vector::F32(self.primary(len, validity)?),
+            DataType::F64 => Vector::F64(self.primary(len, validity)?),
+            DataType::Decimal => Vector::Decimal(self.primary(len, validity)?),
+            DataType::Bool => Vector::Bool(self.primary(len, validity)?),
+            DataType::Date => Vector::Date(self.primary(len, validity)?),
+            DataType::DateTime => Vector::DateTime(self.primary(len, validity)?),
+            DataType::String => {
+                let offsets = self.offsets(len)?;
+                let data = self.take(offsets[len] as usize)?.to_vec();
+                Vector::String(StringVector::try_new(offsets.into(), data.into(), validity).ok_or_else(corrupt)?)
+            }
+            DataType::List(item) => {
+                let offsets = self.offsets(len)?;
+                let values = self.vector(item, offsets[len] as usize)?;
+                Vector::List(ListVector::new(offsets.into(), values, validity))
+            }
+            DataType::Struct(table) => {
+                let names = table.columns.iter().map(|c| c.name.clone()).collect();
+                let children = table.columns.iter().map(|c| self.vector(&c.data_type, len)).collect::<Result<_>>()?;
+                // a struct without fields takes its length from the validity
+                let validity = validity.or_else(|| table.columns.is_empty().then(|| Bitmap::new(len, true)));
+                Vector::Struct(StructVector::new(names, children, validity))
+            }
+            DataType::Map(key, value) => {
+                let offsets = self.offsets(len)?;
+                let keys = self.vector(key, offsets[len] as usize)?;
+                let values = self.vector(value, offsets[len] as usize)?;
+                Vector::Map(MapVector::new(offsets.into(), keys, values, validity))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::qir::{Column, Table};
+    use crate::vector::Value;
+    use super::*;
+
+    fn columns() -> (Vec<DataType>, DataChunk) {
+        let items = Vector::from_values(&DataType::List(Box::new(DataType::I32)), &[
+            Value::List(vec![Value::I32(1), Value::I32(2)]), Value::Null, Value::List(vec![]), Value::List(vec![Value::I32(3)]),
+        ]).unwrap();
+        let point = DataType::Struct(Box::new(Table { name: "point".into(), columns: vec![
+            Column { name: "x".into(), data_type: DataType::F64, nullable: true },
+            Column { name: "label".into(), data_type: DataType::String, nullable: true },
+        ] }));
+        let points = Vector::Struct(StructVector::new(vec!["x".into(), "label".into()], vec![
+            Vector::from(vec![Some(1.5f64), None, Some(-0.0), Some(f64::INFINITY)]),
+            Vector::from(vec![Some("a"), Some("b"), None, Some("")]),
+        ], Some(Bitmap::from_words(vec![0b1011], 4))));
+        let types = vec![DataType::I64, DataType::Bool, DataType::String, DataType::Decimal, DataType::Date,
+            DataType::List(Box::new(DataType::I32)), point];
+        let chunk = DataChunk::new(vec![
+            Vector::from(vec![Some(1i64), None, Some(i64::MIN), Some(4)]),
+            Vector::from(vec![Some(true), Some(false), None, Some(true)]),
+            Vector::from(vec![Some("héllo"), None, Some(""), Some("a\0b")]),
+            Vector::from_values(&DataType::Decimal, &[Value::Decimal(-5), Value::Null, Value::Decimal(i128::MAX), Value::Decimal(0)]).unwrap(),
+            Vector::from_values(&DataType::Date, &[Value::Date(19_000), Value::Date(-1), Value::Null, Value::Date(0)]).unwrap(),
+            items,
+            points,
+        ]);
+        (types, chunk)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (types, chunk) = columns();
+        let mut file = vec![];
+        write_chunk(&mut file, &chunk).unwrap();
+        // slices do not start at zero, their offsets are rebased
+        write_chunk(&mut file, &chunk.slice(1, 3)).unwrap();
+        write_chunk(&mut file, &chunk.take(&[3, 0])).unwrap();
+        write_chunk(&mut file, &DataChunk::empty(5)).unwrap();
+
+        let mut input = file.as_slice();
+        assert_eq!(read_chunk(&mut input, &types).unwrap().unwrap(), chunk);
+        assert_eq!(read_chunk(&mut input, &types).unwrap().unwrap(), chunk.slice(1, 3));
+        assert_eq!(read_chunk(&mut input, &types).unwrap().unwrap(), chunk.take(&[3, 0]));
+        assert_eq!(read_chunk(&mut input, &[]).unwrap().unwrap().len(), 5);
+        assert!(read_chunk(&mut input, &types).unwrap().is_none());
+        assert!(memory_size(chunk.column(2)) < memory_size(&Vector::Decimal(PrimaryVector::new(vec![0i128; 4]))));
+
+        let mut truncated = &file[..file.len() / 2];
+        read_chunk(&mut truncated, &types).unwrap();
+        assert!(read_chunk(&mut truncated, &types).is_err());
+
+        // a string with offsets [-1, 1] over "ab"
+        let mut block = vec![0];
+        [-1i32, 1].iter().for_each(|o| o.write(&mut block));
+        block.extend_from_slice(b"ab");
+        assert!(Cursor { bytes: &block, position: 0 }.vector(&DataType::String, 1).is_err());
+    }
+
+    #[test]
+    fn test_spill_file() {
+        let (types, chunk) = columns();
+        let (file, mut writer) = SpillFile::create(&std::env::temp_dir()).unwrap();
+        write_chunk(&mut writer, &chunk).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let mut reader = file.open().unwrap();
+        assert_eq!(read_chunk(&mut reader, &types).unwrap().unwrap(), chunk);
+        assert!(read_chunk(&mut reader, &types).unwrap().is_none());
+
+        let path = file.path().to_path_buf();
+        assert!(path.exists());
+        drop(file);
+        assert!(!path.exists());
+    }
+}
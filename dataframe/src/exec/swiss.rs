@@ -0,0 +1,308 @@
+//! A swiss table of the entries of the hash join and the hash group-by, with the tag groups
+//! studied in `playgrounds/try_cpu/src/bin/TestHash.rs`.
+//!
+//! The slots are in groups of `GROUP_SIZE`. Every slot has a tag: the top 15 bits of the hash
+//! of its entry, or `EMPTY`. A key is looked up in the group chosen by the low bits of its hash,
+//! the tags of the whole group are compared with its tag in one SIMD compare and only the slots
+//! with an equal tag are candidates. Full groups continue in the next group. At most half the
+//! slots are used, few lookups ever leave their first group.
+//!
+//! The table stores the hashes of its entries, not their keys. Lookups work on a batch of rows
+//! in rounds like the rest of the engine: each round finds the next candidate entry of every
+//! pending row, and the user compares all (row, entry) pairs at once, column by column.
+
+use std::simd::cmp::SimdPartialEq;
+use std::simd::Simd;
+
+/// the slots of a group, compared at once
+pub const GROUP_SIZE: usize = 16;
+
+/// the tag of an empty slot, tags of entries have the top bit clear
+const EMPTY: u16 = 0x8000;
+
+/// no entry, in the results of `SwissTable::find`
+pub const NONE: u32 = u32::MAX;
+
+type Tags = Simd<u16, GROUP_SIZE>;
+
+#[inline]
+fn tag(hash: u64) -> u16 {
+    (hash >> 49) as u16
+}
+
+/// the keys of the entries of a `SwissTable`, stored by its user
+pub trait EntryKeys {
+    /// and `equal[i]` with whether row `rows[i]` equals the key of entry `entries[i]`
+    fn equal(&self, rows: &[u32], entries: &[u32], equal: &mut [bool]);
+
+    /// store the key of row `row` as the key of the next entry
+    fn insert(&mut self, row: u32);
+}
+
+/// where the lookup of a row is: a group and its slots already tried
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    group: usize,
+    tried: u64,
+}
+
+/// the next step of a lookup
+enum Step {
+    /// an entry with the hash of the row
+    Candidate(u32),
+    /// the key is not in the table, it would be inserted into this slot
+    Missing(usize),
+}
+
+#[derive(Debug)]
+pub struct SwissTable {
+    /// the tag of every slot, `GROUP_SIZE` slots per group
+    tags: Vec<u16>,
+    /// the entry of every used slot
+    slots: Vec<u32>,
+    /// the hash of every entry
+    hashes: Vec<u64>,
+    /// the number of groups minus one, a power of two minus one
+    mask: usize,
+}
+
+impl Default for SwissTable {
+    fn default() -> Self {
+        SwissTable { tags: vec![EMPTY; GROUP_SIZE], slots: vec![NONE; GROUP_SIZE], hashes: vec![], mask: 0 }
+    }
+}
+
+impl SwissTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// a table for `capacity` entries without growing
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut table = Self::default();
+        table.reserve(capacity);
+        table
+    }
+
+    /// the number of entries, numbered `0, 1, ...` in the order they were inserted
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// the hash of `entry`
+    pub fn hash(&self, entry: u32) -> u64 {
+        self.hashes[entry as usize]
+    }
+
+    /// keep at most half the slots used with `additional` more entries
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = (self.len() + additional) * 2;
+        if needed <= self.tags.len() {
+            return;
+        }
+        let groups = needed.div_ceil(GROUP_SIZE).next_power_of_two();
+        self.mask = groups - 1;
+        self.tags = vec![EMPTY; groups * GROUP_SIZE];
+        self.slots = vec![NONE; groups * GROUP_SIZE];
+        for entry in 0..self.hashes.len() {
+            let hash = self.hashes[entry];
+            let mut group = hash as usize & self.mask;
+            loop {
+                let empty = self.matches(group, EMPTY);
+                if empty != 0 {
+                    self.set(group * GROUP_SIZE + empty.trailing_zeros() as usize, tag(hash), entry as u32);
+                    break;
+                }
+                group = (group + 1) & self.mask;
+            }
+        }
+    }
+
+    /// the bitmask of the slots of `group` with tag `tag`
+    #[inline]
+    fn matches(&self, group: usize, tag: u16) -> u64 {
+        let tags = Tags::from_slice(&self.tags[group * GROUP_SIZE..(group + 1) * GROUP_SIZE]);
+        tags.simd_eq(Tags::splat(tag)).to_bitmask()
+    }
+
+    fn set(&mut self, slot: usize, tag: u16, entry: u32) {
+        self.tags[slot] = tag;
+        self.slots[slot] = entry;
+    }
+
+    /// the next entry with the hash of a row, or the empty slot that ends its lookup
+    #[inline]
+    fn next(&self, hash: u64, probe: &mut Probe) -> Step {
+        loop {
+            let mut candidates = self.matches(probe.group, tag(hash)) & !probe.tried;
+            while candidates != 0 {
+                let slot = candidates.trailing_zeros() as usize;
+                candidates &= candidates - 1;
+                probe.tried |= 1 << slot;
+                let entry = self.slots[probe.group * GROUP_SIZE + slot];
+                if self.hashes[entry as usize] == hash {
+                    return Step::Candidate(entry);
+                }
+            }
+            let empty = self.matches(probe.group, EMPTY);
+            if empty != 0 {
+                return Step::Missing(probe.group * GROUP_SIZE + empty.trailing_zeros() as usize);
+            }
+            (probe.group, probe.tried) = ((probe.group + 1) & self.mask, 0);
+        }
+    }
+
+    /// start the lookups of all rows of `hashes`
+    fn start(&self, hashes: &[u64]) -> Vec<(u32, Probe)> {
+        hashes.iter().enumerate().map(|(row, &hash)| (row as u32, Probe { group: hash as usize & self.mask, tried: 0 })).collect()
+    }
+
+    /// the entry of every row of `hashes`, `NONE` if its key is not in the table. `equal` is
+    /// called like `EntryKeys::equal`.
+    pub fn find(&self, hashes: &[u64], mut equal: impl FnMut(&[u32], &[u32], &mut [bool])) -> Vec<u32> {
+        let mut entries = vec![NONE; hashes.len()];
+        let mut pending = self.start(hashes);
+        let (mut round, mut missing) = (Round::default(), vec![]);
+        while !pending.is_empty() {
+            round.step(self, hashes, &mut pending, &mut missing);
+            missing.clear();
+            equal(&round.rows, &round.candidates, &mut round.equal);
+            round.finish(&mut entries, &mut pending);
+        }
+        entries
+    }
+
+    /// the entry of every row of `hashes`, inserting the keys that are not in the table
+    pub fn find_or_insert(&mut self, hashes: &[u64], keys: &mut impl EntryKeys) -> Vec<u32> {
+        self.reserve(hashes.len());
+        let mut entries = vec![NONE; hashes.len()];
+        let mut pending = self.start(hashes);
+        let (mut round, mut missing) = (Round::default(), vec![]);
+        while !pending.is_empty() {
+            round.step(self, hashes, &mut pending, &mut missing);
+            for (row, probe, slot) in missing.drain(..) {
+                // another row of the round took the slot, look again
+                if self.tags[slot] != EMPTY {
+                    pending.push((row, probe));
+                    continue;
+                }
+                let (hash, entry) = (hashes[row as usize], self.hashes.len() as u32);
+                self.set(slot, tag(hash), entry);
+                self.hashes.push(hash);
+                keys.insert(row);
+                entries[row as usize] = entry;
+            }
+            keys.equal(&round.rows, &round.candidates, &mut round.equal);
+            round.finish(&mut entries, &mut pending);
+        }
+        entries
+    }
+}
+
+/// the (row, candidate entry) pairs of a round of lookups
+#[derive(Default)]
+struct Round {
+    rows: Vec<u32>,
+    candidates: Vec<u32>,
+    probes: Vec<Probe>,
+    equal: Vec<bool>,
+}
+
+impl Round {
+    /// take the next step of every pending lookup, the rows whose key is missing go to `missing`
+    /// with the empty slot of their group
+    fn step(&mut self, table: &SwissTable, hashes: &[u64], pending: &mut Vec<(u32, Probe)>, missing: &mut Vec<(u32, Probe, usize)>) {
+        self.rows.clear();
+        self.candidates.clear();
+        self.probes.clear();
+        for (row, mut probe) in pending.drain(..) {
+            match table.next(hashes[row as usize], &mut probe) {
+                Step::Candidate(entry) => {
+                    self.rows.push(row);
+                    self.candidates.push(entry);
+                    self.probes.push(probe);
+                }
+                Step::Missing(slot) => missing.push((row, probe, slot)),
+            }
+        }
+        self.equal.clear();
+        self.equal.resize(self.rows.len(), true);
+    }
+
+    /// the rows equal to their candidate found their entry, the others are pending again
+    fn finish(&self, entries: &mut [u32], pending: &mut Vec<(u32, Probe)>) {
+        for i in 0..self.rows.len() {
+            if self.equal[i] {
+                entries[self.rows[i] as usize] = self.candidates[i];
+            } else {
+                pending.push((self.rows[i], self.probes[i]));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// u64 keys with a chosen hash
+    struct Keys<'a> {
+        rows: &'a [u64],
+        entries: Vec<u64>,
+    }
+
+    impl EntryKeys for Keys<'_> {
+        fn equal(&self, rows: &[u32], entries: &[u32], equal: &mut [bool]) {
+            for ((eq, &row), &entry) in equal.iter_mut().zip(rows).zip(entries) {
+                *eq &= self.rows[row as usize] == self.entries[entry as usize];
+            }
+        }
+
+        fn insert(&mut self, row: u32) {
+            self.entries.push(self.rows[row as usize]);
+        }
+    }
+
+    #[test]
+    fn test_find_or_insert() {
+        let mut table = SwissTable::new();
+        let values: Vec<u64> = (0..5000).map(|i| i % 1000).collect();
+        let hashes: Vec<u64> = values.iter().map(|v| v.wrapping_mul(0x9e37_79b9_7f4a_7c15)).collect();
+        let mut keys = Keys { rows: &values, entries: vec![] };
+        let entries = table.find_or_insert(&hashes, &mut keys);
+        assert_eq!(table.len(), 1000);
+        assert_eq!(entries[..1000], (0..1000).collect::<Vec<u32>>());
+        assert!((0..5000).all(|i| entries[i] == entries[i % 1000]));
+        assert!(table.tags.len() >= 2000);
+
+        let probe = [7u64, 1234];
+        let hashes: Vec<u64> = probe.iter().map(|v| v.wrapping_mul(0x9e37_79b9_7f4a_7c15)).collect();
+        let found = table.find(&hashes, |rows, entries, equal| {
+            Keys { rows: &probe, entries: keys.entries.clone() }.equal(rows, entries, equal)
+        });
+        assert_eq!(found, vec![7, NONE]);
+    }
+
+    #[test]
+    fn test_collisions() {
+        // every key has the same hash, the lookups compare the keys of every entry and overflow
+        // into the following groups
+        let values: Vec<u64> = (0..100).chain(0..100).collect();
+        let hashes = vec![42u64; values.len()];
+        let mut table = SwissTable::new();
+        let mut keys = Keys { rows: &values, entries: vec![] };
+        let entries = table.find_or_insert(&hashes, &mut keys);
+        assert_eq!(table.len(), 100);
+        assert_eq!(entries[100..], entries[..100]);
+        assert_eq!(table.hash(entries[99]), 42);
+
+        let found = table.find(&[42, 43], |rows, entries, equal| {
+            Keys { rows: &[99, 0], entries: keys.entries.clone() }.equal(rows, entries, equal)
+        });
+        assert_eq!(found, vec![entries[99], NONE]);
+    }
+}
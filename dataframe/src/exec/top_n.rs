@@ -0,0 +1,174 @@
+//! The bounded heap of the `top_n` sink, `ORDER BY ... LIMIT n`.
+//!
+//! The heap keeps the normalized keys (see `exec::order_by`) of the best `limit` rows seen so
+//! far, the worst on top. Once it is full, the key on top is the threshold of every new chunk:
+//! the keys of the chunk are compared with it first and only the rows before it are gathered,
+//! most chunks of a large input are dropped without materializing a row.
+
+use std::collections::BinaryHeap;
+use crate::error::Result;
+use crate::exec::order_by::{SortKeys, SortOrder};
+use crate::exec::sort::gather_chunk;
+use crate::vector::{DataChunk, Vector, VECTOR_SIZE};
+
+/// a kept row, ordered by its key and then by its arrival so that equal keys keep their order
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Entry {
+    key: Box<[u8]>,
+    seq: u64,
+    /// the row in `TopNHeap::chunks`
+    chunk: u32,
+    row: u32,
+}
+
+pub struct TopNHeap {
+    /// the key columns of the chunks
+    keys: Vec<usize>,
+    orders: Vec<SortOrder>,
+    limit: usize,
+    heap: BinaryHeap<Entry>,
+    /// the gathered rows, including rows evicted from the heap since the last compaction
+    chunks: Vec<DataChunk>,
+    stored: usize,
+    seq: u64,
+}
+
+impl TopNHeap {
+    pub fn new(keys: Vec<usize>, orders: Vec<SortOrder>, limit: usize) -> Self {
+        TopNHeap { keys, orders, limit, heap: BinaryHeap::with_capacity(limit + 1), chunks: vec![], stored: 0, seq: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// the key of the worst kept row once the heap is full, later rows must sort before it
+    fn threshold(&self) -> Option<&[u8]> {
+        self.heap.peek().filter(|_| self.heap.len() == self.limit).map(|e| &*e.key)
+    }
+
+    /// keep the rows of `chunk` that are among the best `limit` rows so far
+    pub fn push(&mut self, chunk: &DataChunk) -> Result<()> {
+        if self.limit == 0 || chunk.is_empty() {
+            return Ok(());
+        }
+        let columns: Vec<&Vector> = self.keys.iter().map(|&i| chunk.column(i)).collect();
+        let keys = SortKeys::encode(&columns, &self.orders)?;
+        let candidates: Vec<usize> = match self.threshold() {
+            Some(threshold) => (0..keys.len()).filter(|&row| keys.key(row) < threshold).collect(),
+            None => (0..keys.len()).collect(),
+        };
+        if candidates.is_empty() {
+            return Ok(());
+        }
+
+        let index = self.chunks.len() as u32;
+        let mut gathered = vec![];
+        for row in candidates {
+            let key = keys.key(row);
+            if self.heap.len() == self.limit {
+                if key >= &*self.heap.peek().unwrap().key {
+                    continue;
+                }
+                self.heap.pop();
+            }
+            self.heap.push(Entry { key: key.into(), seq: self.seq, chunk: index, row: gathered.len() as u32 });
+            self.seq += 1;
+            gathered.push(row as u32);
+        }
+        self.stored += gathered.len();
+        self.chunks.push(gather_chunk(chunk, &gathered));
+        if self.stored > (self.limit * 4).max(VECTOR_SIZE) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// the kept rows in one chunk, dropping the evicted ones
+    fn compact(&mut self) -> Result<()> {
+        let mut entries = std::mem::take(&mut self.heap).into_vec();
+        let chunk = self.gather(&entries)?;
+        for (row, entry) in entries.iter_mut().enumerate() {
+            (entry.chunk, entry.row) = (0, row as u32);
+        }
+        self.heap = BinaryHeap::from(entries);
+        self.stored = chunk.len();
+        self.chunks = vec![chunk];
+        Ok(())
+    }
+
+    /// the rows of `entries`, in that order
+    fn gather(&self, entries: &[Entry]) -> Result<DataChunk> {
+        let mut offsets = Vec::with_capacity(self.chunks.len());
+        let mut offset = 0;
+        for chunk in &self.chunks {
+            offsets.push(offset);
+            offset += chunk.len() as u32;
+        }
+        let positions: Vec<u32> = entries.iter().map(|e| offsets[e.chunk as usize] + e.row).collect();
+        Ok(gather_chunk(&DataChunk::concat(&self.chunks)?, &positions))
+    }
+
+    /// the kept rows, sorted
+    pub fn finish(mut self) -> Result<DataChunk> {
+        let entries = std::mem::take(&mut self.heap).into_sorted_vec();
+        self.gather(&entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vector::{DataChunk, Value, Vector};
+    use super::*;
+
+    fn chunk(values: &[Option<i64>], first_id: i64) -> DataChunk {
+        DataChunk::new(vec![
+            Vector::from(values.to_vec()),
+            Vector::from((0..values.len() as i64).map(|i| first_id + i).collect::<Vec<_>>()),
+        ])
+    }
+
+    fn ids(chunk: &DataChunk) -> Vec<Value> {
+        (0..chunk.len()).map(|i| chunk.column(1).get(i)).collect()
+    }
+
+    #[test]
+    fn test_top_n() {
+        let mut heap = TopNHeap::new(vec![0], vec![SortOrder::ASC], 3);
+        heap.push(&chunk(&[Some(5), None, Some(3), Some(5)], 0)).unwrap();
+        assert_eq!(heap.threshold().map(|t| t.to_vec()), Some(SortKeys::encode(&[&Vector::from(vec![Some(5i64)])], &[SortOrder::ASC]).unwrap().key(0).to_vec()));
+        // 5 is not before the threshold, the equal key of an earlier row stays
+        heap.push(&chunk(&[Some(5), Some(4), Some(9)], 10)).unwrap();
+        assert_eq!(heap.len(), 3);
+        assert_eq!(ids(&heap.finish().unwrap()), vec![Value::I64(2), Value::I64(11), Value::I64(0)]);
+
+        let mut heap = TopNHeap::new(vec![0], vec![SortOrder::DESC], 2);
+        heap.push(&chunk(&[Some(1), Some(2)], 0)).unwrap();
+        heap.push(&chunk(&[None, Some(7)], 2)).unwrap();
+        assert_eq!(ids(&heap.finish().unwrap()), vec![Value::I64(2), Value::I64(3)]);
+
+        let mut heap = TopNHeap::new(vec![0], vec![SortOrder::ASC], 0);
+        heap.push(&chunk(&[Some(1)], 0)).unwrap();
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn test_compact() {
+        let values: Vec<Option<i64>> = (0..20_000).map(|i| Some((i * 7919) % 10_007)).collect();
+        let mut heap = TopNHeap::new(vec![0], vec![SortOrder::ASC], 5);
+        for rows in values.chunks(1000) {
+            heap.push(&chunk(rows, 0)).unwrap();
+        }
+        assert!(heap.stored <= VECTOR_SIZE);
+        let top = heap.finish().unwrap();
+
+        let mut expected: Vec<i64> = values.iter().map(|v| v.unwrap()).collect();
+        expected.sort_unstable();
+        let keys: Vec<Value> = (0..top.len()).map(|i| top.column(0).get(i)).collect();
+        assert_eq!(keys, expected[..5].iter().map(|&v| Value::I64(v)).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,544 @@
+//! Window functions of the `window` sink.
+//!
+//! The rows are sorted by the partition keys, then by the order keys. A partition is a run of
+//! rows with equal partition keys, the peers of a row are the rows of its partition with equal
+//! order keys. Every function is evaluated over all the sorted rows at once:
+//!
+//! - `row_number`, `rank` and `dense_rank` count the rows and the peer groups of a partition.
+//! - `lag` and `lead` gather the row `offset` rows back or ahead in the partition, or a default.
+//! - the other functions see the frame of every row, the rows `start..end` of its partition.
+//!   `first_value` and `last_value` gather the first and last row of the frame. `count`, `sum`
+//!   and `avg` slide an accumulator along the frames, adding the rows that enter a frame and
+//!   removing those that leave it. `min` and `max` query a segment tree of the partition.
+//!
+//! A `rows` frame is given by row offsets, a `range` frame by the peers of the current row or by
+//! offsets of the value of a single order key, days for a date and microseconds for a datetime.
+
+use std::fmt;
+use crate::error::{Error, Result};
+use crate::exec::aggregate::{average_decimal, into_vector, AggregateKind};
+use crate::exec::cast::cast_vector;
+use crate::exec::order_by::{SortKeys, SortOrder};
+use crate::qir::DataType;
+use crate::vector::{PrimaryType, PrimaryVector, Value, Vector};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowKind {
+    RowNumber,
+    Rank,
+    DenseRank,
+    Lag,
+    Lead,
+    FirstValue,
+    LastValue,
+    /// the number of non-null values of the frame
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl WindowKind {
+    pub const ALL: [WindowKind; 12] = [
+        WindowKind::RowNumber, WindowKind::Rank, WindowKind::DenseRank, WindowKind::Lag, WindowKind::Lead,
+        WindowKind::FirstValue, WindowKind::LastValue, WindowKind::Count, WindowKind::Sum, WindowKind::Min,
+        WindowKind::Max, WindowKind::Avg,
+    ];
+
+    /// the function name in the text format
+    pub fn name(self) -> &'static str {
+        match self {
+            WindowKind::RowNumber => "row_number",
+            WindowKind::Rank => "rank",
+            WindowKind::DenseRank => "dense_rank",
+            WindowKind::Lag => "lag",
+            WindowKind::Lead => "lead",
+            WindowKind::FirstValue => "first_value",
+            WindowKind::LastValue => "last_value",
+            WindowKind::Count => "count",
+            WindowKind::Sum => "sum",
+            WindowKind::Min => "min",
+            WindowKind::Max => "max",
+            WindowKind::Avg => "avg",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// whether the function ranks the rows and takes no argument
+    pub fn is_ranking(self) -> bool {
+        matches!(self, WindowKind::RowNumber | WindowKind::Rank | WindowKind::DenseRank)
+    }
+
+    /// whether the function is evaluated over a frame
+    pub fn has_frame(self) -> bool {
+        !self.is_ranking() && !matches!(self, WindowKind::Lag | WindowKind::Lead)
+    }
+
+    /// the type of the result for an argument of type `input`, none for the ranking functions
+    pub fn result_type(self, input: Option<&DataType>) -> Result<DataType> {
+        let input = match input {
+            _ if self.is_ranking() => return Ok(DataType::I64),
+            Some(input) => input,
+            None => return Err(Error::Unsupported(format!("{}()", self.name()))),
+        };
+        let unsupported = || Err(Error::Unsupported(format!("{}({input:?})", self.name())));
+        match self {
+            WindowKind::Lag | WindowKind::Lead | WindowKind::FirstValue | WindowKind::LastValue => Ok(input.clone()),
+            WindowKind::Count => Ok(DataType::I64),
+            WindowKind::Sum => AggregateKind::Sum.result_type(input),
+            WindowKind::Avg => AggregateKind::Avg.result_type(input),
+            WindowKind::Min | WindowKind::Max if input.is_numeric() || input.is_temporal() => Ok(input.clone()),
+            _ => unsupported(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameUnit {
+    Rows,
+    Range,
+}
+
+/// the frame of a function: `rows(-2, 0)` is the current row and the two rows before it,
+/// `range(-1, 1)` the rows whose order key is within 1 of the key of the current row. an offset
+/// of `None` is unbounded, a negative offset precedes the current row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub unit: FrameUnit,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+}
+
+impl Frame {
+    /// the frame of sql without a frame clause: the partition up to the peers of the current row,
+    /// the whole partition without order keys
+    pub const DEFAULT: Frame = Frame { unit: FrameUnit::Range, start: None, end: Some(0) };
+
+    /// whether the frame compares the values of the order key, not only the peers
+    pub fn has_offsets(&self) -> bool {
+        self.unit == FrameUnit::Range && [self.start, self.end].iter().any(|offset| offset.is_some_and(|o| o != 0))
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offset = |offset: Option<i64>| offset.map_or_else(|| "unbounded".to_string(), |o| o.to_string());
+        let unit = match self.unit {
+            FrameUnit::Rows => "rows",
+            FrameUnit::Range => "range",
+        };
+        write!(f, "{unit}({}, {})", offset(self.start), offset(self.end))
+    }
+}
+
+/// whether each row has other keys than the row before it, false without keys
+fn changes(keys: &[&Vector], orders: &[SortOrder], len: usize) -> Result<Vec<bool>> {
+    if keys.is_empty() {
+        return Ok(vec![false; len]);
+    }
+    let keys = SortKeys::encode(keys, orders)?;
+    Ok((0..len).map(|row| row > 0 && keys.key(row) != keys.key(row - 1)).collect())
+}
+
+/// the partitions and peer groups of rows sorted by their partition and order keys
+pub struct Partitions {
+    /// the rows `start..end` of every partition
+    partitions: Vec<(usize, usize)>,
+    /// the rows `start..end` of the peer group of every row
+    peers: Vec<(u32, u32)>,
+}
+
+impl Partitions {
+    /// the partitions of `len` rows sorted by `partition_keys`, then by `order_keys` in `orders`
+    pub fn new(partition_keys: &[&Vector], order_keys: &[&Vector], orders: &[SortOrder], len: usize) -> Result<Self> {
+        let new_partition = changes(partition_keys, &vec![SortOrder::ASC; partition_keys.len()], len)?;
+        let new_peers = changes(order_keys, orders, len)?;
+        let (mut partitions, mut peers) = (vec![], Vec::with_capacity(len));
+        let (mut start, mut peer) = (0, 0);
+        for row in 1..=len {
+            let partition_ends = row == len || new_partition[row];
+            if partition_ends || new_peers[row] {
+                peers.extend(std::iter::repeat_n((peer as u32, row as u32), row - peer));
+                peer = row;
+            }
+            if partition_ends {
+                partitions.push((start, row));
+                start = row;
+            }
+        }
+        Ok(Partitions { partitions, peers })
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// `row_number`, `rank` or `dense_rank` of every row, from 1 in each partition
+    pub fn ranks(&self, kind: WindowKind) -> Vector {
+        let mut ranks = Vec::with_capacity(self.len());
+        for &(start, end) in &self.partitions {
+            let mut dense = 0;
+            for row in start..end {
+                let peer = self.peers[row].0 as usize;
+                dense += (peer == row) as i64;
+                ranks.push(match kind {
+                    WindowKind::RowNumber => (row - start + 1) as i64,
+                    WindowKind::Rank => (peer - start + 1) as i64,
+                    _ => dense,
+                });
+            }
+        }
+        Vector::from(ranks)
+    }
+
+    /// the value of `arg` `offset` rows ahead in the partition, behind for a negative offset,
+    /// `default` past the ends of the partition
+    pub fn shift(&self, arg: &Vector, offset: i64, default: &Value) -> Result<Vector> {
+        let mut indices = Vec::with_capacity(self.len());
+        for &(start, end) in &self.partitions {
+            indices.extend((start..end).map(|row| match row as i64 + offset {
+                shifted if (start as i64..end as i64).contains(&shifted) => shifted as u32,
+                _ => self.len() as u32,
+            }));
+        }
+        gather_or(arg, &indices, default)
+    }
+
+    /// the rows `start..end` of the frame of every row. a range frame with offsets compares the
+    /// values of `order_key`, the only order key.
+    pub fn frames(&self, frame: &Frame, order_key: Option<(&Vector, SortOrder)>) -> Result<Vec<(u32, u32)>> {
+        if frame.has_offsets() {
+            let (key, order) = order_key.ok_or_else(|| Error::Unsupported(format!("a {frame} frame without an order key")))?;
+            return self.range_frames(frame, key, order);
+        }
+        let mut frames = Vec::with_capacity(self.len());
+        for &(start, end) in &self.partitions {
+            for row in start..end {
+                let (first, last) = match frame.unit {
+                    FrameUnit::Rows => (row as i64, row as i64 + 1),
+                    FrameUnit::Range => (self.peers[row].0 as i64, self.peers[row].1 as i64),
+                };
+                let lo = frame.start.map_or(start, |o| (first + o).clamp(start as i64, end as i64) as usize);
+                let hi = frame.end.map_or(end, |o| (last + o).clamp(start as i64, end as i64) as usize);
+                frames.push((lo as u32, hi.max(lo) as u32));
+            }
+        }
+        Ok(frames)
+    }
+
+    fn range_frames(&self, frame: &Frame, key: &Vector, order: SortOrder) -> Result<Vec<(u32, u32)>> {
+        match key {
+            Vector::Date(v) => Ok(self.value_frames(frame, &v.map(|days| days as i64), order, i64::saturating_add)),
+            Vector::DateTime(v) => Ok(self.value_frames(frame, v, order, i64::saturating_add)),
+            key if key.data_type().is_float() => {
+                let Vector::F64(v) = cast_vector(key, &DataType::F64)? else { unreachable!("cast to f64") };
+                Ok(self.value_frames(frame, &v, order, |value, offset| value + offset as f64))
+            }
+            key if key.data_type().is_integer() => {
+                let Vector::I64(v) = cast_vector(key, &DataType::I64)? else { unreachable!("cast to i64") };
+                Ok(self.value_frames(frame, &v, order, i64::saturating_add))
+            }
+            key => Err(Error::Unsupported(format!("a {frame} frame ordered by a {:?} column", key.data_type()))),
+        }
+    }
+
+    /// the frames of the rows whose key is within the offsets of the key of the current row. the
+    /// rows with a null key are at one end of their partition and are the frame of each other.
+    fn value_frames<T: PrimaryType>(&self, frame: &Frame, keys: &PrimaryVector<T>, order: SortOrder, add: impl Fn(T, i64) -> T) -> Vec<(u32, u32)> {
+        let mut frames = Vec::with_capacity(self.len());
+        for &(start, end) in &self.partitions {
+            let valid = (start..end).filter(|&row| keys.is_valid(row)).count();
+            let (valid_start, valid_end) = if order.nulls_first { (end - valid, end) } else { (start, start + valid) };
+            let (null_start, null_end) = if order.nulls_first { (start, valid_start) } else { (valid_end, end) };
+            let values = &keys.values()[valid_start..valid_end];
+            for row in start..end {
+                let (lo, hi) = if !keys.is_valid(row) {
+                    (null_start, null_end)
+                } else {
+                    let value = keys.values()[row];
+                    // the offsets count along the order, towards smaller keys in a descending order
+                    let lo = frame.start.map_or(start, |offset| valid_start + match order.descending {
+                        false => values.partition_point(|&v| v < add(value, offset)),
+                        true => values.partition_point(|&v| v > add(value, offset.saturating_neg())),
+                    });
+                    let hi = frame.end.map_or(end, |offset| valid_start + match order.descending {
+                        false => values.partition_point(|&v| v <= add(value, offset)),
+                        true => values.partition_point(|&v| v >= add(value, offset.saturating_neg())),
+                    });
+                    (lo, hi)
+                };
+                let lo = if frame.start.is_none() { start } else { lo };
+                let hi = if frame.end.is_none() { end } else { hi };
+                frames.push((lo as u32, hi.max(lo) as u32));
+            }
+        }
+        frames
+    }
+}
+
+/// the rows `indices` of `vector`, `default` for the index `vector.len()`
+fn gather_or(vector: &Vector, indices: &[u32], default: &Value) -> Result<Vector> {
+    let data_type = vector.data_type();
+    let default = Vector::from_values(&data_type, std::slice::from_ref(default))?;
+    Ok(Vector::concat(&data_type, &[vector.clone(), default])?.take(indices))
+}
+
+/// the first row of every frame, or the last with `last`, null for an empty frame
+pub fn frame_value(arg: &Vector, frames: &[(u32, u32)], last: bool) -> Result<Vector> {
+    let indices: Vec<u32> = frames.iter()
+        .map(|&(start, end)| match (start < end, last) {
+            (false, _) => arg.len() as u32,
+            (true, false) => start,
+            (true, true) => end - 1,
+        })
+        .collect();
+    gather_or(arg, &indices, &Value::Null)
+}
+
+/// the aggregate `kind` of the values of `arg` in every frame
+pub fn aggregate(kind: WindowKind, arg: &Vector, frames: &[(u32, u32)]) -> Result<Vector> {
+    let data_type = kind.result_type(Some(&arg.data_type()))?;
+    match kind {
+        WindowKind::Count => {
+            // the sums of 1 for every valid row
+            let valid: Vec<u64> = (0..arg.len()).map(|row| arg.is_valid(row) as u64).collect();
+            let counts = slide(&PrimaryVector::new(valid), frames).into_iter().map(|(sum, _)| sum as i64);
+            Ok(Vector::from(counts.collect::<Vec<_>>()))
+        }
+        WindowKind::Sum | WindowKind::Avg => {
+            // the average of numbers is the sum in f64 divided by the count
+            let sum_type = if data_type == DataType::F64 { DataType::F64 } else { AggregateKind::Sum.result_type(&arg.data_type())? };
+            let sums = match cast_vector(arg, &sum_type)? {
+                Vector::I64(v) => sums(&v, frames, kind),
+                Vector::U64(v) => sums(&v, frames, kind),
+                Vector::F64(v) => sums(&v, frames, kind),
+                Vector::Decimal(v) => sums(&v, frames, kind),
+                other => return Err(Error::Unsupported(format!("{}({:?})", kind.name(), other.data_type()))),
+            };
+            Ok(sums)
+        }
+        WindowKind::Min | WindowKind::Max => {
+            let max = kind == WindowKind::Max;
+            match arg.data_type() {
+                DataType::I8 => min_max::<i8>(arg, frames, max),
+                DataType::I16 => min_max::<i16>(arg, frames, max),
+                DataType::I32 | DataType::Date => min_max::<i32>(arg, frames, max),
+                DataType::I64 | DataType::DateTime => min_max::<i64>(arg, frames, max),
+                DataType::U8 => min_max::<u8>(arg, frames, max),
+                DataType::U16 => min_max::<u16>(arg, frames, max),
+                DataType::U32 => min_max::<u32>(arg, frames, max),
+                DataType::U64 => min_max::<u64>(arg, frames, max),
+                DataType::F32 => min_max::<f32>(arg, frames, max),
+                DataType::F64 => min_max::<f64>(arg, frames, max),
+                _ => min_max::<i128>(arg, frames, max),
+            }
+        }
+        _ => Err(Error::Unsupported(format!("{} is not an aggregate", kind.name()))),
+    }
+}
+
+/// an accumulator type of the sliding sums, which can remove what it added
+trait Accumulate: PrimaryType {
+    fn add(self, other: Self) -> Self;
+
+    fn sub(self, other: Self) -> Self;
+
+    /// the average of `count` values summing to `self`
+    fn average(self, count: u64) -> Value;
+}
+
+macro_rules! impl_accumulate {
+    ($($t:ty),*) => {$(
+        impl Accumulate for $t {
+            fn add(self, other: Self) -> Self {
+                self.wrapping_add(other)
+            }
+
+            fn sub(self, other: Self) -> Self {
+                self.wrapping_sub(other)
+            }
+
+            fn average(self, count: u64) -> Value {
+                Value::F64(self as f64 / count as f64)
+            }
+        }
+    )*};
+}
+
+impl_accumulate!(i64, u64);
+
+impl Accumulate for f64 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn sub(self, other: Self) -> Self {
+        self - other
+    }
+
+    fn average(self, count: u64) -> Value {
+        Value::F64(self / count as f64)
+    }
+}
+
+impl Accumulate for i128 {
+    fn add(self, other: Self) -> Self {
+        self.wrapping_add(other)
+    }
+
+    fn sub(self, other: Self) -> Self {
+        self.wrapping_sub(other)
+    }
+
+    /// a decimal, rounded half away from zero like the average of the group-by
+    fn average(self, count: u64) -> Value {
+        Value::Decimal(average_decimal(self, count as i128))
+    }
+}
+
+/// the sum and the number of valid values of every frame. the accumulator moves along with the
+/// frames and starts over when a frame starts before the previous one or after its end.
+fn slide<T: Accumulate>(values: &PrimaryVector<T>, frames: &[(u32, u32)]) -> Vec<(T, u64)> {
+    let (mut lo, mut hi, mut sum, mut count) = (0, 0, T::default(), 0);
+    let mut sums = Vec::with_capacity(frames.len());
+    for &(start, end) in frames {
+        let (start, end) = (start as usize, end as usize);
+        if start < lo || end < hi || start >= hi {
+            (lo, hi, sum, count) = (start, start, T::default(), 0);
+        }
+        for row in (hi..end).filter(|&row| values.is_valid(row)) {
+            sum = sum.add(values.values()[row]);
+            count += 1;
+        }
+        for row in (lo..start).filter(|&row| values.is_valid(row)) {
+            sum = sum.sub(values.values()[row]);
+            count -= 1;
+        }
+        (lo, hi) = (start, end);
+        sums.push((sum, count));
+    }
+    sums
+}
+
+/// the sums or the averages of the frames, null for the frames without values
+fn sums<T: Accumulate>(values: &PrimaryVector<T>, frames: &[(u32, u32)], kind: WindowKind) -> Vector {
+    let sums = slide(values, frames);
+    if kind == WindowKind::Sum {
+        return T::into_vector(PrimaryVector::from_options(sums.into_iter().map(|(sum, count)| (count > 0).then_some(sum))));
+    }
+    let data_type = if T::DATA_TYPE == DataType::Decimal { DataType::Decimal } else { DataType::F64 };
+    let averages: Vec<Value> = sums.into_iter().map(|(sum, count)| if count > 0 { sum.average(count) } else { Value::Null }).collect();
+    Vector::from_values(&data_type, &averages).expect("averages of the result type")
+}
+
+/// the minimum or maximum of the valid values of the ranges of a vector. node `i` combines its
+/// children `2i` and `2i + 1`, the leaves are the values at `len..2 * len`.
+struct SegmentTree<T> {
+    nodes: Vec<Option<T>>,
+    max: bool,
+}
+
+impl<T: PrimaryType> SegmentTree<T> {
+    fn new(values: &PrimaryVector<T>, max: bool) -> Self {
+        let len = values.len();
+        let mut nodes = vec![None; len];
+        nodes.extend(values.iter());
+        let mut tree = SegmentTree { nodes, max };
+        for node in (1..len).rev() {
+            tree.nodes[node] = tree.combine(tree.nodes[2 * node], tree.nodes[2 * node + 1]);
+        }
+        tree
+    }
+
+    fn combine(&self, a: Option<T>, b: Option<T>) -> Option<T> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(if (b > a) == self.max { b } else { a }),
+            (a, None) => a,
+            (None, b) => b,
+        }
+    }
+
+    /// the result of the values `start..end`
+    fn query(&self, start: usize, end: usize) -> Option<T> {
+        let len = self.nodes.len() / 2;
+        let (mut lo, mut hi, mut result) = (start + len, end + len, None);
+        while lo < hi {
+            if lo % 2 == 1 {
+                result = self.combine(result, self.nodes[lo]);
+                lo += 1;
+            }
+            if hi % 2 == 1 {
+                hi -= 1;
+                result = self.combine(result, self.nodes[hi]);
+            }
+            lo /= 2;
+            hi /= 2;
+        }
+        result
+    }
+}
+
+fn min_max<T: PrimaryType>(arg: &Vector, frames: &[(u32, u32)], max: bool) -> Result<Vector> {
+    let values = T::vector(arg).ok_or_else(|| Error::TypeMismatch {
+        expected: format!("{:?}", T::DATA_TYPE),
+        found: format!("{:?}", arg.data_type()),
+    })?;
+    let tree = SegmentTree::new(values, max);
+    let result = PrimaryVector::from_options(frames.iter().map(|&(start, end)| tree.query(start as usize, end as usize)));
+    Ok(into_vector(&arg.data_type(), result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranks_and_shift() {
+        // partitions [0, 4) and [4, 6), peers {1, 2} and {4, 5}
+        let partition = Vector::from(vec!["a", "a", "a", "a", "b", "b"]);
+        let order = Vector::from(vec![1i32, 2, 2, 3, 7, 7]);
+        let partitions = Partitions::new(&[&partition], &[&order], &[SortOrder::ASC], 6).unwrap();
+        assert_eq!(partitions.ranks(WindowKind::RowNumber), Vector::from(vec![1i64, 2, 3, 4, 1, 2]));
+        assert_eq!(partitions.ranks(WindowKind::Rank), Vector::from(vec![1i64, 2, 2, 4, 1, 1]));
+        assert_eq!(partitions.ranks(WindowKind::DenseRank), Vector::from(vec![1i64, 2, 2, 3, 1, 1]));
+
+        let values = Vector::from(vec![10i64, 20, 30, 40, 50, 60]);
+        assert_eq!(partitions.shift(&values, -1, &Value::Null).unwrap(),
+            Vector::from(vec![None, Some(10i64), Some(20), Some(30), None, Some(50)]));
+        assert_eq!(partitions.shift(&values, 2, &Value::I64(0)).unwrap(), Vector::from(vec![30i64, 40, 0, 0, 0, 0]));
+        let frames = partitions.frames(&Frame::DEFAULT, None).unwrap();
+        assert_eq!(frames, vec![(0, 1), (0, 3), (0, 3), (0, 4), (4, 6), (4, 6)]);
+    }
+
+    #[test]
+    fn test_frames() {
+        // days with a gap, a null key last
+        let days = Vector::Date(PrimaryVector::from_options([Some(1), Some(2), Some(2), Some(5), Some(6), None]));
+        let partitions = Partitions::new(&[], &[&days], &[SortOrder::ASC], 6).unwrap();
+        let range = Frame { unit: FrameUnit::Range, start: Some(-1), end: Some(1) };
+        let frames = partitions.frames(&range, Some((&days, SortOrder::ASC))).unwrap();
+        assert_eq!(frames, vec![(0, 3), (0, 3), (0, 3), (3, 5), (3, 5), (5, 6)]);
+        let preceding = Frame { unit: FrameUnit::Range, start: None, end: Some(-3) };
+        let frames = partitions.frames(&preceding, Some((&days, SortOrder::ASC))).unwrap();
+        assert_eq!(frames, vec![(0, 0), (0, 0), (0, 0), (0, 3), (0, 3), (0, 6)]);
+
+        let rows = Frame { unit: FrameUnit::Rows, start: Some(-2), end: Some(0) };
+        let frames = partitions.frames(&rows, None).unwrap();
+        assert_eq!(frames, vec![(0, 1), (0, 2), (0, 3), (1, 4), (2, 5), (3, 6)]);
+        let amounts = Vector::from(vec![Some(1i32), None, Some(4), Some(-2), Some(8), Some(3)]);
+        assert_eq!(aggregate(WindowKind::Sum, &amounts, &frames).unwrap(), Vector::from(vec![1i64, 1, 5, 2, 10, 9]));
+        assert_eq!(aggregate(WindowKind::Count, &amounts, &frames).unwrap(), Vector::from(vec![1i64, 1, 2, 2, 3, 3]));
+        assert_eq!(aggregate(WindowKind::Max, &amounts, &frames).unwrap(), Vector::from(vec![1i32, 1, 4, 4, 8, 8]));
+        assert_eq!(aggregate(WindowKind::Min, &amounts, &[(1, 2), (0, 6), (3, 3)]).unwrap(), Vector::from(vec![None, Some(-2i32), None]));
+        assert_eq!(aggregate(WindowKind::Avg, &amounts, &[(0, 3), (1, 2)]).unwrap(), Vector::from(vec![Some(2.5f64), None]));
+        assert_eq!(frame_value(&amounts, &[(2, 4), (0, 0)], true).unwrap(), Vector::from(vec![Some(-2i32), None]));
+    }
+}
@@ -1,2 +1,7 @@
+#![feature(portable_simd)]
+
 pub mod datatype;
+pub mod error;
+pub mod exec;
 pub mod qir;
+pub mod vector;
@@ -0,0 +1,179 @@
+//! The `aggregate` sink: reduces one column of its input to a single row of aggregates, e.g.
+//! the range of a join key for the `:minmaxes` of a later scan. The result is a
+//! `Vec<DataChunk>` of one chunk with one row.
+//!
+//! ```text
+//! minmax = aggregate :input = v2.customer_id
+//!         :aggregates =
+//!             "min": min,
+//!             "max": max
+//! ```
+
+use std::rc::Rc;
+use crate::error::Result;
+use crate::exec::reduce::{Reducer, Reduction};
+use crate::exec::{ExecutionContext, SinkResult, SinkState};
+use crate::qir::checker::Checker;
+use crate::qir::printer::{name, Printer};
+use crate::qir::{resolve_columns, Column, Operator, Sink};
+use crate::vector::DataChunk;
+
+pub struct SimdAggregate {
+    pub input: Rc<dyn Operator>,
+    /// the aggregated column of the input
+    pub column: String,
+    pub aggregates: Vec<(String, Reduction)>,
+    output: Vec<String>,
+}
+
+impl SimdAggregate {
+    pub fn new(input: Rc<dyn Operator>, column: String, aggregates: Vec<(String, Reduction)>) -> Self {
+        let output = aggregates.iter().map(|(name, _)| name.clone()).collect();
+        SimdAggregate { input, column, aggregates, output }
+    }
+}
+
+impl Operator for SimdAggregate {
+    fn name(&self) -> &'static str {
+        "aggregate"
+    }
+
+    fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", format!("{}.{}", printer.var(&self.input), name(&self.column)));
+        printer.entries("aggregates", self.aggregates.iter().map(|(name, r)| (name.clone(), r.name().to_string())));
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        let input = checker.input(&self.input)?;
+        let column = checker.resolve(&input, std::slice::from_ref(&self.column))?.remove(0);
+        let mut output = Some(vec![]);
+        for (i, (name, reduction)) in self.aggregates.iter().enumerate() {
+            if self.output[..i].contains(name) {
+                checker.error(format!("duplicate column `{name}`"));
+            }
+            match reduction.result_type(&column.data_type) {
+                Ok(data_type) => {
+                    let nullable = !matches!(reduction, Reduction::Count | Reduction::CountNonNull);
+                    output.iter_mut().for_each(|o| o.push(Column { name: name.clone(), data_type: data_type.clone(), nullable }));
+                }
+                Err(e) => {
+                    checker.error(format!("`{name}`: {e}"));
+                    output = None;
+                }
+            }
+        }
+        output
+    }
+}
+
+impl Sink for SimdAggregate {
+    fn open(&self, ctx: &ExecutionContext) -> Result<Box<dyn SinkState>> {
+        let column = resolve_columns(self.input.output(), std::slice::from_ref(&self.column))?[0];
+        let data_type = &ctx.schema(&self.input)?[column].data_type;
+        let reducers = self.aggregates.iter().map(|(_, r)| r.create(data_type)).collect::<Result<Vec<_>>>()?;
+        Ok(Box::new(SimdAggregateState { column, reducers }))
+    }
+}
+
+struct SimdAggregateState {
+    column: usize,
+    reducers: Vec<Box<dyn Reducer>>,
+}
+
+impl SinkState for SimdAggregateState {
+    fn consume(&mut self, _ctx: &ExecutionContext, chunk: DataChunk) -> Result<()> {
+        let column = chunk.column(self.column);
+        for reducer in &mut self.reducers {
+            reducer.update(column)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>, _ctx: &ExecutionContext) -> Result<SinkResult> {
+        let columns = self.reducers.into_iter().map(|r| r.finish()).collect::<Result<Vec<_>>>()?;
+        Ok(Rc::new(vec![DataChunk::new(columns)]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exec::{ExecutionContext, Interpreter};
+    use crate::qir::{DataType, Topology};
+    use crate::vector::{DataChunk, Value, Vector};
+
+    const ORDERS: &str = r#"
+pipeline1: Pipeline =
+    v1 = table_scan :table = "sale_orders"
+            :columns =
+                "customer_id": { data_type: "i32" },
+                "freight": { data_type: "f64", nullable: false }
+    v2 = filter :input = v1
+            :expr = $in.freight > 1
+            :projection = [ "customer_id" ]
+    minmax = aggregate :input = v2.customer_id
+            :aggregates =
+                "min": min,
+                "max": max,
+                "count": count_non_null
+pipeline2: Pipeline =
+    v1 = table_scan :table = "sale_orders"
+            :columns =
+                "customer_id": { data_type: "i32" },
+                "freight": { data_type: "f64", nullable: false }
+            :minmaxes = "customer_id": [pipeline1.minmax.min, pipeline1.minmax.max]
+    v2 = AGGREGATE
+"#;
+
+    fn context() -> ExecutionContext {
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("sale_orders", DataChunk::new(vec![
+            Vector::from(vec![Some(1i32), None, Some(2), Some(9), Some(3)]),
+            Vector::from(vec![1.0f64, 2.0, 3.0, 4.0, 5.0]),
+        ]));
+        ctx
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let source = ORDERS.replace("AGGREGATE", r#"aggregate :input = v1.freight :aggregates = "avg": avg, "squares": sum_squares"#);
+        let topology = Topology::parse(&source).unwrap();
+        let result = Interpreter::new(context()).run(&topology).unwrap();
+        let chunks = result.downcast::<Vec<DataChunk>>().unwrap();
+        assert_eq!(chunks[0].row(0), vec![Value::F64(3.0), Value::F64(55.0)]);
+
+        let printed = topology.to_string();
+        assert!(printed.contains("v3 = aggregate :input = v2.customer_id"), "{printed}");
+        assert_eq!(Topology::parse(&printed).unwrap().to_string(), printed);
+    }
+
+    #[test]
+    fn test_minmax_for_group_by() {
+        let source = ORDERS.replace("AGGREGATE", r#"hash_group_by :input = v1 :group_by = [ "customer_id" ] :aggregates = "n": count($in.freight)"#);
+        let topology = Topology::parse(&source).unwrap();
+        let schemas = topology.check().unwrap();
+        let minmax = &topology.main.parents[0].sink;
+        assert_eq!(schemas.get(minmax).unwrap().iter().map(|c| &c.data_type).collect::<Vec<_>>(),
+            vec![&DataType::I32, &DataType::I32, &DataType::I64]);
+
+        // the range of the filtered rows is 2..=9, the key of the first row is outside of it
+        let result = Interpreter::new(context()).run(&topology).unwrap();
+        let chunks = result.downcast::<Vec<DataChunk>>().unwrap();
+        let mut rows: Vec<Vec<Value>> = (0..chunks[0].len()).map(|i| chunks[0].row(i)).collect();
+        rows.sort_by_key(|row| row[0].to_string());
+        assert_eq!(rows.len(), 5);
+        assert!(rows.iter().all(|row| row[1] == Value::I64(1)));
+    }
+
+    #[test]
+    fn test_check_aggregate() {
+        let source = ORDERS.replace("\"count\": count_non_null", "\"total\": sum_squares, \"min\": count");
+        let err = Topology::parse(&source).err().unwrap().to_string();
+        assert!(err.contains("duplicate column `min`"), "{err}");
+        let source = ORDERS.replace("v2.customer_id", "v2.freight");
+        assert!(Topology::parse(&source).err().unwrap().to_string().contains("unknown column `freight`"));
+    }
+}
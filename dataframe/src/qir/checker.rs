@@ -0,0 +1,233 @@
+//! Static checks of a topology, run before it is executed.
+//!
+//! Every operator resolves the column names it uses against the schema of its input (or its
+//! `Table` for a scan) and infers the columns it produces, see `Operator::check`. Errors do not
+//! stop the check, all of them are reported with the path of the operator, `pipeline2.v3`,
+//! named as in the printed text. An operator whose input has errors is skipped, so a typo is
+//! reported once.
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use crate::error::{CheckError, Error, Result};
+use crate::qir::expr::{Expr, ExprKind};
+use crate::qir::{Column, DataType, Operator, Topology};
+
+/// the inferred output columns of the operators of a topology
+#[derive(Debug, Default)]
+pub struct Schemas {
+    schemas: HashMap<usize, Vec<Column>>,
+}
+
+fn key<T: ?Sized>(rc: &Rc<T>) -> usize {
+    Rc::as_ptr(rc) as *const () as usize
+}
+
+impl Schemas {
+    pub fn get<T: ?Sized>(&self, operator: &Rc<T>) -> Option<&[Column]> {
+        self.schemas.get(&key(operator)).map(|s| s.as_slice())
+    }
+}
+
+/// collects the schemas and errors while the operators check themselves
+pub struct Checker {
+    schemas: Schemas,
+    errors: Vec<CheckError>,
+    /// the path of the operator being checked
+    path: String,
+    /// the operator before the one being checked, which produces its input chunks
+    previous: Option<Rc<dyn Operator>>,
+    /// the operators checked so far, with or without errors
+    checked: HashSet<usize>,
+}
+
+impl Checker {
+    /// report an error of the operator being checked
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.errors.push(CheckError { path: self.path.clone(), message: message.into() });
+    }
+
+    /// the schema of the operator that streams its chunks into the one being checked, `None`
+    /// when it has errors. `input` must be the previous operator of the pipeline.
+    pub fn input(&mut self, input: &Rc<dyn Operator>) -> Option<Vec<Column>> {
+        match &self.previous {
+            Some(previous) if key(previous) == key(input) => self.schemas.get(input).map(|s| s.to_vec()),
+            Some(_) => {
+                self.error(format!("the input `{}` is not the previous operator of the pipeline", input.name()));
+                None
+            }
+            None => {
+                self.error("a source has no input");
+                None
+            }
+        }
+    }
+
+    /// the schema of an operator of a pipeline that runs before, e.g. the sink of a parent
+    /// pipeline. `None` when it has errors.
+    pub fn schema(&mut self, operator: &Rc<dyn Operator>) -> Option<Vec<Column>> {
+        if !self.checked.contains(&key(operator)) {
+            self.error(format!("`{}` does not belong to a parent pipeline", operator.name()));
+        }
+        self.schemas.get(operator).map(|s| s.to_vec())
+    }
+
+    /// the columns of `schema` named `names`, reporting unknown and duplicate names
+    pub fn resolve(&mut self, schema: &[Column], names: &[String]) -> Option<Vec<Column>> {
+        let mut columns = Vec::with_capacity(names.len());
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                self.error(format!("duplicate column `{name}`"));
+            }
+            match schema.iter().find(|c| &c.name == name) {
+                Some(column) => columns.push(column.clone()),
+                None => self.error(format!("column not found: {name}")),
+            }
+        }
+        (columns.len() == names.len()).then_some(columns)
+    }
+
+    /// check that the columns an expression references exist in `schema` with the types the
+    /// expression was built with, and that it produces `data_type`
+    pub fn expr(&mut self, expr: &Expr, schema: &[Column], data_type: &DataType) {
+        let mut errors = vec![];
+        expr.visit(&mut |e| {
+            if let ExprKind::Column(name) = &e.kind {
+                match schema.iter().find(|c| &c.name == name) {
+                    None => errors.push(format!("column not found: {name}")),
+                    Some(column) if column.data_type != e.data_type => errors.push(format!(
+                        "column `{name}` is {:?}, but the expression uses it as {:?}", column.data_type, e.data_type)),
+                    Some(_) => {}
+                }
+            }
+        });
+        if expr.data_type != *data_type {
+            errors.push(format!("the expression `{expr}` is {:?}, expected {data_type:?}", expr.data_type));
+        }
+        for error in errors {
+            self.error(error);
+        }
+    }
+}
+
+impl Topology {
+    /// check the whole topology, returning the output columns of every operator or all the errors
+    pub fn check(&self) -> Result<Schemas> {
+        let mut checker = Checker { schemas: Schemas::default(), errors: vec![], path: String::new(), previous: None, checked: HashSet::new() };
+        for (i, pipeline) in self.pipelines().iter().enumerate() {
+            checker.previous = None;
+            for (j, operator) in pipeline.operators().into_iter().enumerate() {
+                checker.path = format!("pipeline{}.v{} ({})", i + 1, j + 1, operator.name());
+                let errors = checker.errors.len();
+                let schema = operator.check(&mut checker);
+                if let Some(schema) = schema {
+                    if schema.iter().map(|c| &c.name).ne(operator.output().iter()) {
+                        checker.error(format!("the inferred columns {:?} differ from the output {:?}",
+                            schema.iter().map(|c| &c.name).collect::<Vec<_>>(), operator.output()));
+                    }
+                    if checker.errors.len() == errors {
+                        checker.schemas.schemas.insert(key(&operator), schema);
+                    }
+                }
+                checker.checked.insert(key(&operator));
+                checker.previous = Some(operator);
+            }
+        }
+        if checker.errors.is_empty() { Ok(checker.schemas) } else { Err(Error::Check(checker.errors)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::{ExecutionContext, Interpreter};
+    use crate::qir::expr::BinaryOp;
+    use crate::qir::{Filter, IdentitySink, Pipeline, Scan, Table};
+
+    const TEXT: &str = r#"
+pipeline1: Pipeline =
+    v1 = table_scan :table = "customers"
+            :columns =
+                "customer_id": { data_type: "i32", nullable: false },
+                "name": { data_type: "string" },
+                "gender": { data_type: "string" }
+    v2 = filter :input = v1
+            :expr = $in.gender == "M"
+            :projection = [ "customer_id", "name" ]
+    v3 = identity :input = v2
+"#;
+
+    #[test]
+    fn test_infer_schemas() {
+        let topology = Topology::parse(TEXT).unwrap();
+        let schemas = topology.check().unwrap();
+        let sink = schemas.get(&topology.main.sink).unwrap();
+        assert_eq!(sink.iter().map(|c| (c.name.as_str(), &c.data_type)).collect::<Vec<_>>(),
+            vec![("customer_id", &DataType::I32), ("name", &DataType::String)]);
+        assert!(!sink[0].nullable);
+    }
+
+    #[test]
+    fn test_report_all_errors() {
+        let table = Rc::new(Table {
+            name: "customers".to_string(),
+            columns: vec![
+                Column { name: "customer_id".to_string(), data_type: DataType::I32, nullable: false },
+                Column { name: "name".to_string(), data_type: DataType::String, nullable: true },
+            ],
+        });
+        let scan = |output: &[&str]| Rc::new(Scan {
+            name: "customers".to_string(),
+            table: table.clone(),
+            output: output.iter().map(|s| s.to_string()).collect(),
+            minmaxes: vec![],
+            ordered_by: vec![],
+        });
+
+        let parent_scan = scan(&["customer_id", "nmae"]);
+        let parent = Rc::new(Pipeline {
+            source: parent_scan.clone(),
+            operators: vec![],
+            sink: Rc::new(IdentitySink { input: parent_scan }),
+            parents: vec![],
+        });
+
+        // the predicate was built against a schema where `name` is an i32
+        let stale = [Column { name: "name".to_string(), data_type: DataType::I32, nullable: true }];
+        let predicate = Expr::binary(BinaryOp::Gt, Expr::column(&stale, "name").unwrap(), Expr::literal(1)).unwrap();
+        let main_scan = scan(&["customer_id", "name"]);
+        let filter: Rc<dyn Operator> = Rc::new(Filter::new(main_scan.clone(), predicate,
+            vec!["customer_id".to_string(), "id".to_string()]));
+        let main = Rc::new(Pipeline {
+            source: main_scan.clone(),
+            operators: vec![filter],
+            sink: Rc::new(IdentitySink { input: main_scan }),
+            parents: vec![parent],
+        });
+
+        let errors = match (Topology { main }).check() {
+            Err(Error::Check(errors)) => errors,
+            Err(other) => panic!("unexpected {other:?}"),
+            Ok(_) => panic!("expected errors"),
+        };
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, vec![
+            "pipeline1.v1 (table_scan): column not found: nmae",
+            "pipeline2.v2 (filter): column `name` is String, but the expression uses it as I32",
+            "pipeline2.v2 (filter): column not found: id",
+            "pipeline2.v3 (identity): the input `table_scan` is not the previous operator of the pipeline",
+        ]);
+    }
+
+    #[test]
+    fn test_interpreter_checks_first() {
+        let table = Rc::new(Table {
+            name: "customers".to_string(),
+            columns: vec![Column { name: "customer_id".to_string(), data_type: DataType::I32, nullable: false }],
+        });
+        let scan = Rc::new(Scan { name: "customers".to_string(), table, output: vec!["id".to_string()], minmaxes: vec![], ordered_by: vec![] });
+        let main = Rc::new(Pipeline { source: scan.clone(), operators: vec![], sink: Rc::new(IdentitySink { input: scan }), parents: vec![] });
+        // the table is not registered, the checker fails before the scan is opened
+        let err = Interpreter::new(ExecutionContext::new()).run(&Topology { main }).err().unwrap();
+        assert_eq!(err.to_string(), "invalid topology:\n  pipeline1.v1 (table_scan): column not found: id");
+    }
+}
@@ -0,0 +1,434 @@
+//! Typed expressions, used by `Filter` predicates and projections.
+//!
+//! An `Expr` is built bottom-up by constructors that check the operand types, so every node
+//! carries its resolved `DataType` and nullability. Operands of different numeric types are
+//! coerced by inserting casts (literals are converted in place).
+
+use crate::error::{Error, Result};
+use crate::exec::cast::{can_cast, cast_exact, cast_value};
+use crate::qir::{Column, DataType};
+use crate::vector::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+impl BinaryOp {
+    pub fn is_comparison(self) -> bool {
+        matches!(self, BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq)
+    }
+
+    pub fn is_logical(self) -> bool {
+        matches!(self, BinaryOp::And | BinaryOp::Or)
+    }
+
+    pub fn is_arithmetic(self) -> bool {
+        matches!(self, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod)
+    }
+
+    /// the operator as written in the qir text format
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Eq => "==",
+            BinaryOp::NotEq => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+        }
+    }
+
+    /// the comparison with its operands swapped, `a < b` <=> `b > a`
+    pub fn flip(self) -> BinaryOp {
+        match self {
+            BinaryOp::Lt => BinaryOp::Gt,
+            BinaryOp::LtEq => BinaryOp::GtEq,
+            BinaryOp::Gt => BinaryOp::Lt,
+            BinaryOp::GtEq => BinaryOp::LtEq,
+            op => op,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    /// a column of the operator's input, `$in.name`
+    Column(String),
+    Literal(Value),
+    Binary { op: BinaryOp, left: Box<Expr>, right: Box<Expr> },
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    IsNull { expr: Box<Expr>, negated: bool },
+    /// sql `LIKE`, `%` matches any string and `_` any single character
+    Like { expr: Box<Expr>, pattern: String, negated: bool },
+    InList { expr: Box<Expr>, list: Vec<Expr>, negated: bool },
+    /// convert to the expression's `data_type`
+    Cast(Box<Expr>),
+    Function { name: String, args: Vec<Expr> },
+}
+
+/// a typed expression node
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub data_type: DataType,
+    pub nullable: bool,
+}
+
+fn mismatch(expected: impl Into<String>, found: &DataType) -> Error {
+    Error::TypeMismatch { expected: expected.into(), found: format!("{found:?}") }
+}
+
+/// the width rank of an integer type
+fn integer_rank(data_type: &DataType) -> u8 {
+    match data_type {
+        DataType::I8 | DataType::U8 => 1,
+        DataType::I16 | DataType::U16 => 2,
+        DataType::I32 | DataType::U32 => 3,
+        _ => 4,
+    }
+}
+
+/// the type both operands of a comparison or arithmetic are converted to, if any
+pub fn common_type(left: &DataType, right: &DataType) -> Option<DataType> {
+    if left == right {
+        return Some(left.clone());
+    }
+    match (left, right) {
+        (l, r) if l.is_numeric() && r.is_numeric() => Some(if l.is_float() || r.is_float() {
+            DataType::F64
+        } else if *l == DataType::Decimal || *r == DataType::Decimal {
+            DataType::Decimal
+        } else if l.is_signed_integer() == r.is_signed_integer() {
+            if integer_rank(l) >= integer_rank(r) { l.clone() } else { r.clone() }
+        } else {
+            DataType::I64
+        }),
+        (DataType::Date, DataType::DateTime) | (DataType::DateTime, DataType::Date) => Some(DataType::DateTime),
+        _ => None,
+    }
+}
+
+/// the result type of a scalar function, `None` when it is unknown or the arguments do not fit
+fn function_type(name: &str, args: &[DataType]) -> Option<DataType> {
+    use DataType::*;
+    match (name, args) {
+        ("abs", [t]) if t.is_numeric() => Some(t.clone()),
+        ("lower" | "upper" | "trim", [String]) => Some(String),
+        ("length", [String]) => Some(I64),
+        ("substr", [String, a, b]) if a.is_integer() && b.is_integer() => Some(String),
+        ("concat", args) if !args.is_empty() && args.iter().all(|a| *a == String) => Some(String),
+        ("starts_with" | "ends_with" | "contains", [String, String]) => Some(Bool),
+        ("coalesce", [first, rest @ ..]) if rest.iter().all(|a| a == first) => Some(first.clone()),
+        ("year" | "month" | "day", [t]) if t.is_temporal() => Some(I32),
+        ("to_date", [t]) if t.is_temporal() || *t == String => Some(Date),
+        ("to_timestamp_seconds", [t]) if t.is_integer() => Some(DateTime),
+        _ => None,
+    }
+}
+
+impl Expr {
+    fn new(kind: ExprKind, data_type: DataType, nullable: bool) -> Expr {
+        Expr { kind, data_type, nullable }
+    }
+
+    /// a reference to the column `name` of the operator's input
+    pub fn column(input: &[Column], name: &str) -> Result<Expr> {
+        let column = input.iter().find(|c| c.name == name).ok_or_else(|| Error::ColumnNotFound(name.to_string()))?;
+        Ok(Expr::new(ExprKind::Column(name.to_string()), column.data_type.clone(), column.nullable))
+    }
+
+    /// a literal. an untyped `Null` takes the type of the expression it is combined with.
+    pub fn literal(value: impl Into<Value>) -> Expr {
+        let value = value.into();
+        let data_type = value.data_type().unwrap_or(DataType::Bool);
+        let nullable = value.is_null();
+        Expr::new(ExprKind::Literal(value), data_type, nullable)
+    }
+
+    /// a null literal of `data_type`
+    pub fn null(data_type: DataType) -> Expr {
+        Expr::new(ExprKind::Literal(Value::Null), data_type, true)
+    }
+
+    pub fn is_literal(&self) -> bool {
+        matches!(self.kind, ExprKind::Literal(_))
+    }
+
+    /// convert to `data_type`, folding the conversion of literals
+    pub fn cast(self, data_type: DataType) -> Result<Expr> {
+        if self.data_type == data_type {
+            return Ok(self);
+        }
+        if let ExprKind::Literal(value) = &self.kind {
+            let value = cast_value(value, &data_type)?;
+            let nullable = value.is_null();
+            return Ok(Expr::new(ExprKind::Literal(value), data_type, nullable));
+        }
+        if !can_cast(&self.data_type, &data_type) {
+            return Err(Error::TypeMismatch { expected: format!("{data_type:?}"), found: format!("cast from {:?}", self.data_type) });
+        }
+        let nullable = self.nullable || self.data_type == DataType::String;
+        Ok(Expr::new(ExprKind::Cast(Box::new(self)), data_type, nullable))
+    }
+
+    /// convert both operands to a common type. a numeric literal takes the type of the other
+    /// operand when it fits exactly, string literals compared with dates are parsed.
+    pub(crate) fn coerce(left: Expr, right: Expr) -> Result<(Expr, Expr)> {
+        let target = match (&left, &right) {
+            (l, r) if l.data_type == r.data_type => return Ok((left, right)),
+            (l, r) if r.fits(&l.data_type) => l.data_type.clone(),
+            (l, r) if l.fits(&r.data_type) => r.data_type.clone(),
+            (l, r) if l.is_literal() && matches!(l.kind, ExprKind::Literal(Value::Null)) => r.data_type.clone(),
+            (l, r) if r.is_literal() && matches!(r.kind, ExprKind::Literal(Value::Null)) => l.data_type.clone(),
+            (l, r) if r.is_literal() && r.data_type == DataType::String && l.data_type.is_temporal() => l.data_type.clone(),
+            (l, r) if l.is_literal() && l.data_type == DataType::String && r.data_type.is_temporal() => r.data_type.clone(),
+            (l, r) => common_type(&l.data_type, &r.data_type).ok_or_else(|| Error::TypeMismatch {
+                expected: format!("{:?}", l.data_type),
+                found: format!("{:?}", r.data_type),
+            })?,
+        };
+        Ok((left.cast(target.clone())?, right.cast(target)?))
+    }
+
+    /// whether this is a numeric literal that converts to the numeric `data_type` without loss
+    fn fits(&self, data_type: &DataType) -> bool {
+        let ExprKind::Literal(value) = &self.kind else { return false };
+        if !(self.data_type.is_numeric() && data_type.is_numeric()) || value.is_null() {
+            return false;
+        }
+        let converted = cast_value(value, data_type).and_then(|v| cast_value(&v, &self.data_type));
+        converted.is_ok_and(|v| v == *value)
+    }
+
+    pub fn binary(op: BinaryOp, left: Expr, right: Expr) -> Result<Expr> {
+        let nullable = left.nullable || right.nullable;
+        if op.is_logical() {
+            for side in [&left, &right] {
+                if side.data_type != DataType::Bool {
+                    return Err(mismatch("Bool", &side.data_type));
+                }
+            }
+            return Ok(Expr::new(ExprKind::Binary { op, left: Box::new(left), right: Box::new(right) }, DataType::Bool, nullable));
+        }
+
+        // date +/- days, date - date
+        if op.is_arithmetic() && left.data_type == DataType::Date {
+            let data_type = match (op, &right.data_type) {
+                (BinaryOp::Add | BinaryOp::Sub, t) if t.is_integer() => DataType::Date,
+                (BinaryOp::Sub, DataType::Date) => DataType::I32,
+                _ => return Err(mismatch("days (integer) or Date", &right.data_type)),
+            };
+            let right = if right.data_type.is_integer() { right.cast(DataType::I32)? } else { right };
+            return Ok(Expr::new(ExprKind::Binary { op, left: Box::new(left), right: Box::new(right) }, data_type, nullable));
+        }
+
+        let (left, right) = Expr::coerce(left, right)?;
+        let data_type = if op.is_comparison() {
+            if matches!(left.data_type, DataType::List(_) | DataType::Struct(_) | DataType::Map(..)) {
+                return Err(mismatch("comparable type", &left.data_type));
+            }
+            DataType::Bool
+        } else {
+            if !left.data_type.is_numeric() {
+                return Err(mismatch("numeric type", &left.data_type));
+            }
+            left.data_type.clone()
+        };
+        // integer division by zero yields null
+        let nullable = nullable || (op == BinaryOp::Div || op == BinaryOp::Mod) && !data_type.is_float();
+        Ok(Expr::new(ExprKind::Binary { op, left: Box::new(left), right: Box::new(right) }, data_type, nullable))
+    }
+
+    pub fn and(self, other: Expr) -> Result<Expr> {
+        Expr::binary(BinaryOp::And, self, other)
+    }
+
+    pub fn or(self, other: Expr) -> Result<Expr> {
+        Expr::binary(BinaryOp::Or, self, other)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Result<Expr> {
+        if self.data_type != DataType::Bool {
+            return Err(mismatch("Bool", &self.data_type));
+        }
+        let nullable = self.nullable;
+        Ok(Expr::new(ExprKind::Not(Box::new(self)), DataType::Bool, nullable))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn neg(self) -> Result<Expr> {
+        if !(self.data_type.is_signed_integer() || self.data_type.is_float() || self.data_type == DataType::Decimal) {
+            return Err(mismatch("signed numeric type", &self.data_type));
+        }
+        let (data_type, nullable) = (self.data_type.clone(), self.nullable);
+        Ok(Expr::new(ExprKind::Negate(Box::new(self)), data_type, nullable))
+    }
+
+    pub fn is_null(self, negated: bool) -> Expr {
+        Expr::new(ExprKind::IsNull { expr: Box::new(self), negated }, DataType::Bool, false)
+    }
+
+    pub fn like(self, pattern: &str, negated: bool) -> Result<Expr> {
+        if self.data_type != DataType::String {
+            return Err(mismatch("String", &self.data_type));
+        }
+        let nullable = self.nullable;
+        Ok(Expr::new(ExprKind::Like { expr: Box::new(self), pattern: pattern.to_string(), negated }, DataType::Bool, nullable))
+    }
+
+    pub fn in_list(self, list: Vec<Expr>, negated: bool) -> Result<Expr> {
+        // literals take the type of the expression, other items are coerced to a common type
+        let mut target = self.data_type.clone();
+        for item in list.iter().filter(|item| !item.is_literal()) {
+            target = common_type(&target, &item.data_type).ok_or_else(|| mismatch(format!("{target:?}"), &item.data_type))?;
+        }
+        let expr = self.cast(target.clone())?;
+        // a number the type can not hold matches no row, instead of the number it would wrap to
+        let wraps = |item: &Expr| match &item.kind {
+            ExprKind::Literal(value) => item.data_type.is_numeric() && target.is_integer() && cast_exact(value, &target).is_err(),
+            _ => false,
+        };
+        let list = list.into_iter()
+            .filter(|item| !wraps(item))
+            .map(|item| item.cast(target.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        let nullable = expr.nullable || list.iter().any(|item| item.nullable);
+        Ok(Expr::new(ExprKind::InList { expr: Box::new(expr), list, negated }, DataType::Bool, nullable))
+    }
+
+    /// a call of a built-in scalar function
+    pub fn function(name: &str, args: Vec<Expr>) -> Result<Expr> {
+        let types: Vec<DataType> = args.iter().map(|a| a.data_type.clone()).collect();
+        let data_type = function_type(name, &types)
+            .ok_or_else(|| Error::Unsupported(format!("function {name}({types:?})")))?;
+        let nullable = match name {
+            "coalesce" => args.iter().all(|a| a.nullable),
+            _ => args.iter().any(|a| a.nullable),
+        };
+        Ok(Expr::new(ExprKind::Function { name: name.to_string(), args }, data_type, nullable))
+    }
+
+    /// the names of all input columns referenced by this expression
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = vec![];
+        self.visit(&mut |expr| {
+            if let ExprKind::Column(name) = &expr.kind
+                && !columns.contains(&name.as_str()) {
+                columns.push(name.as_str());
+            }
+        });
+        columns
+    }
+
+    /// call `f` on this node and all its descendants, parents first
+    pub fn visit<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        f(self);
+        match &self.kind {
+            ExprKind::Column(_) | ExprKind::Literal(_) => {}
+            ExprKind::Binary { left, right, .. } => {
+                left.visit(f);
+                right.visit(f);
+            }
+            ExprKind::Not(expr) | ExprKind::Negate(expr) | ExprKind::Cast(expr) => expr.visit(f),
+            ExprKind::IsNull { expr, .. } | ExprKind::Like { expr, .. } => expr.visit(f),
+            ExprKind::InList { expr, list, .. } => {
+                expr.visit(f);
+                list.iter().for_each(|item| item.visit(f));
+            }
+            ExprKind::Function { args, .. } => args.iter().for_each(|arg| arg.visit(f)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::column;
+
+    fn sale_orders() -> Vec<Column> {
+        vec![
+            column! { name = "customer_id", data_type = I32, nullable = false },
+            column! { name = "gender", data_type = String, nullable = false },
+            column! { name = "freight", data_type = F64 },
+            column! { name = "order_date", data_type = Date },
+        ]
+    }
+
+    #[test]
+    fn test_readme_predicate() {
+        let input = sale_orders();
+        // $in.gender == "M" && $in.freight > 10
+        let gender = Expr::binary(BinaryOp::Eq, Expr::column(&input, "gender").unwrap(), Expr::literal("M")).unwrap();
+        let freight = Expr::binary(BinaryOp::Gt, Expr::column(&input, "freight").unwrap(), Expr::literal(10)).unwrap();
+        let predicate = gender.clone().and(freight.clone()).unwrap();
+
+        assert_eq!(predicate.data_type, DataType::Bool);
+        assert!(!gender.nullable);
+        assert!(predicate.nullable);
+        // the integer literal is converted to the column's type
+        match &freight.kind {
+            ExprKind::Binary { right, .. } => assert_eq!(right.kind, ExprKind::Literal(Value::F64(10.0))),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(predicate.columns(), vec!["gender", "freight"]);
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let input = sale_orders();
+        let gender = Expr::column(&input, "gender").unwrap();
+        let freight = Expr::column(&input, "freight").unwrap();
+        assert!(Expr::binary(BinaryOp::Add, gender.clone(), freight.clone()).is_err());
+        assert!(gender.clone().and(freight.clone()).is_err());
+        assert!(freight.clone().like("a%", false).is_err());
+        assert!(Expr::column(&input, "missing").is_err());
+        assert!(Expr::function("lower", vec![freight]).is_err());
+    }
+
+    #[test]
+    fn test_coercions() {
+        let input = sale_orders();
+        let id = Expr::column(&input, "customer_id").unwrap();
+        let sum = Expr::binary(BinaryOp::Add, id.clone(), Expr::column(&input, "freight").unwrap()).unwrap();
+        assert_eq!(sum.data_type, DataType::F64);
+
+        let date = Expr::binary(BinaryOp::Gt, Expr::column(&input, "order_date").unwrap(), Expr::literal("1990-01-01")).unwrap();
+        match &date.kind {
+            ExprKind::Binary { right, .. } => assert_eq!(right.kind, ExprKind::Literal(Value::Date(7305))),
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let in_list = id.in_list(vec![Expr::literal(1i64), Expr::literal(2u8)], false).unwrap();
+        assert_eq!(in_list.data_type, DataType::Bool);
+        assert!(!in_list.nullable);
+
+        // a literal out of the range of the type is dropped rather than wrapped
+        let small = Expr::literal(7u8).in_list(vec![Expr::literal(300), Expr::literal(7)], false).unwrap();
+        match &small.kind {
+            ExprKind::InList { list, .. } => assert_eq!(list.iter().map(|i| i.kind.clone()).collect::<Vec<_>>(), vec![ExprKind::Literal(Value::U8(7))]),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! The `hash_group_by` sink: groups the rows of its input by key columns and aggregates each
+//! group. The result is a `Vec<DataChunk>` of the key columns followed by the aggregates.
+//!
+//! ```text
+//! v4 = hash_group_by :input = v3
+//!         :group_by = [ "name" ]
+//!         :aggregates =
+//!             "count": count($in.freight),
+//!             "sum": sum($in.freight * 2)
+//! ```
+//!
+//! Integer keys whose ranges are known from the `:minmaxes` of the scan are grouped by a
+//! `DirectTable` instead of the hash table.
+
+use std::rc::Rc;
+use crate::error::Result;
+use crate::exec::aggregate::{AggregateFunction, AggregateKind};
+use crate::exec::cast::cast_value;
+use crate::exec::group_by::Groups;
+use crate::exec::{ExecutionContext, SinkResult, SinkState};
+use crate::qir::checker::Checker;
+use crate::qir::expr::Expr;
+use crate::qir::printer::{strings, Printer};
+use crate::qir::{resolve_columns, Column, DataType, MinMax, Operator, Sink};
+use crate::vector::{DataChunk, Value, Vector, VECTOR_SIZE};
+
+/// an aggregate column, `"name": kind(arg)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    pub name: String,
+    pub kind: AggregateKind,
+    pub arg: Expr,
+}
+
+impl Aggregate {
+    /// the output column, an error when the function does not support the argument type
+    pub fn column(&self, grouped: bool) -> Result<Column> {
+        let data_type = self.kind.result_type(&self.arg.data_type)?;
+        // every group has a row, but without keys there is one group even without rows
+        let nullable = match self.kind {
+            AggregateKind::Count | AggregateKind::CountDistinct => false,
+            _ => self.arg.nullable || !grouped,
+        };
+        Ok(Column { name: self.name.clone(), data_type, nullable })
+    }
+}
+
+pub struct HashGroupBy {
+    pub input: Rc<dyn Operator>,
+    pub keys: Vec<String>,
+    pub aggregates: Vec<Aggregate>,
+    output: Vec<String>,
+}
+
+impl HashGroupBy {
+    pub fn new(input: Rc<dyn Operator>, keys: Vec<String>, aggregates: Vec<Aggregate>) -> Self {
+        let output = keys.iter().cloned().chain(aggregates.iter().map(|a| a.name.clone())).collect();
+        HashGroupBy { input, keys, aggregates, output }
+    }
+
+    /// the ranges of the keys when all of them are known, see `Operator::min_max`
+    pub fn bounds(&self, ctx: &ExecutionContext) -> Result<Option<Vec<(i64, i64)>>> {
+        let integer = |value: Value| match cast_value(&value, &DataType::I64) {
+            Ok(Value::I64(v)) if value.data_type().is_some_and(|t| t.is_integer()) => Some(v),
+            _ => None,
+        };
+        let mut bounds = vec![];
+        for key in &self.keys {
+            let Some(minmax) = self.input.min_max(key) else { return Ok(None) };
+            match (integer(minmax.min.resolve(ctx)?), integer(minmax.max.resolve(ctx)?)) {
+                (Some(min), Some(max)) => bounds.push((min, max)),
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(bounds))
+    }
+}
+
+impl Operator for HashGroupBy {
+    fn name(&self) -> &'static str {
+        "hash_group_by"
+    }
+
+    fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", printer.var(&self.input));
+        printer.arg("group_by", strings(&self.keys));
+        printer.entries("aggregates", self.aggregates.iter().map(|a| (a.name.clone(), format!("{}({})", a.kind.name(), a.arg))));
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        let input = checker.input(&self.input)?;
+        let mut output = checker.resolve(&input, &self.keys);
+        for (i, aggregate) in self.aggregates.iter().enumerate() {
+            if self.output[..self.keys.len() + i].contains(&aggregate.name) {
+                checker.error(format!("duplicate column `{}`", aggregate.name));
+            }
+            checker.expr(&aggregate.arg, &input, &aggregate.arg.data_type);
+            match aggregate.column(!self.keys.is_empty()) {
+                Ok(column) => output.iter_mut().for_each(|o| o.push(column.clone())),
+                Err(e) => {
+                    checker.error(format!("`{}`: {e}", aggregate.name));
+                    output = None;
+                }
+            }
+        }
+        output
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        self.keys.iter().any(|k| k == column).then(|| self.input.min_max(column))?
+    }
+}
+
+impl Sink for HashGroupBy {
+    fn open(&self, ctx: &ExecutionContext) -> Result<Box<dyn SinkState>> {
+        let keys = resolve_columns(self.input.output(), &self.keys)?;
+        let schema = ctx.schema(&self.input)?;
+        let types: Vec<_> = keys.iter().map(|&i| schema[i].data_type.clone()).collect();
+        let functions = self.aggregates.iter()
+            .map(|a| a.kind.create(&a.arg.data_type))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Box::new(HashGroupByState {
+            names: self.input.output().to_vec(),
+            keys,
+            args: self.aggregates.iter().map(|a| a.arg.clone()).collect(),
+            table: Groups::new(&types, self.bounds(ctx)?.as_deref()),
+            functions,
+        }))
+    }
+}
+
+struct HashGroupByState {
+    /// the columns of the input chunks
+    names: Vec<String>,
+    keys: Vec<usize>,
+    args: Vec<Expr>,
+    table: Groups,
+    functions: Vec<Box<dyn AggregateFunction>>,
+}
+
+impl SinkState for HashGroupByState {
+    fn consume(&mut self, _ctx: &ExecutionContext, chunk: DataChunk) -> Result<()> {
+        let keys: Vec<&Vector> = self.keys.iter().map(|&i| chunk.column(i)).collect();
+        let groups = self.table.find_or_insert(&keys, chunk.len());
+        for (function, arg) in self.functions.iter_mut().zip(&self.args) {
+            let input = arg.evaluate(&chunk, &self.names)?;
+            function.resize(self.table.len());
+            function.update(&groups, &input)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>, _ctx: &ExecutionContext) -> Result<SinkResult> {
+        let groups = self.table.len();
+        let mut columns = self.table.finish();
+        for mut function in self.functions {
+            function.resize(groups);
+            columns.push(function.finish()?);
+        }
+        let result = DataChunk::new(columns);
+        let chunks: Vec<DataChunk> = (0..groups).step_by(VECTOR_SIZE)
+            .map(|offset| result.slice(offset, VECTOR_SIZE.min(groups - offset)))
+            .collect();
+        Ok(Rc::new(chunks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::Interpreter;
+    use crate::qir::Topology;
+
+    const ORDERS: &str = r#"
+pipeline1: Pipeline =
+    v1 = table_scan :table = "sale_orders"
+            :columns =
+                "region": { data_type: "string", nullable: false },
+                "customer_id": { data_type: "i32" },
+                "freight": { data_type: "f64", nullable: false }
+    v2 = hash_group_by :input = v1
+            :group_by = GROUP_BY
+            :aggregates =
+                "count": count($in.customer_id),
+                "customers": count_distinct($in.customer_id),
+                "total": sum($in.freight * 2),
+                "max_id": max($in.customer_id),
+                "first": first($in.customer_id)
+"#;
+
+    fn run(group_by: &str) -> Vec<Vec<Value>> {
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("sale_orders", DataChunk::new(vec![
+            Vector::from(vec!["north", "south", "north", "north", "south"]),
+            Vector::from(vec![Some(1i32), None, Some(2), Some(1), Some(3)]),
+            Vector::from(vec![1.0f64, 2.0, 3.0, 4.0, 5.0]),
+        ]));
+        let topology = Topology::parse(&ORDERS.replace("GROUP_BY", group_by)).unwrap();
+        let result = Interpreter::new(ctx).run(&topology).unwrap();
+        let chunks = result.downcast::<Vec<DataChunk>>().unwrap();
+        let mut rows: Vec<Vec<Value>> = chunks.iter().flat_map(|c| (0..c.len()).map(|i| c.row(i))).collect();
+        rows.sort_by_key(|row| row[0].to_string());
+        rows
+    }
+
+    #[test]
+    fn test_group_by() {
+        assert_eq!(run(r#"[ "region" ]"#), vec![
+            vec!["north".into(), Value::I64(3), Value::I64(2), Value::F64(16.0), Value::I32(2), Value::I32(1)],
+            vec!["south".into(), Value::I64(1), Value::I64(1), Value::F64(14.0), Value::I32(3), Value::Null],
+        ]);
+        assert_eq!(run("[]"), vec![vec![Value::I64(4), Value::I64(3), Value::F64(30.0), Value::I32(3), Value::I32(1)]]);
+    }
+
+    #[test]
+    fn test_direct_group_by() {
+        // the range of the keys is computed by the first pipeline
+        let source = r#"
+pipeline1: Pipeline =
+    v1 = table_scan :table = "sale_orders" :columns = "customer_id": { data_type: "i32" }
+    v2 = hash_group_by :input = v1
+            :group_by = []
+            :aggregates = "min": min($in.customer_id), "max": max($in.customer_id)
+pipeline2: Pipeline =
+    v1 = table_scan :table = "sale_orders"
+            :columns =
+                "customer_id": { data_type: "i32" },
+                "freight": { data_type: "f64", nullable: false }
+            :minmaxes = "customer_id": [pipeline1.v2.min, pipeline1.v2.max], "freight": [1, 5]
+    v2 = hash_group_by :input = v1 :group_by = [ "customer_id" ] :aggregates = "total": sum($in.freight)
+"#;
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("sale_orders", DataChunk::new(vec![
+            Vector::from(vec![Some(1i32), None, Some(2), Some(1), Some(3)]),
+            Vector::from(vec![1.0f64, 2.0, 3.0, 4.0, 5.0]),
+        ]));
+        let topology = Topology::parse(source).unwrap();
+        let printed = topology.to_string();
+        assert!(printed.contains(r#":minmaxes =
+                "customer_id": [pipeline1.v2.min, pipeline1.v2.max],
+                "freight": [1.0, 5.0]"#), "{printed}");
+        assert_eq!(Topology::parse(&printed).unwrap().to_string(), printed);
+
+        let result = Interpreter::new(ctx).run(&topology).unwrap();
+        let chunks = result.downcast::<Vec<DataChunk>>().unwrap();
+        assert_eq!(chunks[0].column(0), &Vector::from(vec![Some(1i32), None, Some(2), Some(3)]));
+        assert_eq!(chunks[0].column(1), &Vector::from(vec![Some(5.0f64), Some(2.0), Some(3.0), Some(5.0)]));
+
+        let group_by = |key: &str| HashGroupBy::new(topology.main.source.clone(), vec![key.to_string()], vec![]);
+        let ctx = ExecutionContext::new();
+        assert_eq!(group_by("freight").bounds(&ctx).unwrap(), None);
+        assert!(group_by("customer_id").bounds(&ctx).is_err(), "the first pipeline has not run");
+    }
+
+    #[test]
+    fn test_check_group_by() {
+        let topology = Topology::parse(&ORDERS.replace("GROUP_BY", r#"[ "region" ]"#)).unwrap();
+        let schemas = topology.check().unwrap();
+        let output = schemas.get(&topology.main.sink).unwrap();
+        assert_eq!(output.iter().map(|c| &c.data_type).collect::<Vec<_>>(),
+            vec![&DataType::String, &DataType::I64, &DataType::I64, &DataType::F64, &DataType::I32, &DataType::I32]);
+        assert!(!output[1].nullable && !output[3].nullable);
+
+        let err = Topology::parse(&ORDERS.replace("GROUP_BY", "[]").replace("max($in.customer_id)", "sum($in.region)"))
+            .err().unwrap().to_string();
+        assert!(err.contains("unsupported: sum(String)"), "{err}");
+    }
+
+    #[test]
+    fn test_print_group_by() {
+        let topology = Topology::parse(&ORDERS.replace("GROUP_BY", r#"[ "region" ]"#)).unwrap();
+        let printed = topology.to_string();
+        assert!(printed.contains("\"total\": sum($in.freight * 2.0)"), "{printed}");
+        assert_eq!(Topology::parse(&printed).unwrap().to_string(), printed);
+    }
+}
@@ -0,0 +1,165 @@
+//! Arrow ipc files: an `arrow_scan` source reading the columns of a file instead of a registered
+//! table, and a `write_arrow` sink writing the chunks of a pipeline to a file.
+//!
+//! ```text
+//! pipeline1: Pipeline =
+//!     v1 = arrow_scan :path = "users.arrow"
+//!             :columns =
+//!                 "id": { data_type: "i32", nullable: false },
+//!                 "email": { data_type: "string" }
+//!     v2 = write_arrow :input = v1 :path = "emails.arrow"
+//! ```
+//!
+//! The arguments of an `arrow_scan` are those of a `parquet_scan`, `:columns` lists the columns of
+//! the file the plan uses with their types in the file (see `ArrowFile::schema`). The vectors of
+//! the scan view the memory mapped file, see `exec::ipc`. The result of a `write_arrow` is the
+//! number of rows written, a `usize`.
+
+use std::path::PathBuf;
+use std::rc::Rc;
+use crate::error::{Error, Result};
+use crate::exec::ipc::{ArrowFile, ArrowWriter};
+use crate::exec::{ExecutionContext, SinkResult, SinkState, SourceReader};
+use crate::qir::checker::Checker;
+use crate::qir::printer::Printer;
+use crate::qir::{lexer, Column, MinMax, Operator, Scan, Sink, Source};
+use crate::vector::DataChunk;
+
+pub struct ArrowScan {
+    pub path: PathBuf,
+    /// the columns, output, minmaxes and ordering of the scan, `table.name` is the path
+    pub scan: Scan,
+}
+
+impl Operator for ArrowScan {
+    fn name(&self) -> &'static str {
+        "arrow_scan"
+    }
+
+    fn output(&self) -> &[String] {
+        &self.scan.output
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("path", lexer::quote(&self.path.to_string_lossy()));
+        self.scan.print_columns(printer);
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        self.scan.check(checker)
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        self.scan.min_max(column)
+    }
+
+    fn ordering(&self) -> Vec<String> {
+        self.scan.ordering()
+    }
+}
+
+impl Source for ArrowScan {
+    fn open(&self, _ctx: &ExecutionContext) -> Result<Box<dyn SourceReader>> {
+        let columns = self.scan.output.iter()
+            .map(|name| self.scan.table.columns.iter().find(|c| c.name == *name).cloned().ok_or_else(|| Error::ColumnNotFound(name.clone())))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Box::new(ArrowFile::open(&self.path)?.read(&columns)?))
+    }
+}
+
+pub struct ArrowWrite {
+    pub input: Rc<dyn Operator>,
+    pub path: PathBuf,
+}
+
+impl Operator for ArrowWrite {
+    fn name(&self) -> &'static str {
+        "write_arrow"
+    }
+
+    fn output(&self) -> &[String] {
+        self.input.output()
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", printer.var(&self.input));
+        printer.arg("path", lexer::quote(&self.path.to_string_lossy()));
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        checker.input(&self.input)
+    }
+}
+
+impl Sink for ArrowWrite {
+    fn open(&self, ctx: &ExecutionContext) -> Result<Box<dyn SinkState>> {
+        Ok(Box::new(ArrowWriteState { writer: ArrowWriter::create(&self.path, &ctx.schema(&self.input)?)? }))
+    }
+}
+
+struct ArrowWriteState {
+    writer: ArrowWriter,
+}
+
+impl SinkState for ArrowWriteState {
+    fn consume(&mut self, _ctx: &ExecutionContext, chunk: DataChunk) -> Result<()> {
+        self.writer.write(&chunk)
+    }
+
+    fn finish(self: Box<Self>, _ctx: &ExecutionContext) -> Result<SinkResult> {
+        Ok(Rc::new(self.writer.finish()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exec::{ExecutionContext, Interpreter};
+    use crate::qir::Topology;
+    use crate::vector::{DataChunk, Value, Vector};
+
+    #[test]
+    fn test_write_and_scan_arrow() {
+        let path = std::env::temp_dir().join(format!("dataframe-{}-scan.arrow", std::process::id()));
+        let write = format!(r#"
+pipeline1: Pipeline =
+    v1 = table_scan :table = "users"
+            :columns =
+                "id": {{ data_type: "i32", nullable: false }},
+                "email": {{ data_type: "string" }}
+    v2 = filter :input = v1
+            :expr = $in.id > 1
+            :projection = [ "email", "id" ]
+    v3 = write_arrow :input = v2 :path = "{}"
+"#, path.to_string_lossy());
+        let topology = Topology::parse(&write).unwrap();
+        let printed = topology.to_string();
+        assert!(printed.contains(&format!("v3 = write_arrow :input = v2\n            :path = \"{}\"", path.to_string_lossy())), "{printed}");
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("users", DataChunk::new(vec![
+            Vector::from(vec![1i32, 2, 3]),
+            Vector::from(vec![Some("alice@example.com"), Some("bob@example.com"), None]),
+        ]));
+        let written = Interpreter::new(ctx).run(&topology).unwrap();
+        assert_eq!(*written.downcast::<usize>().unwrap(), 2);
+
+        let scan = format!(r#"
+pipeline1: Pipeline =
+    v1 = arrow_scan :path = "{}"
+            :columns =
+                "email": {{ data_type: "string" }},
+                "id": {{ data_type: "i32", nullable: false }}
+            :output = [ "id", "email" ]
+    v2 = identity :input = v1
+"#, path.to_string_lossy());
+        let topology = Topology::parse(&scan).unwrap();
+        assert_eq!(Topology::parse(&topology.to_string()).unwrap().to_string(), topology.to_string());
+        let result = Interpreter::new(ExecutionContext::new()).run(&topology).unwrap();
+        let chunks = result.downcast::<Vec<DataChunk>>().unwrap();
+        let rows: Vec<_> = chunks.iter().flat_map(|c| (0..c.len()).map(|i| c.row(i))).collect();
+        assert_eq!(rows, vec![
+            vec![Value::I32(2), Value::String("bob@example.com".into())],
+            vec![Value::I32(3), Value::Null],
+        ]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
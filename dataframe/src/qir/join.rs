@@ -0,0 +1,722 @@
+//! Hash joins: a `build_hash` sink collects the build side into a `JoinHashTable`, the
+//! `hash_join` operators of a later pipeline probe it with every chunk of their input.
+//!
+//! ```text
+//! ht1 = build_hash :input = v2 :key = "customer_id" :value = "name"
+//! ...
+//! v3 = hash_left_join :input = v2 :ht = pipeline1.ht1
+//!         :key = $in.customer_id
+//!         :projection = [ $in.customer_id, $ht.name, $in.freight ]
+//! ```
+//!
+//! A build side larger than the memory budget of the execution context becomes a grace hash
+//! join (see `exec::grace`): the `build_hash` spills its rows in partitions, the join spills its
+//! input in the same partitions and joins the pairs of partitions once its input is exhausted.
+//!
+//! The `build_hash` also collects the key ranges and a bloom filter of its keys, which inner and
+//! semi joins push down to the scan of their pipeline (see `exec::runtime_filter`).
+
+use std::cell::Cell;
+use std::rc::Rc;
+use crate::error::{Error, Result};
+use std::path::PathBuf;
+use crate::exec::grace::{GraceTable, Partition, PartitionReader, Partitioner, MAX_LEVEL};
+use crate::exec::join::JoinHashTable;
+use crate::exec::runtime_filter::{RuntimeFilter, RuntimeFilterBuilder};
+use crate::exec::spill::memory_size;
+use crate::exec::{ExecutionContext, SinkResult, SinkState, SourceReader};
+use crate::qir::checker::Checker;
+use crate::qir::printer::{name, strings, Printer};
+use crate::qir::{resolve_columns, Column, DataType, MinMax, Operator, Sink};
+use crate::vector::{DataChunk, SelectionVector, Vector};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinType {
+    /// the pairs of matching rows
+    Inner,
+    /// the pairs of matching rows, and every input row without a match with nulls for the
+    /// hash table columns
+    Left,
+    /// the input rows with a match
+    Semi,
+    /// the input rows without a match, including those with a null key
+    Anti,
+}
+
+impl JoinType {
+    pub const ALL: [JoinType; 4] = [JoinType::Inner, JoinType::Left, JoinType::Semi, JoinType::Anti];
+
+    /// the operator name in the text format
+    pub fn operator(self) -> &'static str {
+        match self {
+            JoinType::Inner => "hash_join",
+            JoinType::Left => "hash_left_join",
+            JoinType::Semi => "hash_semi_join",
+            JoinType::Anti => "hash_anti_join",
+        }
+    }
+}
+
+/// a column of the output of a join, `$in.name` or `$ht.name`
+#[derive(Debug, Clone, PartialEq)]
+pub enum JoinColumn {
+    /// a column of the probe input
+    Probe(String),
+    /// a column of the hash table
+    Build(String),
+}
+
+impl JoinColumn {
+    pub fn name(&self) -> &str {
+        match self {
+            JoinColumn::Probe(name) | JoinColumn::Build(name) => name,
+        }
+    }
+}
+
+impl std::fmt::Display for JoinColumn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinColumn::Probe(column) => write!(f, "$in.{}", name(column)),
+            JoinColumn::Build(column) => write!(f, "$ht.{}", name(column)),
+        }
+    }
+}
+
+/// `"a"` for a single value, `[ "a", "b" ]` otherwise
+fn one_or_many(values: &[String]) -> String {
+    match values {
+        [value] => crate::qir::lexer::quote(value),
+        _ => strings(values),
+    }
+}
+
+/// a sink building the hash table of a join, its output is the key columns followed by the
+/// value columns. without a hash join probing it, the sink only collects the rows of the merge
+/// joins reading it, see `qir::merge_join`.
+pub struct BuildHash {
+    pub input: Rc<dyn Operator>,
+    pub keys: Vec<String>,
+    pub values: Vec<String>,
+    output: Vec<String>,
+    /// whether a hash join probes the table
+    probed: Cell<bool>,
+    /// whether a merge join reads the rows, which then stay in memory in the order of the input
+    merged: Cell<bool>,
+}
+
+impl BuildHash {
+    pub fn new(input: Rc<dyn Operator>, keys: Vec<String>, values: Vec<String>) -> Self {
+        let output = keys.iter().chain(&values).cloned().collect();
+        BuildHash { input, keys, values, output, probed: Cell::new(false), merged: Cell::new(false) }
+    }
+
+    /// keep the rows in the order of the input for a merge join
+    pub fn merge(&self) {
+        self.merged.set(true);
+    }
+}
+
+impl Operator for BuildHash {
+    fn name(&self) -> &'static str {
+        "build_hash"
+    }
+
+    fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", printer.var(&self.input));
+        printer.arg("key", one_or_many(&self.keys));
+        if !self.values.is_empty() {
+            printer.arg("value", one_or_many(&self.values));
+        }
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        let input = checker.input(&self.input)?;
+        if self.keys.is_empty() {
+            checker.error("a hash table needs at least one key");
+        }
+        checker.resolve(&input, &self.output)
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        self.output.iter().any(|c| c == column).then(|| self.input.min_max(column))?
+    }
+
+    /// the order of the input, which the rows keep once `merge` is called
+    fn ordering(&self) -> Vec<String> {
+        self.input.ordering().into_iter().take_while(|c| self.output.contains(c)).collect()
+    }
+}
+
+impl Sink for BuildHash {
+    fn open(&self, ctx: &ExecutionContext) -> Result<Box<dyn SinkState>> {
+        let projection = resolve_columns(self.input.output(), &self.output)?;
+        let schema = ctx.schema(&self.input)?;
+        let columns: Vec<Column> = projection.iter().map(|&i| schema[i].clone()).collect();
+        let types: Vec<DataType> = columns.iter().map(|c| c.data_type.clone()).collect();
+        let filter = RuntimeFilterBuilder::new(&types[..self.keys.len()], ctx.memory_budget())?;
+        Ok(Box::new(BuildHashState {
+            projection, types, keys: self.keys.len(), probed: self.probed.get(), merged: self.merged.get(), chunks: vec![], buffered: 0, spilled: None, filter,
+        }))
+    }
+}
+
+/// the rows of `chunks` in one chunk, an empty chunk of `types` without chunks
+pub(crate) fn concat_or_empty(chunks: &[DataChunk], types: &[DataType]) -> Result<DataChunk> {
+    if chunks.is_empty() {
+        Ok(DataChunk::new(types.iter().map(|t| Vector::new_null(t, 0)).collect()))
+    } else {
+        DataChunk::concat(chunks)
+    }
+}
+
+/// collects the projected chunks, the result is a `JoinHashTable`, or a `GraceTable` once the
+/// chunks exceed the memory budget
+struct BuildHashState {
+    projection: Vec<usize>,
+    /// the types of the projected columns
+    types: Vec<DataType>,
+    keys: usize,
+    /// whether to build a hash table, and whether to keep the rows in memory for a merge join
+    probed: bool,
+    merged: bool,
+    chunks: Vec<DataChunk>,
+    /// the memory size of `chunks`
+    buffered: usize,
+    spilled: Option<Partitioner>,
+    filter: RuntimeFilterBuilder,
+}
+
+impl SinkState for BuildHashState {
+    fn consume(&mut self, ctx: &ExecutionContext, chunk: DataChunk) -> Result<()> {
+        let chunk = chunk.project(&self.projection);
+        if !self.probed {
+            self.chunks.push(chunk);
+            return Ok(());
+        }
+        self.filter.push(&chunk.columns[..self.keys].iter().collect::<Vec<_>>(), chunk.len())?;
+        if let Some(partitions) = &mut self.spilled {
+            return partitions.push(&chunk);
+        }
+        self.buffered += chunk.columns.iter().map(memory_size).sum::<usize>();
+        self.chunks.push(chunk);
+        if !self.merged && ctx.memory_budget().is_some_and(|budget| self.buffered > budget) {
+            let mut partitions = Partitioner::new(&ctx.spill_dir(), (0..self.keys).collect(), 0)?;
+            for chunk in self.chunks.drain(..) {
+                partitions.push(&chunk)?;
+            }
+            self.spilled = Some(partitions);
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>, _ctx: &ExecutionContext) -> Result<SinkResult> {
+        if !self.probed {
+            return Ok(Rc::new(self.chunks));
+        }
+        let filter = self.filter.finish()?;
+        if let Some(partitions) = self.spilled {
+            return Ok(Rc::new(GraceTable { partitions: partitions.finish()?, filter: Rc::new(filter) }));
+        }
+        let data = concat_or_empty(&self.chunks, &self.types)?;
+        Ok(Rc::new(JoinHashTable::build(data, self.keys)?.with_filter(filter)))
+    }
+}
+
+/// probes the hash table `ht` built by a parent pipeline with the `keys` of each input chunk. the
+/// output has no ordering: a build side that spills is joined one partition after the other
+pub struct HashJoin {
+    pub input: Rc<dyn Operator>,
+    pub ht: Rc<BuildHash>,
+    pub join_type: JoinType,
+    /// the key columns of the input, compared with the keys of `ht` in order
+    pub keys: Vec<String>,
+    pub projection: Vec<JoinColumn>,
+    output: Vec<String>,
+}
+
+impl HashJoin {
+    pub fn new(input: Rc<dyn Operator>, ht: Rc<BuildHash>, join_type: JoinType, keys: Vec<String>, projection: Vec<JoinColumn>) -> Self {
+        let output = projection.iter().map(|c| c.name().to_string()).collect();
+        ht.probed.set(true);
+        HashJoin { input, ht, join_type, keys, projection, output }
+    }
+}
+
+impl Operator for HashJoin {
+    fn name(&self) -> &'static str {
+        self.join_type.operator()
+    }
+
+    fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    fn execute(&self, ctx: &ExecutionContext, input: DataChunk) -> Result<DataChunk> {
+        // the plan, and the spilled input of a build side that spilled, are kept between the chunks
+        let mut state = match ctx.take_state(self) {
+            Some(state) => state.downcast::<HashJoinState>().expect("the state of a hash join"),
+            None => Box::new(HashJoinState { plan: self.plan()?, probe: None }),
+        };
+        let output = match ctx.sink_result(&self.ht)?.downcast::<JoinHashTable>() {
+            Ok(table) => state.plan.join(&table, &input),
+            Err(result) if result.is::<GraceTable>() => {
+                // the build side spilled, the input is spilled in the same partitions and joined in `finish`
+                let mut probe = match state.probe.take() {
+                    Some(probe) => probe,
+                    None => Partitioner::new(&ctx.spill_dir(), state.plan.keys.clone(), 0)?,
+                };
+                probe.push(&input)?;
+                state.probe = Some(probe);
+                DataChunk::empty(0)
+            }
+            Err(_) => return Err(Error::TypeMismatch { expected: "JoinHashTable".to_string(), found: "another sink result".to_string() }),
+        };
+        ctx.set_state(self, state);
+        Ok(output)
+    }
+
+    fn finish(&self, ctx: &ExecutionContext) -> Result<Option<Box<dyn SourceReader>>> {
+        let Some(state) = ctx.take_state(self) else { return Ok(None) };
+        let HashJoinState { plan, probe } = *state.downcast::<HashJoinState>().expect("the state of a hash join");
+        let Some(probe) = probe else { return Ok(None) };
+        let probe = probe.finish()?;
+        let table = ctx.sink_result(&self.ht)?.downcast::<GraceTable>()
+            .map_err(|_| Error::TypeMismatch { expected: "GraceTable".to_string(), found: "another sink result".to_string() })?;
+        let types = |columns: Vec<Column>| columns.into_iter().map(|c| c.data_type).collect();
+        Ok(Some(Box::new(GraceJoinReader {
+            plan,
+            build_keys: (0..self.ht.keys.len()).collect(),
+            build_types: types(ctx.schema(&self.ht)?),
+            probe_types: types(ctx.schema(&self.input)?),
+            budget: ctx.memory_budget(),
+            spill_dir: ctx.spill_dir(),
+            pending: table.partitions.iter().cloned().zip(probe).rev().collect(),
+            current: None,
+        })))
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", printer.var(&self.input));
+        printer.arg("ht", printer.var(&(self.ht.clone() as Rc<dyn Operator>)));
+        let keys: Vec<String> = self.keys.iter().map(|k| JoinColumn::Probe(k.clone()).to_string()).collect();
+        match keys.as_slice() {
+            [key] => printer.arg("key", key),
+            _ => printer.arg("key", format!("[ {} ]", keys.join(", "))),
+        }
+        let projection: Vec<String> = self.projection.iter().map(|c| c.to_string()).collect();
+        printer.arg("projection", format!("[ {} ]", projection.join(", ")));
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        let input = checker.input(&self.input);
+        let ht = checker.schema(&(self.ht.clone() as Rc<dyn Operator>));
+        let (input, ht) = (input?, ht?);
+
+        if self.keys.len() != self.ht.keys.len() {
+            checker.error(format!("{} keys are compared with the {} keys of the hash table", self.keys.len(), self.ht.keys.len()));
+        }
+        if let Some(keys) = checker.resolve(&input, &self.keys) {
+            for (key, build) in keys.iter().zip(&ht) {
+                if key.data_type != build.data_type {
+                    checker.error(format!("the key `{}` is {:?}, but the key `{}` of the hash table is {:?}",
+                        key.name, key.data_type, build.name, build.data_type));
+                }
+            }
+        }
+
+        check_projection(checker, self.join_type, self.name(), &input, &ht, &self.projection, "$ht")
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        // the nulls of a left join do not widen the range
+        match self.projection.iter().find(|c| c.name() == column)? {
+            JoinColumn::Probe(name) => self.input.min_max(name),
+            JoinColumn::Build(name) => self.ht.min_max(name),
+        }
+    }
+
+    fn input_column(&self, column: &str) -> Option<&str> {
+        probe_column(&self.projection, column)
+    }
+
+    fn runtime_filter(&self, ctx: &ExecutionContext) -> Result<Option<(Rc<RuntimeFilter>, &[String])>> {
+        // the input rows without a match are in the output of left and anti joins
+        if !matches!(self.join_type, JoinType::Inner | JoinType::Semi) {
+            return Ok(None);
+        }
+        let result = ctx.sink_result(&self.ht)?;
+        let filter = match result.downcast_ref::<JoinHashTable>() {
+            Some(table) => table.filter(),
+            None => result.downcast_ref::<GraceTable>().map(|table| table.filter.clone()),
+        };
+        Ok(filter.map(|filter| (filter, self.keys.as_slice())))
+    }
+}
+
+/// the output columns of a join projecting `projection`, `build_var` is the variable of the
+/// build columns in the text format
+pub(crate) fn check_projection(checker: &mut Checker, join_type: JoinType, operator: &str, input: &[Column], build: &[Column],
+    projection: &[JoinColumn], build_var: &str) -> Option<Vec<Column>> {
+    let mut output = Vec::with_capacity(projection.len());
+    for (i, column) in projection.iter().enumerate() {
+        if projection[..i].iter().any(|c| c.name() == column.name()) {
+            checker.error(format!("duplicate column `{}`", column.name()));
+        }
+        let resolved = match column {
+            JoinColumn::Probe(name) => checker.resolve(input, std::slice::from_ref(name)),
+            JoinColumn::Build(column) if matches!(join_type, JoinType::Semi | JoinType::Anti) => {
+                checker.error(format!("a {operator} can only project columns of its input, found `{build_var}.{}`", name(column)));
+                None
+            }
+            JoinColumn::Build(name) => checker.resolve(build, std::slice::from_ref(name)).map(|mut columns| {
+                columns[0].nullable |= join_type == JoinType::Left;
+                columns
+            }),
+        };
+        output.extend(resolved.into_iter().flatten());
+    }
+    (output.len() == projection.len()).then_some(output)
+}
+
+/// the leading columns of the ordering of the input of a join that are in its output
+pub(crate) fn probe_ordering(input: &dyn Operator, projection: &[JoinColumn]) -> Vec<String> {
+    input.ordering().into_iter().take_while(|c| projection.contains(&JoinColumn::Probe(c.clone()))).collect()
+}
+
+/// the input column of output column `column` of a join
+pub(crate) fn probe_column<'a>(projection: &'a [JoinColumn], column: &str) -> Option<&'a str> {
+    match projection.iter().find(|c| c.name() == column)? {
+        JoinColumn::Probe(name) => Some(name),
+        JoinColumn::Build(_) => None,
+    }
+}
+
+fn position(names: &[String], name: &str) -> Result<usize> {
+    names.iter().position(|n| n == name).ok_or_else(|| Error::ColumnNotFound(name.to_string()))
+}
+
+impl HashJoin {
+    fn plan(&self) -> Result<JoinPlan> {
+        JoinPlan::new(self.join_type, self.input.output(), self.ht.output(), &self.keys, &self.projection)
+    }
+}
+
+/// the state of a hash join between its chunks
+struct HashJoinState {
+    plan: JoinPlan,
+    /// the partitions of the input of a build side that spilled
+    probe: Option<Partitioner>,
+}
+
+/// a column of the output of a join, in the probe input or in the hash table
+#[derive(Debug, Clone, Copy)]
+enum Side {
+    Probe(usize),
+    Build(usize),
+}
+
+/// the rows of a join: the (probe row, build row) pairs of an inner or left join, or whether
+/// each probe row has a match for a semi or anti join
+pub(crate) enum JoinRows {
+    Pairs(Vec<u32>, Vec<u32>),
+    Found(Vec<bool>),
+}
+
+/// the columns of a join resolved to their positions
+#[derive(Debug, Clone)]
+pub(crate) struct JoinPlan {
+    pub join_type: JoinType,
+    /// the key columns of the probe input
+    pub keys: Vec<usize>,
+    projection: Vec<Side>,
+}
+
+impl JoinPlan {
+    /// the plan of a join of an input with columns `probe` and a build side with columns `build`
+    pub fn new(join_type: JoinType, probe: &[String], build: &[String], keys: &[String], projection: &[JoinColumn]) -> Result<Self> {
+        let projection = projection.iter()
+            .map(|column| Ok(match column {
+                JoinColumn::Build(name) if !matches!(join_type, JoinType::Semi | JoinType::Anti) => Side::Build(position(build, name)?),
+                column => Side::Probe(position(probe, column.name())?),
+            }))
+            .collect::<Result<_>>()?;
+        Ok(JoinPlan { join_type, keys: resolve_columns(probe, keys)?, projection })
+    }
+
+    fn join(&self, table: &JoinHashTable, input: &DataChunk) -> DataChunk {
+        let keys: Vec<&Vector> = self.keys.iter().map(|&i| input.column(i)).collect();
+        let rows = match self.join_type {
+            JoinType::Semi | JoinType::Anti => JoinRows::Found(table.contains(&keys, input.len())),
+            JoinType::Inner => {
+                let (probe_rows, build_rows) = table.matches(&keys, input.len(), false);
+                JoinRows::Pairs(probe_rows, build_rows)
+            }
+            JoinType::Left => {
+                let (probe_rows, build_rows) = table.matches(&keys, input.len(), true);
+                JoinRows::Pairs(probe_rows, build_rows)
+            }
+        };
+        self.output(input, table.data(), rows)
+    }
+
+    /// the output columns of the joined `rows` of `input` and the rows `build` of the build side
+    pub fn output(&self, input: &DataChunk, build: &DataChunk, rows: JoinRows) -> DataChunk {
+        let (probe_rows, build_rows) = match rows {
+            JoinRows::Found(found) => {
+                let keep = self.join_type == JoinType::Semi;
+                let selection: SelectionVector = (0..input.len() as u32).filter(|&i| found[i as usize] == keep).collect::<Vec<_>>().into();
+                let probe: Vec<usize> = self.projection.iter().filter_map(|side| match side {
+                    Side::Probe(i) => Some(*i),
+                    Side::Build(_) => None,
+                }).collect();
+                return input.project(&probe).select(&selection);
+            }
+            JoinRows::Pairs(probe_rows, build_rows) => (probe_rows, build_rows),
+        };
+        let columns: Vec<Vector> = self.projection.iter()
+            .map(|side| match *side {
+                Side::Probe(i) => input.column(i).take(&probe_rows),
+                Side::Build(i) => build.column(i).take(&build_rows),
+            })
+            .collect();
+        if columns.is_empty() { DataChunk::empty(probe_rows.len()) } else { DataChunk::new(columns) }
+    }
+}
+
+/// joins the pairs of spilled partitions of a grace hash join one at a time, splitting the
+/// build partitions larger than the memory budget again
+struct GraceJoinReader {
+    plan: JoinPlan,
+    build_keys: Vec<usize>,
+    build_types: Vec<DataType>,
+    probe_types: Vec<DataType>,
+    budget: Option<usize>,
+    spill_dir: PathBuf,
+    /// the (build, probe) pairs left, the next one last
+    pending: Vec<(Partition, Partition)>,
+    /// the table of the current build partition and the rest of its probe partition
+    current: Option<(JoinHashTable, PartitionReader)>,
+}
+
+impl SourceReader for GraceJoinReader {
+    fn next_chunk(&mut self) -> Result<Option<DataChunk>> {
+        loop {
+            if let Some((table, probe)) = &mut self.current {
+                match probe.next_chunk(&self.probe_types)? {
+                    Some(chunk) => {
+                        let output = self.plan.join(table, &chunk);
+                        if !output.is_empty() {
+                            return Ok(Some(output));
+                        }
+                        continue;
+                    }
+                    None => self.current = None,
+                }
+            }
+
+            let Some((build, probe)) = self.pending.pop() else { return Ok(None) };
+            let unmatched = matches!(self.plan.join_type, JoinType::Left | JoinType::Anti);
+            if probe.rows == 0 || (build.rows == 0 && !unmatched) {
+                continue;
+            }
+            if self.budget.is_some_and(|budget| build.bytes > budget) && build.level < MAX_LEVEL {
+                let builds = build.split(&self.build_types, &self.build_keys, &self.spill_dir)?;
+                let probes = probe.split(&self.probe_types, &self.plan.keys, &self.spill_dir)?;
+                self.pending.extend(builds.into_iter().zip(probes).rev());
+                continue;
+            }
+            let data = concat_or_empty(&build.read_all(&self.build_types)?, &self.build_types)?;
+            self.current = Some((JoinHashTable::build(data, self.build_keys.len())?, probe.reader()?));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use crate::exec::runtime_filter::ScanFilter;
+    use crate::exec::Interpreter;
+    use crate::qir::{DataType, Topology};
+    use crate::vector::{Value, VECTOR_SIZE};
+
+    const CUSTOMERS: &str = r#"
+pipeline1: Pipeline =
+    v1 = table_scan :table = "customers"
+            :columns =
+                "customer_id": { data_type: "i32", nullable: false },
+                "region": { data_type: "string", nullable: false },
+                "name": { data_type: "string" }
+    ht1 = build_hash :input = v1 :key = [ "customer_id", "region" ] :value = "name"
+
+pipeline2: Pipeline =
+    v1 = table_scan :table = "sale_orders"
+            :columns =
+                "order_id": { data_type: "i64", nullable: false },
+                "customer_id": { data_type: "i32" },
+                "region": { data_type: "string", nullable: false },
+                "freight": { data_type: "f64", nullable: false }
+    v2 = JOIN :input = v1 :ht = pipeline1.ht1
+            :key = [ v1.customer_id, $in.region ]
+            :projection = PROJECTION
+    v3 = identity :input = v2
+"#;
+
+    fn context() -> ExecutionContext {
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("customers", DataChunk::new(vec![
+            Vector::from(vec![1i32, 2, 3, 1]),
+            Vector::from(vec!["north", "north", "south", "south"]),
+            Vector::from(vec![Some("alice"), Some("bob"), None, Some("alice2")]),
+        ]));
+        ctx.register_table("sale_orders", DataChunk::new(vec![
+            Vector::from(vec![10i64, 11, 12, 13, 14]),
+            Vector::from(vec![Some(1i32), Some(3), Some(1), None, Some(2)]),
+            Vector::from(vec!["south", "north", "north", "north", "north"]),
+            Vector::from(vec![1.0f64, 2.0, 3.0, 4.0, 5.0]),
+        ]));
+        ctx
+    }
+
+    fn run(join: &str, projection: &str) -> Vec<Vec<Value>> {
+        run_in(context(), join, projection)
+    }
+
+    fn run_in(ctx: ExecutionContext, join: &str, projection: &str) -> Vec<Vec<Value>> {
+        let text = CUSTOMERS.replace("JOIN", join).replace("PROJECTION", projection);
+        let topology = Topology::parse(&text).unwrap();
+        let result = Interpreter::new(ctx).run(&topology).unwrap();
+        let chunks = result.downcast::<Vec<DataChunk>>().unwrap();
+        chunks.iter().flat_map(|c| (0..c.len()).map(|i| c.row(i))).collect()
+    }
+
+    #[test]
+    fn test_join_types() {
+        let rows = run("hash_join", "[ $in.order_id, $ht.name ]");
+        assert_eq!(rows, vec![vec![Value::I64(10), "alice2".into()], vec![Value::I64(12), "alice".into()], vec![Value::I64(14), "bob".into()]]);
+
+        let rows = run("hash_left_join", "[ $in.order_id, $ht.name ]");
+        let names: Vec<Value> = rows.iter().map(|r| r[1].clone()).collect();
+        assert_eq!(names, vec!["alice2".into(), Value::Null, "alice".into(), Value::Null, "bob".into()]);
+
+        let ids = |rows: Vec<Vec<Value>>| rows.into_iter().map(|r| r[0].clone()).collect::<Vec<_>>();
+        assert_eq!(ids(run("hash_semi_join", "[ $in.order_id ]")), vec![Value::I64(10), Value::I64(12), Value::I64(14)]);
+        assert_eq!(ids(run("hash_anti_join", "[ $in.order_id ]")), vec![Value::I64(11), Value::I64(13)]);
+    }
+
+    fn sorted(mut rows: Vec<Vec<Value>>) -> Vec<Vec<Value>> {
+        rows.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        rows
+    }
+
+    #[test]
+    fn test_grace_join() {
+        // every build partition exceeds the budget and is split down to the last level
+        for join in JoinType::ALL {
+            let projection = if matches!(join, JoinType::Semi | JoinType::Anti) { "[ $in.order_id ]" } else { "[ $in.order_id, $ht.name ]" };
+            let mut ctx = context();
+            ctx.set_memory_budget(1);
+            assert_eq!(sorted(run_in(ctx, join.operator(), projection)), sorted(run(join.operator(), projection)), "{join:?}");
+        }
+
+        let rows = VECTOR_SIZE * 3;
+        let large = || large_context(rows);
+        let mut ctx = large();
+        ctx.set_memory_budget(64 * 1024);
+        let grace = sorted(run_in(ctx, "hash_left_join", "[ $in.order_id, $ht.name ]"));
+        // 1000 customers have two rows, their orders match both
+        assert!(grace.len() > rows);
+        assert_eq!(grace, sorted(run_in(large(), "hash_left_join", "[ $in.order_id, $ht.name ]")));
+
+        // so the order of the probe input is not kept
+        let text = CUSTOMERS.replace("JOIN", "hash_join").replace("PROJECTION", "[ $in.order_id ]")
+            .replace(r#""freight": { data_type: "f64", nullable: false }"#, r#""freight": { data_type: "f64", nullable: false }
+            :ordered_by = [ "order_id" ]"#);
+        let topology = Topology::parse(&text).unwrap();
+        assert!(topology.main.sink.ordering().is_empty());
+    }
+
+    fn large_context(rows: usize) -> ExecutionContext {
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("customers", DataChunk::new(vec![
+            Vector::from((0..5000).map(|i| i % 4000).collect::<Vec<i32>>()),
+            Vector::from((0..5000).map(|i| if i % 2 == 0 { "north" } else { "south" }).collect::<Vec<_>>()),
+            Vector::from((0..5000).map(|i| format!("customer {i}")).collect::<Vec<_>>()),
+        ]));
+        ctx.register_table("sale_orders", DataChunk::new(vec![
+            Vector::from((0..rows as i64).collect::<Vec<_>>()),
+            Vector::from((0..rows).map(|i| (i % 9 != 0).then_some((i * 7 % 4500) as i32)).collect::<Vec<_>>()),
+            Vector::from((0..rows).map(|i| if i % 3 == 0 { "north" } else { "south" }).collect::<Vec<_>>()),
+            Vector::from(vec![1.0f64; rows]),
+        ]));
+        ctx
+    }
+
+    #[test]
+    fn test_runtime_filter() {
+        let rows = VECTOR_SIZE * 3;
+        let projection = "[ $in.order_id, $ht.name ]";
+        // every customer has a name, the inner join is the left join without its null rows
+        let left = run_in(large_context(rows), "hash_left_join", projection);
+        let inner: Vec<Vec<Value>> = left.iter().filter(|row| !row[1].is_null()).cloned().collect();
+        assert_eq!(sorted(run_in(large_context(rows), "hash_join", projection)), sorted(inner.clone()));
+        let mut ctx = large_context(rows);
+        ctx.set_memory_budget(64 * 1024);
+        assert_eq!(sorted(run_in(ctx, "hash_join", projection)), sorted(inner));
+
+        // the keys of the join are columns of the scan, it leaves out the orders without a
+        // customer of their id and region, up to the false positives of the bloom filter
+        let text = CUSTOMERS.replace("JOIN", "hash_join").replace("PROJECTION", projection);
+        let topology = Topology::parse(&text).unwrap();
+        let interpreter = Interpreter::new(large_context(rows));
+        interpreter.run(&topology).unwrap();
+        let (filter, keys) = topology.main.operators[0].runtime_filter(&interpreter.ctx).unwrap().unwrap();
+        assert_eq!(keys, ["customer_id", "region"]);
+        let mut reader = topology.main.source.open_filtered(&interpreter.ctx, vec![ScanFilter { filter, columns: vec![1, 2] }]).unwrap();
+        let mut scanned = 0;
+        while let Some(chunk) = reader.next_chunk().unwrap() {
+            scanned += chunk.len();
+        }
+        let customers: HashSet<(usize, bool)> = (0..5000).map(|i| (i % 4000, i % 2 == 0)).collect();
+        let candidates = (0..rows).filter(|i| i % 9 != 0 && customers.contains(&(i * 7 % 4500, i % 3 == 0))).count();
+        assert!(scanned >= candidates && scanned < candidates + candidates / 50, "{scanned} of {candidates}");
+
+        let left = Topology::parse(&text.replace("hash_join", "hash_left_join")).unwrap();
+        assert!(left.main.operators[0].runtime_filter(&interpreter.ctx).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_check_join() {
+        let text = CUSTOMERS.replace("JOIN", "hash_left_join").replace("PROJECTION", "[ $in.order_id, $ht.name, $ht.region ]");
+        let topology = Topology::parse(&text).unwrap();
+        let schemas = topology.check().unwrap();
+        let output = schemas.get(&topology.main.sink).unwrap();
+        assert_eq!(output[1].data_type, DataType::String);
+        assert!(output[2].nullable, "the columns of the hash table are nullable in a left join");
+
+        let text = CUSTOMERS.replace("JOIN", "hash_semi_join").replace("PROJECTION", "[ $in.order_id, $ht.name ]");
+        let err = Topology::parse(&text).err().unwrap().to_string();
+        assert!(err.contains("a hash_semi_join can only project columns of its input, found `$ht.name`"), "{err}");
+
+        let text = CUSTOMERS.replace("JOIN", "hash_join").replace("PROJECTION", "[ $in.order_id ]")
+            .replace("[ v1.customer_id, $in.region ]", "[ $in.order_id, $in.region ]");
+        let err = Topology::parse(&text).err().unwrap().to_string();
+        assert!(err.contains("the key `order_id` is I64, but the key `customer_id` of the hash table is I32"), "{err}");
+    }
+
+    #[test]
+    fn test_print_join() {
+        let text = CUSTOMERS.replace("JOIN", "hash_join").replace("PROJECTION", "[ $in.order_id, $ht.name ]");
+        let printed = Topology::parse(&text).unwrap().to_string();
+        assert!(printed.contains("    v2 = build_hash :input = v1"), "{printed}");
+        assert!(printed.contains(r#":key = [ "customer_id", "region" ]"#), "{printed}");
+        assert!(printed.contains(r#":value = "name""#), "{printed}");
+        assert!(printed.contains("v2 = hash_join :input = v1\n            :ht = pipeline1.v2\n            :key = [ $in.customer_id, $in.region ]"), "{printed}");
+        assert_eq!(Topology::parse(&printed).unwrap().to_string(), printed);
+    }
+}
@@ -0,0 +1,279 @@
+//! Tokens of the qir text format, see `qir::parser`.
+
+use crate::error::{Error, Result};
+
+/// a byte range of the source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// the span covering both
+    pub fn to(self, other: Span) -> Span {
+        Span { start: self.start.min(other.start), end: self.end.max(other.end) }
+    }
+
+    /// a parse error at this span, with the line and column (both 1-based) of its start
+    pub fn error(self, source: &str, message: impl Into<String>) -> Error {
+        let start = self.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count() + 1;
+        let text = source[line_start..].lines().next().unwrap_or("");
+        let width = source[start..self.end.clamp(start, source.len())].chars().count().max(1);
+        let message = format!("{}\n  | {text}\n  | {}{}", message.into(), " ".repeat(column - 1), "^".repeat(width));
+        Error::Parse { line, column, message }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Ident(String),
+    /// `$in`, `$ht`
+    Variable(String),
+    String(String),
+    Int(i64),
+    Float(f64),
+    Assign,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+    Not,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Comma,
+    Colon,
+    Dot,
+    Eof,
+}
+
+impl Token {
+    /// how the token is written, for error messages
+    pub fn describe(&self) -> String {
+        match self {
+            Token::Ident(name) => format!("`{name}`"),
+            Token::Variable(name) => format!("`${name}`"),
+            Token::String(s) => format!("string {s:?}"),
+            Token::Int(v) => format!("`{v}`"),
+            Token::Float(v) => format!("`{v:?}`"),
+            Token::Eof => "end of input".to_string(),
+            other => format!("`{}`", other.symbol()),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Token::Assign => "=",
+            Token::Eq => "==",
+            Token::NotEq => "!=",
+            Token::Lt => "<",
+            Token::LtEq => "<=",
+            Token::Gt => ">",
+            Token::GtEq => ">=",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::Not => "!",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::LBracket => "[",
+            Token::RBracket => "]",
+            Token::LBrace => "{",
+            Token::RBrace => "}",
+            Token::Comma => ",",
+            Token::Colon => ":",
+            Token::Dot => ".",
+            _ => "",
+        }
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// whether `name` can be written without quotes
+pub fn is_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(is_ident_start) && chars.all(is_ident_char)
+}
+
+/// split the source into tokens, the last one is `Eof`. `//` starts a comment.
+pub fn tokenize(source: &str) -> Result<Vec<(Token, Span)>> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        chars.next();
+        let next = chars.peek().map(|&(_, c)| c);
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '/' if next == Some('/') => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+                continue;
+            }
+            c if is_ident_start(c) || c == '$' => {
+                let mut name = String::new();
+                while let Some((_, c)) = chars.next_if(|&(_, c)| is_ident_char(c)) {
+                    name.push(c);
+                }
+                if c == '$' {
+                    if name.is_empty() {
+                        return Err(Span::new(start, start + 1).error(source, "expected a variable name after `$`"));
+                    }
+                    Token::Variable(name)
+                } else {
+                    name.insert(0, c);
+                    Token::Ident(name)
+                }
+            }
+            c if c.is_ascii_digit() => {
+                let mut text = c.to_string();
+                let mut float = false;
+                while let Some(&(_, c)) = chars.peek() {
+                    let exponent_sign = (c == '-' || c == '+') && text.ends_with(['e', 'E']);
+                    if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign {
+                        float |= !c.is_ascii_digit();
+                        text.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let span = Span::new(start, start + text.len());
+                if float {
+                    Token::Float(text.parse().map_err(|_| span.error(source, format!("invalid number `{text}`")))?)
+                } else {
+                    Token::Int(text.parse().map_err(|_| span.error(source, format!("integer `{text}` is too large")))?)
+                }
+            }
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((i, '\\')) => match chars.next() {
+                            Some((_, 'n')) => value.push('\n'),
+                            Some((_, 't')) => value.push('\t'),
+                            Some((_, c @ ('"' | '\\'))) => value.push(c),
+                            _ => return Err(Span::new(i, i + 2).error(source, "invalid escape")),
+                        },
+                        Some((_, c)) => value.push(c),
+                        None => return Err(Span::new(start, source.len()).error(source, "unterminated string")),
+                    }
+                }
+                Token::String(value)
+            }
+            _ => {
+                let two = |token: Token, chars: &mut std::iter::Peekable<std::str::CharIndices>| {
+                    chars.next();
+                    token
+                };
+                match (c, next) {
+                    ('=', Some('=')) => two(Token::Eq, &mut chars),
+                    ('!', Some('=')) => two(Token::NotEq, &mut chars),
+                    ('<', Some('=')) => two(Token::LtEq, &mut chars),
+                    ('>', Some('=')) => two(Token::GtEq, &mut chars),
+                    ('&', Some('&')) => two(Token::And, &mut chars),
+                    ('|', Some('|')) => two(Token::Or, &mut chars),
+                    ('=', _) => Token::Assign,
+                    ('!', _) => Token::Not,
+                    ('<', _) => Token::Lt,
+                    ('>', _) => Token::Gt,
+                    ('+', _) => Token::Plus,
+                    ('-', _) => Token::Minus,
+                    ('*', _) => Token::Star,
+                    ('/', _) => Token::Slash,
+                    ('%', _) => Token::Percent,
+                    ('(', _) => Token::LParen,
+                    (')', _) => Token::RParen,
+                    ('[', _) => Token::LBracket,
+                    (']', _) => Token::RBracket,
+                    ('{', _) => Token::LBrace,
+                    ('}', _) => Token::RBrace,
+                    (',', _) => Token::Comma,
+                    (':', _) => Token::Colon,
+                    ('.', _) => Token::Dot,
+                    _ => return Err(Span::new(start, start + c.len_utf8()).error(source, format!("unexpected character `{c}`"))),
+                }
+            }
+        };
+        let end = chars.peek().map(|&(i, _)| i).unwrap_or(source.len());
+        tokens.push((token, Span::new(start, end)));
+    }
+    tokens.push((Token::Eof, Span::new(source.len(), source.len())));
+    Ok(tokens)
+}
+
+/// quote a string literal
+pub fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens: Vec<Token> = tokenize("v2 = filter :expr = $in.freight >= 1.5e3 && $in.name != \"a\\\"b\" // comment")
+            .unwrap().into_iter().map(|(t, _)| t).collect();
+        assert_eq!(tokens, vec![
+            Token::Ident("v2".into()), Token::Assign, Token::Ident("filter".into()), Token::Colon, Token::Ident("expr".into()),
+            Token::Assign, Token::Variable("in".into()), Token::Dot, Token::Ident("freight".into()), Token::GtEq,
+            Token::Float(1500.0), Token::And, Token::Variable("in".into()), Token::Dot, Token::Ident("name".into()),
+            Token::NotEq, Token::String("a\"b".into()), Token::Eof,
+        ]);
+    }
+
+    #[test]
+    fn test_error_position() {
+        let err = tokenize("v1 = table_scan\n  :table = \"customers").unwrap_err();
+        match err {
+            Error::Parse { line, column, message } => {
+                assert_eq!((line, column), (2, 12));
+                assert!(message.starts_with("unterminated string"), "{message}");
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
@@ -1,29 +1,30 @@
-use crate::qir::{Column, DataType, Table};
-use std::rc::Rc;
-
 /// Macro for creating a table
 /// 
 /// # Example
 /// 
 /// ```rust
-///     table!{
-///         name: "users",
-///         columns: [
-///             column! { name = "id", data_type = I64 },
-///             column! { name = "name", data_type = String },
-///         ],
-///     }
-/// }
+/// # use dataframe::{column, table};
+/// let users = table! {
+///     name: "users",
+///     columns: [
+///         column! { name = "id", data_type = I64, nullable = false },
+///         column! { name = "name", data_type = String },
+///     ],
+/// };
 /// ```
 /// 
 #[macro_export]
 macro_rules! column {
     { name = $x:expr, data_type = $type:ident } => {
-        Column {
+        $crate::column! { name = $x, data_type = $type, nullable = true }
+    };
+    { name = $x:expr, data_type = $type:ident, nullable = $nullable:expr } => {
+        $crate::qir::Column {
             name: $x.to_string(),
-            data_type: DataType::$type,
+            data_type: $crate::qir::DataType::$type,
+            nullable: $nullable,
         }
-    }
+    };
 }
 
 #[macro_export] 
@@ -32,7 +33,7 @@ macro_rules! table {
         name: $name:expr,
         columns: [ $($column:expr),* $(,)? ],
     } => {
-        Table {
+        $crate::qir::Table {
             name: $name.to_string(),
             columns: vec![ $($column),* ],
         }
@@ -43,11 +44,15 @@ macro_rules! table {
 /// # 示例
 /// 
 /// ```rust
+/// # use std::rc::Rc;
+/// # use dataframe::{column, scan, table};
+/// # let users_table = Rc::new(table! { name: "users", columns: [ column! { name = "id", data_type = I64 } ], });
 /// scan! {
 ///     name: "users_scan",
 ///     table: users_table,
 ///     output: ["id", "name", "age"]
 /// }
+/// # ;
 /// ```
 #[macro_export]
 macro_rules! scan {
@@ -56,10 +61,12 @@ macro_rules! scan {
         table: $table:expr,
         output: [ $($field:expr),* $(,)? ]
     } => {
-        Scan {
+        $crate::qir::Scan {
             name: $name.to_string(),
             table: $table,
-            output: vec![ $($field.to_string()),* ]
+            output: vec![ $($field.to_string()),* ],
+            minmaxes: vec![],
+            ordered_by: vec![],
         }
     }
 }
@@ -69,11 +76,17 @@ macro_rules! scan {
 /// # 示例
 /// 
 /// ```rust
+/// # use std::rc::Rc;
+/// # use dataframe::qir::expr::{BinaryOp, Expr};
+/// # use dataframe::{column, filter, scan, table};
+/// # let users_table = Rc::new(table! { name: "users", columns: [ column! { name = "id", data_type = I64 } ], });
+/// # let scan_op = Rc::new(scan! { name: "users_scan", table: users_table.clone(), output: ["id", "name", "age"] });
 /// filter! {
 ///     input: scan_op,
-///     predicate: |row| row.age > 18,
+///     predicate: Expr::binary(BinaryOp::Gt, Expr::column(&users_table.columns, "id").unwrap(), Expr::literal(10)).unwrap(),
 ///     output: ["id", "name", "age"]
 /// }
+/// # ;
 /// ```
 #[macro_export]
 macro_rules! filter {
@@ -82,11 +95,7 @@ macro_rules! filter {
         predicate: $predicate:expr,
         output: [ $($field:expr),* $(,)? ]
     } => {
-        Filter {
-            input: $input,
-            predicate: $predicate.to_string(),
-            output: vec![ $($field.to_string()),* ]
-        }
+        $crate::qir::Filter::new($input, $predicate, vec![ $($field.to_string()),* ])
     }
 }
 /// 宏用于创建 IdentitySink 算子
@@ -94,16 +103,21 @@ macro_rules! filter {
 /// # 示例
 /// 
 /// ```rust
+/// # use std::rc::Rc;
+/// # use dataframe::{column, identity, scan, table};
+/// # let users_table = Rc::new(table! { name: "users", columns: [ column! { name = "id", data_type = I64 } ], });
+/// # let agg_op = Rc::new(scan! { name: "users_scan", table: users_table, output: ["id"] });
 /// identity! {
 ///     input: agg_op
 /// }
+/// # ;
 /// ```
 #[macro_export]
 macro_rules! identity {
     {
         input: $input:expr
     } => {
-        Rc::new(IdentitySink {
+        ::std::rc::Rc::new($crate::qir::IdentitySink {
             input: $input
         })
     }
@@ -114,12 +128,22 @@ macro_rules! identity {
 /// # 示例
 /// 
 /// ```rust
+/// # use std::rc::Rc;
+/// # use dataframe::qir::{Operator, Sink};
+/// # use dataframe::qir::expr::Expr;
+/// # use dataframe::{column, filter, identity, pipeline, scan, table};
+/// # let users_table = Rc::new(table! { name: "users", columns: [ column! { name = "id", data_type = I64 } ], });
+/// # let scan_op = Rc::new(scan! { name: "users_scan", table: users_table, output: ["id"] });
+/// # let filter_op: Rc<dyn Operator> = Rc::new(filter! { input: scan_op.clone(), predicate: Expr::literal(true), output: ["id"] });
+/// # let agg_op: Rc<dyn Sink> = identity! { input: filter_op.clone() };
+/// # let pipeline1 = Rc::new(pipeline! { source: scan_op.clone(), operators: [], sink: agg_op.clone() });
 /// pipeline! {
 ///     source: scan_op,
-///     operators: [filter_op, join_op],
+///     operators: [filter_op],
 ///     sink: agg_op,
 ///     parent: pipeline1
 /// }
+/// # ;
 /// ```
 #[macro_export]
 macro_rules! pipeline {
@@ -129,11 +153,11 @@ macro_rules! pipeline {
         sink: $sink:expr
         $(, parent: $parent:expr)?
     } => {
-        Pipeline {
+        $crate::qir::Pipeline {
             source: $source,
             operators: vec![ $($operator),* ],
             sink: $sink,
-            parents: vec![]
+            parents: vec![ $($parent)? ]
         }
     }
 }
@@ -0,0 +1,288 @@
+//! Merge joins: joins an input with the rows of the sink of a parent pipeline when both are
+//! sorted by the join keys, without a hash table. The right rows are the `Vec<DataChunk>` of e.g.
+//! an `identity` or an `order_by`, the sort is checked with `Operator::ordering`. A `hash_join`
+//! whose input and `build_hash` input are sorted by the keys is parsed as a merge join reading the
+//! rows of the `build_hash`, which then builds no hash table unless a hash join probes it.
+//!
+//! ```text
+//! v1 = table_scan :table = "customers" ... :ordered_by = [ "customer_id" ]
+//! v2 = identity :input = v1
+//! ...
+//! v1 = table_scan :table = "sale_orders" ... :ordered_by = [ "customer_id" ]
+//! v2 = merge_left_join :input = v1 :right = pipeline1.v2
+//!         :key = $in.customer_id
+//!         :right_key = $right.customer_id
+//!         :projection = [ $in.order_id, $right.name ]
+//! ```
+//!
+//! The right rows are kept in memory, the input streams past them, see `exec::merge_join`. The
+//! join types are those of the hash joins.
+
+use std::rc::Rc;
+use crate::error::Result;
+use crate::exec::join::JoinHashTable;
+use crate::exec::merge_join::SortedSide;
+use crate::exec::{ExecutionContext, SourceReader};
+use crate::qir::checker::Checker;
+use crate::qir::join::{check_projection, concat_or_empty, probe_column, probe_ordering, JoinColumn, JoinPlan, JoinRows, JoinType};
+use crate::qir::printer::{name, strings, Printer};
+use crate::qir::{resolve_columns, Column, MinMax, Operator, ResultScan, Source};
+use crate::vector::{DataChunk, Vector};
+
+/// the operator name of a merge join of `join_type` in the text format
+pub fn operator(join_type: JoinType) -> &'static str {
+    match join_type {
+        JoinType::Inner => "merge_join",
+        JoinType::Left => "merge_left_join",
+        JoinType::Semi => "merge_semi_join",
+        JoinType::Anti => "merge_anti_join",
+    }
+}
+
+pub struct MergeJoin {
+    pub input: Rc<dyn Operator>,
+    /// the sink of a parent pipeline producing the right rows
+    pub right: Rc<dyn Operator>,
+    pub join_type: JoinType,
+    /// the key columns of the input, compared with `right_keys` in order
+    pub keys: Vec<String>,
+    pub right_keys: Vec<String>,
+    /// the columns of the output, `JoinColumn::Build` for the right columns
+    pub projection: Vec<JoinColumn>,
+    output: Vec<String>,
+}
+
+impl MergeJoin {
+    pub fn new(input: Rc<dyn Operator>, right: Rc<dyn Operator>, join_type: JoinType, keys: Vec<String>, right_keys: Vec<String>,
+        projection: Vec<JoinColumn>) -> Self {
+        let output = projection.iter().map(|c| c.name().to_string()).collect();
+        MergeJoin { input, right, join_type, keys, right_keys, projection, output }
+    }
+
+    fn plan(&self) -> Result<JoinPlan> {
+        JoinPlan::new(self.join_type, self.input.output(), self.right.output(), &self.keys, &self.projection)
+    }
+
+    /// the right rows of the finished parent pipeline
+    fn open_right(&self, ctx: &ExecutionContext) -> Result<SortedSide> {
+        let keys = resolve_columns(self.right.output(), &self.right_keys)?;
+        // a `build_hash` also probed by a hash join keeps its rows in the table
+        if let Ok(table) = ctx.sink_result(&self.right)?.downcast::<JoinHashTable>() {
+            return SortedSide::new(table.data().slice(0, table.len()), &keys);
+        }
+        let mut reader = ResultScan { input: self.right.clone() }.open(ctx)?;
+        let mut chunks = vec![];
+        while let Some(chunk) = reader.next_chunk()? {
+            chunks.push(chunk);
+        }
+        let types: Vec<_> = ctx.schema(&self.right)?.into_iter().map(|c| c.data_type).collect();
+        SortedSide::new(concat_or_empty(&chunks, &types)?, &keys)
+    }
+}
+
+/// the state of a merge join between its chunks
+struct MergeJoinState {
+    plan: JoinPlan,
+    right: SortedSide,
+}
+
+/// `$right.a` for a single key, `[ $right.a, $right.b ]` otherwise
+fn print_keys(var: &str, keys: &[String]) -> String {
+    let keys: Vec<String> = keys.iter().map(|k| format!("{var}.{}", name(k))).collect();
+    match keys.as_slice() {
+        [key] => key.clone(),
+        _ => format!("[ {} ]", keys.join(", ")),
+    }
+}
+
+impl Operator for MergeJoin {
+    fn name(&self) -> &'static str {
+        operator(self.join_type)
+    }
+
+    fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    fn execute(&self, ctx: &ExecutionContext, input: DataChunk) -> Result<DataChunk> {
+        // the plan, the right rows and the position of the merge are kept between the chunks
+        let mut state = match ctx.take_state(self) {
+            Some(state) => state.downcast::<MergeJoinState>().expect("the state of a merge join"),
+            None => Box::new(MergeJoinState { plan: self.plan()?, right: self.open_right(ctx)? }),
+        };
+        let MergeJoinState { plan, right } = state.as_mut();
+        let keys: Vec<&Vector> = plan.keys.iter().map(|&i| input.column(i)).collect();
+        let rows = match self.join_type {
+            JoinType::Semi | JoinType::Anti => JoinRows::Found(right.contains(&keys, input.len())?),
+            JoinType::Inner | JoinType::Left => {
+                let (left_rows, right_rows) = right.matches(&keys, input.len(), self.join_type == JoinType::Left)?;
+                JoinRows::Pairs(left_rows, right_rows)
+            }
+        };
+        let output = plan.output(&input, right.data(), rows);
+        ctx.set_state(self, state);
+        Ok(output)
+    }
+
+    fn finish(&self, ctx: &ExecutionContext) -> Result<Option<Box<dyn SourceReader>>> {
+        ctx.take_state(self);
+        Ok(None)
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", printer.var(&self.input));
+        printer.arg("right", printer.var(&self.right));
+        printer.arg("key", print_keys("$in", &self.keys));
+        printer.arg("right_key", print_keys("$right", &self.right_keys));
+        let projection: Vec<String> = self.projection.iter()
+            .map(|c| match c {
+                JoinColumn::Probe(column) => format!("$in.{}", name(column)),
+                JoinColumn::Build(column) => format!("$right.{}", name(column)),
+            })
+            .collect();
+        printer.arg("projection", format!("[ {} ]", projection.join(", ")));
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        let input = checker.input(&self.input);
+        let right = checker.schema(&self.right);
+        let (input, right) = (input?, right?);
+
+        if self.keys.is_empty() || self.keys.len() != self.right_keys.len() {
+            checker.error(format!("{} keys are compared with {} right keys", self.keys.len(), self.right_keys.len()));
+        }
+        if let (Some(keys), Some(right_keys)) = (checker.resolve(&input, &self.keys), checker.resolve(&right, &self.right_keys)) {
+            for (key, right) in keys.iter().zip(&right_keys) {
+                if key.data_type != right.data_type {
+                    checker.error(format!("the key `{}` is {:?}, but the right key `{}` is {:?}", key.name, key.data_type, right.name, right.data_type));
+                }
+            }
+        }
+        for (side, sorted, keys) in [("input", &self.input, &self.keys), ("right side", &self.right, &self.right_keys)] {
+            if !sorted.ordering().starts_with(keys) {
+                checker.error(format!("the {side} of a {} is not ordered by {}", self.name(), strings(keys)));
+            }
+        }
+        check_projection(checker, self.join_type, self.name(), &input, &right, &self.projection, "$right")
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        match self.projection.iter().find(|c| c.name() == column)? {
+            JoinColumn::Probe(name) => self.input.min_max(name),
+            JoinColumn::Build(name) => self.right.min_max(name),
+        }
+    }
+
+    fn ordering(&self) -> Vec<String> {
+        probe_ordering(self.input.as_ref(), &self.projection)
+    }
+
+    fn input_column(&self, column: &str) -> Option<&str> {
+        probe_column(&self.projection, column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exec::{ExecutionContext, Interpreter};
+    use crate::qir::join::JoinType;
+    use crate::qir::Topology;
+    use crate::vector::{DataChunk, Value, Vector, VECTOR_SIZE};
+
+    const MERGE: &str = r#"
+pipeline1: Pipeline =
+    v1 = table_scan :table = "customers"
+            :columns =
+                "customer_id": { data_type: "i32", nullable: false },
+                "name": { data_type: "string" }
+            :ordered_by = [ "customer_id" ]
+    v2 = RIGHT
+pipeline2: Pipeline =
+    v1 = table_scan :table = "sale_orders"
+            :columns =
+                "order_id": { data_type: "i64", nullable: false },
+                "customer_id": { data_type: "i32" }
+            :ordered_by = [ "customer_id", "order_id" ]
+    v2 = filter :input = v1
+            :expr = $in.order_id >= 0
+            :projection = [ "customer_id", "order_id" ]
+    v3 = JOIN :input = v2 :RIGHT_VAR = pipeline1.v2
+            :key = $in.customer_id
+            KEYS:projection = PROJECTION
+    v4 = identity :input = v3
+"#;
+
+    fn text(join_type: JoinType, merge: bool) -> String {
+        let projection = match join_type {
+            JoinType::Semi | JoinType::Anti => "[ $in.order_id ]",
+            _ if merge => "[ $in.order_id, $right.name ]",
+            _ => "[ $in.order_id, $ht.name ]",
+        };
+        let (join, right, var, keys) = match merge {
+            true => (super::operator(join_type), "identity :input = v1", "right", ":right_key = $right.customer_id\n            "),
+            false => (join_type.operator(), "build_hash :input = v1 :key = \"customer_id\" :value = \"name\"", "ht", ""),
+        };
+        MERGE.replace("RIGHT_VAR", var).replace("RIGHT", right).replace("JOIN", join).replace("KEYS", keys).replace("PROJECTION", projection)
+    }
+
+    fn context() -> ExecutionContext {
+        // customers 0 to 1499 twice except 4, 9, 14, ..., orders of customers 0 to 1999, then
+        // orders without a customer
+        let customers: Vec<i32> = (0..3000).map(|i| i / 2).filter(|id| id % 5 != 4).collect();
+        let names: Vec<Option<String>> = (0..customers.len()).map(|i| (i % 7 != 0).then(|| format!("customer {i}"))).collect();
+        let rows = VECTOR_SIZE * 2 + 100;
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("customers", DataChunk::new(vec![
+            Vector::from(customers.clone()),
+            Vector::from(names.iter().map(|s| s.as_deref()).collect::<Vec<_>>()),
+        ]));
+        ctx.register_table("sale_orders", DataChunk::new(vec![
+            Vector::from((0..rows as i64).collect::<Vec<_>>()),
+            Vector::from((0..rows).map(|i| (i < rows - 50).then_some((i * 2000 / rows) as i32)).collect::<Vec<_>>()),
+        ]));
+        ctx
+    }
+
+    fn run(text: &str) -> Vec<Vec<Value>> {
+        let topology = Topology::parse(text).unwrap();
+        let result = Interpreter::new(context()).run(&topology).unwrap();
+        let chunks = result.downcast::<Vec<DataChunk>>().unwrap();
+        chunks.iter().flat_map(|c| (0..c.len()).map(|i| c.row(i))).collect()
+    }
+
+    #[test]
+    fn test_merge_join() {
+        for join_type in JoinType::ALL {
+            let merged = run(&text(join_type, true));
+            // without the order of the customers the hash table is probed
+            let hashed = text(join_type, false).replacen(":ordered_by = [ \"customer_id\" ]", "", 1);
+            assert!(Topology::parse(&hashed).unwrap().to_string().contains(join_type.operator()));
+            let hashed = run(&hashed);
+            assert!(!merged.is_empty(), "{join_type:?}");
+            // both keep the order of the input rows, the duplicate customers in build order
+            assert_eq!(merged, hashed, "{join_type:?}");
+            // a hash join of sorted inputs is a merge join of the rows of the `build_hash`
+            let chosen = text(join_type, false);
+            let printed = Topology::parse(&chosen).unwrap().to_string();
+            assert!(printed.contains(&format!("v3 = {} :input = v2\n            :right = pipeline1.v2", super::operator(join_type))), "{printed}");
+            assert_eq!(Topology::parse(&printed).unwrap().to_string(), printed);
+            assert_eq!(run(&chosen), merged, "{join_type:?}");
+        }
+    }
+
+    #[test]
+    fn test_check_merge_join() {
+        let text = text(JoinType::Left, true);
+        let printed = Topology::parse(&text).unwrap().to_string();
+        assert!(printed.contains(":ordered_by = [ \"customer_id\", \"order_id\" ]"), "{printed}");
+        assert!(printed.contains("v3 = merge_left_join :input = v2\n            :right = pipeline1.v2\n            :key = $in.customer_id\n            :right_key = $right.customer_id"), "{printed}");
+        assert_eq!(Topology::parse(&printed).unwrap().to_string(), printed);
+
+        let err = Topology::parse(&text.replace(":ordered_by = [ \"customer_id\", \"order_id\" ]", ":ordered_by = [ \"order_id\" ]")).err().unwrap().to_string();
+        assert!(err.contains("the input of a merge_left_join is not ordered by [ \"customer_id\" ]"), "{err}");
+        let err = Topology::parse(&text.replace(":ordered_by = [ \"customer_id\" ]", "")).err().unwrap().to_string();
+        assert!(err.contains("the right side of a merge_left_join is not ordered by [ \"customer_id\" ]"), "{err}");
+        let err = Topology::parse(&text.replace("$right.customer_id", "$in.customer_id")).err().unwrap().to_string();
+        assert!(err.contains("expected a column `$right.name`"), "{err}");
+    }
+}
@@ -1,30 +1,124 @@
 use std::rc::Rc;
+use crate::error::{Error, Result};
+use crate::exec::external_sort::SortedRuns;
+use crate::exec::filter::Predicate;
+use crate::exec::runtime_filter::{RuntimeFilter, ScanFilter};
+use crate::exec::{ExecutionContext, SinkResult, SinkState, SourceReader};
+use crate::vector::{DataChunk, SelectionVector, Value, VECTOR_SIZE};
+use expr::Expr;
+use checker::Checker;
+use printer::{strings, Printer};
 
+pub mod aggregate;
+pub mod checker;
+pub mod expr;
+pub mod group_by;
+pub mod ipc;
+pub mod join;
+pub mod lexer;
 pub mod macros;
+pub mod merge_join;
+pub mod order_by;
+pub mod parquet;
+pub mod parser;
+pub mod printer;
+pub mod window;
 
 pub trait Operator {
+    /// the operator name in the text format, e.g. `filter`
+    fn name(&self) -> &'static str;
 
+    /// names of the columns this operator produces
+    fn output(&self) -> &[String];
+
+    /// process one chunk of the pipeline. sources and sinks are driven by the pipeline itself,
+    /// so the default implementation passes the chunk through unchanged.
+    fn execute(&self, _ctx: &ExecutionContext, input: DataChunk) -> Result<DataChunk> {
+        Ok(input)
+    }
+
+    /// write the arguments in the text format, see `qir::printer`
+    fn print(&self, printer: &mut Printer);
+
+    /// resolve the columns this operator uses and infer the columns it produces, reporting the
+    /// errors to the checker, see `qir::checker`. `None` when the output can not be inferred.
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>>;
+
+    /// the known range of the values of output column `column`, see `Scan::minmaxes`
+    fn min_max(&self, _column: &str) -> Option<MinMax> {
+        None
+    }
+
+    /// called once the input of the pipeline is exhausted, the rows the operator kept back,
+    /// e.g. the joined partitions of a grace hash join
+    fn finish(&self, _ctx: &ExecutionContext) -> Result<Option<Box<dyn SourceReader>>> {
+        Ok(None)
+    }
+
+    /// the columns the output rows are sorted by, the first column first, ascending with nulls
+    /// last as an `order_by` sorts them. a merge join needs both inputs sorted by its keys.
+    fn ordering(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// the input column of output column `column`, when every output row has the value of
+    /// that column in one input row. runtime filters are pushed down through such columns.
+    fn input_column(&self, _column: &str) -> Option<&str> {
+        None
+    }
+
+    /// the predicate of the input rows this operator keeps, e.g. of a `filter`. the source of a
+    /// pipeline may test the predicate of the operator after it, see `Source::open_pushdown`.
+    fn predicate(&self) -> Option<&Expr> {
+        None
+    }
+
+    /// a filter of the input rows this operator drops, known once the parent pipelines ran,
+    /// with the input columns it tests, see `exec::runtime_filter`
+    fn runtime_filter(&self, _ctx: &ExecutionContext) -> Result<Option<(Rc<RuntimeFilter>, &[String])>> {
+        Ok(None)
+    }
 }
 pub trait Source: Operator {
+    /// start a new scan, returning a reader that produces chunks of at most `VECTOR_SIZE` rows
+    fn open(&self, ctx: &ExecutionContext) -> Result<Box<dyn SourceReader>>;
 
+    /// start a new scan that may leave out the rows failing `filters`, the runtime filters of
+    /// the joins of its pipeline. sources that can not skip rows ignore them.
+    fn open_filtered(&self, ctx: &ExecutionContext, filters: Vec<ScanFilter>) -> Result<Box<dyn SourceReader>> {
+        let _ = filters;
+        self.open(ctx)
+    }
+
+    /// like `open_filtered`, and the scan may also leave out the rows failing `predicate`, the
+    /// predicate of the operator after the source. that operator still tests the rows it gets.
+    fn open_pushdown(&self, ctx: &ExecutionContext, filters: Vec<ScanFilter>, predicate: Option<&Expr>) -> Result<Box<dyn SourceReader>> {
+        let _ = predicate;
+        self.open_filtered(ctx, filters)
+    }
 }
 pub trait Sink: Operator {
-
+    /// create the state which consumes the chunks of a pipeline
+    fn open(&self, ctx: &ExecutionContext) -> Result<Box<dyn SinkState>>;
 }
 
 /// Type definitions for a table
+#[derive(Debug, Clone, PartialEq)]
 pub struct Table {
     pub name: String,
     pub columns: Vec<Column>,
 }
 
 /// Column definition for a column in a table
+#[derive(Debug, Clone, PartialEq)]
 pub struct Column {
     pub name: String,
     pub data_type: DataType,
+    pub nullable: bool,
 }
 
 /// Data type for columns
+#[derive(Debug, Clone, PartialEq)]
 pub enum DataType {
     I8,
     I16,
@@ -46,30 +140,368 @@ pub enum DataType {
     Map(Box<DataType>, Box<DataType>),
 }
 
+impl DataType {
+    pub fn is_integer(&self) -> bool {
+        matches!(self, DataType::I8 | DataType::I16 | DataType::I32 | DataType::I64
+            | DataType::U8 | DataType::U16 | DataType::U32 | DataType::U64)
+    }
+
+    pub fn is_signed_integer(&self) -> bool {
+        matches!(self, DataType::I8 | DataType::I16 | DataType::I32 | DataType::I64)
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, DataType::F32 | DataType::F64)
+    }
+
+    /// integers, floats and decimals
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float() || *self == DataType::Decimal
+    }
+
+    pub fn is_temporal(&self) -> bool {
+        matches!(self, DataType::Date | DataType::DateTime)
+    }
+}
+
+/// find the position of each of `names` in `columns`
+fn resolve_columns(columns: &[String], names: &[String]) -> Result<Vec<usize>> {
+    names.iter()
+        .map(|name| columns.iter().position(|c| c == name).ok_or_else(|| Error::ColumnNotFound(name.clone())))
+        .collect()
+}
+
+/// a bound of a `MinMax`: a value, or a column of the single row produced by the sink of a
+/// parent pipeline, e.g. the `min` of `aggregate :aggregates = "min": min($in.id)`
+#[derive(Clone)]
+pub enum Bound {
+    Value(Value),
+    Result { sink: Rc<dyn Operator>, column: String },
+}
+
+impl Bound {
+    /// the value of the bound, `Null` when the sink produced no row
+    pub fn resolve(&self, ctx: &ExecutionContext) -> Result<Value> {
+        match self {
+            Bound::Value(value) => Ok(value.clone()),
+            Bound::Result { sink, column } => {
+                let index = resolve_columns(sink.output(), std::slice::from_ref(column))?[0];
+                let result = ctx.sink_result(sink)?;
+                let chunks = result.downcast_ref::<Vec<DataChunk>>()
+                    .ok_or_else(|| Error::Unsupported(format!("`{}` does not produce rows", sink.name())))?;
+                Ok(chunks.iter().find(|c| !c.is_empty()).map_or(Value::Null, |c| c.column(index).get(0)))
+            }
+        }
+    }
+}
+
+/// the known range of the values of a column, `"id": [1, 100]`
+#[derive(Clone)]
+pub struct MinMax {
+    pub column: String,
+    pub min: Bound,
+    pub max: Bound,
+}
+
 /// a Scan source operator
 pub struct Scan {
     pub name: String,
     pub table: Rc<Table>,
-    pub output: Vec<String>     // TODO resolve symbol -> definition
+    pub output: Vec<String>,
+    /// the ranges of columns known from the table statistics or from a parent pipeline
+    pub minmaxes: Vec<MinMax>,
+    /// the columns the rows of the table are sorted by, see `Operator::ordering`
+    pub ordered_by: Vec<String>,
 }
 
-impl Operator for Scan { }
-impl Source for Scan { }
+impl Operator for Scan {
+    fn name(&self) -> &'static str {
+        "table_scan"
+    }
+
+    fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("table", lexer::quote(&self.table.name));
+        self.print_columns(printer);
+    }
 
-/// a Filter operator
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        let columns = &self.table.columns;
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].iter().any(|c| c.name == column.name) {
+                checker.error(format!("duplicate column `{}` in table `{}`", column.name, self.table.name));
+            }
+        }
+        for (i, minmax) in self.minmaxes.iter().enumerate() {
+            if self.minmaxes[..i].iter().any(|m| m.column == minmax.column) {
+                checker.error(format!("duplicate minmax of `{}`", minmax.column));
+            }
+            let Some(column) = columns.iter().find(|c| c.name == minmax.column) else {
+                checker.error(format!("minmax of unknown column `{}`", minmax.column));
+                continue;
+            };
+            for bound in [&minmax.min, &minmax.max] {
+                let data_type = match bound {
+                    Bound::Value(value) => value.data_type(),
+                    Bound::Result { sink, column } => checker.schema(sink)
+                        .and_then(|schema| schema.into_iter().find(|c| &c.name == column))
+                        .map(|c| c.data_type),
+                };
+                if data_type.as_ref() != Some(&column.data_type) {
+                    checker.error(format!("the minmax of `{}` must be {:?}", column.name, column.data_type));
+                }
+            }
+        }
+        for column in &self.ordered_by {
+            if !columns.iter().any(|c| &c.name == column) {
+                checker.error(format!("ordered by unknown column `{column}`"));
+            }
+        }
+        checker.resolve(columns, &self.output)
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        self.minmaxes.iter().find(|m| m.column == column).cloned()
+    }
+
+    fn ordering(&self) -> Vec<String> {
+        self.ordered_by.iter().take_while(|c| self.output.contains(c)).cloned().collect()
+    }
+}
+
+impl Scan {
+    /// the arguments after `:table`, shared with a `parquet_scan` and an `arrow_scan`
+    fn print_columns(&self, printer: &mut Printer) {
+        printer.columns("columns", &self.table.columns);
+        if !self.output.iter().eq(self.table.columns.iter().map(|c| &c.name)) {
+            printer.arg("output", strings(&self.output));
+        }
+        if !self.minmaxes.is_empty() {
+            let bound = |printer: &Printer, bound: &Bound| match bound {
+                Bound::Value(value) => printer::value(value),
+                Bound::Result { sink, column } => format!("{}.{}", printer.var(sink), printer::name(column)),
+            };
+            let entries: Vec<(String, String)> = self.minmaxes.iter()
+                .map(|m| (m.column.clone(), format!("[{}, {}]", bound(printer, &m.min), bound(printer, &m.max))))
+                .collect();
+            printer.entries("minmaxes", entries);
+        }
+        if !self.ordered_by.is_empty() {
+            printer.arg("ordered_by", strings(&self.ordered_by));
+        }
+    }
+}
+
+impl Source for Scan {
+    fn open(&self, ctx: &ExecutionContext) -> Result<Box<dyn SourceReader>> {
+        self.open_filtered(ctx, vec![])
+    }
+
+    fn open_filtered(&self, ctx: &ExecutionContext, filters: Vec<ScanFilter>) -> Result<Box<dyn SourceReader>> {
+        let data = ctx.table(&self.table.name)?;
+        let columns: Vec<String> = self.table.columns.iter().map(|c| c.name.clone()).collect();
+        let projection = resolve_columns(&columns, &self.output)?;
+        Ok(Box::new(ScanReader { data: Rc::new(data.project(&projection)), offset: 0, filters }))
+    }
+}
+
+struct ScanReader {
+    data: Rc<DataChunk>,
+    offset: usize,
+    filters: Vec<ScanFilter>,
+}
+
+impl SourceReader for ScanReader {
+    fn next_chunk(&mut self) -> Result<Option<DataChunk>> {
+        while self.offset < self.data.len() {
+            let len = VECTOR_SIZE.min(self.data.len() - self.offset);
+            let chunk = self.data.slice(self.offset, len);
+            self.offset += len;
+            if self.filters.is_empty() {
+                return Ok(Some(chunk));
+            }
+            // a chunk without a row in the key ranges is skipped before its keys are hashed
+            let mask = ScanFilter::mask_all(&self.filters, &chunk)?;
+            match mask.count_ones() {
+                0 => continue,
+                rows if rows == len => return Ok(Some(chunk)),
+                _ => return Ok(Some(chunk.select(&SelectionVector::from_bitmap(&mask)))),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// a source streaming the `Vec<DataChunk>` result of the sink of a parent pipeline, e.g. the
+/// sorted rows of an `order_by`, or merging the runs of an `order_by` that spilled to disk
+pub struct ResultScan {
+    pub input: Rc<dyn Operator>,
+}
+
+impl Operator for ResultScan {
+    fn name(&self) -> &'static str {
+        "result_scan"
+    }
+
+    fn output(&self) -> &[String] {
+        self.input.output()
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", printer.var(&self.input));
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        checker.schema(&self.input)
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        self.input.min_max(column)
+    }
+
+    fn ordering(&self) -> Vec<String> {
+        self.input.ordering()
+    }
+}
+
+impl Source for ResultScan {
+    fn open(&self, ctx: &ExecutionContext) -> Result<Box<dyn SourceReader>> {
+        let result = match ctx.sink_result(&self.input)?.downcast::<Vec<DataChunk>>() {
+            Ok(chunks) => return Ok(Box::new(ResultScanReader { chunks, index: 0 })),
+            Err(result) => result,
+        };
+        let runs = result.downcast::<SortedRuns>()
+            .map_err(|_| Error::Unsupported(format!("scan the result of `{}`", self.input.name())))?;
+        Ok(Box::new(SortedRuns::merge(&runs)?))
+    }
+}
+
+struct ResultScanReader {
+    chunks: Rc<Vec<DataChunk>>,
+    index: usize,
+}
+
+impl SourceReader for ResultScanReader {
+    fn next_chunk(&mut self) -> Result<Option<DataChunk>> {
+        let chunk = self.chunks.get(self.index).cloned();
+        self.index += 1;
+        Ok(chunk)
+    }
+}
+
+/// a Filter operator, keeps the rows for which `predicate` is true
 pub struct Filter {
     pub input: Rc<dyn Operator>,
-    pub predicate: String,  // TODO
-    pub output: Vec<String>
+    pub predicate: Expr,
+    pub output: Vec<String>,
+    /// the predicate compiled to the SIMD kernels of `exec::filter`
+    compiled: Predicate,
+}
+
+impl Filter {
+    pub fn new(input: Rc<dyn Operator>, predicate: Expr, output: Vec<String>) -> Self {
+        let compiled = Predicate::compile(&predicate, input.output());
+        Filter { input, predicate, output, compiled }
+    }
+}
+
+impl Operator for Filter {
+    fn name(&self) -> &'static str {
+        "filter"
+    }
+
+    fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    fn execute(&self, _ctx: &ExecutionContext, input: DataChunk) -> Result<DataChunk> {
+        let selection = self.compiled.select(&input, self.input.output())?;
+        let projection = resolve_columns(self.input.output(), &self.output)?;
+        Ok(input.project(&projection).select(&selection))
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", printer.var(&self.input));
+        printer.arg("expr", &self.predicate);
+        printer.arg("projection", strings(&self.output));
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        let input = checker.input(&self.input)?;
+        checker.expr(&self.predicate, &input, &DataType::Bool);
+        checker.resolve(&input, &self.output)
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        self.output.iter().any(|c| c == column).then(|| self.input.min_max(column))?
+    }
+
+    fn ordering(&self) -> Vec<String> {
+        self.input.ordering().into_iter().take_while(|c| self.output.contains(c)).collect()
+    }
+
+    fn input_column(&self, column: &str) -> Option<&str> {
+        self.output.iter().find(|c| *c == column).map(String::as_str)
+    }
+
+    fn predicate(&self) -> Option<&Expr> {
+        Some(&self.predicate)
+    }
 }
-impl Operator for Filter { }
 
 pub struct IdentitySink {
     pub input: Rc<dyn Operator>,
 }
 
-impl Operator for IdentitySink {}
-impl Sink for IdentitySink {}
+impl Operator for IdentitySink {
+    fn name(&self) -> &'static str {
+        "identity"
+    }
+
+    fn output(&self) -> &[String] {
+        self.input.output()
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", printer.var(&self.input));
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        checker.input(&self.input)
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        self.input.min_max(column)
+    }
+
+    fn ordering(&self) -> Vec<String> {
+        self.input.ordering()
+    }
+}
+impl Sink for IdentitySink {
+    fn open(&self, _ctx: &ExecutionContext) -> Result<Box<dyn SinkState>> {
+        Ok(Box::new(IdentitySinkState { chunks: vec![] }))
+    }
+}
+
+/// collects every chunk, the result is a `Vec<DataChunk>`
+struct IdentitySinkState {
+    chunks: Vec<DataChunk>,
+}
+
+impl SinkState for IdentitySinkState {
+    fn consume(&mut self, _ctx: &ExecutionContext, chunk: DataChunk) -> Result<()> {
+        self.chunks.push(chunk);
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>, _ctx: &ExecutionContext) -> Result<SinkResult> {
+        Ok(Rc::new(self.chunks))
+    }
+}
 
 pub struct Pipeline {
     pub source: Rc<dyn Source>,
@@ -82,3 +514,30 @@ pub struct Topology {
     pub main: Rc<Pipeline>,
 }
 
+impl Pipeline {
+    /// the source, operators and sink in pipeline order
+    pub fn operators(&self) -> Vec<Rc<dyn Operator>> {
+        let mut operators: Vec<Rc<dyn Operator>> = vec![self.source.clone() as Rc<dyn Operator>];
+        operators.extend(self.operators.iter().cloned());
+        operators.push(self.sink.clone() as Rc<dyn Operator>);
+        operators
+    }
+}
+
+impl Topology {
+    /// the pipelines reachable from the main pipeline, parents before their children
+    pub fn pipelines(&self) -> Vec<Rc<Pipeline>> {
+        fn collect(pipeline: &Rc<Pipeline>, order: &mut Vec<Rc<Pipeline>>) {
+            if order.iter().any(|p| Rc::ptr_eq(p, pipeline)) {
+                return;
+            }
+            for parent in &pipeline.parents {
+                collect(parent, order);
+            }
+            order.push(pipeline.clone());
+        }
+        let mut order = vec![];
+        collect(&self.main, &mut order);
+        order
+    }
+}
@@ -0,0 +1,287 @@
+//! The `order_by` sink: sorts the rows of its input by key columns. The result is a
+//! `Vec<DataChunk>` of the sorted rows, which a `result_scan` streams into a later pipeline, or
+//! the `SortedRuns` on disk of a sort larger than the memory budget of the execution context,
+//! which the `result_scan` merges (see `exec::external_sort`).
+//!
+//! ```text
+//! v3 = order_by :input = v2
+//!         :order_by =
+//!             "name": asc,
+//!             "freight": desc_nulls_last
+//! ```
+//!
+//! The keys are encoded as normalized keys, see `exec::order_by`. The `top_n` sink keeps only
+//! the first `:limit` rows of the order, `ORDER BY ... LIMIT n`, in a bounded heap.
+
+use std::rc::Rc;
+use crate::error::Result;
+use crate::exec::external_sort::{ExternalSort, Sorted};
+use crate::exec::order_by::{is_sortable, SortOrder};
+use crate::exec::top_n::TopNHeap;
+use crate::exec::{ExecutionContext, SinkResult, SinkState};
+use crate::qir::checker::Checker;
+use crate::qir::printer::Printer;
+use crate::qir::{resolve_columns, Column, MinMax, Operator, Sink};
+use crate::vector::{DataChunk, VECTOR_SIZE};
+
+pub struct OrderBy {
+    pub input: Rc<dyn Operator>,
+    /// the key columns and their order, the first key sorts first
+    pub keys: Vec<(String, SortOrder)>,
+}
+
+fn print_keys(printer: &mut Printer, keys: &[(String, SortOrder)]) {
+    printer.entries("order_by", keys.iter().map(|(name, order)| (name.clone(), order.name().to_string())));
+}
+
+fn check_keys(checker: &mut Checker, input: &[Column], keys: &[(String, SortOrder)]) -> Option<()> {
+    if keys.is_empty() {
+        checker.error("no keys to order by");
+    }
+    let names: Vec<String> = keys.iter().map(|(name, _)| name.clone()).collect();
+    for key in checker.resolve(input, &names)? {
+        if !is_sortable(&key.data_type) {
+            checker.error(format!("can not order by `{}` of type {:?}", key.name, key.data_type));
+        }
+    }
+    Some(())
+}
+
+/// the positions of the key columns in `input` and their orders
+fn key_columns(input: &[String], keys: &[(String, SortOrder)]) -> Result<(Vec<usize>, Vec<SortOrder>)> {
+    let names: Vec<String> = keys.iter().map(|(name, _)| name.clone()).collect();
+    Ok((resolve_columns(input, &names)?, keys.iter().map(|(_, order)| *order).collect()))
+}
+
+/// the leading ascending keys, the ordering of the sorted rows
+fn ascending(keys: &[(String, SortOrder)]) -> Vec<String> {
+    keys.iter().take_while(|(_, order)| *order == SortOrder::ASC).map(|(name, _)| name.clone()).collect()
+}
+
+/// split the rows of a sink into the chunks of its result
+fn into_chunks(rows: DataChunk) -> Vec<DataChunk> {
+    (0..rows.len()).step_by(VECTOR_SIZE).map(|offset| rows.slice(offset, VECTOR_SIZE.min(rows.len() - offset))).collect()
+}
+
+impl Operator for OrderBy {
+    fn name(&self) -> &'static str {
+        "order_by"
+    }
+
+    fn output(&self) -> &[String] {
+        self.input.output()
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", printer.var(&self.input));
+        print_keys(printer, &self.keys);
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        let input = checker.input(&self.input)?;
+        check_keys(checker, &input, &self.keys)?;
+        Some(input)
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        self.input.min_max(column)
+    }
+
+    fn ordering(&self) -> Vec<String> {
+        ascending(&self.keys)
+    }
+}
+
+impl Sink for OrderBy {
+    fn open(&self, ctx: &ExecutionContext) -> Result<Box<dyn SinkState>> {
+        let (keys, orders) = key_columns(self.input.output(), &self.keys)?;
+        let types = ctx.schema(&self.input)?.into_iter().map(|c| c.data_type).collect();
+        let sort = ExternalSort::new(keys, orders, types, ctx.memory_budget(), ctx.spill_dir());
+        Ok(Box::new(OrderByState { sort }))
+    }
+}
+
+/// collects every chunk, sorted at the end or spilled in sorted runs
+struct OrderByState {
+    sort: ExternalSort,
+}
+
+impl SinkState for OrderByState {
+    fn consume(&mut self, _ctx: &ExecutionContext, chunk: DataChunk) -> Result<()> {
+        self.sort.push(chunk)
+    }
+
+    fn finish(self: Box<Self>, _ctx: &ExecutionContext) -> Result<SinkResult> {
+        Ok(match self.sort.finish()? {
+            Sorted::Memory(rows) => Rc::new(into_chunks(rows)),
+            Sorted::Runs(runs) => Rc::new(runs),
+        })
+    }
+}
+
+pub struct TopN {
+    pub input: Rc<dyn Operator>,
+    pub keys: Vec<(String, SortOrder)>,
+    /// the number of rows kept
+    pub limit: usize,
+}
+
+impl Operator for TopN {
+    fn name(&self) -> &'static str {
+        "top_n"
+    }
+
+    fn output(&self) -> &[String] {
+        self.input.output()
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", printer.var(&self.input));
+        print_keys(printer, &self.keys);
+        printer.arg("limit", self.limit);
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        let input = checker.input(&self.input)?;
+        check_keys(checker, &input, &self.keys)?;
+        Some(input)
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        self.input.min_max(column)
+    }
+
+    fn ordering(&self) -> Vec<String> {
+        ascending(&self.keys)
+    }
+}
+
+impl Sink for TopN {
+    fn open(&self, _ctx: &ExecutionContext) -> Result<Box<dyn SinkState>> {
+        let (keys, orders) = key_columns(self.input.output(), &self.keys)?;
+        Ok(Box::new(TopNState { heap: TopNHeap::new(keys, orders, self.limit) }))
+    }
+}
+
+struct TopNState {
+    heap: TopNHeap,
+}
+
+impl SinkState for TopNState {
+    fn consume(&mut self, _ctx: &ExecutionContext, chunk: DataChunk) -> Result<()> {
+        self.heap.push(&chunk)
+    }
+
+    fn finish(self: Box<Self>, _ctx: &ExecutionContext) -> Result<SinkResult> {
+        Ok(Rc::new(into_chunks(self.heap.finish()?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exec::{ExecutionContext, Interpreter};
+    use crate::qir::Topology;
+    use crate::vector::{DataChunk, Value, Vector, VECTOR_SIZE};
+
+    const ORDERS: &str = r#"
+pipeline1: Pipeline =
+    v1 = table_scan :table = "sale_orders"
+            :columns =
+                "order_id": { data_type: "i64", nullable: false },
+                "customer": { data_type: "string" },
+                "freight": { data_type: "f64" }
+    v2 = order_by :input = v1
+            :order_by =
+                "customer": asc,
+                "freight": desc_nulls_last
+pipeline2: Pipeline =
+    v1 = result_scan :input = pipeline1.v2
+    v2 = filter :input = v1
+            :expr = $in.order_id < 1000000
+            :projection = [ "order_id", "customer", "freight" ]
+    v3 = identity :input = v2
+"#;
+
+    fn context(rows: usize) -> ExecutionContext {
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("sale_orders", DataChunk::new(vec![
+            Vector::from((0..rows as i64).collect::<Vec<_>>()),
+            Vector::from((0..rows).map(|i| (i % 5 != 4).then(|| format!("customer_{}", i % 3))).collect::<Vec<_>>()
+                .iter().map(|s| s.as_deref()).collect::<Vec<_>>()),
+            Vector::from((0..rows).map(|i| (i % 7 != 0).then_some((i % 11) as f64)).collect::<Vec<_>>()),
+        ]));
+        ctx
+    }
+
+    #[test]
+    fn test_order_by() {
+        let topology = Topology::parse(ORDERS).unwrap();
+        let rows = VECTOR_SIZE + 500;
+        let result = Interpreter::new(context(rows)).run(&topology).unwrap();
+        let chunks = result.downcast::<Vec<DataChunk>>().unwrap();
+        assert_eq!(chunks[0].len(), VECTOR_SIZE);
+        let sorted = DataChunk::concat(&chunks).unwrap();
+        assert_eq!(sorted.len(), rows);
+
+        let table = context(rows).table("sale_orders").unwrap();
+        let mut expected: Vec<Vec<Value>> = (0..rows).map(|i| table.row(i)).collect();
+        // customer ascending with nulls last, freight descending with nulls last, stable
+        let customer = |row: &Vec<Value>| match &row[1] { Value::String(s) => Some(s.clone()), _ => None };
+        let freight = |row: &Vec<Value>| match row[2] { Value::F64(v) => Some(v), _ => None };
+        expected.sort_by(|a, b| (customer(a).is_none(), customer(a)).cmp(&(customer(b).is_none(), customer(b)))
+            .then_with(|| freight(a).is_none().cmp(&freight(b).is_none()))
+            .then_with(|| freight(b).unwrap_or(0.0).total_cmp(&freight(a).unwrap_or(0.0))));
+        assert_eq!((0..rows).map(|i| sorted.row(i)).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_spilled_order_by() {
+        let topology = Topology::parse(ORDERS).unwrap();
+        let rows = VECTOR_SIZE * 4;
+        let expected = Interpreter::new(context(rows)).run(&topology).unwrap().downcast::<Vec<DataChunk>>().unwrap();
+
+        let spill_dir = std::env::temp_dir().join(format!("dataframe-order-by-{}", std::process::id()));
+        std::fs::create_dir_all(&spill_dir).unwrap();
+        let mut ctx = context(rows);
+        ctx.set_memory_budget(32 * 1024);
+        ctx.set_spill_dir(&spill_dir);
+        let chunks = Interpreter::new(ctx).run(&topology).unwrap().downcast::<Vec<DataChunk>>().unwrap();
+        assert_eq!(chunks, expected);
+        // the runs are removed with the results of the topology
+        assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 0);
+        std::fs::remove_dir(&spill_dir).unwrap();
+    }
+
+    #[test]
+    fn test_print_and_check() {
+        let topology = Topology::parse(ORDERS).unwrap();
+        let printed = topology.to_string();
+        assert!(printed.contains("\"freight\": desc_nulls_last"), "{printed}");
+        assert!(printed.contains("v1 = result_scan :input = pipeline1.v2"), "{printed}");
+        assert_eq!(Topology::parse(&printed).unwrap().to_string(), printed);
+
+        let err = Topology::parse(&ORDERS.replace("desc_nulls_last", "downwards")).err().unwrap().to_string();
+        assert!(err.contains("unknown sort order `downwards`"), "{err}");
+        let err = Topology::parse(&ORDERS.replace("\"customer\": asc", "\"name\": asc")).err().unwrap().to_string();
+        assert!(err.contains("unknown column `name`"), "{err}");
+        let err = Topology::parse(&ORDERS.replace("pipeline1.v2\n", "pipeline1.v1\n")).err().unwrap().to_string();
+        assert!(err.contains("is not the sink of its pipeline"), "{err}");
+    }
+
+    #[test]
+    fn test_top_n() {
+        let source = ORDERS.replace("v2 = order_by", "v2 = top_n").replace("desc_nulls_last", "desc_nulls_last\n            :limit = 10");
+        let topology = Topology::parse(&source).unwrap();
+        let rows = VECTOR_SIZE * 3;
+        let top = Interpreter::new(context(rows)).run(&topology).unwrap().downcast::<Vec<DataChunk>>().unwrap();
+        let sorted = Interpreter::new(context(rows)).run(&Topology::parse(ORDERS).unwrap()).unwrap().downcast::<Vec<DataChunk>>().unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0], sorted[0].slice(0, 10));
+
+        let printed = topology.to_string();
+        assert!(printed.contains("\"freight\": desc_nulls_last\n            :limit = 10"), "{printed}");
+        assert_eq!(Topology::parse(&printed).unwrap().to_string(), printed);
+        let err = Topology::parse(&source.replace(":limit = 10", ":limit = -1")).err().unwrap().to_string();
+        assert!(err.contains("expected a limit"), "{err}");
+    }
+}
@@ -0,0 +1,190 @@
+//! Parquet scans: a source reading the columns of parquet files instead of a registered table.
+//!
+//! ```text
+//! v1 = parquet_scan :path = "hits.parquet"
+//!         :columns =
+//!             "CounterID": { data_type: "i32", nullable: false },
+//!             "URL": { data_type: "string", nullable: false }
+//!         :output = [ "URL" ]
+//!         :minmaxes = "CounterID": [62, 62]
+//! ```
+//!
+//! The arguments are those of a `table_scan` with `:path` in place of `:table`. The path is a
+//! file, a directory of `.parquet` files, or a pattern such as `"hits_multi/hits_*.parquet"`.
+//! `:columns` lists the columns of the files the plan uses, with their types in the files (see
+//! `ParquetFiles::schema`), and only the `:output` columns are read, see `exec::parquet`. The row
+//! groups whose statistics have no value in a `:minmaxes` range, or in the key range of a runtime
+//! filter, are skipped: the minmaxes of a parquet scan hold the values of the rows the plan needs.
+//! A `filter` right after the scan is tested on the columns it uses before the other columns are
+//! read, which are then only decoded for the rows passing it.
+
+use std::path::PathBuf;
+use crate::error::{Error, Result};
+use crate::exec::parquet::ParquetFiles;
+use crate::exec::runtime_filter::ScanFilter;
+use crate::exec::{ExecutionContext, SourceReader};
+use crate::qir::checker::Checker;
+use crate::qir::expr::Expr;
+use crate::qir::printer::Printer;
+use crate::qir::{lexer, Column, MinMax, Operator, Scan, Source};
+
+pub struct ParquetScan {
+    pub path: PathBuf,
+    /// the columns, output, minmaxes and ordering of the scan, `table.name` is the path
+    pub scan: Scan,
+}
+
+impl ParquetScan {
+    fn column(&self, name: &str) -> Result<Column> {
+        self.scan.table.columns.iter().find(|c| c.name == name).cloned().ok_or_else(|| Error::ColumnNotFound(name.to_string()))
+    }
+}
+
+impl Operator for ParquetScan {
+    fn name(&self) -> &'static str {
+        "parquet_scan"
+    }
+
+    fn output(&self) -> &[String] {
+        &self.scan.output
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("path", lexer::quote(&self.path.to_string_lossy()));
+        self.scan.print_columns(printer);
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        self.scan.check(checker)
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        self.scan.min_max(column)
+    }
+
+    fn ordering(&self) -> Vec<String> {
+        self.scan.ordering()
+    }
+}
+
+impl Source for ParquetScan {
+    fn open(&self, ctx: &ExecutionContext) -> Result<Box<dyn SourceReader>> {
+        self.open_filtered(ctx, vec![])
+    }
+
+    fn open_filtered(&self, ctx: &ExecutionContext, filters: Vec<ScanFilter>) -> Result<Box<dyn SourceReader>> {
+        self.open_pushdown(ctx, filters, None)
+    }
+
+    fn open_pushdown(&self, ctx: &ExecutionContext, filters: Vec<ScanFilter>, predicate: Option<&Expr>) -> Result<Box<dyn SourceReader>> {
+        let columns = self.scan.output.iter().map(|name| self.column(name)).collect::<Result<Vec<_>>>()?;
+        let mut ranges = vec![];
+        for minmax in &self.scan.minmaxes {
+            ranges.push((self.column(&minmax.column)?, minmax.min.resolve(ctx)?, minmax.max.resolve(ctx)?));
+        }
+        for scan in &filters {
+            for (&column, range) in scan.columns.iter().zip(scan.filter.ranges()) {
+                if let Some((min, max)) = range {
+                    ranges.push((columns[column].clone(), min.clone(), max.clone()));
+                }
+            }
+        }
+        Ok(Box::new(ParquetFiles::open(&self.path)?.read(&columns, &ranges, filters, predicate, ctx.scan_threads())?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exec::parquet::tests::write_orders;
+    use crate::exec::{ExecutionContext, Interpreter};
+    use crate::qir::Topology;
+    use crate::vector::{DataChunk, Value, Vector};
+
+    const ORDERS: &str = r#"
+pipeline1: Pipeline =
+    v1 = parquet_scan :path = "PATH"
+            :columns =
+                "order_id": { data_type: "i64", nullable: false },
+                "customer_id": { data_type: "i16" },
+                "note": { data_type: "string" }
+            :output = [ "note", "customer_id" ]
+            :minmaxes = "order_id": [1200, 1300]
+    v2 = identity :input = v1
+"#;
+
+    fn run(text: &str, ctx: ExecutionContext) -> Vec<Vec<Value>> {
+        let topology = Topology::parse(text).unwrap();
+        let result = Interpreter::new(ctx).run(&topology).unwrap();
+        let chunks = result.downcast::<Vec<DataChunk>>().unwrap();
+        chunks.iter().flat_map(|c| (0..c.len()).map(|i| c.row(i))).collect()
+    }
+
+    #[test]
+    fn test_parquet_scan() {
+        let path = write_orders("scan", 3500);
+        let text = ORDERS.replace("PATH", &path.to_string_lossy());
+        let printed = Topology::parse(&text).unwrap().to_string();
+        assert!(printed.contains(&format!("v1 = parquet_scan :path = \"{}\"\n", path.to_string_lossy())), "{printed}");
+        assert!(printed.contains(":minmaxes =\n                \"order_id\": [1200, 1300]"), "{printed}");
+        assert_eq!(Topology::parse(&printed).unwrap().to_string(), printed);
+
+        // the minmax only keeps the second row group
+        let rows = run(&text, ExecutionContext::new());
+        assert_eq!(rows.len(), 1000);
+        assert_eq!(rows[0], vec![Value::String("note 1000".into()), Value::I16(0)]);
+        assert_eq!(rows[1], vec![Value::String("note 1001".into()), Value::Null]);
+
+        let err = Topology::parse(&text.replace("\"order_id\": [1200, 1300]", "\"order_id\": [\"a\", \"b\"]")).err().unwrap().to_string();
+        assert!(err.contains("can not cast"), "{err}");
+        let err = Interpreter::new(ExecutionContext::new()).run(&Topology::parse(&text.replace("\"i16\"", "\"i8\"")).unwrap()).err().unwrap();
+        assert_eq!(err.to_string(), "type mismatch: expected I8, found I16 in column `customer_id`");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parquet_scan_runtime_filter() {
+        let path = write_orders("join", 3500);
+        let text = format!(r#"
+pipeline1: Pipeline =
+    v1 = table_scan :table = "picked"
+            :columns = "order_id": {{ data_type: "i64", nullable: false }}
+    ht1 = build_hash :input = v1 :key = "order_id"
+pipeline2: Pipeline =
+    v1 = parquet_scan :path = "{}"
+            :columns =
+                "order_id": {{ data_type: "i64", nullable: false }},
+                "note": {{ data_type: "string" }}
+    v2 = hash_join :input = v1 :ht = pipeline1.ht1
+            :key = $in.order_id
+            :projection = [ $in.order_id, $in.note ]
+    v3 = identity :input = v2
+"#, path.to_string_lossy());
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("picked", DataChunk::new(vec![Vector::from(vec![2001i64, 2500, 2999])]));
+        let rows = run(&text, ctx);
+        assert_eq!(rows, vec![
+            vec![Value::I64(2001), Value::Null],
+            vec![Value::I64(2500), Value::String("note 2500".into())],
+            vec![Value::I64(2999), Value::String("note 2999".into())],
+        ]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parquet_scan_late_filter() {
+        let path = write_orders("late", 3500);
+        let text = ORDERS.replace("PATH", &path.to_string_lossy())
+            .replace("            :output = [ \"note\", \"customer_id\" ]\n            :minmaxes = \"order_id\": [1200, 1300]\n", "")
+            .replace("v2 = identity :input = v1", "v2 = filter :input = v1
+            :expr = $in.customer_id == 5 && $in.order_id >= 1000
+            :projection = [ \"note\", \"order_id\" ]
+    v3 = identity :input = v2");
+        // the first row group fails the filter, the notes of the others are read for 1 of 100 rows
+        let rows = run(&text, ExecutionContext::new());
+        let expected: Vec<Vec<Value>> = (1000..3500i64).filter(|i| i % 100 == 5 && i % 7 != 0)
+            .map(|i| vec![if i % 3 == 0 { Value::Null } else { Value::String(format!("note {i}")) }, Value::I64(i)])
+            .collect();
+        assert_eq!(rows, expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,1423 @@
+//! Parser of the qir text format described in the README.
+//!
+//! ```text
+//! // scan( customers ) |> filter |> identity
+//! pipeline1: Pipeline =
+//!     v1 = table_scan :table = "customers"
+//!             :columns =
+//!                 "customer_id": { data_type: "i32", nullable: false },
+//!                 "name": { data_type: "string" },
+//!                 "gender": { data_type: "string" }
+//!     v2 = filter :input = v1
+//!             :expr = $in.gender == "M" && $in.name like "abc%"
+//!             :projection = [ "customer_id", "name" ]
+//!     v3 = identity :input = v2
+//! ```
+//!
+//! A topology is a list of pipelines, the last one is the main pipeline. A pipeline is a list of
+//! `variable = operator :argument = value ...` statements: the first is the source, the last the
+//! sink. `Pipeline(pipeline1, ...)` lists parents explicitly, a pipeline referenced as
+//! `pipeline1.v3` becomes a parent as well.
+//!
+//! Parsing happens in two steps: the text is parsed into untyped `Node`s, which are then checked
+//! and turned into operators and typed expressions. Errors of both steps point at the offending
+//! source span.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::error::{Error, Result};
+use crate::exec::aggregate::AggregateKind;
+use crate::exec::cast::{cast_exact, cast_value};
+use crate::exec::order_by::{is_sortable, SortOrder};
+use crate::exec::reduce::Reduction;
+use crate::exec::window::{Frame, FrameUnit, WindowKind};
+use crate::qir::aggregate::SimdAggregate;
+use crate::qir::expr::{BinaryOp, Expr};
+use crate::qir::group_by::{Aggregate, HashGroupBy};
+use crate::qir::ipc::{ArrowScan, ArrowWrite};
+use crate::qir::join::{BuildHash, HashJoin, JoinColumn, JoinType};
+use crate::qir::lexer::{tokenize, Span, Token};
+use crate::qir::merge_join::{self, MergeJoin};
+use crate::qir::order_by::{OrderBy, TopN};
+use crate::qir::parquet::ParquetScan;
+use crate::qir::printer::strings;
+use crate::qir::window::{is_range_key, Window, WindowFunction};
+use crate::qir::{Bound, Column, DataType, Filter, IdentitySink, MinMax, Operator, Pipeline, ResultScan, Scan, Sink, Source, Table, Topology};
+use crate::vector::Value;
+
+/// an untyped value or expression of the text
+#[derive(Debug, Clone)]
+struct Node {
+    kind: NodeKind,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    Literal(Value),
+    /// `v1`, `pipeline1.ht1`, `$in.name` (the variable is kept with its `$`)
+    Path(Vec<String>),
+    List(Vec<Node>),
+    /// `{ key: value, ... }`
+    Object(Vec<(String, Node)>),
+    /// `"key": value, "key": value`, commas are optional
+    Entries(Vec<(String, Span, Node)>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Negate(Box<Node>),
+    IsNull { expr: Box<Node>, negated: bool },
+    Like { expr: Box<Node>, pattern: String, negated: bool },
+    InList { expr: Box<Node>, list: Vec<Node>, negated: bool },
+    Cast(Box<Node>, DataType),
+    Call(String, Vec<Node>),
+}
+
+struct Arg {
+    name: String,
+    span: Span,
+    value: Node,
+}
+
+struct Statement {
+    var: String,
+    span: Span,
+    op: String,
+    op_span: Span,
+    args: Vec<Arg>,
+}
+
+struct PipelineDef {
+    name: String,
+    span: Span,
+    parents: Vec<(String, Span)>,
+    statements: Vec<Statement>,
+}
+
+/// recursive descent over the tokens
+struct Parser<'s> {
+    source: &'s str,
+    tokens: Vec<(Token, Span)>,
+    pos: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn new(source: &'s str) -> Result<Self> {
+        Ok(Parser { source, tokens: tokenize(source)?, pos: 0 })
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn peek_at(&self, n: usize) -> &Token {
+        &self.tokens[(self.pos + n).min(self.tokens.len() - 1)].0
+    }
+
+    fn span(&self) -> Span {
+        self.tokens[self.pos].1
+    }
+
+    /// the span of the last consumed token
+    fn prev_span(&self) -> Span {
+        self.tokens[self.pos.saturating_sub(1)].1
+    }
+
+    fn next(&mut self) -> (Token, Span) {
+        let token = self.tokens[self.pos].clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, expected: &str) -> Error {
+        self.span().error(self.source, format!("expected {expected}, found {}", self.peek().describe()))
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == token {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<Span> {
+        if self.peek() == &token {
+            Ok(self.next().1)
+        } else {
+            Err(self.error(&token.describe()))
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Ident(name) if name == keyword)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        self.is_keyword(keyword) && { self.next(); true }
+    }
+
+    fn ident(&mut self) -> Result<(String, Span)> {
+        match self.next() {
+            (Token::Ident(name), span) => Ok((name, span)),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("an identifier"))
+            }
+        }
+    }
+
+    /// an identifier or a quoted name
+    fn name(&mut self) -> Result<(String, Span)> {
+        match self.peek().clone() {
+            Token::String(name) => Ok((name, self.next().1)),
+            _ => self.ident().map_err(|_| self.error("a name")),
+        }
+    }
+
+    fn topology(&mut self) -> Result<Vec<PipelineDef>> {
+        let mut pipelines = vec![];
+        while self.peek() != &Token::Eof {
+            pipelines.push(self.pipeline()?);
+        }
+        if pipelines.is_empty() {
+            return Err(self.error("a pipeline"));
+        }
+        Ok(pipelines)
+    }
+
+    fn pipeline(&mut self) -> Result<PipelineDef> {
+        let (name, span) = self.ident()?;
+        self.expect(Token::Colon)?;
+        if !self.eat_keyword("Pipeline") {
+            return Err(self.error("`Pipeline`"));
+        }
+        let mut parents = vec![];
+        if self.eat(&Token::LParen) {
+            loop {
+                parents.push(self.ident()?);
+                if !self.eat(&Token::Comma) {
+                    break;
+                }
+            }
+            self.expect(Token::RParen)?;
+        }
+        self.expect(Token::Assign)?;
+
+        let mut statements = vec![];
+        // a statement starts with `name =`, a pipeline with `name :`
+        while matches!(self.peek(), Token::Ident(_)) && self.peek_at(1) == &Token::Assign {
+            statements.push(self.statement()?);
+        }
+        if statements.is_empty() {
+            return Err(self.error("a statement `variable = operator ...`"));
+        }
+        Ok(PipelineDef { name, span, parents, statements })
+    }
+
+    fn statement(&mut self) -> Result<Statement> {
+        let (var, span) = self.ident()?;
+        self.expect(Token::Assign)?;
+        let (op, op_span) = self.ident()?;
+        let mut args = vec![];
+        while self.eat(&Token::Colon) {
+            let (name, span) = self.ident()?;
+            self.expect(Token::Assign)?;
+            let value = self.value()?;
+            args.push(Arg { name, span, value });
+        }
+        Ok(Statement { var, span, op, op_span, args })
+    }
+
+    /// whether the next tokens start an entry `"key": value`, and not the next argument `:name =`
+    fn at_entry(&self, offset: usize) -> bool {
+        matches!(self.peek_at(offset), Token::String(_)) && self.peek_at(offset + 1) == &Token::Colon
+            && !(matches!(self.peek_at(offset + 2), Token::Ident(_)) && self.peek_at(offset + 3) == &Token::Assign)
+    }
+
+    /// an argument value: `"key": value` entries or an expression
+    fn value(&mut self) -> Result<Node> {
+        if !self.at_entry(0) {
+            return self.expr();
+        }
+        let start = self.span();
+        let mut entries = vec![];
+        loop {
+            let (key, key_span) = self.name()?;
+            self.expect(Token::Colon)?;
+            entries.push((key, key_span, self.expr()?));
+            if self.peek() == &Token::Comma && self.at_entry(1) {
+                self.next();
+            } else if !self.at_entry(0) {
+                break;
+            }
+        }
+        Ok(Node { kind: NodeKind::Entries(entries), span: start.to(self.prev_span()) })
+    }
+
+    fn expr(&mut self) -> Result<Node> {
+        self.binary(0)
+    }
+
+    /// left associative binary operators, loosest first. comparisons sit between `&&` and `+ -`.
+    const LEVELS: [&'static [(Token, BinaryOp)]; 4] = [
+        &[(Token::Or, BinaryOp::Or)],
+        &[(Token::And, BinaryOp::And)],
+        &[(Token::Plus, BinaryOp::Add), (Token::Minus, BinaryOp::Sub)],
+        &[(Token::Star, BinaryOp::Mul), (Token::Slash, BinaryOp::Div), (Token::Percent, BinaryOp::Mod)],
+    ];
+    const COMPARISON_LEVEL: usize = 2;
+
+    /// the operators of `LEVELS[level..]` and the operands they bind
+    fn binary(&mut self, level: usize) -> Result<Node> {
+        let operand = |parser: &mut Self| match level + 1 {
+            Self::COMPARISON_LEVEL => parser.comparison(),
+            next if next == Self::LEVELS.len() => parser.unary(),
+            next => parser.binary(next),
+        };
+        let mut left = operand(self)?;
+        while let Some(&(_, op)) = Self::LEVELS[level].iter().find(|(token, _)| token == self.peek()) {
+            self.next();
+            let right = operand(self)?;
+            let span = left.span.to(right.span);
+            left = Node { kind: NodeKind::Binary(op, Box::new(left), Box::new(right)), span };
+        }
+        Ok(left)
+    }
+
+    /// `a < b`, `a is [not] null`, `a [not] like "p"`, `a [not] in [...]`. comparisons do not chain.
+    fn comparison(&mut self) -> Result<Node> {
+        let left = self.binary(Self::COMPARISON_LEVEL)?;
+        let op = match self.peek() {
+            Token::Eq => Some(BinaryOp::Eq),
+            Token::NotEq => Some(BinaryOp::NotEq),
+            Token::Lt => Some(BinaryOp::Lt),
+            Token::LtEq => Some(BinaryOp::LtEq),
+            Token::Gt => Some(BinaryOp::Gt),
+            Token::GtEq => Some(BinaryOp::GtEq),
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.next();
+            let right = self.binary(Self::COMPARISON_LEVEL)?;
+            let span = left.span.to(right.span);
+            return Ok(Node { kind: NodeKind::Binary(op, Box::new(left), Box::new(right)), span });
+        }
+
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            if !self.eat_keyword("null") {
+                return Err(self.error("`null`"));
+            }
+            let span = left.span.to(self.prev_span());
+            return Ok(Node { kind: NodeKind::IsNull { expr: Box::new(left), negated }, span });
+        }
+        let negated = self.is_keyword("not") && matches!(self.peek_at(1), Token::Ident(k) if k == "like" || k == "in");
+        if negated {
+            self.next();
+        }
+        if self.eat_keyword("like") {
+            let pattern = match self.next() {
+                (Token::String(pattern), _) => pattern,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("a pattern string"));
+                }
+            };
+            let span = left.span.to(self.prev_span());
+            return Ok(Node { kind: NodeKind::Like { expr: Box::new(left), pattern, negated }, span });
+        }
+        if self.eat_keyword("in") {
+            let list = match self.primary()? {
+                Node { kind: NodeKind::List(list), .. } => list,
+                other => return Err(other.span.error(self.source, "expected a list `[ ... ]`")),
+            };
+            let span = left.span.to(self.prev_span());
+            return Ok(Node { kind: NodeKind::InList { expr: Box::new(left), list, negated }, span });
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        let start = self.span();
+        if self.eat(&Token::Not) {
+            let expr = self.unary()?;
+            let span = start.to(expr.span);
+            return Ok(Node { kind: NodeKind::Not(Box::new(expr)), span });
+        }
+        if self.eat(&Token::Minus) {
+            // a negative number is a literal, `-(1)` is a negation
+            let literal = match self.peek() {
+                Token::Int(v) => Some(match v.checked_neg() {
+                    Some(v) if i32::try_from(v).is_ok() => Value::I32(v as i32),
+                    Some(v) => Value::I64(v),
+                    None => return Err(self.span().error(self.source, "integer is too large")),
+                }),
+                Token::Float(v) => Some(Value::F64(-v)),
+                _ => None,
+            };
+            if let Some(value) = literal {
+                let span = start.to(self.next().1);
+                return Ok(Node { kind: NodeKind::Literal(value), span });
+            }
+            let expr = self.unary()?;
+            let span = start.to(expr.span);
+            return Ok(Node { kind: NodeKind::Negate(Box::new(expr)), span });
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Node> {
+        let (token, start) = self.next();
+        let kind = match token {
+            Token::Int(v) => NodeKind::Literal(match i32::try_from(v) {
+                Ok(v) => Value::I32(v),
+                Err(_) => Value::I64(v),
+            }),
+            Token::Float(v) => NodeKind::Literal(Value::F64(v)),
+            Token::String(s) => NodeKind::Literal(Value::String(s)),
+            Token::Variable(var) => {
+                self.expect(Token::Dot)?;
+                let (name, _) = self.name()?;
+                NodeKind::Path(vec![format!("${var}"), name])
+            }
+            Token::Ident(name) => match name.as_str() {
+                "true" => NodeKind::Literal(Value::Bool(true)),
+                "false" => NodeKind::Literal(Value::Bool(false)),
+                "null" => NodeKind::Literal(Value::Null),
+                "cast" if self.peek() == &Token::LParen => {
+                    self.next();
+                    let expr = self.expr()?;
+                    if !self.eat_keyword("as") {
+                        return Err(self.error("`as`"));
+                    }
+                    let data_type = self.data_type()?;
+                    self.expect(Token::RParen)?;
+                    NodeKind::Cast(Box::new(expr), data_type)
+                }
+                _ if self.peek() == &Token::LParen => {
+                    self.next();
+                    let args = self.list(Token::RParen)?;
+                    NodeKind::Call(name, args)
+                }
+                _ => {
+                    let mut path = vec![name];
+                    while self.eat(&Token::Dot) {
+                        path.push(self.name()?.0);
+                    }
+                    NodeKind::Path(path)
+                }
+            },
+            Token::LParen => {
+                let expr = self.expr()?;
+                self.expect(Token::RParen)?;
+                return Ok(expr);
+            }
+            Token::LBracket => NodeKind::List(self.list(Token::RBracket)?),
+            Token::LBrace => {
+                let mut fields = vec![];
+                while self.peek() != &Token::RBrace {
+                    let (key, _) = self.name()?;
+                    self.expect(Token::Colon)?;
+                    fields.push((key, self.expr()?));
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+                self.expect(Token::RBrace)?;
+                NodeKind::Object(fields)
+            }
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("an expression"));
+            }
+        };
+        Ok(Node { kind, span: start.to(self.prev_span()) })
+    }
+
+    /// comma separated expressions up to `end`, a trailing comma is allowed
+    fn list(&mut self, end: Token) -> Result<Vec<Node>> {
+        let mut items = vec![];
+        while self.peek() != &end {
+            items.push(self.expr()?);
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(end)?;
+        Ok(items)
+    }
+
+    /// `i32`, `list<string>`, `map<string, i64>`, `struct<id: i32, name: string>`
+    fn data_type(&mut self) -> Result<DataType> {
+        let (name, span) = self.ident()?;
+        let data_type = match name.as_str() {
+            "i8" => DataType::I8,
+            "i16" => DataType::I16,
+            "i32" => DataType::I32,
+            "i64" => DataType::I64,
+            "u8" => DataType::U8,
+            "u16" => DataType::U16,
+            "u32" => DataType::U32,
+            "u64" => DataType::U64,
+            "f32" => DataType::F32,
+            "f64" => DataType::F64,
+            "decimal" => DataType::Decimal,
+            "bool" => DataType::Bool,
+            "string" => DataType::String,
+            "date" => DataType::Date,
+            "datetime" => DataType::DateTime,
+            "list" => {
+                self.expect(Token::Lt)?;
+                let element = self.data_type()?;
+                self.expect(Token::Gt)?;
+                DataType::List(Box::new(element))
+            }
+            "map" => {
+                self.expect(Token::Lt)?;
+                let key = self.data_type()?;
+                self.expect(Token::Comma)?;
+                let value = self.data_type()?;
+                self.expect(Token::Gt)?;
+                DataType::Map(Box::new(key), Box::new(value))
+            }
+            "struct" => {
+                self.expect(Token::Lt)?;
+                let mut columns = vec![];
+                loop {
+                    let (name, _) = self.name()?;
+                    self.expect(Token::Colon)?;
+                    columns.push(Column { name, data_type: self.data_type()?, nullable: true });
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+                self.expect(Token::Gt)?;
+                DataType::Struct(Box::new(Table { name: String::new(), columns }))
+            }
+            _ => return Err(span.error(self.source, format!("unknown data type `{name}`"))),
+        };
+        Ok(data_type)
+    }
+}
+
+/// an operator built from a statement, by its role in the pipeline
+enum Built {
+    Source(Rc<dyn Source>),
+    Operator(Rc<dyn Operator>),
+    Sink(Rc<dyn Sink>),
+}
+
+/// a variable of a pipeline: the operator and the columns it produces
+#[derive(Clone)]
+struct Var {
+    operator: Rc<dyn Operator>,
+    schema: Vec<Column>,
+}
+
+/// the arguments of a statement, each must be used exactly once
+struct Args<'a> {
+    statement: &'a Statement,
+    used: Vec<bool>,
+}
+
+impl<'a> Args<'a> {
+    fn new(source: &str, statement: &'a Statement) -> Result<Self> {
+        for (i, arg) in statement.args.iter().enumerate() {
+            if statement.args[..i].iter().any(|a| a.name == arg.name) {
+                return Err(arg.span.error(source, format!("duplicate argument `:{}`", arg.name)));
+            }
+        }
+        Ok(Args { statement, used: vec![false; statement.args.len()] })
+    }
+
+    fn optional(&mut self, name: &str) -> Option<&'a Node> {
+        let index = self.statement.args.iter().position(|a| a.name == name)?;
+        self.used[index] = true;
+        Some(&self.statement.args[index].value)
+    }
+
+    fn required(&mut self, source: &str, name: &str) -> Result<&'a Node> {
+        let statement = self.statement;
+        self.optional(name)
+            .ok_or_else(|| statement.op_span.error(source, format!("missing argument `:{name}` of `{}`", statement.op)))
+    }
+
+    /// fail on arguments the operator does not know
+    fn finish(&self, source: &str) -> Result<()> {
+        match self.used.iter().position(|used| !used) {
+            Some(i) => {
+                let arg = &self.statement.args[i];
+                Err(arg.span.error(source, format!("unknown argument `:{}` of `{}`", arg.name, self.statement.op)))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// turns the parsed pipelines into a `Topology`
+struct Builder<'s> {
+    source: &'s str,
+    /// the built pipelines and their variables, by name
+    pipelines: HashMap<String, (Rc<Pipeline>, HashMap<String, Var>)>,
+    /// the variables of the pipeline being built
+    vars: HashMap<String, Var>,
+    /// the pipelines referenced by the pipeline being built
+    referenced: Vec<Rc<Pipeline>>,
+    /// the `build_hash` sinks built so far, for the `:ht` of joins
+    hash_tables: Vec<Rc<BuildHash>>,
+}
+
+fn ptr_eq<T: ?Sized>(a: &Rc<T>, b: &Rc<T>) -> bool {
+    std::ptr::addr_eq(Rc::as_ptr(a), Rc::as_ptr(b))
+}
+
+impl<'s> Builder<'s> {
+    fn error(&self, span: Span, message: impl Into<String>) -> Error {
+        span.error(self.source, message)
+    }
+
+    fn topology(&mut self, defs: Vec<PipelineDef>) -> Result<Topology> {
+        let mut order = vec![];
+        for def in &defs {
+            if self.pipelines.contains_key(&def.name) {
+                return Err(self.error(def.span, format!("duplicate pipeline `{}`", def.name)));
+            }
+            let pipeline = self.pipeline(def)?;
+            order.push((def.name.clone(), def.span, pipeline.clone()));
+            self.pipelines.insert(def.name.clone(), (pipeline, std::mem::take(&mut self.vars)));
+        }
+
+        let (_, _, main) = order.pop().expect("at least one pipeline");
+        // every other pipeline must be run before the main pipeline
+        for (name, span, pipeline) in order {
+            if !reaches(&main, &pipeline) {
+                return Err(self.error(span, format!("pipeline `{name}` is not a parent of the main pipeline")));
+            }
+        }
+        Ok(Topology { main })
+    }
+
+    fn pipeline(&mut self, def: &PipelineDef) -> Result<Rc<Pipeline>> {
+        self.referenced.clear();
+        let mut parents: Vec<Rc<Pipeline>> = vec![];
+        for (name, span) in &def.parents {
+            let (parent, _) = self.pipelines.get(name).ok_or_else(|| self.error(*span, format!("unknown pipeline `{name}`")))?;
+            parents.push(parent.clone());
+        }
+
+        let mut built = vec![];
+        for statement in &def.statements {
+            if self.vars.contains_key(&statement.var) {
+                return Err(self.error(statement.span, format!("duplicate variable `{}`", statement.var)));
+            }
+            let (operator, schema) = self.statement(statement)?;
+            let var = match &operator {
+                Built::Source(source) => source.clone() as Rc<dyn Operator>,
+                Built::Operator(operator) => operator.clone(),
+                Built::Sink(sink) => sink.clone() as Rc<dyn Operator>,
+            };
+            self.vars.insert(statement.var.clone(), Var { operator: var, schema });
+            built.push((operator, statement));
+        }
+
+        for parent in std::mem::take(&mut self.referenced) {
+            if !parents.iter().any(|p| ptr_eq(p, &parent)) {
+                parents.push(parent);
+            }
+        }
+
+        let last = built.len() - 1;
+        let mut source = None;
+        let mut operators = vec![];
+        let mut sink = None;
+        for (i, (operator, statement)) in built.into_iter().enumerate() {
+            match (operator, i) {
+                (Built::Source(s), 0) => source = Some(s),
+                (Built::Sink(s), i) if i == last && i > 0 => sink = Some(s),
+                (Built::Operator(o), i) if i > 0 && i < last => operators.push(o),
+                (_, 0) => return Err(self.error(statement.op_span, format!("the first operator of a pipeline must be a source, found `{}`", statement.op))),
+                (_, i) if i == last => return Err(self.error(statement.op_span, format!("the last operator of a pipeline must be a sink, found `{}`", statement.op))),
+                _ => return Err(self.error(statement.op_span, format!("`{}` can only be used as the source or sink of a pipeline", statement.op))),
+            }
+        }
+        let (source, sink) = match (source, sink) {
+            (Some(source), Some(sink)) => (source, sink),
+            _ => return Err(self.error(def.span, format!("pipeline `{}` needs a source and a sink", def.name))),
+        };
+        Ok(Rc::new(Pipeline { source, operators, sink, parents }))
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(Built, Vec<Column>)> {
+        let mut args = Args::new(self.source, statement)?;
+        let built = match statement.op.as_str() {
+            "table_scan" | "parquet_scan" | "arrow_scan" => self.table_scan(statement, &mut args)?,
+            "result_scan" => self.result_scan(&mut args)?,
+            "filter" => self.filter(&mut args)?,
+            "identity" => self.identity(&mut args)?,
+            "write_arrow" => self.write_arrow(&mut args)?,
+            "build_hash" => self.build_hash(&mut args)?,
+            "hash_group_by" => self.hash_group_by(&mut args)?,
+            "aggregate" => self.aggregate(&mut args)?,
+            "order_by" => self.order_by(&mut args)?,
+            "top_n" => self.top_n(&mut args)?,
+            "window" => self.window(&mut args)?,
+            op if JoinType::ALL.iter().any(|t| t.operator() == op) => {
+                let join_type = *JoinType::ALL.iter().find(|t| t.operator() == op).unwrap();
+                self.hash_join(join_type, &mut args)?
+            }
+            op if JoinType::ALL.iter().any(|&t| merge_join::operator(t) == op) => {
+                let join_type = *JoinType::ALL.iter().find(|&&t| merge_join::operator(t) == op).unwrap();
+                self.merge_join(join_type, &mut args)?
+            }
+            op => return Err(self.error(statement.op_span, format!("unknown operator `{op}`"))),
+        };
+        args.finish(self.source)?;
+        Ok(built)
+    }
+
+    /// a `table_scan`, or a `parquet_scan` or `arrow_scan` with a `:path` in place of the `:table`
+    fn table_scan(&mut self, statement: &Statement, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let file = statement.op != "table_scan";
+        let name = self.string(args.required(self.source, if file { "path" } else { "table" })?)?;
+        let columns = self.columns(args.required(self.source, "columns")?)?;
+        let (output, span) = match args.optional("output") {
+            Some(node) => (self.strings(node)?, Some(node.span)),
+            None => (columns.iter().map(|c| c.name.clone()).collect(), None),
+        };
+        let schema = self.project(&columns, &output, span)?;
+        let minmaxes = match args.optional("minmaxes") {
+            Some(node) => self.minmaxes(node, &columns)?,
+            None => vec![],
+        };
+        let ordered_by = match args.optional("ordered_by") {
+            Some(node) => {
+                let ordered_by = self.strings(node)?;
+                self.project(&columns, &ordered_by, Some(node.span))?;
+                ordered_by
+            }
+            None => vec![],
+        };
+        let table = Rc::new(Table { name, columns });
+        let scan = Scan { name: statement.var.clone(), table, output, minmaxes, ordered_by };
+        let source: Rc<dyn Source> = match statement.op.as_str() {
+            "parquet_scan" => Rc::new(ParquetScan { path: scan.table.name.clone().into(), scan }),
+            "arrow_scan" => Rc::new(ArrowScan { path: scan.table.name.clone().into(), scan }),
+            _ => Rc::new(scan),
+        };
+        Ok((Built::Source(source), schema))
+    }
+
+    fn result_scan(&mut self, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let node = args.required(self.source, "input")?;
+        let NodeKind::Path(path) = &node.kind else {
+            return Err(self.error(node.span, "expected the sink of a parent pipeline `pipeline1.v3`"));
+        };
+        let input = self.parent_sink(node, path)?;
+        if self.hash_tables.iter().any(|t| ptr_eq(&(t.clone() as Rc<dyn Operator>), &input.operator)) {
+            return Err(self.error(node.span, format!("`{}` is a hash table, not a result of chunks", path.join("."))));
+        }
+        Ok((Built::Source(Rc::new(ResultScan { input: input.operator })), input.schema))
+    }
+
+    fn filter(&mut self, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let input = self.input(args.required(self.source, "input")?)?;
+        let node = args.required(self.source, "expr")?;
+        let predicate = self.expr(node, &input.schema)?;
+        if predicate.data_type != DataType::Bool {
+            return Err(self.error(node.span, format!("the filter expression must be Bool, found {:?}", predicate.data_type)));
+        }
+        let projection = args.required(self.source, "projection")?;
+        let output = self.strings(projection)?;
+        let schema = self.project(&input.schema, &output, Some(projection.span))?;
+        let filter = Rc::new(Filter::new(input.operator, predicate, output));
+        Ok((Built::Operator(filter), schema))
+    }
+
+    fn identity(&mut self, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let input = self.input(args.required(self.source, "input")?)?;
+        Ok((Built::Sink(Rc::new(IdentitySink { input: input.operator })), input.schema))
+    }
+
+    fn write_arrow(&mut self, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let input = self.input(args.required(self.source, "input")?)?;
+        let path = self.string(args.required(self.source, "path")?)?;
+        Ok((Built::Sink(Rc::new(ArrowWrite { input: input.operator, path: path.into() })), input.schema))
+    }
+
+    fn build_hash(&mut self, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let input = self.input(args.required(self.source, "input")?)?;
+        let node = args.required(self.source, "key")?;
+        let keys = self.one_or_many(node)?;
+        let mut schema = self.project(&input.schema, &keys, Some(node.span))?;
+        let mut values = vec![];
+        if let Some(node) = args.optional("value") {
+            values = self.one_or_many(node)?;
+            schema.extend(self.project(&input.schema, &values, Some(node.span))?);
+            if let Some(value) = values.iter().find(|v| keys.contains(v)) {
+                return Err(self.error(node.span, format!("`{value}` is a key and a value")));
+            }
+        }
+        let ht = Rc::new(BuildHash::new(input.operator, keys, values));
+        self.hash_tables.push(ht.clone());
+        Ok((Built::Sink(ht), schema))
+    }
+
+    fn hash_join(&mut self, join_type: JoinType, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let node = args.required(self.source, "input")?;
+        let input = self.input(node)?;
+        // the input columns can be written `$in.name` or `v2.name`
+        let input_var = match &node.kind {
+            NodeKind::Path(path) if path.len() == 1 => path[0].clone(),
+            _ => "$in".to_string(),
+        };
+        let node = args.required(self.source, "ht")?;
+        let ht = self.input(node)?;
+        let table = self.hash_tables.iter().find(|t| ptr_eq(&((*t).clone() as Rc<dyn Operator>), &ht.operator)).cloned()
+            .ok_or_else(|| self.error(node.span, "expected the variable of a `build_hash`"))?;
+
+        let node = args.required(self.source, "key")?;
+        let nodes: Vec<&Node> = match &node.kind {
+            NodeKind::List(items) => items.iter().collect(),
+            _ => vec![node],
+        };
+        if nodes.len() != table.keys.len() {
+            return Err(self.error(node.span, format!("{} keys are compared with the {} keys of the hash table", nodes.len(), table.keys.len())));
+        }
+        let mut keys = vec![];
+        for (node, build) in nodes.into_iter().zip(&ht.schema) {
+            let JoinColumn::Probe(name) = self.join_column(node, &input_var, "$ht")? else {
+                return Err(self.error(node.span, "a key must be a column of the input"));
+            };
+            let key = &self.project(&input.schema, std::slice::from_ref(&name), Some(node.span))?[0];
+            if key.data_type != build.data_type {
+                return Err(self.error(node.span, format!("the key `{name}` is {:?}, but the key `{}` of the hash table is {:?}",
+                    key.data_type, build.name, build.data_type)));
+            }
+            keys.push(name);
+        }
+
+        let node = args.required(self.source, "projection")?;
+        let NodeKind::List(items) = &node.kind else {
+            return Err(self.error(node.span, "expected a list of columns `[ $in.a, $ht.b ]`"));
+        };
+        let (mut projection, mut schema) = (vec![], vec![]);
+        for item in items {
+            let column = self.join_column(item, &input_var, "$ht")?;
+            if schema.iter().any(|c: &Column| c.name == column.name()) {
+                return Err(self.error(item.span, format!("duplicate column `{}`", column.name())));
+            }
+            let names = std::slice::from_ref(match &column {
+                JoinColumn::Probe(name) | JoinColumn::Build(name) => name,
+            });
+            match &column {
+                JoinColumn::Probe(_) => schema.extend(self.project(&input.schema, names, Some(item.span))?),
+                JoinColumn::Build(_) if matches!(join_type, JoinType::Semi | JoinType::Anti) => {
+                    return Err(self.error(item.span, format!("a {} can only project columns of its input, found `{column}`", join_type.operator())));
+                }
+                JoinColumn::Build(_) => {
+                    let mut columns = self.project(&ht.schema, names, Some(item.span))?;
+                    columns[0].nullable |= join_type == JoinType::Left;
+                    schema.extend(columns);
+                }
+            }
+            projection.push(column);
+        }
+        // inputs sorted by the keys are merged instead of probing a hash table
+        if input.operator.ordering().starts_with(&keys) && table.ordering().starts_with(&table.keys) {
+            table.merge();
+            let join = Rc::new(MergeJoin::new(input.operator, ht.operator, join_type, keys, table.keys.clone(), projection));
+            return Ok((Built::Operator(join), schema));
+        }
+        let join = Rc::new(HashJoin::new(input.operator, table, join_type, keys, projection));
+        Ok((Built::Operator(join), schema))
+    }
+
+    fn merge_join(&mut self, join_type: JoinType, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let node = args.required(self.source, "input")?;
+        let input = self.input(node)?;
+        let input_var = match &node.kind {
+            NodeKind::Path(path) if path.len() == 1 => path[0].clone(),
+            _ => "$in".to_string(),
+        };
+        let node = args.required(self.source, "right")?;
+        let NodeKind::Path(path) = &node.kind else {
+            return Err(self.error(node.span, "expected the sink of a parent pipeline `pipeline1.v3`"));
+        };
+        let right = self.parent_sink(node, path)?;
+        let table = self.hash_tables.iter().find(|t| ptr_eq(&((*t).clone() as Rc<dyn Operator>), &right.operator)).cloned();
+
+        let mut keys = vec![];
+        for (arg, var) in [("key", "$in"), ("right_key", "$right")] {
+            let node = args.required(self.source, arg)?;
+            let nodes: Vec<&Node> = match &node.kind {
+                NodeKind::List(items) => items.iter().collect(),
+                _ => vec![node],
+            };
+            let mut names = vec![];
+            for node in nodes {
+                names.push(match (self.join_column(node, &input_var, "$right")?, arg) {
+                    (JoinColumn::Probe(name), "key") | (JoinColumn::Build(name), "right_key") => name,
+                    _ => return Err(self.error(node.span, format!("expected a column `{var}.name`"))),
+                });
+            }
+            keys.push((names, node.span));
+        }
+        let (right_keys, span) = keys.pop().expect("the right keys");
+        let (keys, key_span) = keys.pop().expect("the keys");
+        if keys.len() != right_keys.len() {
+            return Err(self.error(span, format!("{} keys are compared with {} right keys", keys.len(), right_keys.len())));
+        }
+        for (key, right_key) in self.project(&input.schema, &keys, Some(span))?.iter().zip(self.project(&right.schema, &right_keys, Some(span))?) {
+            if key.data_type != right_key.data_type {
+                return Err(self.error(span, format!("the key `{}` is {:?}, but the right key `{}` is {:?}",
+                    key.name, key.data_type, right_key.name, right_key.data_type)));
+            }
+        }
+        for (side, sorted, keys, span) in [("input", &input.operator, &keys, key_span), ("right side", &right.operator, &right_keys, span)] {
+            if !sorted.ordering().starts_with(keys) {
+                return Err(self.error(span, format!("the {side} of a {} is not ordered by {}", merge_join::operator(join_type), strings(keys))));
+            }
+        }
+
+        let node = args.required(self.source, "projection")?;
+        let NodeKind::List(items) = &node.kind else {
+            return Err(self.error(node.span, "expected a list of columns `[ $in.a, $right.b ]`"));
+        };
+        let (mut projection, mut schema) = (vec![], vec![]);
+        for item in items {
+            let column = self.join_column(item, &input_var, "$right")?;
+            if schema.iter().any(|c: &Column| c.name == column.name()) {
+                return Err(self.error(item.span, format!("duplicate column `{}`", column.name())));
+            }
+            match &column {
+                JoinColumn::Probe(name) => schema.extend(self.project(&input.schema, std::slice::from_ref(name), Some(item.span))?),
+                JoinColumn::Build(name) if matches!(join_type, JoinType::Semi | JoinType::Anti) => {
+                    return Err(self.error(item.span, format!("a {} can only project columns of its input, found `$right.{name}`", merge_join::operator(join_type))));
+                }
+                JoinColumn::Build(name) => {
+                    let mut columns = self.project(&right.schema, std::slice::from_ref(name), Some(item.span))?;
+                    columns[0].nullable |= join_type == JoinType::Left;
+                    schema.extend(columns);
+                }
+            }
+            projection.push(column);
+        }
+        if let Some(table) = table {
+            table.merge();
+        }
+        let join = Rc::new(MergeJoin::new(input.operator, right.operator, join_type, keys, right_keys, projection));
+        Ok((Built::Operator(join), schema))
+    }
+
+    fn hash_group_by(&mut self, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let input = self.input(args.required(self.source, "input")?)?;
+        let node = args.required(self.source, "group_by")?;
+        let keys = self.strings(node)?;
+        let mut schema = self.project(&input.schema, &keys, Some(node.span))?;
+        let node = args.required(self.source, "aggregates")?;
+        let NodeKind::Entries(entries) = &node.kind else {
+            return Err(self.error(node.span, "expected aggregates `\"name\": sum($in.column), ...`"));
+        };
+        let mut aggregates = vec![];
+        for (name, span, call) in entries {
+            if schema.iter().any(|c| &c.name == name) {
+                return Err(self.error(*span, format!("duplicate column `{name}`")));
+            }
+            let NodeKind::Call(function, args) = &call.kind else {
+                return Err(self.error(call.span, "expected an aggregate function `sum($in.column)`"));
+            };
+            let kind = AggregateKind::from_name(function)
+                .ok_or_else(|| self.error(call.span, format!("unknown aggregate function `{function}`")))?;
+            let [arg] = args.as_slice() else {
+                return Err(self.error(call.span, format!("`{function}` takes one argument")));
+            };
+            let aggregate = Aggregate { name: name.clone(), kind, arg: self.expr(arg, &input.schema)? };
+            schema.push(aggregate.column(!keys.is_empty()).map_err(|e| self.error(call.span, e.to_string()))?);
+            aggregates.push(aggregate);
+        }
+        let group_by = Rc::new(HashGroupBy::new(input.operator, keys, aggregates));
+        Ok((Built::Sink(group_by), schema))
+    }
+
+    fn aggregate(&mut self, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let node = args.required(self.source, "input")?;
+        let (input, column) = match &node.kind {
+            NodeKind::Path(path) if path.len() == 2 && self.vars.contains_key(&path[0]) => {
+                let input = self.vars[&path[0]].clone();
+                let column = self.project(&input.schema, &path[1..], Some(node.span))?.remove(0);
+                (input, column)
+            }
+            _ => return Err(self.error(node.span, "expected a column of a variable `v2.name`")),
+        };
+        let node = args.required(self.source, "aggregates")?;
+        let NodeKind::Entries(entries) = &node.kind else {
+            return Err(self.error(node.span, "expected aggregates `\"name\": min, ...`"));
+        };
+        let (mut aggregates, mut schema) = (vec![], vec![]);
+        for (name, span, function) in entries {
+            if schema.iter().any(|c: &Column| &c.name == name) {
+                return Err(self.error(*span, format!("duplicate column `{name}`")));
+            }
+            let reduction = match &function.kind {
+                NodeKind::Path(path) if path.len() == 1 => Reduction::from_name(&path[0])
+                    .ok_or_else(|| self.error(function.span, format!("unknown aggregate function `{}`", path[0])))?,
+                _ => return Err(self.error(function.span, "expected an aggregate function `min`")),
+            };
+            let data_type = reduction.result_type(&column.data_type).map_err(|e| self.error(function.span, e.to_string()))?;
+            let nullable = !matches!(reduction, Reduction::Count | Reduction::CountNonNull);
+            schema.push(Column { name: name.clone(), data_type, nullable });
+            aggregates.push((name.clone(), reduction));
+        }
+        let aggregate = Rc::new(SimdAggregate::new(input.operator, column.name, aggregates));
+        Ok((Built::Sink(aggregate), schema))
+    }
+
+    fn order_by(&mut self, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let input = self.input(args.required(self.source, "input")?)?;
+        let keys = self.sort_keys(args.required(self.source, "order_by")?, &input.schema)?;
+        Ok((Built::Sink(Rc::new(OrderBy { input: input.operator, keys })), input.schema))
+    }
+
+    fn top_n(&mut self, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let input = self.input(args.required(self.source, "input")?)?;
+        let keys = self.sort_keys(args.required(self.source, "order_by")?, &input.schema)?;
+        let node = args.required(self.source, "limit")?;
+        let limit = match &node.kind {
+            NodeKind::Literal(Value::I32(n)) if *n >= 0 => *n as usize,
+            NodeKind::Literal(Value::I64(n)) if *n >= 0 => *n as usize,
+            _ => return Err(self.error(node.span, "expected a limit `10`")),
+        };
+        Ok((Built::Sink(Rc::new(TopN { input: input.operator, keys, limit })), input.schema))
+    }
+
+    fn window(&mut self, args: &mut Args) -> Result<(Built, Vec<Column>)> {
+        let input = self.input(args.required(self.source, "input")?)?;
+        let mut partition_by = vec![];
+        if let Some(node) = args.optional("partition_by") {
+            partition_by = self.strings(node)?;
+            for key in self.project(&input.schema, &partition_by, Some(node.span))? {
+                if !is_sortable(&key.data_type) {
+                    return Err(self.error(node.span, format!("can not partition by `{}` of type {:?}", key.name, key.data_type)));
+                }
+            }
+        }
+        let order_by = match args.optional("order_by") {
+            Some(node) => self.sort_keys(node, &input.schema)?,
+            None => vec![],
+        };
+        let range_key = match order_by.as_slice() {
+            [(name, _)] => input.schema.iter().any(|c| &c.name == name && is_range_key(&c.data_type)),
+            _ => false,
+        };
+
+        let node = args.required(self.source, "functions")?;
+        let NodeKind::Entries(entries) = &node.kind else {
+            return Err(self.error(node.span, "expected window functions `\"name\": rank(), ...`"));
+        };
+        let mut schema = input.schema.clone();
+        let mut functions = vec![];
+        for (name, span, call) in entries {
+            if schema.iter().any(|c| &c.name == name) {
+                return Err(self.error(*span, format!("duplicate column `{name}`")));
+            }
+            let NodeKind::Call(function, call_args) = &call.kind else {
+                return Err(self.error(call.span, "expected a window function `rank()`"));
+            };
+            let kind = WindowKind::from_name(function)
+                .ok_or_else(|| self.error(call.span, format!("unknown window function `{function}`")))?;
+            let mut window = WindowFunction::new(name, kind, None);
+            let mut rest = call_args.iter();
+            if kind.is_ranking() {
+                if !call_args.is_empty() {
+                    return Err(self.error(call.span, format!("`{function}` takes no argument")));
+                }
+            } else {
+                let arg = rest.next().ok_or_else(|| self.error(call.span, format!("`{function}` takes an argument")))?;
+                window.arg = Some(self.expr(arg, &input.schema)?);
+            }
+            if matches!(kind, WindowKind::Lag | WindowKind::Lead) {
+                if let Some(node) = rest.next() {
+                    window.offset = match &node.kind {
+                        NodeKind::Literal(Value::I32(n)) if *n >= 0 => *n as usize,
+                        NodeKind::Literal(Value::I64(n)) if *n >= 0 => *n as usize,
+                        _ => return Err(self.error(node.span, "expected an offset `1`")),
+                    };
+                }
+                if let Some(node) = rest.next() {
+                    let NodeKind::Literal(default) = &node.kind else {
+                        return Err(self.error(node.span, "expected a default value"));
+                    };
+                    let data_type = &window.arg.as_ref().expect("the argument of lag and lead").data_type;
+                    window.default = cast_value(default, data_type).map_err(|e| self.error(node.span, e.to_string()))?;
+                }
+            } else if kind.has_frame() && let Some(node) = rest.next() {
+                window.frame = self.frame(node)?;
+                if window.frame.has_offsets() && !range_key {
+                    return Err(self.error(node.span, format!("a {} frame needs one integer, float or temporal order key", window.frame)));
+                }
+            }
+            if let Some(node) = rest.next() {
+                return Err(self.error(node.span, format!("too many arguments of `{function}`")));
+            }
+            schema.push(window.column().map_err(|e| self.error(call.span, e.to_string()))?);
+            functions.push(window);
+        }
+        let window = Rc::new(Window::new(input.operator, partition_by, order_by, functions));
+        Ok((Built::Sink(window), schema))
+    }
+
+    /// `rows(-2, 0)` or `range(unbounded, 1)`
+    fn frame(&self, node: &Node) -> Result<Frame> {
+        let error = || self.error(node.span, "expected a frame `rows(-1, 0)` or `range(unbounded, 0)`");
+        let NodeKind::Call(unit, bounds) = &node.kind else {
+            return Err(error());
+        };
+        let unit = match unit.as_str() {
+            "rows" => FrameUnit::Rows,
+            "range" => FrameUnit::Range,
+            _ => return Err(error()),
+        };
+        let [start, end] = bounds.as_slice() else {
+            return Err(error());
+        };
+        let offset = |node: &Node| match &node.kind {
+            NodeKind::Literal(Value::I32(n)) => Ok(Some(*n as i64)),
+            NodeKind::Literal(Value::I64(n)) => Ok(Some(*n)),
+            NodeKind::Path(path) if path.len() == 1 && path[0] == "unbounded" => Ok(None),
+            _ => Err(self.error(node.span, "expected an offset `-1` or `unbounded`")),
+        };
+        Ok(Frame { unit, start: offset(start)?, end: offset(end)? })
+    }
+
+    /// `"name": asc, "freight": desc_nulls_last`
+    fn sort_keys(&self, node: &Node, schema: &[Column]) -> Result<Vec<(String, SortOrder)>> {
+        let NodeKind::Entries(entries) = &node.kind else {
+            return Err(self.error(node.span, "expected keys `\"name\": asc, ...`"));
+        };
+        let mut keys: Vec<(String, SortOrder)> = vec![];
+        for (name, span, order) in entries {
+            if keys.iter().any(|(key, _)| key == name) {
+                return Err(self.error(*span, format!("duplicate key `{name}`")));
+            }
+            let column = &self.project(schema, std::slice::from_ref(name), Some(*span))?[0];
+            if !is_sortable(&column.data_type) {
+                return Err(self.error(*span, format!("can not order by `{name}` of type {:?}", column.data_type)));
+            }
+            let order = match &order.kind {
+                NodeKind::Path(path) if path.len() == 1 => SortOrder::from_name(&path[0])
+                    .ok_or_else(|| self.error(order.span, format!("unknown sort order `{}`", path[0])))?,
+                _ => return Err(self.error(order.span, "expected a sort order `asc` or `desc`")),
+            };
+            keys.push((name.clone(), order));
+        }
+        Ok(keys)
+    }
+
+    /// `$in.name` or `v2.name` for a column of the input, `$ht.name` for a column of the hash
+    /// table, or `$right.name` of the right side of a merge join with `build_var = "$right"`
+    fn join_column(&self, node: &Node, input_var: &str, build_var: &str) -> Result<JoinColumn> {
+        match &node.kind {
+            NodeKind::Path(path) if path.len() == 2 && (path[0] == "$in" || path[0] == input_var) => Ok(JoinColumn::Probe(path[1].clone())),
+            NodeKind::Path(path) if path.len() == 2 && path[0] == build_var => Ok(JoinColumn::Build(path[1].clone())),
+            _ => Err(self.error(node.span, format!("expected a column `$in.name` or `{build_var}.name`"))),
+        }
+    }
+
+    /// the columns of `schema` named `names`
+    fn project(&self, schema: &[Column], names: &[String], span: Option<Span>) -> Result<Vec<Column>> {
+        names.iter()
+            .map(|name| schema.iter().find(|c| &c.name == name).cloned()
+                .ok_or_else(|| self.error(span.unwrap_or_default(), format!("unknown column `{name}`"))))
+            .collect()
+    }
+
+    fn string(&self, node: &Node) -> Result<String> {
+        match &node.kind {
+            NodeKind::Literal(Value::String(s)) => Ok(s.clone()),
+            _ => Err(self.error(node.span, "expected a string")),
+        }
+    }
+
+    /// `"a"` or `[ "a", "b" ]`
+    fn one_or_many(&self, node: &Node) -> Result<Vec<String>> {
+        match &node.kind {
+            NodeKind::Literal(Value::String(s)) => Ok(vec![s.clone()]),
+            _ => self.strings(node),
+        }
+    }
+
+    fn strings(&self, node: &Node) -> Result<Vec<String>> {
+        match &node.kind {
+            NodeKind::List(items) => items.iter().map(|item| self.string(item)).collect(),
+            _ => Err(self.error(node.span, "expected a list of strings `[ \"a\", \"b\" ]`")),
+        }
+    }
+
+    /// `"name": { data_type: "i32", nullable: false }, ...`
+    fn columns(&self, node: &Node) -> Result<Vec<Column>> {
+        let NodeKind::Entries(entries) = &node.kind else {
+            return Err(self.error(node.span, "expected column definitions `\"name\": { data_type: \"i32\" }, ...`"));
+        };
+        let mut columns: Vec<Column> = vec![];
+        for (name, span, definition) in entries {
+            if columns.iter().any(|c| &c.name == name) {
+                return Err(self.error(*span, format!("duplicate column `{name}`")));
+            }
+            let NodeKind::Object(fields) = &definition.kind else {
+                return Err(self.error(definition.span, "expected `{ data_type: \"i32\", nullable: false }`"));
+            };
+            let mut data_type = None;
+            let mut nullable = true;
+            for (key, value) in fields {
+                match (key.as_str(), &value.kind) {
+                    ("data_type", NodeKind::Literal(Value::String(text))) => data_type = Some(self.data_type(text, value.span)?),
+                    ("nullable", NodeKind::Literal(Value::Bool(b))) => nullable = *b,
+                    ("data_type" | "nullable", _) => return Err(self.error(value.span, format!("invalid value of `{key}`"))),
+                    _ => return Err(self.error(value.span, format!("unknown column property `{key}`"))),
+                }
+            }
+            let data_type = data_type.ok_or_else(|| self.error(definition.span, "missing `data_type`"))?;
+            columns.push(Column { name: name.clone(), data_type, nullable });
+        }
+        Ok(columns)
+    }
+
+    /// `"id": [1, 100], "customer_id": [pipeline1.minmax.min, pipeline1.minmax.max]`
+    fn minmaxes(&mut self, node: &Node, columns: &[Column]) -> Result<Vec<MinMax>> {
+        let NodeKind::Entries(entries) = &node.kind else {
+            return Err(self.error(node.span, "expected ranges `\"column\": [min, max], ...`"));
+        };
+        let mut minmaxes: Vec<MinMax> = vec![];
+        for (name, span, range) in entries {
+            if minmaxes.iter().any(|m| &m.column == name) {
+                return Err(self.error(*span, format!("duplicate minmax of `{name}`")));
+            }
+            let column = columns.iter().find(|c| &c.name == name)
+                .ok_or_else(|| self.error(*span, format!("unknown column `{name}`")))?;
+            let NodeKind::List(items) = &range.kind else {
+                return Err(self.error(range.span, "expected `[min, max]`"));
+            };
+            let [min, max] = items.as_slice() else {
+                return Err(self.error(range.span, "expected `[min, max]`"));
+            };
+            minmaxes.push(MinMax { column: name.clone(), min: self.bound(min, column)?, max: self.bound(max, column)? });
+        }
+        Ok(minmaxes)
+    }
+
+    /// a literal converted to the type of `column`, or a column of the sink of a parent pipeline
+    fn bound(&mut self, node: &Node, column: &Column) -> Result<Bound> {
+        let value = match &node.kind {
+            NodeKind::Literal(value) => value.clone(),
+            NodeKind::Negate(child) => match &child.kind {
+                NodeKind::Literal(Value::I32(v)) => Value::I64(-(*v as i64)),
+                NodeKind::Literal(Value::I64(v)) => Value::I64(-v),
+                NodeKind::Literal(Value::F64(v)) => Value::F64(-v),
+                _ => return Err(self.error(node.span, "expected a number")),
+            },
+            NodeKind::Path(path) if path.len() == 3 => {
+                let var = self.parent_sink(node, &path[..2])?;
+                let result = &self.project(&var.schema, &path[2..], Some(node.span))?[0];
+                if result.data_type != column.data_type {
+                    return Err(self.error(node.span, format!("`{}` is {:?}, but the column `{}` is {:?}",
+                        result.name, result.data_type, column.name, column.data_type)));
+                }
+                return Ok(Bound::Result { sink: var.operator, column: path[2].clone() });
+            }
+            _ => return Err(self.error(node.span, "expected a value or a column of a parent pipeline `pipeline1.v3.min`")),
+        };
+        if value.is_null() {
+            return Err(self.error(node.span, "a bound can not be null"));
+        }
+        // a bound that wrapped around could prune rows in range
+        cast_exact(&value, &column.data_type).map(Bound::Value).map_err(|e| self.error(node.span, e.to_string()))
+    }
+
+    /// parse a data type written as a string, errors point into the string
+    fn data_type(&self, text: &str, span: Span) -> Result<DataType> {
+        let mut parser = Parser::new(text).map_err(|_| self.error(span, format!("invalid data type {text:?}")))?;
+        let data_type = parser.data_type().and_then(|t| parser.expect(Token::Eof).map(|_| t));
+        data_type.map_err(|_| self.error(span, format!("invalid data type {text:?}")))
+    }
+
+    /// a reference to a variable of this pipeline, `v1`, or of a parent pipeline, `pipeline1.v3`
+    fn input(&mut self, node: &Node) -> Result<Var> {
+        match &node.kind {
+            NodeKind::Path(path) if path.len() == 1 => self.vars.get(&path[0]).cloned()
+                .ok_or_else(|| self.error(node.span, format!("unknown variable `{}`", path[0]))),
+            NodeKind::Path(path) if path.len() == 2 => {
+                let (pipeline, vars) = self.pipelines.get(&path[0])
+                    .ok_or_else(|| self.error(node.span, format!("unknown pipeline `{}`", path[0])))?;
+                let var = vars.get(&path[1]).cloned()
+                    .ok_or_else(|| self.error(node.span, format!("unknown variable `{}` of `{}`", path[1], path[0])))?;
+                if !self.referenced.iter().any(|p| ptr_eq(p, pipeline)) {
+                    self.referenced.push(pipeline.clone());
+                }
+                Ok(var)
+            }
+            _ => Err(self.error(node.span, "expected a variable")),
+        }
+    }
+
+    /// the sink of a parent pipeline, `pipeline1.v3`
+    fn parent_sink(&mut self, node: &Node, path: &[String]) -> Result<Var> {
+        if path.len() != 2 {
+            return Err(self.error(node.span, "expected the sink of a parent pipeline `pipeline1.v3`"));
+        }
+        let var = self.input(&Node { kind: NodeKind::Path(path.to_vec()), span: node.span })?;
+        let (pipeline, _) = &self.pipelines[&path[0]];
+        if !ptr_eq(&(pipeline.sink.clone() as Rc<dyn Operator>), &var.operator) {
+            return Err(self.error(node.span, format!("`{}.{}` is not the sink of its pipeline", path[0], path[1])));
+        }
+        Ok(var)
+    }
+
+    /// a typed expression over the columns of `input`, referenced as `$in.name`
+    fn expr(&self, node: &Node, input: &[Column]) -> Result<Expr> {
+        let expr = |child: &Node| self.expr(child, input);
+        let at = |e: Error| match e {
+            Error::Parse { .. } => e,
+            other => self.error(node.span, other.to_string()),
+        };
+        match &node.kind {
+            NodeKind::Literal(value) => Ok(Expr::literal(value.clone())),
+            NodeKind::Path(path) if path.len() == 2 && path[0] == "$in" => Expr::column(input, &path[1]).map_err(at),
+            NodeKind::Path(path) => Err(self.error(node.span, format!("unknown variable `{}`", path.join(".")))),
+            NodeKind::Binary(op, left, right) => Expr::binary(*op, expr(left)?, expr(right)?).map_err(at),
+            NodeKind::Not(child) => expr(child)?.not().map_err(at),
+            NodeKind::Negate(child) => expr(child)?.neg().map_err(at),
+            NodeKind::IsNull { expr: child, negated } => Ok(expr(child)?.is_null(*negated)),
+            NodeKind::Like { expr: child, pattern, negated } => expr(child)?.like(pattern, *negated).map_err(at),
+            NodeKind::InList { expr: child, list, negated } => {
+                let list = list.iter().map(expr).collect::<Result<Vec<_>>>()?;
+                expr(child)?.in_list(list, *negated).map_err(at)
+            }
+            NodeKind::Cast(child, data_type) => expr(child)?.cast(data_type.clone()).map_err(at),
+            NodeKind::Call(name, args) => {
+                let args = args.iter().map(expr).collect::<Result<Vec<_>>>()?;
+                Expr::function(name, args).map_err(at)
+            }
+            NodeKind::List(_) | NodeKind::Object(_) | NodeKind::Entries(_) => Err(self.error(node.span, "expected an expression")),
+        }
+    }
+}
+
+/// whether `target` is `pipeline` or one of its (transitive) parents
+fn reaches(pipeline: &Rc<Pipeline>, target: &Rc<Pipeline>) -> bool {
+    ptr_eq(pipeline, target) || pipeline.parents.iter().any(|p| reaches(p, target))
+}
+
+impl Topology {
+    /// parse the text format, see the module documentation
+    pub fn parse(source: &str) -> Result<Topology> {
+        let defs = Parser::new(source)?.topology()?;
+        let mut builder = Builder { source, pipelines: HashMap::new(), vars: HashMap::new(), referenced: vec![], hash_tables: vec![] };
+        builder.topology(defs)
+    }
+}
+
+impl Expr {
+    /// parse an expression over the columns of `input`, e.g. `$in.freight > 10 && $in.gender == "M"`
+    pub fn parse(source: &str, input: &[Column]) -> Result<Expr> {
+        let mut parser = Parser::new(source)?;
+        let node = parser.expr()?;
+        parser.expect(Token::Eof)?;
+        let builder = Builder { source, pipelines: HashMap::new(), vars: HashMap::new(), referenced: vec![], hash_tables: vec![] };
+        builder.expr(&node, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exec::{ExecutionContext, Interpreter};
+    use crate::vector::{DataChunk, Vector};
+    use super::*;
+
+    const CUSTOMERS: &str = r#"
+// scan( customers ) |> filter |> identity
+pipeline1: Pipeline =
+    v1 = table_scan :table = "customers"
+            :columns =
+                "customer_id": { data_type: "i32", nullable: false },
+                "name": { data_type: "string", nullable: false }
+                "gender": { data_type: "string" }
+    v2 = filter :input = v1
+            :expr = $in.gender == "M" && ($in.name >= "abc" && $in.name < "abd")
+            :projection = [ "customer_id", "name" ]
+    v3 = identity :input = v2
+"#;
+
+    #[test]
+    fn test_parse_and_run() {
+        let topology = Topology::parse(CUSTOMERS).unwrap();
+        assert_eq!(topology.main.operators.len(), 1);
+        assert_eq!(topology.main.sink.output(), &["customer_id", "name"]);
+
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("customers", DataChunk::new(vec![
+            Vector::from(vec![1, 2, 3]),
+            Vector::from(vec!["abc1", "abc2", "xyz"]),
+            Vector::from(vec![Some("M"), None, Some("M")]),
+        ]));
+        let result = Interpreter::new(ctx).run(&topology).unwrap();
+        let chunks = result.downcast_ref::<Vec<DataChunk>>().unwrap();
+        assert_eq!(chunks[0].row(0), vec![Value::I32(1), "abc1".into()]);
+        assert_eq!(chunks[0].len(), 1);
+    }
+
+    #[test]
+    fn test_parents() {
+        let text = r#"
+p1: Pipeline =
+    v1 = table_scan :table = "t" :columns = "a": { data_type: "i64" }
+    v2 = identity :input = v1
+main: Pipeline(p1) =
+    v1 = table_scan :table = "t" :columns = "a": { data_type: "i64" }
+    v2 = identity :input = v1
+"#;
+        let topology = Topology::parse(text).unwrap();
+        assert_eq!(topology.main.parents.len(), 1);
+
+        let orphan = text.replace("Pipeline(p1)", "Pipeline");
+        assert!(matches!(Topology::parse(&orphan), Err(Error::Parse { line: 2, column: 1, .. })));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| match Topology::parse(&CUSTOMERS.replace("$in.gender == \"M\"", text)) {
+            Err(Error::Parse { line, column, message }) => (line, column, message.lines().next().unwrap().to_string()),
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        };
+        assert_eq!(error("$in.gender == 1"), (10, 21, "type mismatch: expected String, found I32".to_string()));
+        assert_eq!(error("$in.sex == \"M\""), (10, 21, "column not found: sex".to_string()));
+        assert_eq!(error("$in.gender =="), (10, 35, "expected an expression, found `&&`".to_string()));
+
+        let unknown = CUSTOMERS.replace(":input = v2", ":input = v2 :limit = 10");
+        assert!(Topology::parse(&unknown).err().unwrap().to_string().contains("unknown argument `:limit` of `identity`"));
+        let missing = CUSTOMERS.replace(":projection", ":columns");
+        assert!(Topology::parse(&missing).err().unwrap().to_string().contains("missing argument `:projection` of `filter`"));
+
+        let minmaxes = |text: &str| {
+            let source = CUSTOMERS.replace("\"gender\": { data_type: \"string\" }", &format!("\"gender\": {{ data_type: \"string\" }}\n :minmaxes = {text}"));
+            Topology::parse(&source).map(|_| ()).map_err(|e| e.to_string())
+        };
+        assert_eq!(minmaxes(r#""customer_id": [-10, 10], "gender": ["F", "M"]"#), Ok(()));
+        assert!(minmaxes(r#""customer_id": [1]"#).unwrap_err().contains("expected `[min, max]`"));
+        assert!(minmaxes(r#""id": [1, 2]"#).unwrap_err().contains("unknown column `id`"));
+        assert!(minmaxes(r#""customer_id": ["a", 2]"#).unwrap_err().contains("can not cast"));
+        assert!(minmaxes(r#""customer_id": [1, 3000000000]"#).unwrap_err().contains("can not cast"));
+        assert!(minmaxes(r#""customer_id": [v2.min, 2]"#).unwrap_err().contains("expected a value"));
+    }
+
+    #[test]
+    fn test_parse_expr() {
+        let input = vec![Column { name: "d".into(), data_type: DataType::Date, nullable: true }];
+        let expr = Expr::parse("$in.d >= \"2025-01-01\" && $in.d - 1 not in [cast(\"2025-01-02\" as date)]", &input).unwrap();
+        assert_eq!(expr.data_type, DataType::Bool);
+        assert!(Expr::parse("year($in.d) + 1 > 2000", &input).is_ok());
+        assert!(Expr::parse("$in.d like \"a%\"", &input).is_err());
+    }
+}
@@ -0,0 +1,372 @@
+//! Pretty-printer of the qir text format, the inverse of `qir::parser`.
+//!
+//! Pipelines are named `pipeline1, pipeline2, ...` with parents before their children and the
+//! main pipeline last, the variables of a pipeline `v1, v2, ...` in pipeline order. Parsing the
+//! printed text gives the same topology again.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::qir::expr::{BinaryOp, Expr, ExprKind};
+use crate::qir::lexer::{is_ident, quote};
+use crate::qir::{Column, DataType, Operator, Topology};
+use crate::vector::Value;
+
+/// indentation of the statements of a pipeline, the arguments and the entries of an argument
+const STATEMENT_INDENT: &str = "    ";
+const ARG_INDENT: &str = "            ";
+const ENTRY_INDENT: &str = "                ";
+
+/// writes the statements of a topology, operators print their own arguments with `Operator::print`
+pub struct Printer {
+    out: String,
+    /// the variable of each operator by the address of its allocation, `(pipeline, variable)`
+    names: HashMap<usize, (usize, String)>,
+    /// the pipeline being printed
+    pipeline: usize,
+    args: usize,
+}
+
+fn key<T: ?Sized>(rc: &Rc<T>) -> usize {
+    Rc::as_ptr(rc) as *const () as usize
+}
+
+impl Printer {
+    /// an argument with a single line value. the first argument follows the operator name, the
+    /// others start a new line.
+    pub fn arg(&mut self, name: &str, value: impl fmt::Display) {
+        self.arg_name(name);
+        self.out.push_str(&format!(" {value}"));
+    }
+
+    fn arg_name(&mut self, name: &str) {
+        if self.args > 0 {
+            self.out.push('\n');
+            self.out.push_str(ARG_INDENT);
+        } else {
+            self.out.push(' ');
+        }
+        self.args += 1;
+        self.out.push_str(&format!(":{name} ="));
+    }
+
+    /// an argument of `"key": value` entries, one per line
+    pub fn entries(&mut self, name: &str, entries: impl IntoIterator<Item = (String, String)>) {
+        self.arg_name(name);
+        let entries: Vec<String> = entries.into_iter().map(|(key, value)| format!("{}: {value}", quote(&key))).collect();
+        for (i, entry) in entries.iter().enumerate() {
+            self.out.push('\n');
+            self.out.push_str(ENTRY_INDENT);
+            self.out.push_str(entry);
+            if i + 1 < entries.len() {
+                self.out.push(',');
+            }
+        }
+    }
+
+    /// an argument of column definitions
+    pub fn columns(&mut self, name: &str, columns: &[Column]) {
+        self.entries(name, columns.iter().map(|c| {
+            let definition = format!("{{ data_type: {}, nullable: {} }}", quote(&c.data_type.to_string()), c.nullable);
+            (c.name.clone(), definition)
+        }));
+    }
+
+    /// the variable of an operator: `v1` within the pipeline, `pipeline1.v1` for another pipeline
+    pub fn var(&self, operator: &Rc<dyn Operator>) -> String {
+        match self.names.get(&key(operator)) {
+            Some((pipeline, var)) if *pipeline == self.pipeline => var.clone(),
+            Some((pipeline, var)) => format!("pipeline{}.{var}", pipeline + 1),
+            None => "<unknown>".to_string(),
+        }
+    }
+}
+
+/// `[ "a", "b" ]`
+pub fn strings(values: &[String]) -> String {
+    let items: Vec<String> = values.iter().map(|v| quote(v)).collect();
+    format!("[ {} ]", items.join(", "))
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pipelines = self.pipelines();
+
+        let mut printer = Printer { out: String::new(), names: HashMap::new(), pipeline: 0, args: 0 };
+        for (i, pipeline) in pipelines.iter().enumerate() {
+            let operators = pipeline.operators();
+            for (j, operator) in operators.iter().enumerate() {
+                printer.names.insert(key(operator), (i, format!("v{}", j + 1)));
+            }
+
+            if i > 0 {
+                printer.out.push('\n');
+            }
+            let parents: Vec<String> = pipeline.parents.iter()
+                .map(|p| format!("pipeline{}", pipelines.iter().position(|q| Rc::ptr_eq(p, q)).unwrap() + 1))
+                .collect();
+            let parents = if parents.is_empty() { String::new() } else { format!("({})", parents.join(", ")) };
+            printer.out.push_str(&format!("pipeline{}: Pipeline{parents} =\n", i + 1));
+
+            printer.pipeline = i;
+            for (j, operator) in operators.iter().enumerate() {
+                printer.out.push_str(&format!("{STATEMENT_INDENT}v{} = {}", j + 1, operator.name()));
+                printer.args = 0;
+                operator.print(&mut printer);
+                printer.out.push('\n');
+            }
+        }
+        f.write_str(&printer.out)
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataType::I8 => write!(f, "i8"),
+            DataType::I16 => write!(f, "i16"),
+            DataType::I32 => write!(f, "i32"),
+            DataType::I64 => write!(f, "i64"),
+            DataType::U8 => write!(f, "u8"),
+            DataType::U16 => write!(f, "u16"),
+            DataType::U32 => write!(f, "u32"),
+            DataType::U64 => write!(f, "u64"),
+            DataType::F32 => write!(f, "f32"),
+            DataType::F64 => write!(f, "f64"),
+            DataType::Decimal => write!(f, "decimal"),
+            DataType::Bool => write!(f, "bool"),
+            DataType::String => write!(f, "string"),
+            DataType::Date => write!(f, "date"),
+            DataType::DateTime => write!(f, "datetime"),
+            DataType::List(element) => write!(f, "list<{element}>"),
+            DataType::Map(key, value) => write!(f, "map<{key}, {value}>"),
+            DataType::Struct(table) => {
+                let fields: Vec<String> = table.columns.iter().map(|c| format!("{}: {}", name(&c.name), c.data_type)).collect();
+                write!(f, "struct<{}>", fields.join(", "))
+            }
+        }
+    }
+}
+
+/// a column name, quoted unless it is an identifier
+pub fn name(name: &str) -> String {
+    if is_ident(name) { name.to_string() } else { quote(name) }
+}
+
+/// a value as a literal the parser converts back to it given its type, e.g. a date as a string
+pub fn value(value: &Value) -> String {
+    plain_literal(value).map_or_else(|| value.to_string(), |(text, _)| text)
+}
+
+/// the type an untyped literal combined with `other` is converted to
+fn context(other: &Expr) -> Option<&DataType> {
+    (!other.is_literal()).then_some(&other.data_type)
+}
+
+/// binding strength, higher binds tighter
+fn precedence(expr: &Expr) -> u8 {
+    match &expr.kind {
+        ExprKind::Binary { op: BinaryOp::Or, .. } => 1,
+        ExprKind::Binary { op: BinaryOp::And, .. } => 2,
+        ExprKind::Binary { op: BinaryOp::Add | BinaryOp::Sub, .. } => 4,
+        ExprKind::Binary { op: BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod, .. } => 5,
+        ExprKind::Binary { .. } | ExprKind::IsNull { .. } | ExprKind::Like { .. } | ExprKind::InList { .. } => 3,
+        ExprKind::Not(_) | ExprKind::Negate(_) => 6,
+        ExprKind::Literal(value) if matches!(value, Value::I32(v) if *v < 0) || matches!(value, Value::I64(v) if *v < 0) => 6,
+        _ => 7,
+    }
+}
+
+/// how a literal is written without a cast, and the value the parser reads from that text
+fn plain_literal(value: &Value) -> Option<(String, Value)> {
+    let integer = |v: i128| match v {
+        v if i32::try_from(v).is_ok() => Some((v.to_string(), Value::I32(v as i32))),
+        v if i64::try_from(v).is_ok() => Some((v.to_string(), Value::I64(v as i64))),
+        _ => None,
+    };
+    match value {
+        Value::I8(v) => integer(*v as i128),
+        Value::I16(v) => integer(*v as i128),
+        Value::I32(v) => integer(*v as i128),
+        Value::I64(v) => integer(*v as i128),
+        Value::U8(v) => integer(*v as i128),
+        Value::U16(v) => integer(*v as i128),
+        Value::U32(v) => integer(*v as i128),
+        Value::U64(v) => integer(*v as i128),
+        Value::F32(v) if v.is_finite() => Some((format!("{v:?}"), Value::F64(format!("{v:?}").parse().ok()?))),
+        Value::F64(v) if v.is_finite() => Some((format!("{v:?}"), Value::F64(*v))),
+        Value::Bool(_) | Value::Null => Some((value.to_string(), value.clone())),
+        // parsed from a string by the cast or by the comparison with a temporal column
+        Value::String(_) | Value::Decimal(_) | Value::Date(_) | Value::DateTime(_) => {
+            let text = value.to_string();
+            Some((quote(&text), Value::String(text)))
+        }
+        _ => None,
+    }
+}
+
+struct Printed<'a> {
+    expr: &'a Expr,
+    /// the type of the non-literal operand this literal is compared or combined with, the parser
+    /// converts an untyped literal to it
+    context: Option<&'a DataType>,
+}
+
+impl Printed<'_> {
+    fn child<'a>(&self, f: &mut fmt::Formatter<'_>, child: &'a Expr, context: Option<&'a DataType>, parens: bool) -> fmt::Result {
+        let printed = Printed { expr: child, context };
+        if parens { write!(f, "({printed})") } else { write!(f, "{printed}") }
+    }
+
+    fn literal(&self, f: &mut fmt::Formatter<'_>, value: &Value) -> fmt::Result {
+        let data_type = &self.expr.data_type;
+        let Some((text, parsed)) = plain_literal(value) else {
+            return write!(f, "{value}");
+        };
+        // the parser converts the literal to the type of the other operand, check it gives this one
+        let converted = self.context.is_some_and(|context| {
+            let other = Expr { kind: ExprKind::Column(String::new()), data_type: context.clone(), nullable: true };
+            Expr::coerce(other, Expr::literal(parsed.clone())).is_ok_and(|(_, literal)| literal == *self.expr)
+        });
+        if converted || Expr::literal(parsed) == *self.expr {
+            write!(f, "{text}")
+        } else {
+            // keep the type with a cast, the parser folds the cast of a literal
+            write!(f, "cast({text} as {data_type})")
+        }
+    }
+}
+
+impl fmt::Display for Printed<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = precedence(self.expr);
+        match &self.expr.kind {
+            ExprKind::Column(column) => write!(f, "$in.{}", name(column)),
+            ExprKind::Literal(value) => self.literal(f, value),
+            ExprKind::Binary { op, left, right } => {
+                // comparisons do not chain, other operators are left associative
+                let left_parens = precedence(left) < level || level == 3 && precedence(left) == 3;
+                self.child(f, left, context(right), left_parens)?;
+                write!(f, " {} ", op.symbol())?;
+                self.child(f, right, context(left), precedence(right) <= level)
+            }
+            ExprKind::Not(expr) => {
+                write!(f, "!")?;
+                self.child(f, expr, None, precedence(expr) < level)
+            }
+            ExprKind::Negate(expr) => {
+                write!(f, "-")?;
+                self.child(f, expr, None, precedence(expr) < level || expr.is_literal())
+            }
+            ExprKind::IsNull { expr, negated } => {
+                self.child(f, expr, None, precedence(expr) <= level)?;
+                write!(f, " is {}null", if *negated { "not " } else { "" })
+            }
+            ExprKind::Like { expr, pattern, negated } => {
+                self.child(f, expr, None, precedence(expr) <= level)?;
+                write!(f, " {}like {}", if *negated { "not " } else { "" }, quote(pattern))
+            }
+            ExprKind::InList { expr, list, negated } => {
+                self.child(f, expr, None, precedence(expr) <= level)?;
+                write!(f, " {}in [", if *negated { "not " } else { "" })?;
+                let context = context(expr);
+                for (i, item) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.child(f, item, context, false)?;
+                }
+                write!(f, "]")
+            }
+            ExprKind::Cast(expr) => {
+                write!(f, "cast(")?;
+                self.child(f, expr, None, false)?;
+                write!(f, " as {})", self.expr.data_type)
+            }
+            ExprKind::Function { name, args } => {
+                write!(f, "{name}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.child(f, arg, None, false)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// the expression in the text format, e.g. `$in.freight > 10.0 && $in.gender == "M"`
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Printed { expr: self, context: None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::qir::{Scan, Sink, Table};
+    use crate::{column, filter, identity, pipeline, scan, table};
+    use super::*;
+
+    const TEXT: &str = r#"pipeline1: Pipeline =
+    v1 = table_scan :table = "customers"
+            :columns =
+                "customer_id": { data_type: "i32", nullable: false },
+                "name": { data_type: "string", nullable: true },
+                "tags": { data_type: "map<string, list<i64>>", nullable: true }
+            :output = [ "customer_id", "name" ]
+    v2 = identity :input = v1
+
+pipeline2: Pipeline(pipeline1) =
+    v1 = table_scan :table = "sale_orders"
+            :columns =
+                "customer_id": { data_type: "i32", nullable: false },
+                "freight": { data_type: "f64", nullable: true },
+                "amount": { data_type: "decimal", nullable: true },
+                "order_date": { data_type: "date", nullable: true }
+    v2 = filter :input = v1
+            :expr = $in.freight > 10.0 && $in.amount < cast("12.5000" as decimal) || !($in.order_date >= "2025-01-01") && $in.customer_id - -1 not in [1, 2]
+            :projection = [ "customer_id", "freight" ]
+    v3 = filter :input = v2
+            :expr = cast($in.customer_id as f64) * ($in.freight + 1.0) > 100.0 && lower(cast($in.customer_id as string)) like "1%"
+            :projection = [ "freight" ]
+    v4 = identity :input = v3
+"#;
+
+    #[test]
+    fn test_round_trip() {
+        let topology = Topology::parse(TEXT).unwrap();
+        assert_eq!(topology.to_string(), TEXT);
+    }
+
+    #[test]
+    fn test_print_macro_plan() {
+        let table = Rc::new(table! {
+            name: "users",
+            columns: [ column! { name = "id", data_type = I64, nullable = false }, column! { name = "age", data_type = U8 } ],
+        });
+        let scan: Rc<Scan> = Rc::new(scan! { name: "users_scan", table: table.clone(), output: ["id", "age"] });
+        let predicate = Expr::binary(BinaryOp::Gt, Expr::column(&table.columns, "age").unwrap(), Expr::literal(18)).unwrap();
+        let filter: Rc<dyn Operator> = Rc::new(filter! { input: scan.clone(), predicate: predicate, output: ["id"] });
+        let sink: Rc<dyn Sink> = identity! { input: filter.clone() };
+        let main = Rc::new(pipeline! { source: scan, operators: [filter], sink: sink });
+
+        let text = Topology { main }.to_string();
+        assert!(text.contains(":expr = $in.age > 18"), "{text}");
+        assert_eq!(Topology::parse(&text).unwrap().to_string(), text);
+    }
+
+    #[test]
+    fn test_print_expr() {
+        let columns = vec![
+            column! { name = "a", data_type = I64 },
+            column! { name = "b b", data_type = Bool },
+        ];
+        let expr = Expr::parse("(-(1) + $in.a * 2 > 3) == $in.\"b b\"", &columns).unwrap();
+        assert_eq!(expr.to_string(), "(cast(-(1) as i64) + $in.a * 2 > 3) == $in.\"b b\"");
+        let table = Table { name: "t".into(), columns };
+        assert_eq!(DataType::Struct(Box::new(table)).to_string(), "struct<a: i64, \"b b\": bool>");
+    }
+}
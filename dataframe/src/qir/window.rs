@@ -0,0 +1,331 @@
+//! The `window` sink: computes window functions over the partitions of its input. The result is
+//! a `Vec<DataChunk>` of the input rows sorted by the partition keys and the order keys, followed
+//! by a column per function.
+//!
+//! ```text
+//! v3 = window :input = v2
+//!         :partition_by = [ "product_id" ]
+//!         :order_by = "order_date": asc
+//!         :functions =
+//!             "rank": rank(),
+//!             "previous": lag($in.amount, 1, 0.0),
+//!             "moving": sum($in.amount, rows(-2, 0)),
+//!             "nearby": avg($in.amount, range(-1, 1))
+//! ```
+//!
+//! The functions with a frame take it as an optional last argument, `rows(start, end)` or
+//! `range(start, end)` with negative offsets preceding the current row and `unbounded` ends, see
+//! `exec::window`. Without it the frame reaches from the start of the partition to the last
+//! peer of the current row. The input is kept in memory.
+
+use std::rc::Rc;
+use crate::error::Result;
+use crate::exec::order_by::{is_sortable, SortKeys, SortOrder};
+use crate::exec::sort::gather_chunk;
+use crate::exec::window::{aggregate, frame_value, Frame, Partitions, WindowKind};
+use crate::exec::{ExecutionContext, SinkResult, SinkState};
+use crate::qir::checker::Checker;
+use crate::qir::expr::Expr;
+use crate::qir::printer::{value, strings, Printer};
+use crate::qir::{resolve_columns, Column, DataType, MinMax, Operator, Sink};
+use crate::vector::{DataChunk, Value, Vector, VECTOR_SIZE};
+
+/// a window function column, `"name": kind(arg, ...)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowFunction {
+    pub name: String,
+    pub kind: WindowKind,
+    /// the argument, none for the ranking functions
+    pub arg: Option<Expr>,
+    /// the rows `lag` looks back and `lead` looks ahead, and their value past the partition
+    pub offset: usize,
+    pub default: Value,
+    /// the frame of the functions with one, see `WindowKind::has_frame`
+    pub frame: Frame,
+}
+
+impl WindowFunction {
+    /// a function without the arguments of `lag`, `lead` and a frame
+    pub fn new(name: &str, kind: WindowKind, arg: Option<Expr>) -> Self {
+        WindowFunction { name: name.to_string(), kind, arg, offset: 1, default: Value::Null, frame: Frame::DEFAULT }
+    }
+
+    /// the output column, an error when the function does not support the argument type
+    pub fn column(&self) -> Result<Column> {
+        let data_type = self.kind.result_type(self.arg.as_ref().map(|a| &a.data_type))?;
+        let nullable = !(self.kind.is_ranking() || self.kind == WindowKind::Count);
+        Ok(Column { name: self.name.clone(), data_type, nullable })
+    }
+
+    fn print(&self) -> String {
+        let mut args: Vec<String> = self.arg.iter().map(|a| a.to_string()).collect();
+        if matches!(self.kind, WindowKind::Lag | WindowKind::Lead) {
+            args.push(self.offset.to_string());
+            if !self.default.is_null() {
+                args.push(value(&self.default));
+            }
+        }
+        if self.kind.has_frame() && self.frame != Frame::DEFAULT {
+            args.push(self.frame.to_string());
+        }
+        format!("{}({})", self.kind.name(), args.join(", "))
+    }
+}
+
+pub struct Window {
+    pub input: Rc<dyn Operator>,
+    pub partition_by: Vec<String>,
+    /// the order of the rows of a partition, the peers of a row have equal keys
+    pub order_by: Vec<(String, SortOrder)>,
+    pub functions: Vec<WindowFunction>,
+    output: Vec<String>,
+}
+
+impl Window {
+    pub fn new(input: Rc<dyn Operator>, partition_by: Vec<String>, order_by: Vec<(String, SortOrder)>, functions: Vec<WindowFunction>) -> Self {
+        let output = input.output().iter().cloned().chain(functions.iter().map(|f| f.name.clone())).collect();
+        Window { input, partition_by, order_by, functions, output }
+    }
+}
+
+impl Operator for Window {
+    fn name(&self) -> &'static str {
+        "window"
+    }
+
+    fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    fn print(&self, printer: &mut Printer) {
+        printer.arg("input", printer.var(&self.input));
+        if !self.partition_by.is_empty() {
+            printer.arg("partition_by", strings(&self.partition_by));
+        }
+        if !self.order_by.is_empty() {
+            printer.entries("order_by", self.order_by.iter().map(|(name, order)| (name.clone(), order.name().to_string())));
+        }
+        printer.entries("functions", self.functions.iter().map(|f| (f.name.clone(), f.print())));
+    }
+
+    fn check(&self, checker: &mut Checker) -> Option<Vec<Column>> {
+        let mut output = checker.input(&self.input)?;
+        let input = output.clone();
+        let keys: Vec<String> = self.order_by.iter().map(|(name, _)| name.clone()).collect();
+        let partition_by = checker.resolve(&input, &self.partition_by);
+        let order_by = checker.resolve(&input, &keys);
+        for key in partition_by.iter().chain(&order_by).flatten() {
+            if !is_sortable(&key.data_type) {
+                checker.error(format!("can not partition or order by `{}` of type {:?}", key.name, key.data_type));
+            }
+        }
+        for function in &self.functions {
+            if output.iter().any(|c| c.name == function.name) {
+                checker.error(format!("duplicate column `{}`", function.name));
+            }
+            if let Some(arg) = &function.arg {
+                checker.expr(arg, &input, &arg.data_type);
+            }
+            if function.kind.has_frame() && function.frame.has_offsets() {
+                match order_by.as_deref() {
+                    Some([key]) if is_range_key(&key.data_type) => {}
+                    _ => checker.error(format!("`{}`: a {} frame needs one integer, float or temporal order key", function.name, function.frame)),
+                }
+            }
+            match function.column() {
+                Ok(column) => output.push(column),
+                Err(e) => checker.error(format!("`{}`: {e}", function.name)),
+            }
+        }
+        (output.len() == self.output.len()).then_some(output)
+    }
+
+    fn min_max(&self, column: &str) -> Option<MinMax> {
+        self.input.output().iter().any(|c| c == column).then(|| self.input.min_max(column))?
+    }
+
+    fn ordering(&self) -> Vec<String> {
+        let order_by = self.order_by.iter().take_while(|(_, order)| *order == SortOrder::ASC).map(|(name, _)| name.clone());
+        self.partition_by.iter().cloned().chain(order_by).collect()
+    }
+}
+
+/// whether a `range` frame with offsets can be ordered by a key of `data_type`
+pub fn is_range_key(data_type: &DataType) -> bool {
+    data_type.is_integer() || data_type.is_float() || data_type.is_temporal()
+}
+
+impl Sink for Window {
+    fn open(&self, ctx: &ExecutionContext) -> Result<Box<dyn SinkState>> {
+        let keys: Vec<String> = self.order_by.iter().map(|(name, _)| name.clone()).collect();
+        Ok(Box::new(WindowState {
+            names: self.input.output().to_vec(),
+            types: ctx.schema(&self.input)?.into_iter().map(|c| c.data_type).collect(),
+            partition_by: resolve_columns(self.input.output(), &self.partition_by)?,
+            order_by: resolve_columns(self.input.output(), &keys)?,
+            orders: self.order_by.iter().map(|(_, order)| *order).collect(),
+            functions: self.functions.clone(),
+            chunks: vec![],
+        }))
+    }
+}
+
+/// collects every chunk, the functions are computed at the end
+struct WindowState {
+    names: Vec<String>,
+    types: Vec<DataType>,
+    partition_by: Vec<usize>,
+    order_by: Vec<usize>,
+    orders: Vec<SortOrder>,
+    functions: Vec<WindowFunction>,
+    chunks: Vec<DataChunk>,
+}
+
+impl WindowState {
+    /// the input rows sorted by the partition keys, then by the order keys
+    fn sorted(&mut self) -> Result<DataChunk> {
+        let rows = match self.chunks.len() {
+            0 => DataChunk::new(self.types.iter().map(|t| Vector::new_null(t, 0)).collect()),
+            _ => DataChunk::concat(&std::mem::take(&mut self.chunks))?,
+        };
+        if self.partition_by.is_empty() && self.order_by.is_empty() {
+            return Ok(rows);
+        }
+        let keys: Vec<&Vector> = self.partition_by.iter().chain(&self.order_by).map(|&i| rows.column(i)).collect();
+        let orders: Vec<SortOrder> = self.partition_by.iter().map(|_| SortOrder::ASC).chain(self.orders.iter().copied()).collect();
+        let permutation = SortKeys::encode(&keys, &orders)?.argsort();
+        Ok(gather_chunk(&rows, &permutation))
+    }
+
+    fn evaluate(&self, function: &WindowFunction, rows: &DataChunk, partitions: &Partitions) -> Result<Vector> {
+        let Some(arg) = &function.arg else {
+            return Ok(partitions.ranks(function.kind));
+        };
+        let arg = arg.evaluate(rows, &self.names)?;
+        let offset = function.offset as i64;
+        let frames = || partitions.frames(&function.frame, self.order_by.first().map(|&k| (rows.column(k), self.orders[0])));
+        match function.kind {
+            WindowKind::Lag => partitions.shift(&arg, -offset, &function.default),
+            WindowKind::Lead => partitions.shift(&arg, offset, &function.default),
+            WindowKind::FirstValue => frame_value(&arg, &frames()?, false),
+            WindowKind::LastValue => frame_value(&arg, &frames()?, true),
+            kind => aggregate(kind, &arg, &frames()?),
+        }
+    }
+}
+
+impl SinkState for WindowState {
+    fn consume(&mut self, _ctx: &ExecutionContext, chunk: DataChunk) -> Result<()> {
+        self.chunks.push(chunk);
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>, _ctx: &ExecutionContext) -> Result<SinkResult> {
+        let rows = self.sorted()?;
+        let column = |columns: &[usize]| columns.iter().map(|&i| rows.column(i)).collect::<Vec<_>>();
+        let partitions = Partitions::new(&column(&self.partition_by), &column(&self.order_by), &self.orders, rows.len())?;
+        let mut columns = rows.columns.clone();
+        for function in &self.functions {
+            columns.push(self.evaluate(function, &rows, &partitions)?);
+        }
+        let result = DataChunk::new(columns);
+        let chunks: Vec<DataChunk> = (0..result.len()).step_by(VECTOR_SIZE)
+            .map(|offset| result.slice(offset, VECTOR_SIZE.min(result.len() - offset)))
+            .collect();
+        Ok(Rc::new(chunks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exec::{ExecutionContext, Interpreter};
+    use crate::qir::{DataType, Topology};
+    use crate::vector::{DataChunk, Value, Vector};
+
+    const SALES: &str = r#"
+pipeline1: Pipeline =
+    v1 = table_scan :table = "sales"
+            :columns =
+                "product_id": { data_type: "i32", nullable: false },
+                "order_date": { data_type: "date", nullable: false },
+                "amount": { data_type: "i64" }
+    v2 = window :input = v1
+            :partition_by = [ "product_id" ]
+            :order_by = "order_date": asc
+            :functions =
+                FUNCTIONS
+"#;
+
+    fn run(functions: &str) -> Vec<Vec<Value>> {
+        let mut ctx = ExecutionContext::new();
+        let date = |day: u32| Value::parse_date(&format!("2024-01-{day:02}")).unwrap();
+        ctx.register_table("sales", DataChunk::new(vec![
+            Vector::from(vec![2i32, 1, 1, 2, 1, 1, 1]),
+            Vector::from_values(&DataType::Date, &[date(3), date(2), date(1), date(1), date(2), date(5), date(6)]).unwrap(),
+            Vector::from(vec![Some(10i64), Some(20), Some(30), None, Some(50), Some(60), Some(70)]),
+        ]));
+        let topology = Topology::parse(&SALES.replace("FUNCTIONS", functions)).unwrap();
+        let result = Interpreter::new(ctx).run(&topology).unwrap();
+        let chunks = result.downcast::<Vec<DataChunk>>().unwrap();
+        // the date column is left out, the rows are sorted by product and date
+        chunks.iter().flat_map(|c| (0..c.len()).map(|i| c.row(i))).map(|mut row| { row.remove(1); row }).collect()
+    }
+
+    fn column(rows: &[Vec<Value>], i: usize) -> Vec<Value> {
+        rows.iter().map(|row| row[i].clone()).collect()
+    }
+
+    fn ints(values: &[Option<i64>]) -> Vec<Value> {
+        values.iter().map(|v| v.map_or(Value::Null, Value::I64)).collect()
+    }
+
+    #[test]
+    fn test_window() {
+        let rows = run(r#""row": row_number(), "rank": rank(), "dense": dense_rank(),
+                "previous": lag($in.amount, 1), "next": lead($in.amount, 2, -1), "first": first_value($in.amount)"#);
+        // product 1: days 1, 2, 2, 5, 6, product 2: days 1, 3
+        assert_eq!(column(&rows, 0), [1, 1, 1, 1, 1, 2, 2].map(Value::I32).to_vec());
+        assert_eq!(column(&rows, 1), ints(&[Some(30), Some(20), Some(50), Some(60), Some(70), None, Some(10)]));
+        assert_eq!(column(&rows, 2), ints(&[Some(1), Some(2), Some(3), Some(4), Some(5), Some(1), Some(2)]));
+        assert_eq!(column(&rows, 3), ints(&[Some(1), Some(2), Some(2), Some(4), Some(5), Some(1), Some(2)]));
+        assert_eq!(column(&rows, 4), ints(&[Some(1), Some(2), Some(2), Some(3), Some(4), Some(1), Some(2)]));
+        assert_eq!(column(&rows, 5), ints(&[None, Some(30), Some(20), Some(50), Some(60), None, None]));
+        assert_eq!(column(&rows, 6), ints(&[Some(50), Some(60), Some(70), Some(-1), Some(-1), Some(-1), Some(-1)]));
+        assert_eq!(column(&rows, 7), ints(&[Some(30), Some(30), Some(30), Some(30), Some(30), None, None]));
+    }
+
+    #[test]
+    fn test_window_frames() {
+        let rows = run(r#""total": sum($in.amount), "moving": sum($in.amount, rows(-1, 0)), "nearby": max($in.amount, range(-1, 1)),
+                "rest": count($in.amount, range(1, unbounded)), "all": last_value($in.amount, rows(unbounded, unbounded))"#);
+        // the default frame ends with the peers of the current row
+        assert_eq!(column(&rows, 2), ints(&[Some(30), Some(100), Some(100), Some(160), Some(230), None, Some(10)]));
+        assert_eq!(column(&rows, 3), ints(&[Some(30), Some(50), Some(70), Some(110), Some(130), None, Some(10)]));
+        assert_eq!(column(&rows, 4), ints(&[Some(50), Some(50), Some(50), Some(70), Some(70), None, Some(10)]));
+        assert_eq!(column(&rows, 5), ints(&[Some(4), Some(2), Some(2), Some(1), Some(0), Some(1), Some(0)]));
+        assert_eq!(column(&rows, 6), ints(&[Some(70), Some(70), Some(70), Some(70), Some(70), Some(10), Some(10)]));
+    }
+
+    #[test]
+    fn test_check_window() {
+        let source = SALES.replace("FUNCTIONS", r#""previous": lag($in.amount, 2, 0), "moving": avg($in.amount, range(-7, 0)), "rank": rank()"#);
+        let topology = Topology::parse(&source).unwrap();
+        let printed = topology.to_string();
+        assert!(printed.contains(r#""previous": lag($in.amount, 2, 0),"#), "{printed}");
+        assert!(printed.contains(r#""moving": avg($in.amount, range(-7, 0)),"#), "{printed}");
+        assert_eq!(Topology::parse(&printed).unwrap().to_string(), printed);
+        let schemas = topology.check().unwrap();
+        let output = schemas.get(&topology.main.sink).unwrap();
+        assert_eq!(output.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["product_id", "order_date", "amount", "previous", "moving", "rank"]);
+        assert_eq!(topology.main.sink.ordering(), ["product_id", "order_date"]);
+
+        let err = Topology::parse(&source.replace("range(-7, 0)", "range(-7, 0, 1)")).err().unwrap().to_string();
+        assert!(err.contains("expected a frame"), "{err}");
+        let err = Topology::parse(&source.replace("rank()", "rank($in.amount)")).err().unwrap().to_string();
+        assert!(err.contains("`rank` takes no argument"), "{err}");
+        let err = Topology::parse(&source.replace(":order_by = \"order_date\": asc", "")).err().unwrap().to_string();
+        assert!(err.contains("a range(-7, 0) frame needs one integer, float or temporal order key"), "{err}");
+        let err = Topology::parse(&source.replace("avg($in.amount", "avg($in.order_date")).err().unwrap().to_string();
+        assert!(err.contains("unsupported: avg(Date)"), "{err}");
+    }
+}
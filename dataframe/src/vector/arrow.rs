@@ -0,0 +1,450 @@
+//! Conversions between vectors and arrow arrays, chunks and record batches, and data types. The
+//! fixed width vectors, the strings and the offsets of lists and maps have the layout of their
+//! arrow arrays, so their buffers are shared both ways instead of copied:
+//!
+//! ```text
+//! Int64Array   values: ScalarBuffer<i64> <-> Buffer<i64> of Vector::I64
+//! StringArray  offsets: i32, values: u8  <-> Buffer<i32>, Buffer<u8> of Vector::String
+//! ListArray    offsets: i32, values      <-> Buffer<i32>, child vector of Vector::List
+//! BooleanArray bits                       -> one bool per row, copied
+//! ```
+//!
+//! Validity bitmaps are copied, they are a bit per row. Arrow types without a vector of their
+//! layout (dictionaries, large and view strings, large lists, other timestamp units or decimal
+//! scales) are cast to the arrow type of the vector first, a value out of its range is an error.
+//! The fields of structs, the elements of lists and the values of maps are nullable in arrow, like
+//! in `Vector::data_type`.
+
+use std::panic::AssertUnwindSafe;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use arrow::array::{Array, ArrayRef, ArrowPrimitiveType, AsArray, BooleanArray, ListArray, MapArray, PrimitiveArray, RecordBatch,
+    RecordBatchOptions, StringArray, StructArray};
+use arrow::buffer::{BooleanBuffer, Buffer as ArrowBuffer, NullBuffer, OffsetBuffer, ScalarBuffer};
+use arrow::compute::CastOptions;
+use arrow::datatypes::{self as arrow_types, ArrowNativeType, DataType as ArrowType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use crate::error::{Error, Result};
+use crate::qir::{Column, DataType, Table};
+use crate::vector::{Bitmap, Buffer, DataChunk, ListVector, MapVector, PrimaryType, PrimaryVector, StringVector, StructVector, Vector,
+    DECIMAL_SCALE};
+
+/// the arrow type of the vectors of `data_type`
+pub fn to_arrow_type(data_type: &DataType) -> Result<ArrowType> {
+    Ok(match data_type {
+        DataType::I8 => ArrowType::Int8,
+        DataType::I16 => ArrowType::Int16,
+        DataType::I32 => ArrowType::Int32,
+        DataType::I64 => ArrowType::Int64,
+        DataType::U8 => ArrowType::UInt8,
+        DataType::U16 => ArrowType::UInt16,
+        DataType::U32 => ArrowType::UInt32,
+        DataType::U64 => ArrowType::UInt64,
+        DataType::F32 => ArrowType::Float32,
+        DataType::F64 => ArrowType::Float64,
+        DataType::Decimal => ArrowType::Decimal128(38, DECIMAL_SCALE as i8),
+        DataType::Bool => ArrowType::Boolean,
+        DataType::String => ArrowType::Utf8,
+        DataType::Date => ArrowType::Date32,
+        DataType::DateTime => ArrowType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        DataType::List(element) => ArrowType::new_list(to_arrow_type(element)?, true),
+        DataType::Struct(table) => {
+            let fields = table.columns.iter().map(|c| Ok(Field::new(c.name.clone(), to_arrow_type(&c.data_type)?, true)));
+            ArrowType::Struct(fields.collect::<Result<_>>()?)
+        }
+        DataType::Map(key, value) => ArrowType::Map(map_entries(to_arrow_type(key)?, to_arrow_type(value)?), false),
+    })
+}
+
+/// the field of the entries of a map, named like the fields of arrow's `MapBuilder`
+fn map_entries(key: ArrowType, value: ArrowType) -> Arc<Field> {
+    let fields = Fields::from(vec![Field::new("keys", key, false), Field::new("values", value, true)]);
+    Arc::new(Field::new("entries", ArrowType::Struct(fields), false))
+}
+
+/// the type of the vectors read from arrow arrays of `data_type`
+pub fn from_arrow_type(data_type: &ArrowType) -> Result<DataType> {
+    Ok(match data_type {
+        ArrowType::Int8 => DataType::I8,
+        ArrowType::Int16 => DataType::I16,
+        ArrowType::Int32 => DataType::I32,
+        ArrowType::Int64 => DataType::I64,
+        ArrowType::UInt8 => DataType::U8,
+        ArrowType::UInt16 => DataType::U16,
+        ArrowType::UInt32 => DataType::U32,
+        ArrowType::UInt64 => DataType::U64,
+        ArrowType::Float16 | ArrowType::Float32 => DataType::F32,
+        ArrowType::Float64 => DataType::F64,
+        ArrowType::Decimal128(..) | ArrowType::Decimal256(..) => DataType::Decimal,
+        ArrowType::Boolean => DataType::Bool,
+        ArrowType::Utf8 | ArrowType::LargeUtf8 | ArrowType::Utf8View => DataType::String,
+        ArrowType::Date32 | ArrowType::Date64 => DataType::Date,
+        ArrowType::Timestamp(..) => DataType::DateTime,
+        ArrowType::Dictionary(_, values) => from_arrow_type(values)?,
+        ArrowType::List(element) | ArrowType::LargeList(element) | ArrowType::FixedSizeList(element, _) => {
+            DataType::List(Box::new(from_arrow_type(element.data_type())?))
+        }
+        ArrowType::Struct(fields) => {
+            let columns = fields.iter().map(|f| Column::try_from(f.as_ref())).collect::<Result<_>>()?;
+            DataType::Struct(Box::new(Table { name: String::new(), columns }))
+        }
+        ArrowType::Map(entries, _) => match entries.data_type() {
+            ArrowType::Struct(fields) if fields.len() == 2 => {
+                DataType::Map(Box::new(from_arrow_type(fields[0].data_type())?), Box::new(from_arrow_type(fields[1].data_type())?))
+            }
+            _ => return Err(Error::Unsupported(format!("reading arrow arrays of {data_type}"))),
+        },
+        _ => return Err(Error::Unsupported(format!("reading arrow arrays of {data_type}"))),
+    })
+}
+
+impl TryFrom<&DataType> for ArrowType {
+    type Error = Error;
+
+    fn try_from(data_type: &DataType) -> Result<Self> {
+        to_arrow_type(data_type)
+    }
+}
+
+impl TryFrom<&ArrowType> for DataType {
+    type Error = Error;
+
+    fn try_from(data_type: &ArrowType) -> Result<Self> {
+        from_arrow_type(data_type)
+    }
+}
+
+impl TryFrom<&Column> for Field {
+    type Error = Error;
+
+    fn try_from(column: &Column) -> Result<Self> {
+        Ok(Field::new(column.name.clone(), to_arrow_type(&column.data_type)?, column.nullable))
+    }
+}
+
+impl TryFrom<&Field> for Column {
+    type Error = Error;
+
+    fn try_from(field: &Field) -> Result<Self> {
+        Ok(Column { name: field.name().clone(), data_type: from_arrow_type(field.data_type())?, nullable: field.is_nullable() })
+    }
+}
+
+/// an arrow buffer over the memory of `buffer`, which it keeps alive
+fn to_arrow_buffer<T: ArrowNativeType>(buffer: &Buffer<T>) -> ArrowBuffer {
+    let values = buffer.as_slice();
+    let owner = Arc::new(AssertUnwindSafe(buffer.clone()));
+    // SAFETY: `owner` shares the allocation of `values`, which is never written while a buffer views it
+    unsafe { ArrowBuffer::from_custom_allocation(NonNull::from(values).cast(), size_of_val(values), owner) }
+}
+
+fn to_offset_buffer(offsets: &Buffer<i32>) -> OffsetBuffer<i32> {
+    OffsetBuffer::new(ScalarBuffer::new(to_arrow_buffer(offsets), 0, offsets.len()))
+}
+
+fn to_null_buffer(validity: Option<&Bitmap>) -> Option<NullBuffer> {
+    validity.map(|v| NullBuffer::new(BooleanBuffer::new(ArrowBuffer::from_vec(v.words().to_vec()), 0, v.len())))
+}
+
+fn from_null_buffer(nulls: Option<&NullBuffer>) -> Option<Bitmap> {
+    nulls.map(|n| Bitmap::from_words(n.inner().bit_chunks().iter_padded().collect(), n.len()))
+}
+
+fn to_primitive<A: ArrowPrimitiveType>(vector: &PrimaryVector<A::Native>) -> PrimitiveArray<A>
+where A::Native: PrimaryType {
+    let values = ScalarBuffer::new(to_arrow_buffer(vector.buffer()), 0, vector.len());
+    PrimitiveArray::new(values, to_null_buffer(vector.validity()))
+}
+
+fn from_primitive<A: ArrowPrimitiveType>(array: &dyn Array) -> PrimaryVector<A::Native>
+where A::Native: PrimaryType {
+    let array = array.as_primitive::<A>();
+    PrimaryVector::with_validity(Buffer::from_owner(array.values().clone()), from_null_buffer(array.nulls()))
+}
+
+impl Vector {
+    /// the arrow array of the vector, sharing the buffers of fixed width vectors, strings and offsets
+    pub fn to_arrow(&self) -> Result<ArrayRef> {
+        Ok(match self {
+            Vector::I8(v) => Arc::new(to_primitive::<arrow_types::Int8Type>(v)),
+            Vector::I16(v) => Arc::new(to_primitive::<arrow_types::Int16Type>(v)),
+            Vector::I32(v) => Arc::new(to_primitive::<arrow_types::Int32Type>(v)),
+            Vector::I64(v) => Arc::new(to_primitive::<arrow_types::Int64Type>(v)),
+            Vector::U8(v) => Arc::new(to_primitive::<arrow_types::UInt8Type>(v)),
+            Vector::U16(v) => Arc::new(to_primitive::<arrow_types::UInt16Type>(v)),
+            Vector::U32(v) => Arc::new(to_primitive::<arrow_types::UInt32Type>(v)),
+            Vector::U64(v) => Arc::new(to_primitive::<arrow_types::UInt64Type>(v)),
+            Vector::F32(v) => Arc::new(to_primitive::<arrow_types::Float32Type>(v)),
+            Vector::F64(v) => Arc::new(to_primitive::<arrow_types::Float64Type>(v)),
+            Vector::Decimal(v) => Arc::new(to_primitive::<arrow_types::Decimal128Type>(v).with_data_type(to_arrow_type(&DataType::Decimal)?)),
+            Vector::Date(v) => Arc::new(to_primitive::<arrow_types::Date32Type>(v)),
+            Vector::DateTime(v) => Arc::new(to_primitive::<arrow_types::TimestampMicrosecondType>(v).with_data_type(to_arrow_type(&DataType::DateTime)?)),
+            Vector::Bool(v) => Arc::new(BooleanArray::new(v.values().iter().copied().collect(), to_null_buffer(v.validity()))),
+            Vector::String(v) => {
+                // SAFETY: the data between the offsets of a string vector is utf-8
+                Arc::new(unsafe { StringArray::new_unchecked(to_offset_buffer(v.offsets()), to_arrow_buffer(v.data()), to_null_buffer(v.validity())) })
+            }
+            Vector::List(v) => {
+                let values = v.values().to_arrow()?;
+                let field = Arc::new(Field::new_list_field(values.data_type().clone(), true));
+                Arc::new(ListArray::try_new(field, to_offset_buffer(v.offsets()), values, to_null_buffer(v.validity()))?)
+            }
+            Vector::Struct(v) => {
+                let nulls = to_null_buffer(v.validity());
+                match v.children().is_empty() {
+                    true => Arc::new(StructArray::new_empty_fields(v.len(), nulls)),
+                    false => {
+                        let children = v.children().iter().map(|c| c.to_arrow()).collect::<Result<Vec<_>>>()?;
+                        let fields = v.names().iter().zip(&children).map(|(name, c)| Field::new(name, c.data_type().clone(), true)).collect();
+                        Arc::new(StructArray::try_new(fields, children, nulls)?)
+                    }
+                }
+            }
+            Vector::Map(v) => {
+                let (keys, values) = (v.keys().to_arrow()?, v.values().to_arrow()?);
+                let field = map_entries(keys.data_type().clone(), values.data_type().clone());
+                let ArrowType::Struct(fields) = field.data_type() else { unreachable!("the entries of a map are a struct") };
+                let entries = StructArray::try_new(fields.clone(), vec![keys, values], None)?;
+                Arc::new(MapArray::try_new(field, to_offset_buffer(v.offsets()), entries, to_null_buffer(v.validity()), false)?)
+            }
+        })
+    }
+
+    /// the vector of an arrow array, sharing its buffers when the vector has the same layout
+    pub fn from_arrow(array: &ArrayRef) -> Result<Vector> {
+        let data_type = from_arrow_type(array.data_type())?;
+        let arrow_type = match array.data_type() {
+            // the values of a timestamp are UTC in every time zone
+            ArrowType::Timestamp(_, zone) => ArrowType::Timestamp(TimeUnit::Microsecond, zone.clone()),
+            // nested arrays are converted child by child, see below
+            ArrowType::List(_) | ArrowType::Struct(_) | ArrowType::Map(..) => array.data_type().clone(),
+            ArrowType::LargeList(element) | ArrowType::FixedSizeList(element, _) => ArrowType::List(element.clone()),
+            _ => to_arrow_type(&data_type)?,
+        };
+        let cast;
+        let array = match array.data_type() == &arrow_type {
+            true => array.as_ref(),
+            false => {
+                // a value the vector can not hold is an error rather than a null
+                let options = CastOptions { safe: false, ..CastOptions::default() };
+                cast = arrow::compute::cast_with_options(array, &arrow_type, &options)?;
+                cast.as_ref()
+            }
+        };
+        Ok(match data_type {
+            DataType::I8 => Vector::I8(from_primitive::<arrow_types::Int8Type>(array)),
+            DataType::I16 => Vector::I16(from_primitive::<arrow_types::Int16Type>(array)),
+            DataType::I32 => Vector::I32(from_primitive::<arrow_types::Int32Type>(array)),
+            DataType::I64 => Vector::I64(from_primitive::<arrow_types::Int64Type>(array)),
+            DataType::U8 => Vector::U8(from_primitive::<arrow_types::UInt8Type>(array)),
+            DataType::U16 => Vector::U16(from_primitive::<arrow_types::UInt16Type>(array)),
+            DataType::U32 => Vector::U32(from_primitive::<arrow_types::UInt32Type>(array)),
+            DataType::U64 => Vector::U64(from_primitive::<arrow_types::UInt64Type>(array)),
+            DataType::F32 => Vector::F32(from_primitive::<arrow_types::Float32Type>(array)),
+            DataType::F64 => Vector::F64(from_primitive::<arrow_types::Float64Type>(array)),
+            DataType::Decimal => Vector::Decimal(from_primitive::<arrow_types::Decimal128Type>(array)),
+            DataType::Date => Vector::Date(from_primitive::<arrow_types::Date32Type>(array)),
+            DataType::DateTime => Vector::DateTime(from_primitive::<arrow_types::TimestampMicrosecondType>(array)),
+            DataType::Bool => {
+                let array = array.as_boolean();
+                Vector::Bool(PrimaryVector::with_validity(array.values().iter().collect::<Vec<_>>(), from_null_buffer(array.nulls())))
+            }
+            DataType::String => {
+                let array = array.as_string::<i32>();
+                let offsets = Buffer::from_owner(array.offsets().inner().clone());
+                let data = Buffer::from_owner(ScalarBuffer::<u8>::from(array.values().clone()));
+                let vector = StringVector::try_new(offsets, data, from_null_buffer(array.nulls()))
+                    .ok_or_else(|| Error::Unsupported("an arrow string array with invalid offsets".into()))?;
+                Vector::String(vector)
+            }
+            DataType::List(_) => {
+                let array = array.as_list::<i32>();
+                let offsets = Buffer::from_owner(array.offsets().inner().clone());
+                Vector::List(ListVector::new(offsets, Vector::from_arrow(array.values())?, from_null_buffer(array.nulls())))
+            }
+            DataType::Struct(_) => {
+                let array = array.as_struct();
+                let names = array.column_names().into_iter().map(String::from).collect();
+                let children = array.columns().iter().map(Vector::from_arrow).collect::<Result<_>>()?;
+                let validity = from_null_buffer(array.nulls()).or_else(|| array.columns().is_empty().then(|| Bitmap::new(array.len(), true)));
+                Vector::Struct(StructVector::new(names, children, validity))
+            }
+            DataType::Map(..) => {
+                let array = array.as_map();
+                let offsets = Buffer::from_owner(array.offsets().inner().clone());
+                let (keys, values) = (Vector::from_arrow(array.keys())?, Vector::from_arrow(array.values())?);
+                Vector::Map(MapVector::new(offsets, keys, values, from_null_buffer(array.nulls())))
+            }
+        })
+    }
+}
+
+impl TryFrom<&Vector> for ArrayRef {
+    type Error = Error;
+
+    fn try_from(vector: &Vector) -> Result<Self> {
+        vector.to_arrow()
+    }
+}
+
+impl TryFrom<&ArrayRef> for Vector {
+    type Error = Error;
+
+    fn try_from(array: &ArrayRef) -> Result<Self> {
+        Vector::from_arrow(array)
+    }
+}
+
+impl DataChunk {
+    /// the record batch of the chunk, `schema` has a field per column, e.g. of the `Column`s of a
+    /// pipeline converted by `Field::try_from`
+    pub fn to_record_batch(&self, schema: SchemaRef) -> Result<RecordBatch> {
+        let columns = self.columns.iter().map(|c| c.to_arrow()).collect::<Result<Vec<_>>>()?;
+        let options = RecordBatchOptions::new().with_row_count(Some(self.len()));
+        Ok(RecordBatch::try_new_with_options(schema, columns, &options)?)
+    }
+}
+
+/// a record batch of nullable columns named `column1`, `column2`, ... like the columns of a
+/// `VALUES` list in DataFusion. see `DataChunk::to_record_batch` to name them.
+impl TryFrom<&DataChunk> for RecordBatch {
+    type Error = Error;
+
+    fn try_from(chunk: &DataChunk) -> Result<Self> {
+        let fields = chunk.columns.iter().enumerate()
+            .map(|(i, c)| Ok(Field::new(format!("column{}", i + 1), to_arrow_type(&c.data_type())?, true)))
+            .collect::<Result<Vec<_>>>()?;
+        chunk.to_record_batch(Arc::new(Schema::new(fields)))
+    }
+}
+
+/// the columns of the batch in order, their names are left out
+impl TryFrom<&RecordBatch> for DataChunk {
+    type Error = Error;
+
+    fn try_from(batch: &RecordBatch) -> Result<Self> {
+        if batch.num_columns() == 0 {
+            return Ok(DataChunk::empty(batch.num_rows()));
+        }
+        Ok(DataChunk::new(batch.columns().iter().map(Vector::try_from).collect::<Result<_>>()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Date64Array, Decimal128Array, Int64Array, LargeListArray, StringDictionaryBuilder, TimestampMillisecondArray,
+        TimestampSecondArray};
+    use arrow::datatypes::Int8Type;
+    use crate::vector::Value;
+    use super::*;
+
+    #[test]
+    fn test_arrow_round_trip() {
+        let vectors = vec![
+            Vector::I64(PrimaryVector::from_options([Some(1), None, Some(3)])),
+            Vector::Decimal(PrimaryVector::new(vec![12_500i128, -1, 0])),
+            Vector::Bool(PrimaryVector::from_options([Some(true), Some(false), None])),
+            Vector::from(vec![Some("a"), None, Some("ccc")]),
+            Vector::Date(PrimaryVector::new(vec![0, 10957, -1])),
+            Vector::DateTime(PrimaryVector::from_options([None, Some(1_700_000_000_000_000), Some(0)])),
+        ];
+        for vector in vectors {
+            let array = vector.to_arrow().unwrap();
+            assert_eq!(array.data_type(), &to_arrow_type(&vector.data_type()).unwrap());
+            assert_eq!(array.null_count(), vector.null_count());
+            let back = Vector::from_arrow(&array).unwrap();
+            assert_eq!(back, vector);
+            // the slices of a vector convert to the slices of its array
+            assert_eq!(Vector::from_arrow(&vector.slice(1, 2).to_arrow().unwrap()).unwrap(), vector.slice(1, 2));
+        }
+    }
+
+    #[test]
+    fn test_arrow_shares_buffers() {
+        let array: ArrayRef = Arc::new(Int64Array::from(vec![Some(7), None, Some(9)]));
+        let Vector::I64(vector) = Vector::from_arrow(&array).unwrap() else { unreachable!() };
+        assert_eq!(vector.values().as_ptr(), array.as_primitive::<arrow_types::Int64Type>().values().as_ptr());
+        assert_eq!(vector.get(1), None);
+        let back = Vector::I64(vector.clone()).to_arrow().unwrap();
+        assert_eq!(back.as_primitive::<arrow_types::Int64Type>().values().as_ptr(), vector.values().as_ptr());
+
+        let strings = Vector::from(vec!["x", "yy"]);
+        let array = strings.to_arrow().unwrap();
+        let Vector::String(s) = &strings else { unreachable!() };
+        assert_eq!(array.as_string::<i32>().values().as_ptr(), s.data().as_ptr());
+    }
+
+    #[test]
+    fn test_arrow_cast_types() {
+        let mut dictionary = StringDictionaryBuilder::<Int8Type>::new();
+        dictionary.append_value("b");
+        dictionary.append_null();
+        dictionary.append_value("b");
+        let array: ArrayRef = Arc::new(dictionary.finish());
+        assert_eq!(Vector::from_arrow(&array).unwrap(), Vector::from(vec![Some("b"), None, Some("b")]));
+        let array: ArrayRef = Arc::new(TimestampMillisecondArray::from(vec![1_000, -1]));
+        assert_eq!(Vector::from_arrow(&array).unwrap().get(0), Value::DateTime(1_000_000));
+        let array: ArrayRef = Arc::new(Date64Array::from(vec![86_400_000 * 2]));
+        assert_eq!(Vector::from_arrow(&array).unwrap().get(0), Value::Date(2));
+        // values out of the range of the vector are errors, not nulls
+        let array: ArrayRef = Arc::new(TimestampSecondArray::from(vec![i64::MAX]));
+        assert!(matches!(Vector::from_arrow(&array), Err(Error::Arrow(_))));
+        let array: ArrayRef = Arc::new(Decimal128Array::from(vec![i128::MAX]).with_precision_and_scale(38, 0).unwrap());
+        assert!(matches!(Vector::from_arrow(&array), Err(Error::Arrow(_))));
+        let array: ArrayRef = Arc::new(arrow::array::BinaryArray::from(vec![b"x".as_ref()]));
+        assert_eq!(Vector::from_arrow(&array).err().unwrap().to_string(), "unsupported: reading arrow arrays of Binary");
+    }
+
+    fn round_trip(vector: &Vector) {
+        let array = ArrayRef::try_from(vector).unwrap();
+        assert_eq!(array.data_type(), &ArrowType::try_from(&vector.data_type()).unwrap());
+        assert_eq!(DataType::try_from(array.data_type()).unwrap(), vector.data_type());
+        assert_eq!(&Vector::try_from(&array).unwrap(), vector);
+        let slice = vector.slice(1, 2);
+        assert_eq!(Vector::try_from(&slice.to_arrow().unwrap()).unwrap(), slice);
+    }
+
+    #[test]
+    fn test_arrow_nested_types() {
+        let lists = [Value::List(vec![Value::I32(1), Value::Null]), Value::Null, Value::List(vec![]), Value::List(vec![Value::I32(4)])];
+        round_trip(&Vector::from_values(&DataType::List(Box::new(DataType::I32)), &lists).unwrap());
+        let point = DataType::Struct(Box::new(Table { name: String::new(), columns: vec![
+            Column { name: "x".into(), data_type: DataType::F64, nullable: true },
+            Column { name: "tags".into(), data_type: DataType::List(Box::new(DataType::String)), nullable: true },
+        ] }));
+        let points = [
+            Value::Struct(vec![Value::F64(1.5), Value::List(vec![Value::String("a".into())])]),
+            Value::Null,
+            Value::Struct(vec![Value::Null, Value::List(vec![])]),
+        ];
+        round_trip(&Vector::from_values(&point, &points).unwrap());
+        let scores = DataType::Map(Box::new(DataType::String), Box::new(DataType::I64));
+        let maps = [
+            Value::Map(vec![(Value::String("a".into()), Value::I64(1)), (Value::String("b".into()), Value::Null)]),
+            Value::Map(vec![]),
+            Value::Null,
+        ];
+        round_trip(&Vector::from_values(&scores, &maps).unwrap());
+
+        let array: ArrayRef = Arc::new(LargeListArray::from_iter_primitive::<Int8Type, _, _>(vec![Some(vec![Some(1i8)]), None]));
+        assert_eq!(Vector::try_from(&array).unwrap().get(0), Value::List(vec![Value::I8(1)]));
+    }
+
+    #[test]
+    fn test_record_batches() {
+        // a chunk and its record batch share the buffers of their columns
+        let strings = Vector::from(vec!["x", "yy"]);
+        let chunk = DataChunk::new(vec![Vector::from(vec![1i64, 2]), strings.clone()]);
+        let batch = RecordBatch::try_from(&chunk).unwrap();
+        assert_eq!(batch.schema().field(1).name(), "column2");
+        let back = DataChunk::try_from(&batch).unwrap();
+        assert_eq!(back, chunk);
+        let (Vector::String(s), Vector::String(b)) = (&strings, back.column(1)) else { unreachable!() };
+        assert_eq!(b.data().as_ptr(), s.data().as_ptr());
+
+        let column = Column { name: "id".into(), data_type: DataType::I64, nullable: false };
+        let schema = Arc::new(Schema::new(vec![Field::try_from(&column).unwrap()]));
+        assert_eq!(Column::try_from(schema.field(0)).unwrap(), column);
+        let err = DataChunk::new(vec![Vector::I64(PrimaryVector::from_options([None]))]).to_record_batch(schema).err().unwrap();
+        assert!(err.to_string().contains("declared as non-nullable"), "{err}");
+    }
+}
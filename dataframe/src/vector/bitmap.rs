@@ -0,0 +1,193 @@
+use std::fmt;
+
+/// A packed array of bits, bit `i` is bit `i % 64` of word `i / 64`.
+/// Used as the validity mask of a vector: a set bit means the row is not null.
+/// Bits beyond `len` are always zero.
+#[derive(Clone, PartialEq, Default)]
+pub struct Bitmap {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitmap {
+    /// a bitmap of `len` bits, all set to `value`
+    pub fn new(len: usize, value: bool) -> Self {
+        let fill = if value { u64::MAX } else { 0 };
+        let mut bitmap = Bitmap { words: vec![fill; len.div_ceil(64)], len };
+        bitmap.clear_tail();
+        bitmap
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Bitmap { words: Vec::with_capacity(capacity.div_ceil(64)), len: 0 }
+    }
+
+    /// wrap packed words, bits beyond `len` are ignored
+    pub fn from_words(words: Vec<u64>, len: usize) -> Self {
+        assert!(words.len() * 64 >= len);
+        let mut bitmap = Bitmap { words, len };
+        bitmap.words.truncate(len.div_ceil(64));
+        bitmap.clear_tail();
+        bitmap
+    }
+
+    fn clear_tail(&mut self) {
+        if !self.len.is_multiple_of(64) {
+            let last = self.words.len() - 1;
+            self.words[last] &= (1u64 << (self.len % 64)) - 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> bool {
+        debug_assert!(index < self.len);
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    #[inline]
+    pub fn set(&mut self, index: usize, value: bool) {
+        debug_assert!(index < self.len);
+        if value {
+            self.words[index / 64] |= 1 << (index % 64);
+        } else {
+            self.words[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    pub fn push(&mut self, value: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+        self.len += 1;
+        self.set(self.len - 1, value);
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn count_zeros(&self) -> usize {
+        self.len - self.count_ones()
+    }
+
+    pub fn all_set(&self) -> bool {
+        self.count_ones() == self.len
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(|i| self.get(i))
+    }
+
+    /// copy `len` bits starting at `offset` into a new bitmap
+    pub fn slice(&self, offset: usize, len: usize) -> Bitmap {
+        assert!(offset + len <= self.len);
+        if offset.is_multiple_of(64) {
+            let words = self.words[offset / 64..(offset + len).div_ceil(64)].to_vec();
+            return Bitmap::from_words(words, len);
+        }
+        (offset..offset + len).map(|i| self.get(i)).collect()
+    }
+
+    /// gather the bits at `indices` into a new bitmap
+    pub fn take(&self, indices: &[u32]) -> Bitmap {
+        indices.iter().map(|&i| self.get(i as usize)).collect()
+    }
+
+    pub fn and(&self, other: &Bitmap) -> Bitmap {
+        assert_eq!(self.len, other.len);
+        let words = self.words.iter().zip(&other.words).map(|(a, b)| a & b).collect();
+        Bitmap { words, len: self.len }
+    }
+
+    pub fn or(&self, other: &Bitmap) -> Bitmap {
+        assert_eq!(self.len, other.len);
+        let words = self.words.iter().zip(&other.words).map(|(a, b)| a | b).collect();
+        Bitmap { words, len: self.len }
+    }
+
+    pub fn not(&self) -> Bitmap {
+        let mut bitmap = Bitmap { words: self.words.iter().map(|w| !w).collect(), len: self.len };
+        bitmap.clear_tail();
+        bitmap
+    }
+
+    /// append all bits of `other`
+    pub fn extend_from_bitmap(&mut self, other: &Bitmap) {
+        if self.len.is_multiple_of(64) {
+            self.words.extend_from_slice(&other.words);
+            self.len += other.len;
+        } else {
+            other.iter().for_each(|bit| self.push(bit));
+        }
+    }
+}
+
+impl Extend<bool> for Bitmap {
+    fn extend<I: IntoIterator<Item = bool>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|bit| self.push(bit));
+    }
+}
+
+impl FromIterator<bool> for Bitmap {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut bitmap = Bitmap::with_capacity(iter.size_hint().0);
+        bitmap.extend(iter);
+        bitmap
+    }
+}
+
+impl fmt::Debug for Bitmap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Bitmap(")?;
+        for bit in self.iter() {
+            write!(f, "{}", if bit { '1' } else { '0' })?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitmap() {
+        let mut bitmap = Bitmap::new(70, true);
+        assert_eq!(bitmap.count_ones(), 70);
+        bitmap.set(3, false);
+        bitmap.set(65, false);
+        assert!(!bitmap.get(3) && bitmap.get(4) && !bitmap.get(65));
+        assert_eq!(bitmap.count_zeros(), 2);
+        assert_eq!(bitmap.not().count_ones(), 2);
+
+        let slice = bitmap.slice(2, 3);
+        assert_eq!(slice.iter().collect::<Vec<_>>(), vec![true, false, true]);
+        let slice = bitmap.slice(64, 6);
+        assert_eq!(slice.iter().collect::<Vec<_>>(), vec![true, false, true, true, true, true]);
+
+        assert_eq!(bitmap.take(&[65, 0, 3]).iter().collect::<Vec<_>>(), vec![false, true, false]);
+    }
+
+    #[test]
+    fn test_push_and_extend() {
+        let mut bitmap: Bitmap = [true, false, true].into_iter().collect();
+        bitmap.extend_from_bitmap(&Bitmap::new(64, false));
+        bitmap.push(true);
+        assert_eq!(bitmap.len(), 68);
+        assert_eq!(bitmap.count_ones(), 3);
+        assert!(bitmap.get(67));
+    }
+}
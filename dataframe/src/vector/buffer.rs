@@ -0,0 +1,134 @@
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// An immutable array of `T` that is cheap to clone and to slice: clones and slices share the
+/// same allocation.
+pub struct Buffer<T> {
+    data: Arc<Allocation<T>>,
+    offset: usize,
+    len: usize,
+}
+
+/// the memory of a buffer: a vector, or memory owned by another buffer type such as an arrow buffer
+enum Allocation<T> {
+    Vec(Vec<T>),
+    Owner(Box<dyn AsRef<[T]> + Send + Sync>),
+}
+
+impl<T> Allocation<T> {
+    fn as_slice(&self) -> &[T] {
+        match self {
+            Allocation::Vec(data) => data,
+            Allocation::Owner(owner) => (**owner).as_ref(),
+        }
+    }
+}
+
+impl<T> Buffer<T> {
+    /// a view of the elements of `owner` without copying them, e.g. the values of an arrow array
+    /// in a memory mapped file. `owner` lives until the last view of it is dropped.
+    pub fn from_owner(owner: impl AsRef<[T]> + Send + Sync + 'static) -> Self {
+        let len = owner.as_ref().len();
+        Buffer { data: Arc::new(Allocation::Owner(Box::new(owner))), offset: 0, len }
+    }
+
+    /// a zero-copy view of `len` elements starting at `offset`
+    pub fn slice(&self, offset: usize, len: usize) -> Self {
+        assert!(offset + len <= self.len, "slice {}..{} out of buffer of {}", offset, offset + len, self.len);
+        Buffer { data: self.data.clone(), offset: self.offset + offset, len }
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.data.as_slice()[self.offset..self.offset + self.len]
+    }
+
+    /// whether both buffers are views of the same allocation
+    pub fn ptr_eq(&self, other: &Buffer<T>) -> bool {
+        Arc::ptr_eq(&self.data, &other.data)
+    }
+}
+
+impl<T: Clone> Buffer<T> {
+    /// take the elements out, without copying when this buffer is the only view of its allocation
+    pub fn into_vec(self) -> Vec<T> {
+        if self.offset == 0 && self.len == self.data.as_slice().len() {
+            match Arc::try_unwrap(self.data) {
+                Ok(Allocation::Vec(data)) => data,
+                Ok(owner) => owner.as_slice().to_vec(),
+                Err(data) => data.as_slice().to_vec(),
+            }
+        } else {
+            self.as_slice().to_vec()
+        }
+    }
+}
+
+impl<T> Clone for Buffer<T> {
+    fn clone(&self) -> Self {
+        Buffer { data: self.data.clone(), offset: self.offset, len: self.len }
+    }
+}
+
+impl<T> Deref for Buffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T> From<Vec<T>> for Buffer<T> {
+    fn from(data: Vec<T>) -> Self {
+        let len = data.len();
+        Buffer { data: Arc::new(Allocation::Vec(data)), offset: 0, len }
+    }
+}
+
+impl<T> FromIterator<T> for Buffer<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Buffer::from(iter.into_iter().collect::<Vec<T>>())
+    }
+}
+
+impl<T> Default for Buffer<T> {
+    fn default() -> Self {
+        Buffer::from(Vec::new())
+    }
+}
+
+impl<T: PartialEq> PartialEq for Buffer<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Buffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_slice().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice_shares_allocation() {
+        let buffer = Buffer::from(vec![1, 2, 3, 4, 5]);
+        let slice = buffer.slice(1, 3);
+        assert_eq!(&*slice, &[2, 3, 4]);
+        assert!(slice.ptr_eq(&buffer));
+        assert_eq!(slice.slice(1, 2).as_slice(), &[3, 4]);
+        assert_eq!(slice.into_vec(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_from_owner() {
+        let owner: Arc<[i64]> = Arc::from(vec![1, 2, 3]);
+        let buffer = Buffer::from_owner(owner.clone());
+        assert_eq!(buffer.slice(1, 2).as_slice(), &[2, 3]);
+        assert_eq!(buffer.as_ptr(), owner.as_ptr());
+        assert_eq!(buffer.into_vec(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::error::{Error, Result};
+use crate::qir::DataType;
+use crate::vector::{Bitmap, ListVector, MapVector, PrimaryType, PrimaryVector, StringVector, StructVector, Value, Vector};
+
+/// appends values one at a time and produces a vector of the builder's data type
+pub enum VectorBuilder {
+    I8(PrimaryBuilder<i8>),
+    I16(PrimaryBuilder<i16>),
+    I32(PrimaryBuilder<i32>),
+    I64(PrimaryBuilder<i64>),
+    U8(PrimaryBuilder<u8>),
+    U16(PrimaryBuilder<u16>),
+    U32(PrimaryBuilder<u32>),
+    U64(PrimaryBuilder<u64>),
+    F32(PrimaryBuilder<f32>),
+    F64(PrimaryBuilder<f64>),
+    Decimal(PrimaryBuilder<i128>),
+    Bool(PrimaryBuilder<bool>),
+    String { offsets: Vec<i32>, data: Vec<u8>, validity: Bitmap },
+    Date(PrimaryBuilder<i32>),
+    DateTime(PrimaryBuilder<i64>),
+    List { offsets: Vec<i32>, values: Box<VectorBuilder>, validity: Bitmap },
+    Struct { names: Vec<String>, children: Vec<VectorBuilder>, validity: Bitmap },
+    Map { offsets: Vec<i32>, keys: Box<VectorBuilder>, values: Box<VectorBuilder>, validity: Bitmap },
+}
+
+pub struct PrimaryBuilder<T: PrimaryType> {
+    data: Vec<T>,
+    validity: Bitmap,
+}
+
+impl<T: PrimaryType> PrimaryBuilder<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        PrimaryBuilder { data: Vec::with_capacity(capacity), validity: Bitmap::with_capacity(capacity) }
+    }
+
+    #[inline]
+    pub fn push(&mut self, value: Option<T>) {
+        self.data.push(value.unwrap_or_default());
+        self.validity.push(value.is_some());
+    }
+
+    pub fn extend(&mut self, vector: &PrimaryVector<T>) {
+        self.data.extend_from_slice(vector.values());
+        match vector.validity() {
+            Some(validity) => self.validity.extend_from_bitmap(validity),
+            None => self.validity.extend_from_bitmap(&Bitmap::new(vector.len(), true)),
+        }
+    }
+
+    pub fn finish(self) -> PrimaryVector<T> {
+        PrimaryVector::with_validity(self.data, Some(self.validity))
+    }
+}
+
+fn type_mismatch(data_type: &DataType, value: &Value) -> Error {
+    Error::TypeMismatch { expected: format!("{data_type:?}"), found: format!("{value:?}") }
+}
+
+impl VectorBuilder {
+    pub fn new(data_type: &DataType) -> Self {
+        Self::with_capacity(data_type, 0)
+    }
+
+    pub fn with_capacity(data_type: &DataType, capacity: usize) -> Self {
+        match data_type {
+            DataType::I8 => VectorBuilder::I8(PrimaryBuilder::with_capacity(capacity)),
+            DataType::I16 => VectorBuilder::I16(PrimaryBuilder::with_capacity(capacity)),
+            DataType::I32 => VectorBuilder::I32(PrimaryBuilder::with_capacity(capacity)),
+            DataType::I64 => VectorBuilder::I64(PrimaryBuilder::with_capacity(capacity)),
+            DataType::U8 => VectorBuilder::U8(PrimaryBuilder::with_capacity(capacity)),
+            DataType::U16 => VectorBuilder::U16(PrimaryBuilder::with_capacity(capacity)),
+            DataType::U32 => VectorBuilder::U32(PrimaryBuilder::with_capacity(capacity)),
+            DataType::U64 => VectorBuilder::U64(PrimaryBuilder::with_capacity(capacity)),
+            DataType::F32 => VectorBuilder::F32(PrimaryBuilder::with_capacity(capacity)),
+            DataType::F64 => VectorBuilder::F64(PrimaryBuilder::with_capacity(capacity)),
+            DataType::Decimal => VectorBuilder::Decimal(PrimaryBuilder::with_capacity(capacity)),
+            DataType::Bool => VectorBuilder::Bool(PrimaryBuilder::with_capacity(capacity)),
+            DataType::String => VectorBuilder::String { offsets: vec![0], data: vec![], validity: Bitmap::with_capacity(capacity) },
+            DataType::Date => VectorBuilder::Date(PrimaryBuilder::with_capacity(capacity)),
+            DataType::DateTime => VectorBuilder::DateTime(PrimaryBuilder::with_capacity(capacity)),
+            DataType::List(element) => VectorBuilder::List {
+                offsets: vec![0],
+                values: Box::new(VectorBuilder::new(element)),
+                validity: Bitmap::with_capacity(capacity),
+            },
+            DataType::Struct(table) => VectorBuilder::Struct {
+                names: table.columns.iter().map(|c| c.name.clone()).collect(),
+                children: table.columns.iter().map(|c| VectorBuilder::with_capacity(&c.data_type, capacity)).collect(),
+                validity: Bitmap::with_capacity(capacity),
+            },
+            DataType::Map(key, value) => VectorBuilder::Map {
+                offsets: vec![0],
+                keys: Box::new(VectorBuilder::new(key)),
+                values: Box::new(VectorBuilder::new(value)),
+                validity: Bitmap::with_capacity(capacity),
+            },
+        }
+    }
+
+    /// append a value, which must be `Null` or match the builder's data type
+    pub fn push(&mut self, value: &Value) -> Result<()> {
+        macro_rules! push_primary {
+            ($builder:expr, $variant:ident) => {
+                match value {
+                    Value::Null => $builder.push(None),
+                    Value::$variant(v) => $builder.push(Some(*v)),
+                    _ => return Err(type_mismatch(&self.data_type(), value)),
+                }
+            };
+        }
+        match self {
+            VectorBuilder::I8(b) => push_primary!(b, I8),
+            VectorBuilder::I16(b) => push_primary!(b, I16),
+            VectorBuilder::I32(b) => push_primary!(b, I32),
+            VectorBuilder::I64(b) => push_primary!(b, I64),
+            VectorBuilder::U8(b) => push_primary!(b, U8),
+            VectorBuilder::U16(b) => push_primary!(b, U16),
+            VectorBuilder::U32(b) => push_primary!(b, U32),
+            VectorBuilder::U64(b) => push_primary!(b, U64),
+            VectorBuilder::F32(b) => push_primary!(b, F32),
+            VectorBuilder::F64(b) => push_primary!(b, F64),
+            VectorBuilder::Decimal(b) => push_primary!(b, Decimal),
+            VectorBuilder::Bool(b) => push_primary!(b, Bool),
+            VectorBuilder::Date(b) => push_primary!(b, Date),
+            VectorBuilder::DateTime(b) => push_primary!(b, DateTime),
+            VectorBuilder::String { offsets, data, validity } => match value {
+                Value::Null | Value::String(_) => {
+                    if let Value::String(s) = value {
+                        data.extend_from_slice(s.as_bytes());
+                    }
+                    offsets.push(data.len() as i32);
+                    validity.push(!value.is_null());
+                }
+                _ => return Err(type_mismatch(&DataType::String, value)),
+            },
+            VectorBuilder::List { offsets, values, validity } => {
+                match value {
+                    Value::Null => {}
+                    Value::List(elements) => {
+                        for element in elements {
+                            values.push(element)?;
+                        }
+                    }
+                    _ => return Err(type_mismatch(&DataType::List(Box::new(values.data_type())), value)),
+                }
+                offsets.push(values.len() as i32);
+                validity.push(!value.is_null());
+            }
+            VectorBuilder::Struct { children, validity, .. } => {
+                match value {
+                    Value::Null => {
+                        for child in children.iter_mut() {
+                            child.push(&Value::Null)?;
+                        }
+                    }
+                    Value::Struct(fields) if fields.len() == children.len() => {
+                        for (child, field) in children.iter_mut().zip(fields) {
+                            child.push(field)?;
+                        }
+                    }
+                    _ => return Err(type_mismatch(&self.data_type(), value)),
+                }
+                validity.push(!value.is_null());
+            }
+            VectorBuilder::Map { offsets, keys, values, validity } => {
+                match value {
+                    Value::Null => {}
+                    Value::Map(entries) => {
+                        for (key, value) in entries {
+                            keys.push(key)?;
+                            values.push(value)?;
+                        }
+                    }
+                    _ => return Err(type_mismatch(&DataType::Map(Box::new(keys.data_type()), Box::new(values.data_type())), value)),
+                }
+                offsets.push(keys.len() as i32);
+                validity.push(!value.is_null());
+            }
+        }
+        Ok(())
+    }
+
+    /// append every row of `vector`, which must have the builder's data type
+    pub fn extend(&mut self, vector: &Vector) -> Result<()> {
+        match (&mut *self, vector) {
+            (VectorBuilder::I8(b), Vector::I8(v)) => b.extend(v),
+            (VectorBuilder::I16(b), Vector::I16(v)) => b.extend(v),
+            (VectorBuilder::I32(b), Vector::I32(v)) => b.extend(v),
+            (VectorBuilder::I64(b), Vector::I64(v)) => b.extend(v),
+            (VectorBuilder::U8(b), Vector::U8(v)) => b.extend(v),
+            (VectorBuilder::U16(b), Vector::U16(v)) => b.extend(v),
+            (VectorBuilder::U32(b), Vector::U32(v)) => b.extend(v),
+            (VectorBuilder::U64(b), Vector::U64(v)) => b.extend(v),
+            (VectorBuilder::F32(b), Vector::F32(v)) => b.extend(v),
+            (VectorBuilder::F64(b), Vector::F64(v)) => b.extend(v),
+            (VectorBuilder::Decimal(b), Vector::Decimal(v)) => b.extend(v),
+            (VectorBuilder::Bool(b), Vector::Bool(v)) => b.extend(v),
+            (VectorBuilder::Date(b), Vector::Date(v)) => b.extend(v),
+            (VectorBuilder::DateTime(b), Vector::DateTime(v)) => b.extend(v),
+            (VectorBuilder::String { offsets, data, validity }, Vector::String(v)) => {
+                for value in v.iter() {
+                    data.extend_from_slice(value.unwrap_or("").as_bytes());
+                    offsets.push(data.len() as i32);
+                    validity.push(value.is_some());
+                }
+            }
+            (builder, vector) => {
+                if builder.data_type() != vector.data_type() {
+                    return Err(Error::TypeMismatch {
+                        expected: format!("{:?}", builder.data_type()),
+                        found: format!("{:?}", vector.data_type()),
+                    });
+                }
+                for i in 0..vector.len() {
+                    builder.push(&vector.get(i))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn data_type(&self) -> DataType {
+        match self {
+            VectorBuilder::I8(_) => DataType::I8,
+            VectorBuilder::I16(_) => DataType::I16,
+            VectorBuilder::I32(_) => DataType::I32,
+            VectorBuilder::I64(_) => DataType::I64,
+            VectorBuilder::U8(_) => DataType::U8,
+            VectorBuilder::U16(_) => DataType::U16,
+            VectorBuilder::U32(_) => DataType::U32,
+            VectorBuilder::U64(_) => DataType::U64,
+            VectorBuilder::F32(_) => DataType::F32,
+            VectorBuilder::F64(_) => DataType::F64,
+            VectorBuilder::Decimal(_) => DataType::Decimal,
+            VectorBuilder::Bool(_) => DataType::Bool,
+            VectorBuilder::String { .. } => DataType::String,
+            VectorBuilder::Date(_) => DataType::Date,
+            VectorBuilder::DateTime(_) => DataType::DateTime,
+            VectorBuilder::List { values, .. } => DataType::List(Box::new(values.data_type())),
+            VectorBuilder::Struct { names, children, .. } => {
+                let columns = names.iter().zip(children).map(|(name, child)| crate::qir::Column {
+                    name: name.clone(),
+                    data_type: child.data_type(),
+                    nullable: true,
+                });
+                DataType::Struct(Box::new(crate::qir::Table { name: String::new(), columns: columns.collect() }))
+            }
+            VectorBuilder::Map { keys, values, .. } => DataType::Map(Box::new(keys.data_type()), Box::new(values.data_type())),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            VectorBuilder::I8(b) => b.data.len(),
+            VectorBuilder::I16(b) => b.data.len(),
+            VectorBuilder::I32(b) | VectorBuilder::Date(b) => b.data.len(),
+            VectorBuilder::I64(b) | VectorBuilder::DateTime(b) => b.data.len(),
+            VectorBuilder::U8(b) => b.data.len(),
+            VectorBuilder::U16(b) => b.data.len(),
+            VectorBuilder::U32(b) => b.data.len(),
+            VectorBuilder::U64(b) => b.data.len(),
+            VectorBuilder::F32(b) => b.data.len(),
+            VectorBuilder::F64(b) => b.data.len(),
+            VectorBuilder::Decimal(b) => b.data.len(),
+            VectorBuilder::Bool(b) => b.data.len(),
+            VectorBuilder::String { validity, .. }
+            | VectorBuilder::List { validity, .. }
+            | VectorBuilder::Struct { validity, .. }
+            | VectorBuilder::Map { validity, .. } => validity.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn finish(self) -> Vector {
+        match self {
+            VectorBuilder::I8(b) => Vector::I8(b.finish()),
+            VectorBuilder::I16(b) => Vector::I16(b.finish()),
+            VectorBuilder::I32(b) => Vector::I32(b.finish()),
+            VectorBuilder::I64(b) => Vector::I64(b.finish()),
+            VectorBuilder::U8(b) => Vector::U8(b.finish()),
+            VectorBuilder::U16(b) => Vector::U16(b.finish()),
+            VectorBuilder::U32(b) => Vector::U32(b.finish()),
+            VectorBuilder::U64(b) => Vector::U64(b.finish()),
+            VectorBuilder::F32(b) => Vector::F32(b.finish()),
+            VectorBuilder::F64(b) => Vector::F64(b.finish()),
+            VectorBuilder::Decimal(b) => Vector::Decimal(b.finish()),
+            VectorBuilder::Bool(b) => Vector::Bool(b.finish()),
+            VectorBuilder::Date(b) => Vector::Date(b.finish()),
+            VectorBuilder::DateTime(b) => Vector::DateTime(b.finish()),
+            VectorBuilder::String { offsets, data, validity } => {
+                let vector = StringVector::try_new(offsets.into(), data.into(), Some(validity));
+                Vector::String(vector.expect("a string builder only holds utf-8 strings"))
+            }
+            VectorBuilder::List { offsets, values, validity } => {
+                Vector::List(ListVector::new(offsets.into(), values.finish(), Some(validity)))
+            }
+            VectorBuilder::Struct { names, children, validity } => {
+                Vector::Struct(StructVector::new(names, children.into_iter().map(|c| c.finish()).collect(), Some(validity)))
+            }
+            VectorBuilder::Map { offsets, keys, values, validity } => {
+                Vector::Map(MapVector::new(offsets.into(), keys.finish(), values.finish(), Some(validity)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_nested() {
+        let data_type = DataType::List(Box::new(DataType::I32));
+        let mut builder = VectorBuilder::new(&data_type);
+        builder.push(&Value::List(vec![Value::I32(1), Value::I32(2)])).unwrap();
+        builder.push(&Value::Null).unwrap();
+        builder.push(&Value::List(vec![Value::Null])).unwrap();
+        assert!(builder.push(&Value::I32(1)).is_err());
+
+        let vector = builder.finish();
+        assert_eq!(vector.data_type(), data_type);
+        assert_eq!(vector.len(), 3);
+        assert_eq!(vector.get(0), Value::List(vec![Value::I32(1), Value::I32(2)]));
+        assert_eq!(vector.get(1), Value::Null);
+        assert_eq!(vector.take(&[2]).get(0), Value::List(vec![Value::Null]));
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut builder = VectorBuilder::new(&DataType::String);
+        builder.extend(&Vector::from(vec!["a", "b"])).unwrap();
+        builder.push(&Value::Null).unwrap();
+        assert!(builder.extend(&Vector::from(vec![1i32])).is_err());
+        assert_eq!(builder.finish(), Vector::String(StringVector::from_options(vec![Some("a"), Some("b"), None])));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::error::{Error, Result};
+use crate::vector::{SelectionVector, Value, Vector};
+
+/// a batch of rows, stored column by column. all columns have the same length.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataChunk {
+    pub columns: Vec<Vector>,
+    len: usize,
+}
+
+impl DataChunk {
+    pub fn new(columns: Vec<Vector>) -> Self {
+        let len = columns.first().map(|c| c.len()).unwrap_or(0);
+        debug_assert!(columns.iter().all(|c| c.len() == len));
+        DataChunk { columns, len }
+    }
+
+    /// a chunk without columns, only carries a row count
+    pub fn empty(len: usize) -> Self {
+        DataChunk { columns: vec![], len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn column(&self, index: usize) -> &Vector {
+        &self.columns[index]
+    }
+
+    /// the values of row `index`
+    pub fn row(&self, index: usize) -> Vec<Value> {
+        self.columns.iter().map(|c| c.get(index)).collect()
+    }
+
+    pub fn slice(&self, offset: usize, len: usize) -> DataChunk {
+        DataChunk { columns: self.columns.iter().map(|c| c.slice(offset, len)).collect(), len }
+    }
+
+    pub fn take(&self, indices: &[u32]) -> DataChunk {
+        DataChunk { columns: self.columns.iter().map(|c| c.take(indices)).collect(), len: indices.len() }
+    }
+
+    /// keep only the selected rows, without copying when every row is selected
+    pub fn select(&self, selection: &SelectionVector) -> DataChunk {
+        if selection.is_all(self.len) {
+            return self.clone();
+        }
+        self.take(selection)
+    }
+
+    /// keep only the columns at `indices`, in that order
+    pub fn project(&self, indices: &[usize]) -> DataChunk {
+        DataChunk { columns: indices.iter().map(|&i| self.columns[i].clone()).collect(), len: self.len }
+    }
+
+    /// concatenate chunks with the same columns
+    pub fn concat(chunks: &[DataChunk]) -> Result<DataChunk> {
+        let Some(first) = chunks.first() else {
+            return Ok(DataChunk::empty(0));
+        };
+        if chunks.iter().any(|c| c.columns.len() != first.columns.len()) {
+            return Err(Error::Unsupported("concat chunks with different columns".to_string()));
+        }
+        let len = chunks.iter().map(|c| c.len).sum();
+        let columns = (0..first.columns.len())
+            .map(|i| {
+                let vectors: Vec<Vector> = chunks.iter().map(|c| c.columns[i].clone()).collect();
+                Vector::concat(&first.columns[i].data_type(), &vectors)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(DataChunk { columns, len })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_slice_and_take() {
+        let chunk = DataChunk::new(vec![
+            Vector::from(vec![1i32, 2, 3, 4]),
+            Vector::from(vec!["a", "b", "c", "d"]),
+        ]);
+        assert_eq!(chunk.len(), 4);
+
+        let sliced = chunk.slice(1, 2);
+        assert_eq!(sliced.column(0), &Vector::from(vec![2i32, 3]));
+
+        let taken = chunk.take(&[3, 0]);
+        assert_eq!(taken.column(1), &Vector::from(vec!["d", "a"]));
+        assert_eq!(taken.row(0), vec![Value::I32(4), "d".into()]);
+
+        let projected = chunk.project(&[1]);
+        assert_eq!(projected.columns.len(), 1);
+        assert_eq!(projected.len(), 4);
+
+        let concat = DataChunk::concat(&[sliced, taken]).unwrap();
+        assert_eq!(concat.column(0), &Vector::from(vec![2i32, 3, 4, 1]));
+    }
+}
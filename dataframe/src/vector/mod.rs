@@ -0,0 +1,301 @@
+//! The columnar batch format shared by all operators.
+//!
+//! - `Vector`: a column, one variant per `DataType`. fixed width types are `PrimaryVector<T>`,
+//!   strings, lists and maps use arrow-like offsets.
+//! - `Bitmap`: the validity mask of a vector, `None` when a vector has no nulls.
+//! - `SelectionVector`: the rows of a chunk selected by a filter.
+//! - `DataChunk`: a group of vectors of the same length, at most `VECTOR_SIZE` rows flow
+//!   through a pipeline at a time.
+
+use crate::qir::{Column, DataType, Table};
+
+mod arrow;
+mod bitmap;
+mod buffer;
+mod builder;
+mod chunk;
+mod nested;
+mod primary;
+mod selection;
+mod string;
+mod value;
+
+pub use self::arrow::{from_arrow_type, to_arrow_type};
+pub use bitmap::Bitmap;
+pub use buffer::Buffer;
+pub use builder::{PrimaryBuilder, VectorBuilder};
+pub use chunk::DataChunk;
+pub use nested::{ListVector, MapVector, StructVector};
+pub use primary::{PrimaryType, PrimaryVector};
+pub use selection::SelectionVector;
+pub use string::StringVector;
+pub use value::{Value, DECIMAL_SCALE};
+
+/// the number of rows a source pushes through a pipeline at a time
+pub const VECTOR_SIZE: usize = 2048;
+
+/// a column of values
+#[derive(Debug, Clone, PartialEq)]
+pub enum Vector {
+    I8(PrimaryVector<i8>),
+    I16(PrimaryVector<i16>),
+    I32(PrimaryVector<i32>),
+    I64(PrimaryVector<i64>),
+    U8(PrimaryVector<u8>),
+    U16(PrimaryVector<u16>),
+    U32(PrimaryVector<u32>),
+    U64(PrimaryVector<u64>),
+    F32(PrimaryVector<f32>),
+    F64(PrimaryVector<f64>),
+    /// fixed point, scaled by 10^DECIMAL_SCALE
+    Decimal(PrimaryVector<i128>),
+    Bool(PrimaryVector<bool>),
+    String(StringVector),
+    /// days since 1970-01-01
+    Date(PrimaryVector<i32>),
+    /// microseconds since 1970-01-01 00:00:00 UTC
+    DateTime(PrimaryVector<i64>),
+    List(ListVector),
+    Struct(StructVector),
+    Map(MapVector),
+}
+
+/// evaluate `$body` with `$v` bound to the inner vector of any variant
+macro_rules! with_vector {
+    ($vector:expr, $v:ident => $body:expr) => {
+        match $vector {
+            Vector::I8($v) => $body,
+            Vector::I16($v) => $body,
+            Vector::I32($v) => $body,
+            Vector::I64($v) => $body,
+            Vector::U8($v) => $body,
+            Vector::U16($v) => $body,
+            Vector::U32($v) => $body,
+            Vector::U64($v) => $body,
+            Vector::F32($v) => $body,
+            Vector::F64($v) => $body,
+            Vector::Decimal($v) => $body,
+            Vector::Bool($v) => $body,
+            Vector::String($v) => $body,
+            Vector::Date($v) => $body,
+            Vector::DateTime($v) => $body,
+            Vector::List($v) => $body,
+            Vector::Struct($v) => $body,
+            Vector::Map($v) => $body,
+        }
+    };
+}
+
+/// like `with_vector!`, re-wrapping the result in the same variant
+macro_rules! map_vector {
+    ($vector:expr, $v:ident => $body:expr) => {
+        match $vector {
+            Vector::I8($v) => Vector::I8($body),
+            Vector::I16($v) => Vector::I16($body),
+            Vector::I32($v) => Vector::I32($body),
+            Vector::I64($v) => Vector::I64($body),
+            Vector::U8($v) => Vector::U8($body),
+            Vector::U16($v) => Vector::U16($body),
+            Vector::U32($v) => Vector::U32($body),
+            Vector::U64($v) => Vector::U64($body),
+            Vector::F32($v) => Vector::F32($body),
+            Vector::F64($v) => Vector::F64($body),
+            Vector::Decimal($v) => Vector::Decimal($body),
+            Vector::Bool($v) => Vector::Bool($body),
+            Vector::String($v) => Vector::String($body),
+            Vector::Date($v) => Vector::Date($body),
+            Vector::DateTime($v) => Vector::DateTime($body),
+            Vector::List($v) => Vector::List($body),
+            Vector::Struct($v) => Vector::Struct($body),
+            Vector::Map($v) => Vector::Map($body),
+        }
+    };
+}
+
+impl Vector {
+    pub fn data_type(&self) -> DataType {
+        match self {
+            Vector::I8(_) => DataType::I8,
+            Vector::I16(_) => DataType::I16,
+            Vector::I32(_) => DataType::I32,
+            Vector::I64(_) => DataType::I64,
+            Vector::U8(_) => DataType::U8,
+            Vector::U16(_) => DataType::U16,
+            Vector::U32(_) => DataType::U32,
+            Vector::U64(_) => DataType::U64,
+            Vector::F32(_) => DataType::F32,
+            Vector::F64(_) => DataType::F64,
+            Vector::Decimal(_) => DataType::Decimal,
+            Vector::Bool(_) => DataType::Bool,
+            Vector::String(_) => DataType::String,
+            Vector::Date(_) => DataType::Date,
+            Vector::DateTime(_) => DataType::DateTime,
+            Vector::List(v) => DataType::List(Box::new(v.values().data_type())),
+            Vector::Struct(v) => {
+                let columns = v.names().iter().zip(v.children())
+                    .map(|(name, child)| Column { name: name.clone(), data_type: child.data_type(), nullable: true })
+                    .collect();
+                DataType::Struct(Box::new(Table { name: String::new(), columns }))
+            }
+            Vector::Map(v) => DataType::Map(Box::new(v.keys().data_type()), Box::new(v.values().data_type())),
+        }
+    }
+
+    /// build a vector of `data_type` from values, which must be `Null` or of that type
+    pub fn from_values(data_type: &DataType, values: &[Value]) -> crate::error::Result<Vector> {
+        let mut builder = VectorBuilder::with_capacity(data_type, values.len());
+        for value in values {
+            builder.push(value)?;
+        }
+        Ok(builder.finish())
+    }
+
+    /// a vector of `len` nulls
+    pub fn new_null(data_type: &DataType, len: usize) -> Vector {
+        let mut builder = VectorBuilder::with_capacity(data_type, len);
+        for _ in 0..len {
+            builder.push(&Value::Null).expect("null matches every type");
+        }
+        builder.finish()
+    }
+
+    /// concatenate vectors of the same data type
+    pub fn concat(data_type: &DataType, vectors: &[Vector]) -> crate::error::Result<Vector> {
+        let mut builder = VectorBuilder::with_capacity(data_type, vectors.iter().map(|v| v.len()).sum());
+        for vector in vectors {
+            builder.extend(vector)?;
+        }
+        Ok(builder.finish())
+    }
+
+    pub fn len(&self) -> usize {
+        with_vector!(self, v => v.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn validity(&self) -> Option<&Bitmap> {
+        with_vector!(self, v => v.validity())
+    }
+
+    pub fn is_valid(&self, index: usize) -> bool {
+        with_vector!(self, v => v.is_valid(index))
+    }
+
+    pub fn null_count(&self) -> usize {
+        self.validity().map(|v| v.count_zeros()).unwrap_or(0)
+    }
+
+    /// the value of row `index`, row-at-a-time access for tests and rarely used paths
+    pub fn get(&self, index: usize) -> Value {
+        if !self.is_valid(index) {
+            return Value::Null;
+        }
+        match self {
+            Vector::I8(v) => Value::I8(v.values()[index]),
+            Vector::I16(v) => Value::I16(v.values()[index]),
+            Vector::I32(v) => Value::I32(v.values()[index]),
+            Vector::I64(v) => Value::I64(v.values()[index]),
+            Vector::U8(v) => Value::U8(v.values()[index]),
+            Vector::U16(v) => Value::U16(v.values()[index]),
+            Vector::U32(v) => Value::U32(v.values()[index]),
+            Vector::U64(v) => Value::U64(v.values()[index]),
+            Vector::F32(v) => Value::F32(v.values()[index]),
+            Vector::F64(v) => Value::F64(v.values()[index]),
+            Vector::Decimal(v) => Value::Decimal(v.values()[index]),
+            Vector::Bool(v) => Value::Bool(v.values()[index]),
+            Vector::String(v) => Value::String(v.value(index).to_string()),
+            Vector::Date(v) => Value::Date(v.values()[index]),
+            Vector::DateTime(v) => Value::DateTime(v.values()[index]),
+            Vector::List(v) => v.get(index),
+            Vector::Struct(v) => v.get(index),
+            Vector::Map(v) => v.get(index),
+        }
+    }
+
+    /// rows `offset..offset+len`, sharing the underlying buffers
+    pub fn slice(&self, offset: usize, len: usize) -> Vector {
+        map_vector!(self, v => v.slice(offset, len))
+    }
+
+    /// gather the rows at `indices` into a new vector
+    pub fn take(&self, indices: &[u32]) -> Vector {
+        map_vector!(self, v => v.take(indices))
+    }
+
+    pub fn select(&self, selection: &SelectionVector) -> Vector {
+        self.take(selection)
+    }
+}
+
+impl From<Vec<&str>> for Vector {
+    fn from(values: Vec<&str>) -> Self {
+        Vector::String(StringVector::from_options(values.into_iter().map(Some)))
+    }
+}
+
+impl From<Vec<Option<&str>>> for Vector {
+    fn from(values: Vec<Option<&str>>) -> Self {
+        Vector::String(StringVector::from_options(values))
+    }
+}
+
+impl From<Vec<String>> for Vector {
+    fn from(values: Vec<String>) -> Self {
+        Vector::String(StringVector::from_options(values.iter().map(|s| Some(s.as_str()))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_types() {
+        let vector = Vector::from(vec![Some(1i64), None, Some(3)]);
+        assert_eq!(vector.data_type(), DataType::I64);
+        assert_eq!(vector.null_count(), 1);
+        assert_eq!(vector.get(2), Value::I64(3));
+        assert_eq!(vector.slice(1, 2).get(0), Value::Null);
+
+        let date = Vector::Date(PrimaryVector::new(vec![0, 1]));
+        assert_eq!(date.get(1).to_string(), "1970-01-02");
+
+        let strings = Vector::from(vec!["x", "y", "z"]);
+        assert_eq!(strings.select(&SelectionVector::from(vec![0, 2])), Vector::from(vec!["x", "z"]));
+    }
+
+    #[test]
+    fn test_struct_and_map() {
+        let person = Vector::Struct(StructVector::new(
+            vec!["id".into(), "name".into()],
+            vec![Vector::from(vec![1i32, 2]), Vector::from(vec!["a", "b"])],
+            Some([true, false].into_iter().collect()),
+        ));
+        assert_eq!(person.get(0), Value::Struct(vec![Value::I32(1), "a".into()]));
+        assert_eq!(person.get(1), Value::Null);
+        match person.data_type() {
+            DataType::Struct(table) => assert_eq!(table.columns[1].data_type, DataType::String),
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let tags = Vector::Map(MapVector::new(
+            Buffer::from(vec![0, 2, 2]),
+            Vector::from(vec!["k1", "k2"]),
+            Vector::from(vec![10u8, 20]),
+            None,
+        ));
+        assert_eq!(tags.get(0), Value::Map(vec![("k1".into(), Value::U8(10)), ("k2".into(), Value::U8(20))]));
+        assert_eq!(tags.get(1), Value::Map(vec![]));
+        assert_eq!(Vector::concat(&tags.data_type(), &[tags.clone(), tags.slice(0, 1)]).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_from_values() {
+        let vector = Vector::from_values(&DataType::Decimal, &[Value::Decimal(15000), Value::Null]).unwrap();
+        assert_eq!(vector.get(0).to_string(), "1.5000");
+        assert_eq!(Vector::new_null(&DataType::String, 2).null_count(), 2);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::vector::{Bitmap, Buffer, Value, Vector};
+
+/// gather the child rows of the lists at `indices`, returning the new offsets and the child row indices
+fn take_ranges(offsets: &[i32], indices: &[u32]) -> (Vec<i32>, Vec<u32>) {
+    let mut new_offsets = Vec::with_capacity(indices.len() + 1);
+    let mut rows = Vec::new();
+    new_offsets.push(0);
+    for &i in indices {
+        let (start, end) = (offsets[i as usize], offsets[i as usize + 1]);
+        rows.extend(start as u32..end as u32);
+        new_offsets.push(rows.len() as i32);
+    }
+    (new_offsets, rows)
+}
+
+/// A vector of lists, row `i` is `values[offsets[i]..offsets[i+1]]`.
+#[derive(Debug, Clone)]
+pub struct ListVector {
+    offsets: Buffer<i32>,
+    values: Box<Vector>,
+    validity: Option<Bitmap>,
+}
+
+impl ListVector {
+    pub fn new(offsets: Buffer<i32>, values: Vector, validity: Option<Bitmap>) -> Self {
+        debug_assert!(!offsets.is_empty() && offsets[offsets.len() - 1] as usize <= values.len());
+        ListVector { offsets, values: Box::new(values), validity: validity.filter(|v| !v.all_set()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn offsets(&self) -> &Buffer<i32> {
+        &self.offsets
+    }
+
+    /// the child vector holding the elements of all lists
+    pub fn values(&self) -> &Vector {
+        &self.values
+    }
+
+    pub fn validity(&self) -> Option<&Bitmap> {
+        self.validity.as_ref()
+    }
+
+    pub fn is_valid(&self, index: usize) -> bool {
+        self.validity.as_ref().is_none_or(|v| v.get(index))
+    }
+
+    /// the elements of row `index`
+    pub fn value(&self, index: usize) -> Vector {
+        let start = self.offsets[index] as usize;
+        self.values.slice(start, self.offsets[index + 1] as usize - start)
+    }
+
+    pub fn get(&self, index: usize) -> Value {
+        if !self.is_valid(index) {
+            return Value::Null;
+        }
+        let list = self.value(index);
+        Value::List((0..list.len()).map(|i| list.get(i)).collect())
+    }
+
+    pub fn slice(&self, offset: usize, len: usize) -> Self {
+        ListVector {
+            offsets: self.offsets.slice(offset, len + 1),
+            values: self.values.clone(),
+            validity: self.validity.as_ref().map(|v| v.slice(offset, len)).filter(|v| !v.all_set()),
+        }
+    }
+
+    pub fn take(&self, indices: &[u32]) -> Self {
+        let (offsets, rows) = take_ranges(&self.offsets, indices);
+        ListVector::new(offsets.into(), self.values.take(&rows), self.validity.as_ref().map(|v| v.take(indices)))
+    }
+}
+
+/// A vector of structs, one child vector per field.
+#[derive(Debug, Clone)]
+pub struct StructVector {
+    names: Vec<String>,
+    children: Vec<Vector>,
+    validity: Option<Bitmap>,
+    len: usize,
+}
+
+impl StructVector {
+    pub fn new(names: Vec<String>, children: Vec<Vector>, validity: Option<Bitmap>) -> Self {
+        assert_eq!(names.len(), children.len());
+        let len = children.first().map(|c| c.len()).or(validity.as_ref().map(|v| v.len())).unwrap_or(0);
+        debug_assert!(children.iter().all(|c| c.len() == len));
+        StructVector { names, children, validity: validity.filter(|v| !v.all_set()), len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn children(&self) -> &[Vector] {
+        &self.children
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Vector> {
+        self.names.iter().position(|n| n == name).map(|i| &self.children[i])
+    }
+
+    pub fn validity(&self) -> Option<&Bitmap> {
+        self.validity.as_ref()
+    }
+
+    pub fn is_valid(&self, index: usize) -> bool {
+        self.validity.as_ref().is_none_or(|v| v.get(index))
+    }
+
+    pub fn get(&self, index: usize) -> Value {
+        if !self.is_valid(index) {
+            return Value::Null;
+        }
+        Value::Struct(self.children.iter().map(|c| c.get(index)).collect())
+    }
+
+    pub fn slice(&self, offset: usize, len: usize) -> Self {
+        StructVector {
+            names: self.names.clone(),
+            children: self.children.iter().map(|c| c.slice(offset, len)).collect(),
+            validity: self.validity.as_ref().map(|v| v.slice(offset, len)).filter(|v| !v.all_set()),
+            len,
+        }
+    }
+
+    pub fn take(&self, indices: &[u32]) -> Self {
+        StructVector {
+            names: self.names.clone(),
+            children: self.children.iter().map(|c| c.take(indices)).collect(),
+            validity: self.validity.as_ref().map(|v| v.take(indices)).filter(|v| !v.all_set()),
+            len: indices.len(),
+        }
+    }
+}
+
+/// A vector of maps, row `i` holds the entries `keys[offsets[i]..offsets[i+1]]` and the
+/// matching `values`.
+#[derive(Debug, Clone)]
+pub struct MapVector {
+    offsets: Buffer<i32>,
+    keys: Box<Vector>,
+    values: Box<Vector>,
+    validity: Option<Bitmap>,
+}
+
+impl MapVector {
+    pub fn new(offsets: Buffer<i32>, keys: Vector, values: Vector, validity: Option<Bitmap>) -> Self {
+        assert_eq!(keys.len(), values.len());
+        MapVector { offsets, keys: Box::new(keys), values: Box::new(values), validity: validity.filter(|v| !v.all_set()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn offsets(&self) -> &Buffer<i32> {
+        &self.offsets
+    }
+
+    pub fn keys(&self) -> &Vector {
+        &self.keys
+    }
+
+    pub fn values(&self) -> &Vector {
+        &self.values
+    }
+
+    pub fn validity(&self) -> Option<&Bitmap> {
+        self.validity.as_ref()
+    }
+
+    pub fn is_valid(&self, index: usize) -> bool {
+        self.validity.as_ref().is_none_or(|v| v.get(index))
+    }
+
+    pub fn get(&self, index: usize) -> Value {
+        if !self.is_valid(index) {
+            return Value::Null;
+        }
+        let (start, end) = (self.offsets[index] as usize, self.offsets[index + 1] as usize);
+        Value::Map((start..end).map(|i| (self.keys.get(i), self.values.get(i))).collect())
+    }
+
+    pub fn slice(&self, offset: usize, len: usize) -> Self {
+        MapVector {
+            offsets: self.offsets.slice(offset, len + 1),
+            keys: self.keys.clone(),
+            values: self.values.clone(),
+            validity: self.validity.as_ref().map(|v| v.slice(offset, len)).filter(|v| !v.all_set()),
+        }
+    }
+
+    pub fn take(&self, indices: &[u32]) -> Self {
+        let (offsets, rows) = take_ranges(&self.offsets, indices);
+        MapVector::new(offsets.into(), self.keys.take(&rows), self.values.take(&rows), self.validity.as_ref().map(|v| v.take(indices)))
+    }
+}
+
+macro_rules! impl_row_eq {
+    ($($t:ty),*) => {
+        $(impl PartialEq for $t {
+            fn eq(&self, other: &Self) -> bool {
+                self.len() == other.len() && (0..self.len()).all(|i| self.get(i) == other.get(i))
+            }
+        })*
+    };
+}
+
+impl_row_eq!(ListVector, StructVector, MapVector);
@@ -0,0 +1,199 @@
+use std::fmt::Debug;
+use crate::qir::DataType;
+use crate::vector::{Bitmap, Buffer, Value, Vector};
+
+/// A fixed width type that is stored as a flat array in a `PrimaryVector`.
+pub trait PrimaryType: Copy + Default + PartialEq + PartialOrd + Debug + Send + Sync + 'static {
+    const DATA_TYPE: DataType;
+
+    /// the `PrimaryVector<Self>` inside a vector, also for logical types stored as `Self`
+    /// (`Date` for `i32`, `DateTime` for `i64`)
+    fn vector(vector: &Vector) -> Option<&PrimaryVector<Self>>;
+
+    fn into_vector(vector: PrimaryVector<Self>) -> Vector;
+
+    fn into_value(self) -> Value;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+macro_rules! impl_primary_type {
+    ($t:ty, $variant:ident $(, $logical:ident)*) => {
+        impl PrimaryType for $t {
+            const DATA_TYPE: DataType = DataType::$variant;
+
+            fn vector(vector: &Vector) -> Option<&PrimaryVector<Self>> {
+                match vector {
+                    Vector::$variant(v) $(| Vector::$logical(v))* => Some(v),
+                    _ => None,
+                }
+            }
+
+            fn into_vector(vector: PrimaryVector<Self>) -> Vector {
+                Vector::$variant(vector)
+            }
+
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+
+            fn from_value(value: &Value) -> Option<Self> {
+                match value {
+                    Value::$variant(v) $(| Value::$logical(v))* => Some(*v),
+                    _ => None,
+                }
+            }
+        }
+
+        impl From<$t> for Value {
+            fn from(value: $t) -> Self {
+                Value::$variant(value)
+            }
+        }
+    };
+}
+
+impl_primary_type!(i8, I8);
+impl_primary_type!(i16, I16);
+impl_primary_type!(i32, I32, Date);
+impl_primary_type!(i64, I64, DateTime);
+impl_primary_type!(u8, U8);
+impl_primary_type!(u16, U16);
+impl_primary_type!(u32, U32);
+impl_primary_type!(u64, U64);
+impl_primary_type!(f32, F32);
+impl_primary_type!(f64, F64);
+impl_primary_type!(i128, Decimal);
+impl_primary_type!(bool, Bool);
+
+/// a flat vector of a `PrimaryType`, with an optional validity bitmap (`None` when there are no nulls).
+/// the value of a null row is unspecified (usually `T::default()`).
+#[derive(Debug, Clone)]
+pub struct PrimaryVector<T: PrimaryType> {
+    data: Buffer<T>,
+    validity: Option<Bitmap>,
+}
+
+impl<T: PrimaryType> PrimaryVector<T> {
+    pub fn new(data: impl Into<Buffer<T>>) -> Self {
+        PrimaryVector { data: data.into(), validity: None }
+    }
+
+    pub fn with_validity(data: impl Into<Buffer<T>>, validity: Option<Bitmap>) -> Self {
+        let data = data.into();
+        let validity = validity.filter(|v| !v.all_set());
+        debug_assert!(validity.as_ref().is_none_or(|v| v.len() == data.len()));
+        PrimaryVector { data, validity }
+    }
+
+    pub fn from_options<I: IntoIterator<Item = Option<T>>>(values: I) -> Self {
+        let (data, validity): (Vec<T>, Bitmap) = values.into_iter()
+            .map(|v| (v.unwrap_or_default(), v.is_some()))
+            .unzip();
+        Self::with_validity(data, Some(validity))
+    }
+
+    pub fn data_type(&self) -> DataType {
+        T::DATA_TYPE
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// the raw values, including the unspecified values of null rows
+    pub fn values(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn buffer(&self) -> &Buffer<T> {
+        &self.data
+    }
+
+    pub fn validity(&self) -> Option<&Bitmap> {
+        self.validity.as_ref()
+    }
+
+    #[inline]
+    pub fn is_valid(&self, index: usize) -> bool {
+        self.validity.as_ref().is_none_or(|v| v.get(index))
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<T> {
+        if self.is_valid(index) { Some(self.data[index]) } else { None }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Option<T>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    pub fn slice(&self, offset: usize, len: usize) -> Self {
+        PrimaryVector {
+            data: self.data.slice(offset, len),
+            validity: self.validity.as_ref().map(|v| v.slice(offset, len)).filter(|v| !v.all_set()),
+        }
+    }
+
+    pub fn take(&self, indices: &[u32]) -> Self {
+        let data: Vec<T> = indices.iter().map(|&i| self.data[i as usize]).collect();
+        Self::with_validity(data, self.validity.as_ref().map(|v| v.take(indices)))
+    }
+
+    /// apply `f` to every value, including those of null rows, keeping the validity
+    pub fn map<U: PrimaryType>(&self, f: impl Fn(T) -> U) -> PrimaryVector<U> {
+        PrimaryVector { data: self.data.iter().map(|&v| f(v)).collect(), validity: self.validity.clone() }
+    }
+
+    pub fn into_parts(self) -> (Buffer<T>, Option<Bitmap>) {
+        (self.data, self.validity)
+    }
+}
+
+impl<T: PrimaryType> PartialEq for PrimaryVector<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: PrimaryType> From<Vec<T>> for Vector {
+    fn from(data: Vec<T>) -> Self {
+        T::into_vector(PrimaryVector::new(data))
+    }
+}
+
+impl<T: PrimaryType> From<Vec<Option<T>>> for Vector {
+    fn from(data: Vec<Option<T>>) -> Self {
+        T::into_vector(PrimaryVector::from_options(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_primary_type() {
+        let vector: PrimaryVector<i32> = PrimaryVector::new(vec![1, 2, 3]);
+        assert_eq!(vector.data_type(), DataType::I32);
+
+        let date = Vector::Date(vector.clone());
+        assert_eq!(i32::vector(&date), Some(&vector));
+        assert_eq!(i64::vector(&date), None);
+    }
+
+    #[test]
+    fn test_nulls() {
+        let vector = PrimaryVector::from_options(vec![Some(1.5), None, Some(3.0)]);
+        assert_eq!(vector.get(1), None);
+        assert_eq!(vector.take(&[2, 1]).iter().collect::<Vec<_>>(), vec![Some(3.0), None]);
+        assert!(vector.slice(2, 1).validity().is_none());
+
+        let no_nulls = PrimaryVector::from_options(vec![Some(1u8), Some(2)]);
+        assert!(no_nulls.validity().is_none());
+    }
+}
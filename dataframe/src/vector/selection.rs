@@ -0,0 +1,130 @@
+use std::ops::Deref;
+use crate::vector::Bitmap;
+
+/// The positions of the selected rows of a chunk, in ascending order.
+/// Filters produce a selection instead of copying the data, the chunk is materialized once
+/// with `DataChunk::select`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SelectionVector {
+    indices: Vec<u32>,
+}
+
+impl SelectionVector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        SelectionVector { indices: Vec::with_capacity(capacity) }
+    }
+
+    /// select every row of a chunk of `len` rows
+    pub fn all(len: usize) -> Self {
+        SelectionVector { indices: (0..len as u32).collect() }
+    }
+
+    /// select the rows whose bit is set
+    pub fn from_bitmap(bitmap: &Bitmap) -> Self {
+        let mut indices = Vec::with_capacity(bitmap.count_ones());
+        for (w, &word) in bitmap.words().iter().enumerate() {
+            let mut word = word;
+            while word != 0 {
+                indices.push((w * 64) as u32 + word.trailing_zeros());
+                word &= word - 1;
+            }
+        }
+        SelectionVector { indices }
+    }
+
+    #[inline]
+    pub fn push(&mut self, index: u32) {
+        debug_assert!(self.indices.last().is_none_or(|&last| last < index));
+        self.indices.push(index);
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn into_indices(self) -> Vec<u32> {
+        self.indices
+    }
+
+    /// whether every row of a chunk of `len` rows is selected
+    pub fn is_all(&self, len: usize) -> bool {
+        self.indices.len() == len
+    }
+
+    /// the rows selected by both
+    pub fn intersect(&self, other: &SelectionVector) -> SelectionVector {
+        let (mut i, mut j) = (0, 0);
+        let mut indices = Vec::with_capacity(self.len().min(other.len()));
+        while i < self.len() && j < other.len() {
+            match self.indices[i].cmp(&other.indices[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    indices.push(self.indices[i]);
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        SelectionVector { indices }
+    }
+
+    /// the rows selected by either
+    pub fn union(&self, other: &SelectionVector) -> SelectionVector {
+        let mut indices: Vec<u32> = self.indices.iter().chain(&other.indices).copied().collect();
+        indices.sort_unstable();
+        indices.dedup();
+        SelectionVector { indices }
+    }
+
+    /// the rows of a chunk of `len` rows that are not selected
+    pub fn complement(&self, len: usize) -> SelectionVector {
+        let mut indices = Vec::with_capacity(len - self.len());
+        let mut selected = self.indices.iter().peekable();
+        for i in 0..len as u32 {
+            if selected.peek() == Some(&&i) {
+                selected.next();
+            } else {
+                indices.push(i);
+            }
+        }
+        SelectionVector { indices }
+    }
+}
+
+impl Deref for SelectionVector {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        &self.indices
+    }
+}
+
+impl From<Vec<u32>> for SelectionVector {
+    fn from(indices: Vec<u32>) -> Self {
+        debug_assert!(indices.windows(2).all(|w| w[0] < w[1]));
+        SelectionVector { indices }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_set_operations() {
+        let bitmap: Bitmap = (0..130).map(|i| i % 3 == 0).collect();
+        let by3 = SelectionVector::from_bitmap(&bitmap);
+        assert_eq!(by3.len(), 44);
+        assert_eq!(&by3[..4], &[0, 3, 6, 9]);
+
+        let by2 = SelectionVector::from((0..130).step_by(2).collect::<Vec<u32>>());
+        assert_eq!(&by3.intersect(&by2)[..3], &[0, 6, 12]);
+        assert_eq!(by3.union(&by2).len(), 44 + 65 - 22);
+        assert_eq!(SelectionVector::from(vec![0, 3, 6, 9]).complement(10).indices(), &[1, 2, 4, 5, 7, 8]);
+    }
+}
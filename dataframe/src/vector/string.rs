@@ -0,0 +1,138 @@
+use crate::vector::{Bitmap, Buffer};
+
+/// A vector of utf-8 strings, stored like arrow's `Utf8` array: row `i` is
+/// `data[offsets[i]..offsets[i+1]]`. `offsets` has `len + 1` entries and need not start at zero.
+#[derive(Debug, Clone)]
+pub struct StringVector {
+    offsets: Buffer<i32>,
+    data: Buffer<u8>,
+    validity: Option<Bitmap>,
+}
+
+impl StringVector {
+    /// build from raw parts, `None` unless the offsets are non-negative, increasing, within `data`
+    /// and on the char boundaries of its utf-8
+    pub fn try_new(offsets: Buffer<i32>, data: Buffer<u8>, validity: Option<Bitmap>) -> Option<Self> {
+        if offsets.is_empty() || offsets[0] < 0 || offsets.windows(2).any(|w| w[0] > w[1]) {
+            return None;
+        }
+        let (first, last) = (offsets[0] as usize, offsets[offsets.len() - 1] as usize);
+        if last > data.len() {
+            return None;
+        }
+        let text = std::str::from_utf8(&data[first..last]).ok()?;
+        if !offsets.iter().all(|&o| text.is_char_boundary(o as usize - first)) {
+            return None;
+        }
+        let validity = validity.filter(|v| !v.all_set());
+        if validity.as_ref().is_some_and(|v| v.len() != offsets.len() - 1) {
+            return None;
+        }
+        Some(StringVector { offsets, data, validity })
+    }
+
+    pub fn from_options<'a, I: IntoIterator<Item = Option<&'a str>>>(values: I) -> Self {
+        let mut offsets = vec![0i32];
+        let mut data = Vec::new();
+        let mut validity = Bitmap::default();
+        for value in values {
+            data.extend_from_slice(value.unwrap_or("").as_bytes());
+            offsets.push(data.len() as i32);
+            validity.push(value.is_some());
+        }
+        StringVector { offsets: offsets.into(), data: data.into(), validity: Some(validity).filter(|v| !v.all_set()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn offsets(&self) -> &Buffer<i32> {
+        &self.offsets
+    }
+
+    pub fn data(&self) -> &Buffer<u8> {
+        &self.data
+    }
+
+    pub fn validity(&self) -> Option<&Bitmap> {
+        self.validity.as_ref()
+    }
+
+    #[inline]
+    pub fn is_valid(&self, index: usize) -> bool {
+        self.validity.as_ref().is_none_or(|v| v.get(index))
+    }
+
+    /// the string of row `index`, empty for a null row
+    #[inline]
+    pub fn value(&self, index: usize) -> &str {
+        let bytes = &self.data[self.offsets[index] as usize..self.offsets[index + 1] as usize];
+        // SAFETY: the data between two offsets is checked (or built) as utf-8 on construction
+        unsafe { std::str::from_utf8_unchecked(bytes) }
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&str> {
+        if self.is_valid(index) { Some(self.value(index)) } else { None }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Option<&str>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    /// a zero-copy slice, sharing both the offsets and the data
+    pub fn slice(&self, offset: usize, len: usize) -> Self {
+        StringVector {
+            offsets: self.offsets.slice(offset, len + 1),
+            data: self.data.clone(),
+            validity: self.validity.as_ref().map(|v| v.slice(offset, len)).filter(|v| !v.all_set()),
+        }
+    }
+
+    pub fn take(&self, indices: &[u32]) -> Self {
+        Self::from_options(indices.iter().map(|&i| self.get(i as usize)))
+    }
+}
+
+impl PartialEq for StringVector {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_vector() {
+        let vector = StringVector::from_options(vec![Some("abc"), None, Some(""), Some("xyz")]);
+        assert_eq!(vector.len(), 4);
+        assert_eq!(vector.get(0), Some("abc"));
+        assert_eq!(vector.get(1), None);
+        assert_eq!(vector.get(2), Some(""));
+
+        let slice = vector.slice(2, 2);
+        assert!(slice.data().ptr_eq(vector.data()));
+        assert!(slice.validity().is_none());
+        assert_eq!(slice.iter().collect::<Vec<_>>(), vec![Some(""), Some("xyz")]);
+
+        assert_eq!(vector.take(&[3, 1]).iter().collect::<Vec<_>>(), vec![Some("xyz"), None]);
+    }
+
+    #[test]
+    fn test_try_new_rejects_invalid_utf8() {
+        let offsets = Buffer::from(vec![0, 2]);
+        assert!(StringVector::try_new(offsets.clone(), Buffer::from(vec![0xc3, 0x28]), None).is_none());
+        assert!(StringVector::try_new(offsets, Buffer::from(b"ok".to_vec()), None).is_some());
+        // an offset inside the two bytes of `é`, and a negative offset
+        assert!(StringVector::try_new(Buffer::from(vec![0, 2, 3]), Buffer::from("aé".as_bytes().to_vec()), None).is_none());
+        assert!(StringVector::try_new(Buffer::from(vec![0, 1, 3]), Buffer::from("aé".as_bytes().to_vec()), None).is_some());
+        assert!(StringVector::try_new(Buffer::from(vec![-1, 2]), Buffer::from(b"ok".to_vec()), None).is_none());
+    }
+}
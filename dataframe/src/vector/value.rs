@@ -0,0 +1,190 @@
+use std::fmt;
+use chrono::{DateTime, NaiveDate};
+use crate::qir::DataType;
+
+/// the number of fractional digits of a `Decimal`, which is stored as an `i128` scaled by 10^DECIMAL_SCALE
+pub const DECIMAL_SCALE: u32 = 4;
+
+/// A single value of a vector. Used for literals, aggregate results and row-at-a-time access,
+/// operators should work on whole vectors instead.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum Value {
+    Null,
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    /// fixed point, scaled by 10^DECIMAL_SCALE
+    Decimal(i128),
+    Bool(bool),
+    String(String),
+    /// days since 1970-01-01
+    Date(i32),
+    /// microseconds since 1970-01-01 00:00:00 UTC
+    DateTime(i64),
+    List(Vec<Value>),
+    Struct(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// the data type of a non-null value. nested values take the type of their first element,
+    /// and are `None` when that can not be derived.
+    pub fn data_type(&self) -> Option<DataType> {
+        Some(match self {
+            Value::Null => return None,
+            Value::I8(_) => DataType::I8,
+            Value::I16(_) => DataType::I16,
+            Value::I32(_) => DataType::I32,
+            Value::I64(_) => DataType::I64,
+            Value::U8(_) => DataType::U8,
+            Value::U16(_) => DataType::U16,
+            Value::U32(_) => DataType::U32,
+            Value::U64(_) => DataType::U64,
+            Value::F32(_) => DataType::F32,
+            Value::F64(_) => DataType::F64,
+            Value::Decimal(_) => DataType::Decimal,
+            Value::Bool(_) => DataType::Bool,
+            Value::String(_) => DataType::String,
+            Value::Date(_) => DataType::Date,
+            Value::DateTime(_) => DataType::DateTime,
+            Value::List(values) => DataType::List(Box::new(values.iter().find_map(|v| v.data_type())?)),
+            Value::Struct(_) => return None,
+            Value::Map(entries) => {
+                let (key, value) = entries.first()?;
+                DataType::Map(Box::new(key.data_type()?), Box::new(value.data_type()?))
+            }
+        })
+    }
+
+    /// parse a `yyyy-mm-dd` date
+    pub fn parse_date(text: &str) -> Option<Value> {
+        let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?;
+        Some(Value::Date((date - NaiveDate::default()).num_days() as i32))
+    }
+
+    /// parse a `yyyy-mm-dd hh:mm:ss[.ffffff]` date time
+    pub fn parse_datetime(text: &str) -> Option<Value> {
+        let datetime = chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f").ok()?;
+        Some(Value::DateTime(datetime.and_utc().timestamp_micros()))
+    }
+
+    /// parse a decimal such as `-12.5`, rounding half away from zero to DECIMAL_SCALE digits,
+    /// `None` if it is not a decimal or does not fit
+    pub fn parse_decimal(text: &str) -> Option<Value> {
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (int, frac) = text.split_once('.').unwrap_or((text, ""));
+        if int.is_empty() && frac.is_empty() || !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut digits = int.bytes().chain(frac.bytes().chain(std::iter::repeat(b'0')).take(DECIMAL_SCALE as usize));
+        let mut value = digits.try_fold(0i128, |value, b| value.checked_mul(10)?.checked_add((b - b'0') as i128))?;
+        if frac.as_bytes().get(DECIMAL_SCALE as usize).is_some_and(|&b| b >= b'5') {
+            value = value.checked_add(1)?;
+        }
+        Some(Value::Decimal(if negative { -value } else { value }))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::I8(v) => write!(f, "{v}"),
+            Value::I16(v) => write!(f, "{v}"),
+            Value::I32(v) => write!(f, "{v}"),
+            Value::I64(v) => write!(f, "{v}"),
+            Value::U8(v) => write!(f, "{v}"),
+            Value::U16(v) => write!(f, "{v}"),
+            Value::U32(v) => write!(f, "{v}"),
+            Value::U64(v) => write!(f, "{v}"),
+            Value::F32(v) => write!(f, "{v:?}"),
+            Value::F64(v) => write!(f, "{v:?}"),
+            Value::Decimal(v) => {
+                let scale = 10u128.pow(DECIMAL_SCALE);
+                let sign = if *v < 0 { "-" } else { "" };
+                let abs = v.unsigned_abs();
+                write!(f, "{sign}{}.{:0width$}", abs / scale, abs % scale, width = DECIMAL_SCALE as usize)
+            }
+            Value::Bool(v) => write!(f, "{v}"),
+            Value::String(v) => write!(f, "{v}"),
+            Value::Date(days) => {
+                let date = NaiveDate::default() + chrono::Duration::days(*days as i64);
+                write!(f, "{}", date.format("%Y-%m-%d"))
+            }
+            Value::DateTime(micros) => match DateTime::from_timestamp_micros(*micros) {
+                Some(datetime) => write!(f, "{}", datetime.naive_utc().format("%Y-%m-%d %H:%M:%S%.f")),
+                None => write!(f, "{micros}"),
+            },
+            Value::List(values) | Value::Struct(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Value::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        let date = Value::parse_date("1990-01-02").unwrap();
+        assert_eq!(date, Value::Date(7306));
+        assert_eq!(date.to_string(), "1990-01-02");
+
+        let datetime = Value::parse_datetime("1970-01-01 00:00:01.5").unwrap();
+        assert_eq!(datetime, Value::DateTime(1_500_000));
+
+        assert_eq!(Value::parse_decimal("-12.5"), Some(Value::Decimal(-125000)));
+        assert_eq!(Value::Decimal(-125000).to_string(), "-12.5000");
+        assert_eq!(Value::parse_decimal("1.2x"), None);
+        assert_eq!(Value::parse_decimal("--5"), None);
+        assert_eq!(Value::parse_decimal("-0.00005"), Some(Value::Decimal(-1)));
+        assert_eq!(Value::parse_decimal("0.00004"), Some(Value::Decimal(0)));
+        assert_eq!(Value::parse_decimal(&"9".repeat(40)), None);
+        assert_eq!(Value::Decimal(i128::MIN).to_string(), "-17014118346046923173168730371588410.5728");
+    }
+}